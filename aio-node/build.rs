@@ -0,0 +1,11 @@
+/// Generates the gRPC server and client (see `src/grpc.rs`) out of the protobuf schemas,
+/// using the vendored `protoc` so that no protobuf compiler has to be installed.<br/>
+/// The client is generated without its `connect` helper (which needs the 2021 prelude),
+/// it is built out of a `Channel` instead.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure()
+        .build_transport(false)
+        .compile_protos(&["proto/node.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,263 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use blockchain::{Block, Blockchain, BlockchainError, Keypair, Transaction};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+/// How many commands may wait for the chain, before the clients sending more are held back.
+const COMMAND_QUEUE_SIZE: usize = 1024;
+
+/// A query of the chain, which may also change it (e.g. to subscribe to it or to flush it)
+type Query = Box<dyn FnOnce(&mut Blockchain) + Send>;
+
+/// The commands that the chain actor executes, one after another (see `spawn`).
+pub enum Command {
+    /// Submits the transaction to the mempool
+    SubmitTx {
+        transaction: Transaction,
+        reply: oneshot::Sender<Result<(), BlockchainError>>,
+    },
+
+    /// Appends the block (e.g. received from a peer)
+    AppendBlock {
+        block: Block,
+        reply: oneshot::Sender<Result<(), BlockchainError>>,
+    },
+
+    /// Produces a block out of (at most `max_txs`) pending transactions, signed by the
+    /// keypair (if set), replying with the produced block
+    ProduceBlock {
+        max_txs: usize,
+        keypair: Option<Keypair>,
+        reply: oneshot::Sender<Result<Option<Block>, BlockchainError>>,
+    },
+
+    /// Runs the query against the chain
+    Query(Query),
+
+    /// Stops the actor, even if clients are left (see `ChainClient::stop`)
+    Stop,
+}
+
+/// Will move the chain into an actor, a task (running on its own thread) that owns it and
+/// executes the commands sent by the returned client, so that the tasks of the node (the
+/// network, the APIs and the block production) share the chain without locking it.<br/>
+/// Once all clients are dropped (or one of them stops it), the actor persists the world
+/// state and stops.
+pub fn spawn(chain: Blockchain) -> io::Result<ChainClient> {
+    let (commands, receiver) = mpsc::channel(COMMAND_QUEUE_SIZE);
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let actor = thread::spawn(move || runtime.block_on(run(chain, receiver)));
+    Ok(ChainClient {
+        commands,
+        actor: Arc::new(Mutex::new(Some(actor))),
+    })
+}
+
+/// Will execute the commands until all clients are dropped or one of them stops the actor
+async fn run(mut chain: Blockchain, mut commands: mpsc::Receiver<Command>) {
+    while let Some(command) = commands.recv().await {
+        // A client that stopped waiting for the reply doesn't need it anymore
+        match command {
+            Command::SubmitTx { transaction, reply } => {
+                let _ = reply.send(chain.submit_transaction(transaction));
+            }
+            Command::AppendBlock { block, reply } => {
+                let _ = reply.send(chain.append_block(block).map(|_| ()));
+            }
+            Command::ProduceBlock {
+                max_txs,
+                keypair,
+                reply,
+            } => {
+                let produced = match &keypair {
+                    Some(keypair) => chain.produce_signed_block(max_txs, keypair),
+                    None => chain.produce_block(max_txs),
+                };
+                let _ = reply.send(produced.map(|_| chain.blocks().last().cloned()));
+            }
+            Command::Query(query) => query(&mut chain),
+            Command::Stop => break,
+        }
+    }
+
+    debug!("Chain actor stopped");
+    if let Err(err) = chain.flush() {
+        warn!(error = %err, "Could not persist the world state");
+    }
+    // Closing the storage (releasing its lock) before the actor's thread ends
+    drop(chain);
+}
+
+/// Sends commands to the chain actor (see `spawn`) and waits for their replies, either
+/// asynchronously or (on threads not running an async runtime) by blocking.<br/>
+/// Clones share the same actor.
+#[derive(Clone, Debug)]
+pub struct ChainClient {
+    commands: mpsc::Sender<Command>,
+
+    /// The thread of the actor, until it is stopped
+    actor: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+}
+
+impl ChainClient {
+    /// Will stop the actor once the commands sent before are executed and wait until its
+    /// thread ends, so that the chain (and its storage) is dropped when it returns.<br/>
+    /// The commands sent by the clients afterwards fail.
+    pub fn stop(&self) {
+        // The actor may already have stopped, if another client stopped it
+        let _ = self.commands.blocking_send(Command::Stop);
+        let actor = self
+            .actor
+            .lock()
+            .expect("The actor handle is not poisoned")
+            .take();
+        if let Some(actor) = actor {
+            if actor.join().is_err() {
+                warn!("The chain actor panicked");
+            }
+        }
+    }
+
+    /// Will submit the transaction to the mempool, blocking the current thread
+    pub fn blocking_submit_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> Result<(), BlockchainError> {
+        self.blocking_request(|reply| Command::SubmitTx { transaction, reply })
+    }
+
+    /// Will append the block, blocking the current thread
+    pub fn blocking_append_block(&self, block: Block) -> Result<(), BlockchainError> {
+        self.blocking_request(|reply| Command::AppendBlock { block, reply })
+    }
+
+    /// Will produce a block (signed by the keypair, if set) and return it, blocking the
+    /// current thread
+    pub fn blocking_produce_block(
+        &self,
+        max_txs: usize,
+        keypair: Option<Keypair>,
+    ) -> Result<Option<Block>, BlockchainError> {
+        self.blocking_request(|reply| Command::ProduceBlock {
+            max_txs,
+            keypair,
+            reply,
+        })
+    }
+
+    /// Will run the query against the chain and return its result
+    pub async fn query<F, R>(&self, query: F) -> R
+    where
+        F: FnOnce(&mut Blockchain) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.request(|reply| query_command(query, reply)).await
+    }
+
+    /// Will run the query against the chain and return its result, blocking the current thread
+    pub fn blocking_query<F, R>(&self, query: F) -> R
+    where
+        F: FnOnce(&mut Blockchain) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.blocking_request(|reply| query_command(query, reply))
+    }
+
+    async fn request<R, F>(&self, command: F) -> R
+    where
+        F: FnOnce(oneshot::Sender<R>) -> Command,
+    {
+        let (reply, response) = oneshot::channel();
+        if self.commands.send(command(reply)).await.is_err() {
+            panic!("The chain actor stopped");
+        }
+        response.await.expect("The chain actor stopped")
+    }
+
+    fn blocking_request<R, F>(&self, command: F) -> R
+    where
+        F: FnOnce(oneshot::Sender<R>) -> Command,
+    {
+        let (reply, response) = oneshot::channel();
+        if self.commands.blocking_send(command(reply)).is_err() {
+            panic!("The chain actor stopped");
+        }
+        response.blocking_recv().expect("The chain actor stopped")
+    }
+}
+
+/// Will wrap the query into a command replying with its result
+fn query_command<F, R>(query: F, reply: oneshot::Sender<R>) -> Command
+where
+    F: FnOnce(&mut Blockchain) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    Command::Query(Box::new(move |chain| {
+        let _ = reply.send(query(chain));
+    }))
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::thread;
+
+    use blockchain::{Blockchain, GenesisConfig, Transaction, TransactionData};
+
+    use super::spawn;
+
+    #[test]
+    fn clients_share_the_chain() {
+        let config = GenesisConfig::from_toml(
+            r#"
+            chain_id = "test"
+            [[accounts]]
+            id = "alice"
+            balance = 10
+            "#,
+        )
+        .unwrap();
+        let client = spawn(Blockchain::from_genesis(&config).unwrap()).unwrap();
+
+        // Clients on other threads submit transactions concurrently
+        let submitters: Vec<_> = ["bob", "carol"]
+            .iter()
+            .enumerate()
+            .map(|(nonce, id)| {
+                let client = client.clone();
+                let transaction = Transaction::new(
+                    "alice".into(),
+                    TransactionData::CreateUserAccount(id.to_string()),
+                    nonce as u128,
+                )
+                .with_chain_id("test".into());
+                thread::spawn(move || client.blocking_submit_transaction(transaction))
+            })
+            .collect();
+        for submitter in submitters {
+            submitter.join().unwrap().unwrap();
+        }
+        assert_eq!(
+            2,
+            client.blocking_query(|chain| chain.pending_transactions().len())
+        );
+
+        let block = client.blocking_produce_block(10, None).unwrap().unwrap();
+        assert_eq!(2, block.transactions().len());
+        assert!(client.blocking_append_block(block).is_err());
+
+        // ... as do async tasks
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let height = runtime.block_on(client.query(|chain| chain.len()));
+        assert_eq!(2, height);
+    }
+}
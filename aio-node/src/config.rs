@@ -0,0 +1,467 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use blockchain::BlockchainError;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::limits::Limits;
+use crate::noise::NodeId;
+use crate::peers::DEFAULT_MAX_PEERS;
+
+/// The file (inside the data directory) holding the configuration of the node, if any.
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// The environment variables overriding the configuration start with this, followed by the
+/// section and the key (e.g. `AIO_NODE_NETWORK_MAX_PEERS`).
+const ENV_PREFIX: &str = "AIO_NODE_";
+
+/// The port of the RPC API of a development chain, unless another one is configured.
+const DEV_RPC_PORT: u16 = 8080;
+
+/// The configuration of a running node, layered out of (from the lowest to the highest
+/// precedence) the defaults, the configuration file (see `load`), the environment
+/// (see `with_env`) and the command line (see `ConfigOverrides`).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    pub network: NetworkConfig,
+    pub rpc: RpcConfig,
+    pub consensus: ConsensusConfig,
+    pub storage: StorageConfig,
+    pub logging: LoggingConfig,
+}
+
+/// How the node joins the network and which peers it is held to (see `Limits`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// The address to accept peer connections on
+    pub listen: SocketAddr,
+
+    /// The peers to gossip blocks and transactions with
+    pub peers: Vec<SocketAddr>,
+
+    /// The nodes to learn (more) peers from when starting, besides gossiping with them
+    pub bootnodes: Vec<SocketAddr>,
+
+    /// The maximum number of discovered (rather than configured) peers
+    pub max_peers: usize,
+
+    /// Whether the nodes of the local network are discovered (through mDNS)
+    pub mdns: bool,
+
+    /// The ids of the only nodes allowed to connect, any node may connect if empty
+    pub allowed_peers: Vec<NodeId>,
+
+    /// The requests per second that a peer or a client may send
+    pub rate_limit: u32,
+
+    /// The size (in bytes) of the biggest P2P message accepted from peers
+    pub max_message_size: u32,
+
+    /// The P2P connections that an IP address may hold open at once
+    pub max_connections_per_ip: usize,
+
+    /// The size (in bytes) of the biggest API request body accepted from clients
+    pub max_request_size: usize,
+
+    /// The seconds that a peer or a client may stall, before it is disconnected
+    pub timeout: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        let limits = Limits::default();
+        NetworkConfig {
+            listen: SocketAddr::from(([0, 0, 0, 0], 7000)),
+            peers: Vec::new(),
+            bootnodes: Vec::new(),
+            max_peers: DEFAULT_MAX_PEERS,
+            mdns: true,
+            allowed_peers: Vec::new(),
+            rate_limit: limits.requests_per_second,
+            max_message_size: limits.max_message_size,
+            max_connections_per_ip: limits.max_connections_per_ip,
+            max_request_size: limits.max_body_size,
+            timeout: limits.timeout.as_secs(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Will return the limits that peers and clients are held to
+    pub fn limits(&self) -> Limits {
+        Limits {
+            requests_per_second: self.rate_limit,
+            max_message_size: self.max_message_size,
+            max_connections_per_ip: self.max_connections_per_ip,
+            max_body_size: self.max_request_size,
+            timeout: Duration::from_secs(self.timeout),
+        }
+    }
+}
+
+/// The ports that the APIs of the node are served on, each disabled if not set.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// The port of the (HTTP/JSON) RPC API
+    pub port: Option<u16>,
+
+    /// The port of the (WebSocket) subscription API
+    pub ws_port: Option<u16>,
+
+    /// The port of the gRPC API
+    pub grpc_port: Option<u16>,
+}
+
+/// How the node produces blocks.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConsensusConfig {
+    /// The address (of the keystore) whose key signs the produced blocks, as required by
+    /// Proof of Stake networks
+    pub validator: Option<String>,
+
+    /// How often (in seconds) a block is produced out of the mempool, 0 producing one as
+    /// soon as a transaction arrives (like development chains do)
+    pub block_interval: u64,
+
+    /// The maximum number of transactions a produced block includes
+    pub max_block_transactions: usize,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        ConsensusConfig {
+            validator: None,
+            block_interval: 5,
+            max_block_transactions: 100,
+        }
+    }
+}
+
+/// How much of the chain the node keeps.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Keeps the transactions of only this many latest blocks (older blocks are pruned to
+    /// their headers), all blocks are kept if not set
+    pub keep_blocks: Option<usize>,
+}
+
+/// What the node logs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// The filter of the logs, e.g. `info` or `aio_node=debug`
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".into(),
+        }
+    }
+}
+
+impl NodeConfig {
+    /// Will adapt the configuration to a local development chain (see `aio-node run --dev`):
+    /// blocks are produced as soon as transactions arrive, the local network isn't joined
+    /// and the RPC API is served (on `DEV_RPC_PORT`, unless another port is configured)
+    pub fn dev(mut self) -> Self {
+        self.consensus.block_interval = 0;
+        self.network.mdns = false;
+        self.rpc.port = self.rpc.port.or(Some(DEV_RPC_PORT));
+        self
+    }
+
+    /// Will parse the configuration from TOML, the missing keys (and sections) keeping
+    /// their defaults
+    pub fn from_toml(toml: &str) -> Result<Self, BlockchainError> {
+        toml::from_str(toml).map_err(config_error)
+    }
+
+    /// Will load the configuration from the (TOML) file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|err| {
+            BlockchainError::InvalidConfig(format!("{}: {}", path.display(), err))
+        })?;
+        Self::from_toml(&content)
+    }
+
+    /// Will load the configuration file (if set, the one of the data directory otherwise,
+    /// if there is one) and override it with the environment variables of the node (see
+    /// `with_env`)
+    pub fn resolve(file: Option<&Path>, data_dir: &Path) -> Result<Self, BlockchainError> {
+        let default_file = data_dir.join(CONFIG_FILE);
+        let config = match file {
+            Some(file) => Self::load(file)?,
+            None if default_file.exists() => Self::load(default_file)?,
+            None => Self::default(),
+        };
+        config.with_env(std::env::vars())
+    }
+
+    /// Will override the configuration with the variables named `AIO_NODE_<SECTION>_<KEY>`,
+    /// e.g. `AIO_NODE_NETWORK_LISTEN=0.0.0.0:7001` or `AIO_NODE_RPC_PORT=8080` (and the level
+    /// of the logs with `RUST_LOG`).<br/>
+    /// The values are parsed as TOML (e.g. `AIO_NODE_NETWORK_PEERS='["10.0.0.1:7000"]'`),
+    /// falling back to plain strings. Variables of other sections are ignored.
+    pub fn with_env<I>(self, vars: I) -> Result<Self, BlockchainError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut document = toml::Value::try_from(&self).map_err(config_error)?;
+        let table = document
+            .as_table_mut()
+            .expect("the configuration is a table");
+        for (name, value) in vars {
+            let (section, key) = match name
+                .strip_prefix(ENV_PREFIX)
+                .and_then(|name| name.split_once('_'))
+            {
+                Some((section, key)) => (section.to_lowercase(), key.to_lowercase()),
+                None if name == "RUST_LOG" => ("logging".into(), "level".into()),
+                None => continue,
+            };
+            if let Some(toml::Value::Table(section)) = table.get_mut(&section) {
+                section.insert(key, parse_env_value(&value));
+            }
+        }
+        document
+            .try_into()
+            .map_err(|err| BlockchainError::InvalidConfig(format!("Environment: {}", err)))
+    }
+}
+
+/// The command line flags of `run` overriding the configuration (see `NodeConfig`).
+#[derive(Debug, Default, Args)]
+pub struct ConfigOverrides {
+    /// The configuration file, `config.toml` of the data directory (if there is one)
+    /// if not set
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// The address to accept peer connections on [default: 0.0.0.0:7000]
+    #[arg(long)]
+    pub listen: Option<SocketAddr>,
+
+    /// The peers to gossip blocks and transactions with
+    #[arg(long = "peer")]
+    pub peers: Vec<SocketAddr>,
+
+    /// The nodes to learn (more) peers from when starting, besides gossiping with them
+    #[arg(long = "bootnode")]
+    pub bootnodes: Vec<SocketAddr>,
+
+    /// The maximum number of discovered (rather than configured) peers [default: 25]
+    #[arg(long)]
+    pub max_peers: Option<usize>,
+
+    /// Disables the discovery of the nodes of the local network (through mDNS)
+    #[arg(long)]
+    pub no_mdns: bool,
+
+    /// The ids of the only nodes allowed to connect (as logged when they start and shown
+    /// by `GET /status`), any node may connect if not set
+    #[arg(long = "allowed-peer")]
+    pub allowed_peers: Vec<NodeId>,
+
+    /// The requests (P2P messages and API requests) per second that a peer or a client
+    /// (by its IP address) may send, before it is refused [default: 100]
+    #[arg(long)]
+    pub rate_limit: Option<u32>,
+
+    /// The size (in bytes) of the biggest P2P message accepted from peers [default: 16 MiB]
+    #[arg(long)]
+    pub max_message_size: Option<u32>,
+
+    /// The P2P connections that an IP address may hold open at once (the connections of
+    /// all peers are limited to the maximum number of peers) [default: 8]
+    #[arg(long)]
+    pub max_connections_per_ip: Option<usize>,
+
+    /// The size (in bytes) of the biggest API request body accepted from clients
+    /// [default: 1 MiB]
+    #[arg(long)]
+    pub max_request_size: Option<usize>,
+
+    /// The seconds that a peer or a client may stall, before it is disconnected [default: 10]
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// The port to serve the (HTTP/JSON) RPC API on, disabled if not set
+    #[arg(long)]
+    pub rpc_port: Option<u16>,
+
+    /// The port to serve the (WebSocket) subscription API on, disabled if not set
+    #[arg(long)]
+    pub ws_port: Option<u16>,
+
+    /// The port to serve the gRPC API on, disabled if not set
+    #[arg(long)]
+    pub grpc_port: Option<u16>,
+
+    /// Keeps the transactions of only this many latest blocks (older blocks are pruned
+    /// to their headers), all blocks are kept if not set
+    #[arg(long)]
+    pub keep_blocks: Option<usize>,
+
+    /// How often (in seconds) a block is produced, 0 producing one as soon as a transaction
+    /// arrives [default: 5]
+    #[arg(long)]
+    pub block_interval: Option<u64>,
+
+    /// Signs the produced blocks with the key of this address (of the keystore),
+    /// as required by Proof of Stake networks
+    #[arg(long)]
+    pub validator: Option<String>,
+
+    /// The filter of the logs, e.g. `debug` [default: info]
+    #[arg(long)]
+    pub log_level: Option<String>,
+}
+
+impl ConfigOverrides {
+    /// Will override the configuration with the flags that are set (the lists replace the
+    /// configured ones, unless empty)
+    pub fn apply(&self, config: &mut NodeConfig) {
+        fn set<T: Clone>(target: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+        fn set_list<T: Clone>(target: &mut Vec<T>, values: &[T]) {
+            if !values.is_empty() {
+                *target = values.to_vec();
+            }
+        }
+
+        let network = &mut config.network;
+        set(&mut network.listen, self.listen);
+        set_list(&mut network.peers, &self.peers);
+        set_list(&mut network.bootnodes, &self.bootnodes);
+        set(&mut network.max_peers, self.max_peers);
+        if self.no_mdns {
+            network.mdns = false;
+        }
+        set_list(&mut network.allowed_peers, &self.allowed_peers);
+        set(&mut network.rate_limit, self.rate_limit);
+        set(&mut network.max_message_size, self.max_message_size);
+        set(
+            &mut network.max_connections_per_ip,
+            self.max_connections_per_ip,
+        );
+        set(&mut network.max_request_size, self.max_request_size);
+        set(&mut network.timeout, self.timeout);
+
+        let rpc = &mut config.rpc;
+        set(&mut rpc.port, self.rpc_port.map(Some));
+        set(&mut rpc.ws_port, self.ws_port.map(Some));
+        set(&mut rpc.grpc_port, self.grpc_port.map(Some));
+        set(&mut config.storage.keep_blocks, self.keep_blocks.map(Some));
+        set(&mut config.consensus.block_interval, self.block_interval);
+        set(
+            &mut config.consensus.validator,
+            self.validator.clone().map(Some),
+        );
+        set(&mut config.logging.level, self.log_level.clone());
+    }
+}
+
+/// Will parse the value of an environment variable as TOML, or as a string if it isn't
+fn parse_env_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+fn config_error<E: ToString>(err: E) -> BlockchainError {
+    BlockchainError::InvalidConfig(err.to_string())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::net::SocketAddr;
+
+    use super::{ConfigOverrides, NodeConfig};
+
+    #[test]
+    fn configuration_is_layered() {
+        let file = NodeConfig::from_toml(
+            r#"
+            [network]
+            listen = "127.0.0.1:7100"
+            peers = ["10.0.0.1:7000"]
+            max_peers = 5
+
+            [rpc]
+            port = 8080
+
+            [consensus]
+            block_interval = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(5, file.network.max_peers);
+        assert_eq!(Some(8080), file.rpc.port);
+        // The missing keys and sections keep their defaults
+        assert_eq!(100, file.consensus.max_block_transactions);
+        assert_eq!("info", file.logging.level);
+        assert!(NodeConfig::from_toml("[network]\nunknown = 1").is_err());
+
+        let var = |name: &str, value: &str| (name.to_string(), value.to_string());
+        let env = file
+            .with_env(vec![
+                var("AIO_NODE_NETWORK_MAX_PEERS", "10"),
+                var("AIO_NODE_NETWORK_BOOTNODES", r#"["10.0.0.2:7000"]"#),
+                var("AIO_NODE_CONSENSUS_VALIDATOR", "alice"),
+                var("AIO_NODE_RPC_PORT", "8081"),
+                var("RUST_LOG", "debug"),
+                // Other variables of the node are left alone
+                var("AIO_NODE_PASSWORD", "secret"),
+                var("PATH", "/bin"),
+            ])
+            .unwrap();
+        assert_eq!(10, env.network.max_peers);
+        assert_eq!(1, env.network.bootnodes.len());
+        assert_eq!(Some("alice".to_string()), env.consensus.validator);
+        assert_eq!("debug", env.logging.level);
+        assert!(env
+            .clone()
+            .with_env(vec![var("AIO_NODE_NETWORK_MAX_PEERS", "many")])
+            .is_err());
+
+        let mut config = env;
+        let listen: SocketAddr = "127.0.0.1:7200".parse().unwrap();
+        ConfigOverrides {
+            listen: Some(listen),
+            rpc_port: Some(8082),
+            no_mdns: true,
+            ..ConfigOverrides::default()
+        }
+        .apply(&mut config);
+        assert_eq!(listen, config.network.listen);
+        assert_eq!(Some(8082), config.rpc.port);
+        assert!(!config.network.mdns);
+        // What isn't overridden is kept
+        assert_eq!(10, config.network.max_peers);
+        assert_eq!(
+            vec![SocketAddr::from(([10, 0, 0, 1], 7000))],
+            config.network.peers
+        );
+        assert_eq!(2, config.consensus.block_interval);
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tracing::{debug, info, warn};
+
+use crate::p2p::Network;
+use crate::peers::PeerSource;
+
+/// The multicast group of mDNS.
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// The port of mDNS.
+const MDNS_PORT: u16 = 5353;
+
+/// The name that nodes announce themselves under.
+const SERVICE: &str = "_aio-node._tcp.local";
+
+/// The (DNS) type of the announced records, TXT.
+const TXT: u16 = 16;
+
+/// The (DNS) type asking for records of any type.
+const ANY: u16 = 255;
+
+/// How long (in seconds) others may cache an announcement.
+const TTL: u32 = 120;
+
+/// How often a node announces itself (besides answering queries).
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The mDNS packets that nodes exchange.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Packet {
+    /// Asks the nodes of the local network to announce themselves
+    Query,
+
+    /// Announces that a node (identified by a random id, so that it recognizes its own
+    /// announcements) accepts peer connections on the port, at the address it is sent from
+    Announcement { id: u64, port: u16 },
+}
+
+/// Will discover the nodes of the local network through mDNS (multicast DNS) and add them to
+/// the peers of the network: the node queries for the others when it starts and announces
+/// itself (as a TXT record of `_aio-node._tcp.local` carrying its port) in response to
+/// queries and every minute.<br/>
+/// Only the records of the nodes are understood, not the service discovery (DNS-SD) of other
+/// services sharing the group.
+pub fn spawn(network: Arc<Network>, port: u16) -> io::Result<()> {
+    let socket = bind()?;
+    let group = SocketAddr::V4(SocketAddrV4::new(MDNS_GROUP, MDNS_PORT));
+    let id = RandomState::new().build_hasher().finish();
+    let announcement = encode(&Packet::Announcement { id, port });
+    socket.send_to(&encode(&Packet::Query), group)?;
+    socket.send_to(&announcement, group)?;
+
+    let announcer = socket.try_clone()?;
+    let periodic = announcement.clone();
+    thread::spawn(move || loop {
+        thread::sleep(ANNOUNCE_INTERVAL);
+        if let Err(err) = announcer.send_to(&periodic, group) {
+            warn!(error = %err, "Could not announce the node");
+        }
+    });
+
+    thread::spawn(move || {
+        let mut buf = [0u8; 9000];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(err) => {
+                    warn!(error = %err, "mDNS discovery stopped");
+                    return;
+                }
+            };
+            match decode(&buf[..len]) {
+                Some(Packet::Query) => {
+                    if let Err(err) = socket.send_to(&announcement, group) {
+                        warn!(error = %err, "Could not announce the node");
+                    }
+                }
+                Some(Packet::Announcement { id: other, port }) if other != id => {
+                    let addr = SocketAddr::new(from.ip(), port);
+                    if network.add_peer(addr, PeerSource::Mdns) {
+                        info!(%addr, "Discovered a peer on the local network");
+                    }
+                }
+                _ => debug!(%from, "Ignored mDNS packet"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Will bind a socket to the mDNS port (shared with other responders of the host)
+/// and join the group
+fn bind() -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+    let socket = UdpSocket::from(socket);
+    socket.join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_loop_v4(true)?;
+    Ok(socket)
+}
+
+/// Will encode the packet as a DNS message: a query (for the TXT record of the service)
+/// or a response (holding the TXT record, with the strings `id=<hex>` and `port=<port>`)
+pub fn encode(packet: &Packet) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let (flags, questions, answers) = match packet {
+        Packet::Query => (0u16, 1u16, 0u16),
+        // A response, authoritative for the record
+        Packet::Announcement { .. } => (0x8400, 0, 1),
+    };
+    for field in [0, flags, questions, answers, 0, 0] {
+        bytes.extend_from_slice(&field.to_be_bytes());
+    }
+    encode_name(&mut bytes, SERVICE);
+    bytes.extend_from_slice(&TXT.to_be_bytes());
+    // The class IN
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+
+    if let Packet::Announcement { id, port } = packet {
+        bytes.extend_from_slice(&TTL.to_be_bytes());
+        let mut data = Vec::new();
+        for text in [format!("id={:x}", id), format!("port={}", port)] {
+            data.push(text.len() as u8);
+            data.extend_from_slice(text.as_bytes());
+        }
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&data);
+    }
+    bytes
+}
+
+/// Will decode the DNS message, `None` if it is malformed or not about the service
+pub fn decode(bytes: &[u8]) -> Option<Packet> {
+    let field = |pos: usize| Some(u16::from_be_bytes([*bytes.get(pos)?, *bytes.get(pos + 1)?]));
+    let is_response = field(2)? & 0x8000 != 0;
+    let (questions, answers) = (field(4)?, field(6)?);
+
+    let mut pos = 12;
+    let mut queried = false;
+    for _ in 0..questions {
+        let (name, next) = decode_name(bytes, pos)?;
+        queried |= name == SERVICE && matches!(field(next)?, TXT | ANY);
+        pos = next + 4;
+    }
+    if !is_response {
+        return if queried { Some(Packet::Query) } else { None };
+    }
+
+    for _ in 0..answers {
+        let (name, next) = decode_name(bytes, pos)?;
+        let kind = field(next)?;
+        let len = usize::from(field(next + 8)?);
+        let data = bytes.get(next + 10..next + 10 + len)?;
+        pos = next + 10 + len;
+        if name == SERVICE && kind == TXT {
+            return decode_announcement(data);
+        }
+    }
+    None
+}
+
+/// Will decode the strings of the TXT record of an announcement
+fn decode_announcement(mut data: &[u8]) -> Option<Packet> {
+    let (mut id, mut port) = (None, None);
+    while let Some((&len, rest)) = data.split_first() {
+        let text = std::str::from_utf8(rest.get(..usize::from(len))?).ok()?;
+        data = &rest[usize::from(len)..];
+        match text.split_once('=') {
+            Some(("id", value)) => id = u64::from_str_radix(value, 16).ok(),
+            Some(("port", value)) => port = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(Packet::Announcement {
+        id: id?,
+        port: port?,
+    })
+}
+
+/// Will encode the (dot separated) name as DNS labels
+fn encode_name(bytes: &mut Vec<u8>, name: &str) {
+    for label in name.split('.') {
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label.as_bytes());
+    }
+    bytes.push(0);
+}
+
+/// Will decode the name starting at the position, following (a limited amount of)
+/// compression pointers, and return it along with the position after it
+fn decode_name(bytes: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..32 {
+        let len = *bytes.get(pos)?;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let pointer = usize::from(u16::from_be_bytes([len & 0x3f, *bytes.get(pos + 1)?]));
+                end.get_or_insert(pos + 2);
+                pos = pointer;
+            }
+            len => {
+                let label = bytes.get(pos + 1..pos + 1 + usize::from(len))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + usize::from(len);
+            }
+        }
+    }
+    None
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{decode, encode, Packet};
+
+    #[test]
+    fn packets_round_trip_as_dns_messages() {
+        let announcement = Packet::Announcement {
+            id: 0xfeed,
+            port: 7000,
+        };
+        assert_eq!(Some(announcement.clone()), decode(&encode(&announcement)));
+        assert_eq!(Some(Packet::Query), decode(&encode(&Packet::Query)));
+
+        // Names may be compressed: the answer's name points to the one of the question
+        let mut response = encode(&announcement);
+        response[5] = 1;
+        let question = response[12..12 + 22 + 4].to_vec();
+        response.splice(12..12, question);
+        response.splice(12 + 26..12 + 26 + 22, [0xc0, 12]);
+        assert_eq!(Some(announcement), decode(&response));
+
+        // Malformed or unrelated messages are ignored
+        let query = encode(&Packet::Query);
+        assert_eq!(None, decode(&query[..query.len() - 3]));
+        assert_eq!(None, decode(&[0xc0; 40]));
+        let mut other = query.clone();
+        other[13] = b'x';
+        assert_eq!(None, decode(&other));
+    }
+}
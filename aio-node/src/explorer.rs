@@ -0,0 +1,384 @@
+use std::collections::BTreeMap;
+
+use blockchain::{
+    Account, Block, Blockchain, Hash, Transaction, TransactionKind, TransactionLocation, WorldState,
+};
+use serde_json::{json, Value};
+
+use crate::rpc::Response;
+
+/// The number of blocks (or transactions) a list contains, unless a `limit` is queried.
+const DEFAULT_LIMIT: usize = 20;
+
+/// The maximum number of blocks (or transactions) a list contains.
+const MAX_LIMIT: usize = 100;
+
+/// Will answer the views of the (block) explorer, `None` if the path isn't one of them:
+/// - `GET /blocks?limit=<n>` the summaries of the latest blocks (newest first)
+/// - `GET /blocks?from=<height>&limit=<n>` the summaries of the blocks from the height on
+/// - `GET /block/<hash>` a block including its transactions
+/// - `GET /tx/<hash>` a transaction, its receipt and the block it is stored in
+/// - `GET /transactions?kind=<kind>&page=<n>&limit=<n>` a page of the transactions of the kind
+///   (e.g. `TransferTokens`), oldest first
+/// - `GET /account/<id>` an account including its store
+/// - `GET /account/<id>/transactions?page=<n>&limit=<n>` a page of the transactions sent by the
+///   account, oldest first
+/// - `GET /account/<id>/history?page=<n>&limit=<n>` a page of the changes of the account's
+///   balance by the transactions touching it, oldest first
+///
+/// Every view is rendered as (simple) HTML instead of JSON if `format=html` is queried.
+pub fn handle(chain: &Blockchain, segments: &[&str], query: &str) -> Option<Response> {
+    let limit = query_param(query, "limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_LIMIT)
+        .min(MAX_LIMIT);
+    let page: usize = query_param(query, "page")
+        .and_then(|page| page.parse().ok())
+        .unwrap_or(0);
+    let response = match segments {
+        ["blocks"] => match query_param(query, "from").map(|from| (from, from.parse::<usize>())) {
+            Some((from, Err(_))) => Response::error(400, format!("Invalid height: {}", from)),
+            from => {
+                let blocks: Vec<Value> = match from {
+                    Some((_, Ok(from))) => chain
+                        .blocks_range(from, from.saturating_add(limit))
+                        .iter()
+                        .zip(from..)
+                        .map(|(block, height)| block_summary(height, block))
+                        .collect(),
+                    _ => chain
+                        .blocks()
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .take(limit)
+                        .map(|(height, block)| block_summary(height, block))
+                        .collect(),
+                };
+                Response::ok(json!({ "height": chain.len(), "blocks": blocks }))
+            }
+        },
+        ["transactions"] => {
+            let kind = query_param(query, "kind").unwrap_or_default();
+            match serde_json::from_value::<TransactionKind>(json!(kind)) {
+                Ok(kind) => {
+                    let transactions: Vec<Value> = chain
+                        .transactions_by_type(kind)
+                        .skip(page.saturating_mul(limit))
+                        .take(limit)
+                        .map(transaction_summary)
+                        .collect();
+                    Response::ok(json!({ "page": page, "transactions": transactions }))
+                }
+                Err(_) => Response::error(400, format!("Unknown transaction kind `{}`", kind)),
+            }
+        }
+        ["account", id, "transactions"] => {
+            let id = chain.resolve_account_id(id);
+            let transactions: Vec<Value> = chain
+                .transactions_by_account(&id, page, limit)
+                .into_iter()
+                .map(transaction_summary)
+                .collect();
+            Response::ok(json!({ "id": id, "page": page, "transactions": transactions }))
+        }
+        ["account", id, "history"] => {
+            let id = chain.resolve_account_id(id);
+            let history: Vec<Value> = chain
+                .account_history(&id, page, limit)
+                .into_iter()
+                .map(|change| {
+                    json!({
+                        "block_height": change.block_height,
+                        "transaction_hash": change.transaction_hash.to_string(),
+                        "delta": change.delta.to_string(),
+                    })
+                })
+                .collect();
+            Response::ok(json!({ "id": id, "page": page, "history": history }))
+        }
+        ["block", hash] => match parse_hash(hash)
+            .and_then(|hash| Some((chain.block_height(&hash)?, chain.get_block_by_hash(&hash)?)))
+        {
+            Some((height, block)) => {
+                let mut view = block_summary(height, block);
+                view["transactions"] = json!(block.transactions());
+                Response::ok(view)
+            }
+            None => Response::error(404, format!("Block {} does not exist", hash)),
+        },
+        ["tx", hash] => match parse_hash(hash).and_then(|hash| {
+            Some((
+                chain.transaction_location(&hash)?,
+                chain.get_transaction(&hash)?,
+            ))
+        }) {
+            Some((location, transaction)) => Response::ok(json!({
+                "hash": hash,
+                "block_hash": chain.blocks()[location.block_height].hash(),
+                "block_height": location.block_height,
+                "index": location.index,
+                "transaction": transaction,
+                "receipt": chain.get_receipt(&transaction.calculate_hash()),
+            })),
+            None if chain.pruned_height() > 0 => Response::error(
+                404,
+                format!(
+                    "Transaction {} does not exist (the blocks below height {} were pruned)",
+                    hash,
+                    chain.pruned_height()
+                ),
+            ),
+            None => Response::error(404, format!("Transaction {} does not exist", hash)),
+        },
+        ["account", id] => {
+            let id = chain.resolve_account_id(id);
+            match chain.get_account_by_id(&id) {
+                Some(account) => Response::ok(json!({
+                    "id": id,
+                    "balance": account.tokens().to_string(),
+                    "assets": asset_balances(account),
+                    "nfts": account.nfts(),
+                    "nonce": account.nonce().to_string(),
+                    "type": account.account_type(),
+                    "store": account.store(),
+                })),
+                None => Response::error(404, format!("Account `{}` does not exist", id)),
+            }
+        }
+        _ => return None,
+    };
+
+    if query_param(query, "format") == Some("html") {
+        return Some(render_html(&segments.join("/"), response));
+    }
+    Some(response)
+}
+
+fn block_summary(height: usize, block: &Block) -> Value {
+    json!({
+        "height": height,
+        "hash": block.hash(),
+        "prev_hash": block.prev_hash(),
+        "timestamp": block.timestamp(),
+        "difficulty": block.difficulty(),
+        "state_root": block.state_root(),
+        "transaction_count": block.get_transaction_count(),
+        "pruned": block.is_pruned(),
+    })
+}
+
+fn transaction_summary((location, transaction): (TransactionLocation, &Transaction)) -> Value {
+    json!({
+        "hash": transaction.calculate_hash(),
+        "block_height": location.block_height,
+        "index": location.index,
+        "transaction": transaction,
+    })
+}
+
+fn parse_hash(hash: &str) -> Option<Hash> {
+    hash.parse().ok()
+}
+
+/// Will return the value of the parameter of the (URL) query
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Will return the balances of the account's assets (as strings, like its balance), by their ids
+pub(crate) fn asset_balances(account: &Account) -> BTreeMap<&String, String> {
+    account
+        .assets()
+        .iter()
+        .map(|(asset, amount)| (asset, amount.to_string()))
+        .collect()
+}
+
+/// Will render the response as an HTML page
+fn render_html(title: &str, response: Response) -> Response {
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title></head><body><h1>{}</h1>",
+        escape(title),
+        escape(title)
+    );
+    render_value(&response.body, &mut html);
+    html.push_str("</body></html>");
+    Response {
+        html: Some(html),
+        ..response
+    }
+}
+
+/// Will render objects and arrays as (nested) tables
+fn render_value(value: &Value, html: &mut String) {
+    match value {
+        Value::Object(map) => {
+            html.push_str("<table border=\"1\">");
+            for (key, value) in map {
+                html.push_str(&format!("<tr><th>{}</th><td>", escape(key)));
+                render_value(value, html);
+                html.push_str("</td></tr>");
+            }
+            html.push_str("</table>");
+        }
+        Value::Array(values) => {
+            html.push_str("<ol start=\"0\">");
+            for value in values {
+                html.push_str("<li>");
+                render_value(value, html);
+                html.push_str("</li>");
+            }
+            html.push_str("</ol>");
+        }
+        Value::String(s) => html.push_str(&escape(s)),
+        other => html.push_str(&escape(&other.to_string())),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use blockchain::{Blockchain, GenesisConfig, Transaction, TransactionData};
+
+    use super::handle;
+
+    fn chain() -> (Blockchain, Transaction) {
+        let config = GenesisConfig::from_toml(
+            r#"
+            chain_id = "test"
+            [[accounts]]
+            id = "alice"
+            balance = 10
+            "#,
+        )
+        .unwrap();
+        let mut chain = Blockchain::from_genesis(&config).unwrap();
+        let transaction = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("<bob>".into()),
+            0,
+        )
+        .with_chain_id("test".into());
+        chain.submit_transaction(transaction.clone()).unwrap();
+        chain.produce_block(1).unwrap();
+        (chain, transaction)
+    }
+
+    #[test]
+    fn json_views() {
+        let (chain, transaction) = chain();
+        let last_hash = chain.get_last_block_hash().unwrap().to_string();
+
+        let blocks = handle(&chain, &["blocks"], "limit=1").unwrap();
+        assert_eq!(2, blocks.body["height"]);
+        assert_eq!(1, blocks.body["blocks"].as_array().unwrap().len());
+        assert_eq!(last_hash, blocks.body["blocks"][0]["hash"]);
+
+        let block = handle(&chain, &["block", &last_hash], "").unwrap();
+        assert_eq!(1, block.body["height"]);
+        assert_eq!(1, block.body["transactions"].as_array().unwrap().len());
+
+        let hash = transaction.calculate_hash().to_string();
+        let tx = handle(&chain, &["tx", &hash], "").unwrap();
+        assert_eq!(last_hash, tx.body["block_hash"]);
+        assert_eq!(0, tx.body["index"]);
+        assert_eq!("Success", tx.body["receipt"]["status"]);
+
+        let account = handle(&chain, &["account", "alice"], "").unwrap();
+        assert_eq!("10", account.body["balance"]);
+
+        assert_eq!(404, handle(&chain, &["block", "00"], "").unwrap().status);
+        assert_eq!(
+            404,
+            handle(&chain, &["tx", &"0".repeat(128)], "")
+                .unwrap()
+                .status
+        );
+        assert_eq!(
+            404,
+            handle(&chain, &["account", "carol"], "").unwrap().status
+        );
+        assert!(handle(&chain, &["unknown"], "").is_none());
+    }
+
+    #[test]
+    fn paginated_views() {
+        let (chain, transaction) = chain();
+        let hash = transaction.calculate_hash().to_string();
+
+        let blocks = handle(&chain, &["blocks"], "from=1&limit=5").unwrap();
+        assert_eq!(1, blocks.body["blocks"].as_array().unwrap().len());
+        assert_eq!(1, blocks.body["blocks"][0]["height"]);
+        assert_eq!(400, handle(&chain, &["blocks"], "from=x").unwrap().status);
+
+        let created = handle(&chain, &["transactions"], "kind=CreateUserAccount").unwrap();
+        let created = created.body["transactions"].as_array().unwrap().clone();
+        assert_eq!(hash, created.last().unwrap()["hash"]);
+        assert_eq!(1, created.last().unwrap()["block_height"]);
+        let next = handle(
+            &chain,
+            &["transactions"],
+            "kind=CreateUserAccount&page=1&limit=1",
+        );
+        assert_eq!(created.get(1), next.unwrap().body["transactions"].get(0));
+        assert_eq!(
+            400,
+            handle(&chain, &["transactions"], "kind=Unknown")
+                .unwrap()
+                .status
+        );
+
+        let sent = handle(&chain, &["account", "alice", "transactions"], "limit=100").unwrap();
+        let sent = sent.body["transactions"].as_array().unwrap().clone();
+        assert_eq!(hash, sent.last().unwrap()["hash"]);
+        let none = handle(&chain, &["account", "<bob>", "transactions"], "").unwrap();
+        assert!(none.body["transactions"].as_array().unwrap().is_empty());
+
+        let history = handle(&chain, &["account", "alice", "history"], "page=1&limit=1").unwrap();
+        let history = history.body["history"].as_array().unwrap().clone();
+        assert_eq!(1, history.len());
+        assert_eq!("10", history[0]["delta"]);
+    }
+
+    #[test]
+    fn pruned_views() {
+        let (mut chain, transaction) = chain();
+        chain.set_pruning(Some(0)).unwrap();
+
+        let last_hash = chain.get_last_block_hash().unwrap().to_string();
+        let block = handle(&chain, &["block", &last_hash], "").unwrap();
+        assert_eq!(true, block.body["pruned"]);
+        assert_eq!(0, block.body["transactions"].as_array().unwrap().len());
+
+        let hash = transaction.calculate_hash().to_string();
+        let tx = handle(&chain, &["tx", &hash], "").unwrap();
+        assert_eq!(404, tx.status);
+        assert!(tx.body["error"].as_str().unwrap().contains("pruned"));
+    }
+
+    #[test]
+    fn html_views() {
+        let (chain, _) = chain();
+        let response = handle(&chain, &["account", "<bob>"], "format=html").unwrap();
+        let html = response.html.unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;bob&gt;"));
+        assert!(!html.contains("<bob>"));
+    }
+}
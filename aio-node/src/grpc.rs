@@ -0,0 +1,353 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+
+use blockchain::{Account, Block, Hash, Transaction, WorldState};
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use tracing::warn;
+
+use crate::limits::RateLimiter;
+use crate::p2p::Network;
+
+/// The messages, the server and the client generated out of `proto/node.proto`
+pub mod proto {
+    tonic::include_proto!("aio.node");
+}
+
+use proto::get_block_request::Block as BlockId;
+use proto::node_server::{Node, NodeServer};
+
+/// Will start serving the gRPC API of the node (in a background thread)
+/// and return the address that it is listening on.<br/>
+/// It offers what the HTTP/JSON API does (see `rpc::serve`), with the typed messages
+/// of `proto/node.proto`, held to the same limits (of the rate of each client, the size of
+/// the requests and the time to answer them), until the node is shut down.
+pub fn serve(network: Arc<Network>, addr: SocketAddr) -> io::Result<SocketAddr> {
+    let listener = std::net::TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    thread::spawn(move || {
+        let served = runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            let incoming = TcpIncoming::from_listener(listener, true, None)?;
+            let shutdown = network.shutdown().clone();
+            let stopped = async move {
+                let _ = tokio::task::spawn_blocking(move || shutdown.wait()).await;
+            };
+            let service = NodeServer::new(NodeService {
+                network: Arc::clone(&network),
+            })
+            .max_decoding_message_size(network.limits().max_body_size);
+            Server::builder()
+                .timeout(network.limits().timeout)
+                .add_service(InterceptedService::new(service, rate_limit(&network)))
+                .serve_with_incoming_shutdown(incoming, stopped)
+                .await?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+        });
+        if let Err(err) = served {
+            warn!(error = %err, "gRPC API stopped");
+        }
+    });
+
+    Ok(local_addr)
+}
+
+/// Will return an interceptor refusing the requests of the clients exceeding the rate limit
+// The interceptor has to return tonic's own (large) error
+#[allow(clippy::result_large_err)]
+fn rate_limit(network: &Network) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+    let limiter = Arc::new(RateLimiter::new(network.limits().requests_per_second));
+    move |request| match request.remote_addr() {
+        Some(addr) if !limiter.allow(addr.ip()) => {
+            Err(Status::resource_exhausted("Too many requests"))
+        }
+        _ => Ok(request),
+    }
+}
+
+/// Answers the requests of the gRPC API out of the chain of the network
+struct NodeService {
+    network: Arc<Network>,
+}
+
+#[tonic::async_trait]
+impl Node for NodeService {
+    async fn get_status(
+        &self,
+        _request: Request<proto::GetStatusRequest>,
+    ) -> Result<Response<proto::Status>, Status> {
+        let status = self
+            .network
+            .chain()
+            .query(|chain| proto::Status {
+                chain_id: chain.chain_id().into(),
+                height: chain.len() as u64,
+                last_block_hash: chain.get_last_block_hash().map(|hash| hash.to_hex()),
+                pending_transactions: chain.pending_transactions().len() as u64,
+            })
+            .await;
+        Ok(Response::new(status))
+    }
+
+    async fn get_block(
+        &self,
+        request: Request<proto::GetBlockRequest>,
+    ) -> Result<Response<proto::Block>, Status> {
+        let height = match request.into_inner().block {
+            Some(BlockId::Height(height)) => height as usize,
+            Some(BlockId::Hash(hash)) => {
+                let parsed: Hash = hash.parse().map_err(|_| invalid_hash(&hash))?;
+                self.network
+                    .chain()
+                    .query(move |chain| chain.block_height(&parsed))
+                    .await
+                    .ok_or_else(|| Status::not_found(format!("Block {} does not exist", hash)))?
+            }
+            None => {
+                return Err(Status::invalid_argument(
+                    "Neither a height nor a hash is set",
+                ))
+            }
+        };
+        self.network
+            .chain()
+            .query(move |chain| {
+                let block = chain.blocks().get(height)?;
+                Some(block_message(height, block))
+            })
+            .await
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("There is no block at height {}", height)))
+    }
+
+    async fn get_transaction(
+        &self,
+        request: Request<proto::GetTransactionRequest>,
+    ) -> Result<Response<proto::Transaction>, Status> {
+        let hash = request.into_inner().hash;
+        let parsed: Hash = hash.parse().map_err(|_| invalid_hash(&hash))?;
+        self.network
+            .chain()
+            .query(move |chain| {
+                let transaction = chain.get_transaction(&parsed)?;
+                let location = chain.transaction_location(&parsed)?;
+                Some(transaction_message(
+                    transaction,
+                    Some(location.block_height),
+                ))
+            })
+            .await
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("Transaction {} does not exist", hash)))
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<proto::GetAccountRequest>,
+    ) -> Result<Response<proto::Account>, Status> {
+        let id = request.into_inner().id;
+        self.network
+            .chain()
+            .query(move |chain| {
+                let id = chain.resolve_account_id(&id);
+                match chain.get_account_by_id(&id) {
+                    Some(account) => Ok(account_message(id, account)),
+                    None => Err(id),
+                }
+            })
+            .await
+            .map(Response::new)
+            .map_err(|id| Status::not_found(format!("Account `{}` does not exist", id)))
+    }
+
+    async fn submit_transaction(
+        &self,
+        request: Request<proto::SubmitTransactionRequest>,
+    ) -> Result<Response<proto::SubmitTransactionResponse>, Status> {
+        let transaction: Transaction = serde_json::from_str(&request.into_inner().json)
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let hash = transaction.calculate_hash();
+        // Submitting also broadcasts the transaction to the peers, which blocks
+        let network = Arc::clone(&self.network);
+        tokio::task::spawn_blocking(move || network.submit_transaction(transaction))
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        Ok(Response::new(proto::SubmitTransactionResponse {
+            hash: hash.to_hex(),
+        }))
+    }
+}
+
+fn invalid_hash(hash: &str) -> Status {
+    Status::invalid_argument(format!("Invalid hash: {}", hash))
+}
+
+fn block_message(height: usize, block: &Block) -> proto::Block {
+    proto::Block {
+        height: height as u64,
+        hash: block.hash().map(|hash| hash.to_hex()).unwrap_or_default(),
+        prev_hash: block.prev_hash().map(|hash| hash.to_hex()),
+        timestamp: block.timestamp(),
+        difficulty: block.difficulty(),
+        nonce: block.nonce().to_string(),
+        state_root: block.state_root().map(|hash| hash.to_hex()),
+        transactions_root: block.transactions_root().to_hex(),
+        pruned: block.is_pruned(),
+        transactions: block
+            .transactions()
+            .iter()
+            .map(|transaction| transaction_message(transaction, Some(height)))
+            .collect(),
+    }
+}
+
+fn transaction_message(
+    transaction: &Transaction,
+    block_height: Option<usize>,
+) -> proto::Transaction {
+    let record = serde_json::to_value(transaction.record()).unwrap_or_default();
+    proto::Transaction {
+        hash: transaction.calculate_hash().to_hex(),
+        from: transaction.sender().into(),
+        nonce: transaction.nonce().to_string(),
+        fee: transaction.fee().to_string(),
+        chain_id: transaction.chain_id().into(),
+        created_at: transaction.created_at(),
+        kind: format!("{:?}", transaction.record().kind()),
+        record: record.to_string(),
+        block_height: block_height.map(|height| height as u64),
+    }
+}
+
+fn account_message(id: String, account: &Account) -> proto::Account {
+    proto::Account {
+        id,
+        balance: account.tokens().to_string(),
+        nonce: account.nonce().to_string(),
+        assets: account
+            .assets()
+            .iter()
+            .map(|(asset, balance)| (asset.clone(), balance.to_string()))
+            .collect(),
+        frozen: account.is_frozen(),
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use blockchain::{Blockchain, GenesisConfig, Transaction, TransactionData};
+    use tonic::transport::Channel;
+
+    use super::proto::get_block_request::Block as BlockId;
+    use super::proto::node_client::NodeClient;
+    use super::proto::{
+        GetAccountRequest, GetBlockRequest, GetStatusRequest, GetTransactionRequest,
+        SubmitTransactionRequest,
+    };
+    use super::serve;
+    use crate::limits::Limits;
+    use crate::noise::Identity;
+    use crate::p2p::Network;
+
+    #[test]
+    fn typed_clients_query_and_submit() {
+        let config = GenesisConfig::from_toml(
+            r#"
+            chain_id = "test"
+            [[accounts]]
+            id = "alice"
+            balance = 10
+            "#,
+        )
+        .unwrap();
+        let chain = Blockchain::from_genesis(&config).unwrap();
+        let network = Network::new(
+            crate::actor::spawn(chain).unwrap(),
+            Vec::new(),
+            Limits::default(),
+            Identity::generate(),
+        );
+        let addr = serve(Arc::clone(&network), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let channel = Channel::from_shared(format!("http://{}", addr))
+                .unwrap()
+                .connect()
+                .await
+                .unwrap();
+            let mut client = NodeClient::new(channel);
+
+            let status = client.get_status(GetStatusRequest {}).await.unwrap();
+            assert_eq!("test", status.get_ref().chain_id);
+            assert_eq!(1, status.get_ref().height);
+
+            let account = client
+                .get_account(GetAccountRequest { id: "alice".into() })
+                .await
+                .unwrap();
+            assert_eq!("10", account.get_ref().balance);
+            let missing = client
+                .get_account(GetAccountRequest { id: "bob".into() })
+                .await
+                .unwrap_err();
+            assert_eq!(tonic::Code::NotFound, missing.code());
+
+            let transaction = Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount("bob".into()),
+                0,
+            )
+            .with_chain_id("test".into());
+            let json = serde_json::to_string(&transaction).unwrap();
+            let submitted = client
+                .submit_transaction(SubmitTransactionRequest { json })
+                .await
+                .unwrap();
+            let produced = Arc::clone(&network);
+            tokio::task::spawn_blocking(move || produced.produce_block(1))
+                .await
+                .unwrap()
+                .unwrap();
+
+            let block = client
+                .get_block(GetBlockRequest {
+                    block: Some(BlockId::Height(1)),
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(1, block.transactions.len());
+            assert_eq!("CreateUserAccount", block.transactions[0].kind);
+            assert_eq!(submitted.get_ref().hash, block.transactions[0].hash);
+
+            let included = client
+                .get_transaction(GetTransactionRequest {
+                    hash: submitted.into_inner().hash,
+                })
+                .await
+                .unwrap();
+            assert_eq!(Some(1), included.get_ref().block_height);
+        });
+    }
+}
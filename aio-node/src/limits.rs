@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::p2p::MAX_MESSAGE_SIZE;
+
+/// The amount of IP addresses the rate limiter tracks, before it forgets the idle ones.
+const MAX_TRACKED_ADDRESSES: usize = 10_000;
+
+/// The thresholds protecting the node from misbehaving peers and clients, so that a single
+/// one can't stall it (configured with the options of `Command::Run`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    /// The requests (P2P messages, RPC and gRPC requests) per second that an IP address may
+    /// send on average, whilst it may send as many at once
+    pub requests_per_second: u32,
+
+    /// The size (in bytes) of the biggest P2P message accepted
+    pub max_message_size: u32,
+
+    /// The P2P connections that an IP address may hold open at once
+    pub max_connections_per_ip: usize,
+
+    /// The size (in bytes) of the biggest RPC request body accepted
+    pub max_body_size: usize,
+
+    /// How long a peer (or a client) may take to send a request or receive a response,
+    /// before its connection is closed
+    pub timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            requests_per_second: 100,
+            max_message_size: MAX_MESSAGE_SIZE,
+            max_connections_per_ip: 8,
+            max_body_size: 1024 * 1024,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Limits the rate of the requests of each IP address, with a token bucket: every address
+/// starts with a second's worth of requests, which is refilled at the rate of the limit.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+
+    /// The tokens left of each address, as of the instant
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl RateLimiter {
+    /// C'tor, allowing the requests per second to each address
+    pub fn new(requests_per_second: u32) -> Self {
+        RateLimiter {
+            requests_per_second: f64::from(requests_per_second),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Will take a request of the address into account and return true, unless the address
+    /// exceeds its rate (in which case the request should be refused)
+    pub fn allow(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let capacity = self.requests_per_second;
+        let refill = |(tokens, since): (f64, Instant)| {
+            let elapsed = now.duration_since(since).as_secs_f64();
+            (tokens + elapsed * self.requests_per_second).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_ADDRESSES {
+            // Addresses whose buckets are full again are as good as new
+            buckets.retain(|_, bucket| refill(*bucket) < capacity);
+        }
+        let bucket = buckets.entry(ip).or_insert((capacity, now));
+        let tokens = refill(*bucket);
+        let allowed = tokens >= 1.0;
+        *bucket = (if allowed { tokens - 1.0 } else { tokens }, now);
+        allowed
+    }
+}
+
+/// Counts the open connections of each IP address, so that connections beyond the limits
+/// are refused before anything is exchanged with them.
+#[derive(Debug, Default)]
+pub struct ConnectionLimiter {
+    open: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl ConnectionLimiter {
+    /// Will count a new connection of the address and return it, unless there would be more
+    /// than `max_total` open connections or more than `max_per_ip` of the address
+    pub fn open(&self, ip: IpAddr, max_total: usize, max_per_ip: usize) -> Option<OpenConnection> {
+        let mut open = self.open.lock().unwrap();
+        let total: usize = open.values().sum();
+        let of_ip = open.get(&ip).copied().unwrap_or(0);
+        if total >= max_total || of_ip >= max_per_ip {
+            return None;
+        }
+        open.insert(ip, of_ip + 1);
+        Some(OpenConnection {
+            ip,
+            open: Arc::clone(&self.open),
+        })
+    }
+}
+
+/// A connection counted by a `ConnectionLimiter`, until it is dropped.
+#[derive(Debug)]
+pub struct OpenConnection {
+    ip: IpAddr,
+    open: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        let mut open = self.open.lock().unwrap();
+        if let Some(count) = open.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.ip);
+            }
+        }
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::net::IpAddr;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::RateLimiter;
+
+    #[test]
+    fn requests_are_limited_per_address() {
+        let limiter = RateLimiter::new(5);
+        let (first, second): (IpAddr, IpAddr) =
+            ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+
+        let allowed = (0..10).filter(|_| limiter.allow(first)).count();
+        assert_eq!(5, allowed);
+        assert!(limiter.allow(second));
+
+        // The bucket is refilled at the rate of the limit
+        thread::sleep(Duration::from_millis(450));
+        assert!(limiter.allow(first));
+        assert!(limiter.allow(first));
+        assert!(!limiter.allow(first));
+    }
+}
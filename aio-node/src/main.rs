@@ -1,54 +1,754 @@
-use blockchain::{Block, Blockchain, Transaction, TransactionData};
+use std::fs;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use blockchain::storage::FileStorage;
+use blockchain::{
+    Blockchain, BlockchainError, GenesisAccount, GenesisConfig, Keypair, Keystore, SignatureScheme,
+    Snapshot, Transaction, Wallet,
+};
+use clap::{Parser, Subcommand};
+use config::NodeConfig;
+use node::Node;
+use tracing_subscriber::EnvFilter;
+
+mod actor;
+mod config;
+mod discovery;
+mod explorer;
+mod grpc;
+mod limits;
+mod metrics;
+mod node;
+mod noise;
+mod p2p;
+mod peers;
+mod repl;
+mod rpc;
+mod shutdown;
+mod sync;
+mod ws;
+
+/// The genesis of the development network, used if no genesis file is provided.
+const DEV_GENESIS: &str = include_str!("../genesis/dev.toml");
+
+/// The mnemonic seed phrase of the pre-funded accounts of a `--dev` chain (well known, like
+/// the ones of the Ethereum development chains, so never to be used for real funds).
+const DEV_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+/// The amount of pre-funded accounts of a `--dev` chain (derived from `DEV_MNEMONIC`).
+const DEV_ACCOUNTS: u32 = 10;
+
+/// The amount of tokens every pre-funded account of a `--dev` chain starts with.
+const DEV_BALANCE: u128 = 1_000_000_000;
+
+/// The time (unix epoch, in milliseconds) of the genesis block of a `--dev` chain, so that
+/// its hash is the same on every machine: 2020-01-01.
+const DEV_GENESIS_TIMESTAMP: u64 = 1_577_836_800_000;
+
+/// The file (inside the data directory) holding the genesis configuration of the network.
+const GENESIS_FILE: &str = "genesis.json";
+
+/// The directory (inside the data directory) holding the blocks and the world state.
+const CHAIN_DIR: &str = "chain";
+
+/// The directory (inside the data directory) holding the encrypted keys of the wallet.
+const KEYSTORE_DIR: &str = "keystore";
+
+/// The file (inside the data directory) holding the key that identifies the node to its peers.
+const NODE_KEY_FILE: &str = "node.key";
+
+/// An all-in-one node of the blockchain.
+#[derive(Debug, Parser)]
+#[command(name = "aio-node", version)]
+struct Cli {
+    /// The directory holding the node's data
+    #[arg(long, global = true, default_value = "data")]
+    data_dir: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Initializes the data directory for the network described by the genesis file
+    Init {
+        /// The genesis configuration (TOML or JSON), the development network if not set
+        #[arg(long)]
+        genesis: Option<PathBuf>,
+
+        /// Joins an existing network: the genesis block is synced from the peers
+        /// instead of being created
+        #[arg(long)]
+        join: bool,
+    },
+
+    /// Runs the node, producing a block out of its mempool every few seconds, as configured
+    /// by the configuration file, the environment and the flags (see `NodeConfig`)
+    Run {
+        #[command(flatten)]
+        overrides: Box<config::ConfigOverrides>,
+
+        /// Runs a local development chain: the data directory is initialized (if needed)
+        /// with accounts pre-funded out of a well-known mnemonic, whilst a block is produced
+        /// as soon as a transaction arrives (unless `--block-interval` is set)
+        #[arg(long)]
+        dev: bool,
+
+        /// The password the key of the validator is encrypted with
+        #[arg(long, env = "AIO_NODE_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+
+    /// Drives the chain of the data directory interactively (e.g. `balance alice`,
+    /// `transfer alice bob 10`, `mine`, `validate`, `dump block 3`, see `help`)
+    Repl,
+
+    /// Exports the blocks of the chain to a (portable, binary) block file
+    ExportChain {
+        /// The file to write the blocks to
+        file: PathBuf,
+    },
+
+    /// Imports (and validates) the blocks of a file, as written by `export-chain`
+    ImportChain {
+        /// The file to read the blocks from
+        file: PathBuf,
+    },
+
+    /// Writes a snapshot (the block headers and the world state) of the chain to a file
+    ExportSnapshot {
+        /// The file to write the snapshot to
+        file: PathBuf,
+    },
+
+    /// Restores the chain of a node (initialized with `--join`) out of a snapshot,
+    /// as written by `export-snapshot`
+    ImportSnapshot {
+        /// The file to read the snapshot from
+        file: PathBuf,
+    },
+
+    /// Manages the (encrypted) keys of the wallet
+    Wallet {
+        #[command(subcommand)]
+        command: WalletCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum WalletCommand {
+    /// Generates a new key and prints its address
+    New {
+        /// The password the key is encrypted with
+        #[arg(long, env = "AIO_NODE_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+
+    /// Derives a key of a mnemonic seed phrase, stores it and prints its address
+    Recover {
+        /// The (BIP39) mnemonic seed phrase
+        #[arg(long, env = "AIO_NODE_MNEMONIC", hide_env_values = true)]
+        mnemonic: String,
+
+        /// The passphrase protecting the mnemonic, if any
+        #[arg(long, default_value = "")]
+        passphrase: String,
+
+        /// The index of the account to derive
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+
+        /// The password the key is encrypted with
+        #[arg(long, env = "AIO_NODE_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+
+    /// Imports a (hex encoded) secret key, stores it and prints its address
+    Import {
+        /// The 32 bytes secret key (e.g. the private key of an Ethereum account)
+        #[arg(long, env = "AIO_NODE_SECRET_KEY", hide_env_values = true)]
+        secret_key: String,
+
+        /// The key is a secp256k1 one, rather than an Ed25519 one
+        #[arg(long)]
+        secp256k1: bool,
+
+        /// The password the key is encrypted with
+        #[arg(long, env = "AIO_NODE_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+
+    /// Lists the addresses of all stored keys
+    List,
+
+    /// Signs a (JSON encoded) transaction with the key of its sender
+    Sign {
+        /// The file to read the transaction from
+        file: PathBuf,
+
+        /// Adds the signature of this owner of the (multisignature) sender instead
+        #[arg(long)]
+        cosigner: Option<String>,
+
+        /// The password the key is encrypted with
+        #[arg(long, env = "AIO_NODE_PASSWORD", hide_env_values = true)]
+        password: String,
+
+        /// The file to write the signed transaction to, stdout if not set
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Prints the (hex encoded) bytes to sign of a (JSON encoded) transaction, to sign it
+    /// offline (e.g. with a hardware wallet)
+    SignBytes {
+        /// The file to read the transaction from
+        file: PathBuf,
+    },
+
+    /// Attaches a signature produced offline (of the bytes printed by `sign-bytes`) to a
+    /// (JSON encoded) transaction, once it is verified
+    Attach {
+        /// The file to read the transaction from
+        file: PathBuf,
+
+        /// The (hex encoded) signature
+        #[arg(long)]
+        signature: String,
+
+        /// The (hex encoded) public key of the signer: 32 bytes for Ed25519, 33 (compressed)
+        /// bytes for secp256k1
+        #[arg(long)]
+        public_key: String,
+
+        /// The file to write the signed transaction to, stdout if not set
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
 
 fn main() {
-    // Create a new Blockchain
-    let mut bc = Blockchain::new();
+    let cli = Cli::parse();
 
-    // Create an empty block (first block has no prev_block)
-    let mut genesis = Block::new(None);
+    // The configuration of a running node filters its logs
+    let config = match &cli.command {
+        Command::Run { overrides, dev, .. } => {
+            match NodeConfig::resolve(overrides.config.as_deref(), &cli.data_dir) {
+                Ok(mut config) => {
+                    if *dev {
+                        config = config.dev();
+                    }
+                    overrides.apply(&mut config);
+                    Some(config)
+                }
+                Err(err) => exit_with(err),
+            }
+        }
+        _ => None,
+    };
 
-    let initial_users = vec!["alice", "bob"];
+    // The logs (filtered by `RUST_LOG`, info by default) go to stderr, the output to stdout
+    let filter = match &config {
+        Some(config) => EnvFilter::try_new(&config.logging.level).unwrap_or_else(|err| {
+            exit_with(BlockchainError::InvalidConfig(format!(
+                "Invalid level of the logs: {}",
+                err
+            )))
+        }),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
 
-    for user in initial_users {
-        let create_account_txn = Transaction::new(
-            user.into(),
-            TransactionData::CreateUserAccount(user.into()),
-            0,
+    let result = match cli.command {
+        Command::Init { genesis, join } => init(&cli.data_dir, genesis.as_deref(), join),
+        Command::Run { dev, password, .. } => {
+            let config = config.expect("the configuration of the node");
+            let initialized = match dev {
+                true => init_dev(&cli.data_dir),
+                false => Ok(()),
+            };
+            let data_dir = &cli.data_dir;
+            initialized.and_then(|()| run(data_dir, &config, password.as_deref()))
+        }
+        Command::Repl => repl(&cli.data_dir),
+        Command::ExportChain { file } => export_chain(&cli.data_dir, &file),
+        Command::ImportChain { file } => import_chain(&cli.data_dir, &file),
+        Command::ExportSnapshot { file } => export_snapshot(&cli.data_dir, &file),
+        Command::ImportSnapshot { file } => import_snapshot(&cli.data_dir, &file),
+        Command::Wallet { command } => wallet(&cli.data_dir, command),
+    };
+
+    if let Err(err) = result {
+        exit_with(err);
+    }
+}
+
+fn exit_with(err: BlockchainError) -> ! {
+    eprintln!("Error: {}", err);
+    process::exit(1);
+}
+
+/// Initializes the data directory for the network of the genesis file (the development
+/// network if not set), see `init_with`.
+fn init(data_dir: &Path, genesis: Option<&Path>, join: bool) -> Result<(), BlockchainError> {
+    let config = match genesis {
+        Some(path) => GenesisConfig::load(path)?,
+        None => GenesisConfig::from_toml(DEV_GENESIS)?,
+    };
+    init_with(data_dir, &config, join)
+}
+
+/// Initializes the data directory (unless it is already) for a development chain, whose
+/// genesis is the one of the development network plus the accounts of `dev_accounts`, and
+/// prints these accounts.
+fn init_dev(data_dir: &Path) -> Result<(), BlockchainError> {
+    let accounts = dev_accounts()?;
+    if !data_dir.join(GENESIS_FILE).exists() {
+        let mut config = GenesisConfig::from_toml(DEV_GENESIS)?;
+        config.timestamp = Some(DEV_GENESIS_TIMESTAMP);
+        config
+            .accounts
+            .extend(accounts.iter().map(|keypair| GenesisAccount {
+                id: keypair.address().to_string(),
+                balance: DEV_BALANCE,
+                stake: 0,
+                admin: false,
+            }));
+        init_with(data_dir, &config, false)?;
+    }
+
+    println!("Development accounts (mnemonic `{}`):", DEV_MNEMONIC);
+    for (index, keypair) in accounts.iter().enumerate() {
+        println!(
+            "  ({}) {} (private key {})",
+            index,
+            keypair.address(),
+            hex::encode(keypair.secret_bytes())
         );
+    }
+    Ok(())
+}
+
+/// Will return the keypairs of the pre-funded accounts of a development chain, derived
+/// from `DEV_MNEMONIC` (see `aio-node wallet recover --index`)
+fn dev_accounts() -> Result<Vec<Keypair>, BlockchainError> {
+    let wallet = Wallet::from_mnemonic(DEV_MNEMONIC, "")?;
+    Ok((0..DEV_ACCOUNTS)
+        .map(|index| wallet.derive_account(index))
+        .collect())
+}
+
+/// Stores the genesis configuration in the data directory and creates the genesis block
+/// (unless the node joins an existing network).
+fn init_with(data_dir: &Path, config: &GenesisConfig, join: bool) -> Result<(), BlockchainError> {
+    let genesis_file = data_dir.join(GENESIS_FILE);
+    if genesis_file.exists() {
+        return Err(BlockchainError::InvalidConfig(format!(
+            "{} is already initialized",
+            data_dir.display()
+        )));
+    }
+    fs::create_dir_all(data_dir).map_err(io_error)?;
+    let json = serde_json::to_string_pretty(config)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()))?;
+    fs::write(&genesis_file, json).map_err(io_error)?;
+
+    let mut chain = open_chain(data_dir)?;
+    if !join {
+        let genesis = Blockchain::from_genesis(config)?.blocks()[0].clone();
+        chain.append_block(genesis)?;
+    }
+    chain.flush()?;
 
-        let create_token_txn = Transaction::new(
-            user.into(),
-            TransactionData::CreateTokens {
-                receiver: user.into(),
-                amount: 100_000_000,
-            },
+    println!(
+        "Initialized chain `{}` in {} ({} blocks)",
+        config.chain_id,
+        data_dir.display(),
+        chain.len()
+    );
+    Ok(())
+}
+
+/// Runs a networked node (see `Node::start`) until it is interrupted (by `SIGINT` or
+/// `SIGTERM`), when it is shut down gracefully (see `Node::shutdown`).
+fn run(
+    data_dir: &Path,
+    config: &NodeConfig,
+    password: Option<&str>,
+) -> Result<(), BlockchainError> {
+    let node = Node::start(data_dir, config, password)?;
+    wait_for_signal().map_err(io_error)?;
+    node.shutdown()
+}
+
+/// Will block until the process is interrupted (or, on Unix, asked to terminate)
+fn wait_for_signal() -> std::io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut terminate = signal(SignalKind::terminate())?;
+            tokio::select! {
+                interrupted = tokio::signal::ctrl_c() => interrupted,
+                _ = terminate.recv() => Ok(()),
+            }
+        }
+        #[cfg(not(unix))]
+        tokio::signal::ctrl_c().await
+    })
+}
+
+/// Runs the REPL (see `repl::run`) on the terminal, against the chain of the data directory
+/// (which no running node may use at the same time).
+fn repl(data_dir: &Path) -> Result<(), BlockchainError> {
+    let mut chain = open_chain(data_dir)?;
+    repl::run(&mut chain, std::io::stdin().lock(), std::io::stdout())
+}
+
+/// Writes all blocks of the chain to the file (see `Blockchain::export_blocks`).
+fn export_chain(data_dir: &Path, file: &Path) -> Result<(), BlockchainError> {
+    let chain = open_chain(data_dir)?;
+    let writer = BufWriter::new(fs::File::create(file).map_err(io_error)?);
+    let exported = chain.export_blocks(writer)?;
+
+    println!("Exported {} blocks to {}", exported, file.display());
+    Ok(())
+}
+
+/// Appends the blocks of the file that the chain doesn't have yet.<br/>
+/// Every block is validated, just like the ones received from peers.
+fn import_chain(data_dir: &Path, file: &Path) -> Result<(), BlockchainError> {
+    let reader = BufReader::new(fs::File::open(file).map_err(io_error)?);
+    let mut chain = open_chain(data_dir)?;
+    let imported = chain.import_blocks(reader)?;
+    chain.flush()?;
+
+    println!("Imported {} blocks", imported);
+    Ok(())
+}
+
+/// Writes a snapshot of the chain to the file (in the binary format).
+fn export_snapshot(data_dir: &Path, file: &Path) -> Result<(), BlockchainError> {
+    let snapshot = open_chain(data_dir)?.snapshot();
+    fs::write(file, snapshot.to_bytes()?).map_err(io_error)?;
+
+    println!(
+        "Exported the snapshot at height {} to {}",
+        snapshot.height(),
+        file.display()
+    );
+    Ok(())
+}
+
+/// Restores the (empty) chain of the data directory out of the snapshot of the file.<br/>
+/// The snapshot is validated against the genesis configuration of the network.
+fn import_snapshot(data_dir: &Path, file: &Path) -> Result<(), BlockchainError> {
+    let config = load_config(data_dir)?;
+    let snapshot = Snapshot::from_bytes(&fs::read(file).map_err(io_error)?)?;
+    let storage = FileStorage::open(data_dir.join(CHAIN_DIR))?;
+    let chain =
+        Blockchain::restore_from_snapshot_with(Box::new(storage), snapshot, config.policy())?;
+
+    println!("Restored the chain at height {}", chain.len());
+    Ok(())
+}
+
+/// Runs a wallet command against the keystore of the data directory.
+fn wallet(data_dir: &Path, command: WalletCommand) -> Result<(), BlockchainError> {
+    let keystore = Keystore::open(data_dir.join(KEYSTORE_DIR))?;
+    match command {
+        WalletCommand::New { password } => {
+            println!("{}", keystore.generate(&password)?);
+        }
+        WalletCommand::Recover {
+            mnemonic,
+            passphrase,
+            index,
+            password,
+        } => {
+            let keypair = Wallet::from_mnemonic(&mnemonic, &passphrase)?.derive_account(index);
+            println!("{}", keystore.import(&keypair, &password)?);
+        }
+        WalletCommand::Import {
+            secret_key,
+            secp256k1,
+            password,
+        } => {
+            let scheme = match secp256k1 {
+                true => SignatureScheme::Secp256k1,
+                false => SignatureScheme::Ed25519,
+            };
+            let keypair = parse_secret_key(&secret_key, scheme)?;
+            println!("{}", keystore.import(&keypair, &password)?);
+        }
+        WalletCommand::List => {
+            for address in keystore.addresses()? {
+                println!("{}", address);
+            }
+        }
+        WalletCommand::Sign {
+            file,
+            cosigner,
+            password,
+            output,
+        } => {
+            let json = sign_transaction(&keystore, &file, cosigner.as_deref(), &password)?;
+            match output {
+                Some(output) => fs::write(output, json).map_err(io_error)?,
+                None => println!("{}", json),
+            }
+        }
+        WalletCommand::SignBytes { file } => {
+            println!("{}", hex::encode(read_transaction(&file)?.sign_bytes()));
+        }
+        WalletCommand::Attach {
+            file,
+            signature,
+            public_key,
+            output,
+        } => {
+            let json = attach_signature(&file, &signature, &public_key)?;
+            match output {
+                Some(output) => fs::write(output, json).map_err(io_error)?,
+                None => println!("{}", json),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Will parse the hex encoded (optionally `0x` prefixed) secret key of the scheme
+fn parse_secret_key(secret_key: &str, scheme: SignatureScheme) -> Result<Keypair, BlockchainError> {
+    let invalid =
+        || BlockchainError::Keystore("The secret key has to be 32 hex encoded bytes".into());
+    let secret_key = secret_key.strip_prefix("0x").unwrap_or(secret_key);
+    let mut secret = [0u8; 32];
+    hex::decode_to_slice(secret_key, &mut secret).map_err(|_| invalid())?;
+    Keypair::from_scheme_secret_bytes(scheme, &secret)
+}
+
+/// Signs the transaction of the file (as its sender or as a cosigner),
+/// returning it (JSON encoded).
+fn sign_transaction(
+    keystore: &Keystore,
+    file: &Path,
+    cosigner: Option<&str>,
+    password: &str,
+) -> Result<String, BlockchainError> {
+    let mut transaction = read_transaction(file)?;
+    match cosigner {
+        Some(cosigner) => keystore.cosign(&mut transaction, cosigner, password)?,
+        None => keystore.sign(&mut transaction, password)?,
+    }
+    serde_json::to_string_pretty(&transaction)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()))
+}
+
+/// Attaches the (hex encoded) signature of the signer with the (hex encoded) public key to the
+/// transaction of the file, returning it (JSON encoded).
+fn attach_signature(
+    file: &Path,
+    signature: &str,
+    public_key: &str,
+) -> Result<String, BlockchainError> {
+    let mut transaction = read_transaction(file)?;
+    let decode = |value: &str| {
+        hex::decode(value.strip_prefix("0x").unwrap_or(value))
+            .map_err(|_| BlockchainError::InvalidSignature)
+    };
+    transaction.attach_signature(&decode(signature)?, &decode(public_key)?)?;
+    serde_json::to_string_pretty(&transaction)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()))
+}
+
+/// Reads the (JSON encoded) transaction of the file.
+fn read_transaction(file: &Path) -> Result<Transaction, BlockchainError> {
+    let json = fs::read_to_string(file).map_err(io_error)?;
+    serde_json::from_str(&json).map_err(|err| BlockchainError::Serialization(err.to_string()))
+}
+
+/// Opens the chain stored in the data directory, using the policy of its network.
+fn open_chain(data_dir: &Path) -> Result<Blockchain, BlockchainError> {
+    let config = load_config(data_dir)?;
+    let storage = FileStorage::open(data_dir.join(CHAIN_DIR))?;
+    Blockchain::open_with(Box::new(storage), config.policy())
+}
+
+/// Loads the genesis configuration stored in the data directory (by `init`).
+fn load_config(data_dir: &Path) -> Result<GenesisConfig, BlockchainError> {
+    GenesisConfig::load(data_dir.join(GENESIS_FILE)).map_err(|err| {
+        BlockchainError::InvalidConfig(format!("{} (run `aio-node init` first)", err))
+    })
+}
+
+fn io_error(err: std::io::Error) -> BlockchainError {
+    BlockchainError::Storage(err.to_string())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use clap::CommandFactory;
+
+    use blockchain::{Keystore, SignatureScheme, Transaction, TransactionData};
+
+    use super::{
+        attach_signature, dev_accounts, export_chain, export_snapshot, import_chain,
+        import_snapshot, init, init_dev, open_chain, parse_secret_key, read_transaction,
+        sign_transaction, Cli, DEV_BALANCE,
+    };
+
+    #[test]
+    fn cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn export_and_import_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        let file = dir.path().join("chain.json");
+
+        init(&source, None, false).unwrap();
+        init(&target, None, true).unwrap();
+        assert!(init(&source, None, false).is_err());
+        assert_eq!(0, open_chain(&target).unwrap().len());
+
+        export_chain(&source, &file).unwrap();
+        import_chain(&target, &file).unwrap();
+        // Importing again doesn't append anything
+        import_chain(&target, &file).unwrap();
+
+        let source = open_chain(&source).unwrap();
+        let target = open_chain(&target).unwrap();
+        assert_eq!(1, target.len());
+        assert_eq!(source.get_last_block_hash(), target.get_last_block_hash());
+        assert_eq!(source.state_root(), target.state_root());
+    }
+
+    #[test]
+    fn dev_chains_are_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("first"), dir.path().join("second"));
+        init_dev(&first).unwrap();
+        init_dev(&second).unwrap();
+        // An initialized data directory is kept
+        init_dev(&first).unwrap();
+
+        let (first, second) = (open_chain(&first).unwrap(), open_chain(&second).unwrap());
+        assert_eq!(1, first.len());
+        assert_eq!(first.get_last_block_hash(), second.get_last_block_hash());
+        for keypair in dev_accounts().unwrap() {
+            let address = keypair.address().to_string();
+            let balance = first.get_account(&address).map(|account| account.tokens());
+            assert_eq!(Some(DEV_BALANCE), balance);
+        }
+    }
+
+    #[test]
+    fn export_and_import_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        let file = dir.path().join("snapshot.bin");
+
+        init(&source, None, false).unwrap();
+        init(&target, None, true).unwrap();
+        export_snapshot(&source, &file).unwrap();
+        import_snapshot(&target, &file).unwrap();
+        // The chain is restored already
+        assert!(import_snapshot(&target, &file).is_err());
+
+        let source = open_chain(&source).unwrap();
+        let target = open_chain(&target).unwrap();
+        assert_eq!(source.get_last_block_hash(), target.get_last_block_hash());
+        assert_eq!(source.state_root(), target.state_root());
+    }
+
+    #[test]
+    fn sign_transaction_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = Keystore::open(dir.path().join("keystore"))
+            .unwrap()
+            .with_kdf_log_n(4);
+        let address = keystore.generate("secret").unwrap();
+
+        let file = dir.path().join("transaction.json");
+        let transaction = Transaction::new(
+            address.clone(),
+            TransactionData::CreateUserAccount("bob".into()),
             0,
         );
+        std::fs::write(&file, serde_json::to_string(&transaction).unwrap()).unwrap();
+
+        let json = sign_transaction(&keystore, &file, None, "secret").unwrap();
+        let signed: Transaction = serde_json::from_str(&json).unwrap();
+        assert!(signed.check_signature());
+        assert!(sign_transaction(&keystore, &file, None, "guess").is_err());
+
+        let json = sign_transaction(&keystore, &file, Some(&address), "secret").unwrap();
+        let cosigned: Transaction = serde_json::from_str(&json).unwrap();
+        assert!(cosigned.signers().contains(&address));
+    }
 
-        genesis.add_transaction(create_account_txn);
+    #[test]
+    fn secp256k1_keys_sign_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = Keystore::open(dir.path().join("keystore"))
+            .unwrap()
+            .with_kdf_log_n(4);
+        let secret = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        let keypair = parse_secret_key(secret, SignatureScheme::Secp256k1).unwrap();
+        assert!(parse_secret_key("0xac09", SignatureScheme::Secp256k1).is_err());
+        let address = keystore.import(&keypair, "secret").unwrap();
 
-        genesis.add_transaction(create_token_txn);
+        let file = dir.path().join("transaction.json");
+        let transaction =
+            Transaction::new(address, TransactionData::CreateUserAccount("bob".into()), 0);
+        std::fs::write(&file, serde_json::to_string(&transaction).unwrap()).unwrap();
+
+        let json = sign_transaction(&keystore, &file, None, "secret").unwrap();
+        let signed: Transaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(SignatureScheme::Secp256k1, signed.scheme());
+        assert!(signed.check_signature());
     }
 
-    let mut res = bc.append_block(genesis);
-    println!("Genesis block successfully added: {:?}", res);
-    println!("Full blockchain printout");
-    println!("{:#?}", bc);
+    #[test]
+    fn transactions_are_signed_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = dev_accounts().unwrap().remove(0);
+        let file = dir.path().join("transaction.json");
+        let transaction = Transaction::new(
+            keypair.address().to_string(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        std::fs::write(&file, serde_json::to_string(&transaction).unwrap()).unwrap();
 
-    // Transfer 1 token from alice to bob
-    let mut block2 = Block::new(bc.get_last_block_hash());
-    block2.add_transaction(Transaction::new(
-        "alice".into(),
-        TransactionData::TransferTokens {
-            to: "bob".into(),
-            amount: 1,
-        },
-        0,
-    ));
+        // The signing device signs the bytes to sign (like `sign` does)
+        let mut signed = read_transaction(&file).unwrap();
+        signed.sign(&keypair);
+        assert_eq!(transaction.sign_bytes(), signed.sign_bytes());
+        let json = serde_json::to_value(&signed).unwrap();
+        let signature = json["signature"].as_str().unwrap();
 
-    res = bc.append_block(block2);
-    println!("Block added: {:?}", res);
-    println!("Full blockchain printout");
-    println!("{:#?}", bc);
-    println!("Blockchain valid: {:?}", bc.check_validity());
+        assert!(attach_signature(&file, "zz", &keypair.public_key()).is_err());
+        let json = attach_signature(&file, signature, &keypair.public_key()).unwrap();
+        let attached: Transaction = serde_json::from_str(&json).unwrap();
+        assert!(attached.check_signature());
+    }
 }
@@ -0,0 +1,132 @@
+use std::fmt::Write;
+
+use blockchain::Blockchain;
+
+/// Will render the metrics of the chain in the (text based) exposition format of Prometheus,
+/// as served by the `/metrics` endpoint of the RPC API.<br/>
+/// The throughput of the transactions is the rate of `aio_transactions_executed_total`,
+/// whilst the average execution time of a block is the rate of
+/// `aio_block_execution_seconds_sum` divided by the one of `aio_block_execution_seconds_count`.
+pub fn render(chain: &Blockchain) -> String {
+    let metrics = chain.metrics();
+    let mut text = String::new();
+
+    metric(
+        &mut text,
+        "aio_chain_height",
+        "gauge",
+        "The amount of blocks of the chain",
+    );
+    sample(&mut text, "aio_chain_height", "", chain.len());
+
+    metric(
+        &mut text,
+        "aio_mempool_size",
+        "gauge",
+        "The amount of transactions waiting to be included in a block",
+    );
+    sample(
+        &mut text,
+        "aio_mempool_size",
+        "",
+        chain.pending_transactions().len(),
+    );
+
+    metric(
+        &mut text,
+        "aio_transactions_executed_total",
+        "counter",
+        "The amount of transactions executed by the appended blocks",
+    );
+    sample(
+        &mut text,
+        "aio_transactions_executed_total",
+        "",
+        metrics.transactions_executed,
+    );
+
+    metric(
+        &mut text,
+        "aio_block_execution_seconds",
+        "summary",
+        "The time spent on checking and executing the appended blocks",
+    );
+    sample(
+        &mut text,
+        "aio_block_execution_seconds_sum",
+        "",
+        metrics.block_execution_time.as_secs_f64(),
+    );
+    sample(
+        &mut text,
+        "aio_block_execution_seconds_count",
+        "",
+        metrics.blocks_appended,
+    );
+
+    metric(
+        &mut text,
+        "aio_blocks_rejected_total",
+        "counter",
+        "The amount of rejected blocks, by the reason of the rejection",
+    );
+    for (reason, count) in &metrics.blocks_rejected {
+        let labels = format!("{{reason=\"{}\"}}", reason);
+        sample(&mut text, "aio_blocks_rejected_total", &labels, count);
+    }
+
+    metric(
+        &mut text,
+        "aio_transactions_evicted_total",
+        "counter",
+        "The amount of transactions evicted from the mempool, by the reason of the eviction",
+    );
+    for (reason, count) in &metrics.transactions_evicted {
+        let labels = format!("{{reason=\"{}\"}}", reason);
+        sample(&mut text, "aio_transactions_evicted_total", &labels, count);
+    }
+    text
+}
+
+/// Will describe the metric (by its `HELP` and `TYPE` lines)
+fn metric(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+}
+
+/// Will add a sample of the metric
+fn sample<V: std::fmt::Display>(text: &mut String, name: &str, labels: &str, value: V) {
+    let _ = writeln!(text, "{}{} {}", name, labels, value);
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use blockchain::{Block, Blockchain, Transaction, TransactionData};
+
+    use super::render;
+
+    #[test]
+    fn renders_the_exposition_format() {
+        let mut chain = Blockchain::new();
+        let mut genesis = Block::new(None);
+        genesis.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("alice".into()),
+            0,
+        ));
+        chain.append_block(genesis.clone()).unwrap();
+        assert!(chain.append_block(genesis).is_err());
+
+        let text = render(&chain);
+        assert!(text.contains("# TYPE aio_chain_height gauge\naio_chain_height 1\n"));
+        assert!(text.contains("\naio_mempool_size 0\n"));
+        assert!(text.contains("\naio_transactions_executed_total 1\n"));
+        assert!(text.contains("\naio_block_execution_seconds_count 1\n"));
+        assert!(text.contains("\naio_blocks_rejected_total{reason=\"prev_hash_mismatch\"} 1\n"));
+    }
+}
@@ -0,0 +1,302 @@
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use blockchain::{BlockchainError, Keystore, Transaction};
+use tracing::{info, warn};
+
+use crate::actor::{self, ChainClient};
+use crate::config::NodeConfig;
+use crate::noise::Identity;
+use crate::p2p::Network;
+use crate::{discovery, grpc, io_error, open_chain, rpc, ws, KEYSTORE_DIR, NODE_KEY_FILE};
+
+/// The file (inside the data directory) holding the transactions that were pending when the
+/// node was shut down, which are submitted again when it starts.
+const MEMPOOL_FILE: &str = "mempool.json";
+
+/// How often the mempool is checked for transactions when blocks are produced as soon as
+/// transactions arrive (see `ConsensusConfig::block_interval`).
+const INSTANT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A running node: the network, the APIs and the block production, sharing the chain of the
+/// data directory.
+pub struct Node {
+    data_dir: PathBuf,
+
+    network: Arc<Network>,
+
+    /// The address of the RPC API, if it is served
+    rpc_addr: Option<SocketAddr>,
+
+    /// Produces a block every block interval, until the node is shut down
+    producer: JoinHandle<()>,
+}
+
+impl Node {
+    /// Will start the node out of the data directory (see `aio-node init`), as configured:
+    /// it listens for peers, bootstraps and syncs with them, serves the APIs and produces
+    /// a block every block interval (signed by the validator, if set, whose key is decrypted
+    /// with the password).
+    pub fn start(
+        data_dir: &Path,
+        config: &NodeConfig,
+        password: Option<&str>,
+    ) -> Result<Self, BlockchainError> {
+        let validator = match &config.consensus.validator {
+            Some(address) => {
+                let password = password.ok_or_else(|| {
+                    BlockchainError::InvalidConfig("The validator's password is not set".into())
+                })?;
+                Some(Keystore::open(data_dir.join(KEYSTORE_DIR))?.load(address, password)?)
+            }
+            None => None,
+        };
+
+        let mut chain = open_chain(data_dir)?;
+        chain.set_pruning(config.storage.keep_blocks)?;
+        let chain = actor::spawn(chain).map_err(io_error)?;
+        restore_mempool(&chain, &data_dir.join(MEMPOOL_FILE))?;
+        let mut identity =
+            Identity::load_or_generate(&data_dir.join(NODE_KEY_FILE)).map_err(io_error)?;
+        identity.allow(config.network.allowed_peers.iter().copied());
+        info!(id = %identity.id(), "Node identity");
+        let network = Network::new(
+            chain,
+            config.network.peers.clone(),
+            config.network.limits(),
+            identity,
+        );
+        network.peers().set_max_peers(config.network.max_peers);
+        let addr = network.listen(config.network.listen).map_err(io_error)?;
+        info!(%addr, "Node is listening");
+
+        network.bootstrap(&config.network.bootnodes, addr.port());
+        info!(peers = network.peers().len(), "Node is bootstrapped");
+        if config.network.mdns {
+            // The node still runs with the configured peers, if the local network can't be
+            // joined
+            match discovery::spawn(Arc::clone(&network), addr.port()) {
+                Ok(()) => info!("Discovering peers on the local network"),
+                Err(err) => warn!(error = %err, "Could not start the mDNS discovery"),
+            }
+        }
+        let has_peers = !network.peers().is_empty();
+
+        let mut rpc_addr = None;
+        if let Some(port) = config.rpc.port {
+            let addr = rpc::serve(Arc::clone(&network), SocketAddr::from(([0, 0, 0, 0], port)))
+                .map_err(io_error)?;
+            info!(%addr, "RPC API is served");
+            rpc_addr = Some(addr);
+        }
+
+        if let Some(port) = config.rpc.ws_port {
+            let addr = ws::serve(Arc::clone(&network), SocketAddr::from(([0, 0, 0, 0], port)))
+                .map_err(io_error)?;
+            info!(%addr, "Subscription API is served");
+        }
+
+        if let Some(port) = config.rpc.grpc_port {
+            let addr = grpc::serve(Arc::clone(&network), SocketAddr::from(([0, 0, 0, 0], port)))
+                .map_err(io_error)?;
+            info!(%addr, "gRPC API is served");
+        }
+
+        if has_peers {
+            if let Err(err) = network.sync() {
+                warn!(error = %err, "Could not sync the chain");
+            }
+            info!(status = ?network.sync_status(), "Sync finished");
+        }
+
+        let producer = {
+            let network = Arc::clone(&network);
+            let instant = config.consensus.block_interval == 0;
+            let interval = match instant {
+                true => INSTANT_POLL_INTERVAL,
+                false => Duration::from_secs(config.consensus.block_interval),
+            };
+            let max_txs = config.consensus.max_block_transactions;
+            thread::spawn(move || {
+                while !network.shutdown().wait_timeout(interval) {
+                    let idle = instant
+                        && network
+                            .chain()
+                            .blocking_query(|chain| chain.pending_transactions().is_empty());
+                    if idle {
+                        continue;
+                    }
+                    let produced = match &validator {
+                        Some(keypair) => network.produce_signed_block(max_txs, keypair),
+                        None => network.produce_block(max_txs),
+                    };
+                    match produced {
+                        Ok(()) => info!("Produced a new block"),
+                        Err(err) => info!(reason = %err, code = err.code(), "No block produced"),
+                    }
+                    let flushed = network.chain().blocking_query(|chain| chain.flush());
+                    if let Err(err) = flushed {
+                        warn!(error = %err, "Could not persist the world state");
+                    }
+                }
+            })
+        };
+
+        Ok(Node {
+            data_dir: data_dir.to_path_buf(),
+            network,
+            rpc_addr,
+            producer,
+        })
+    }
+
+    /// Will shut the node down: the servers stop accepting connections (the requests being
+    /// served are completed), the block being produced is appended and the world state and
+    /// the pending transactions (see `MEMPOOL_FILE`) are persisted, before the chain is
+    /// dropped.
+    pub fn shutdown(self) -> Result<(), BlockchainError> {
+        info!("Shutting down");
+        self.network.shutdown().request();
+        if let Some(addr) = self.rpc_addr {
+            info!(%addr, "RPC API is stopped");
+        }
+        if self.producer.join().is_err() {
+            warn!("The block production failed");
+        }
+
+        // The chain executes the commands in order, so the blocks being appended are done
+        let pending = self.network.chain().blocking_query(|chain| {
+            chain.flush()?;
+            Ok::<_, BlockchainError>(chain.pending_transactions().to_vec())
+        })?;
+        let file = self.data_dir.join(MEMPOOL_FILE);
+        let json = serde_json::to_string(&pending)
+            .map_err(|err| BlockchainError::Serialization(err.to_string()))?;
+        fs::write(&file, json).map_err(io_error)?;
+
+        // The data directory is released once the chain is dropped (e.g. for a restart)
+        self.network.chain().stop();
+        info!(pending = pending.len(), "Node is shut down");
+        Ok(())
+    }
+}
+
+/// Will submit the transactions (that were pending when the node was shut down) of the file
+/// again, skipping the ones that aren't valid anymore, and remove it
+fn restore_mempool(chain: &ChainClient, file: &Path) -> Result<(), BlockchainError> {
+    let json = match fs::read_to_string(file) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(io_error(err)),
+    };
+    let transactions: Vec<Transaction> = serde_json::from_str(&json)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()))?;
+    let restored = chain.blocking_query(|chain| {
+        transactions
+            .into_iter()
+            .filter(|transaction| chain.submit_transaction(transaction.clone()).is_ok())
+            .count()
+    });
+    info!(restored, "Restored the pending transactions");
+    fs::remove_file(file).map_err(io_error)
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::net::{SocketAddr, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    use blockchain::{Transaction, TransactionData};
+
+    use super::Node;
+    use crate::config::NodeConfig;
+    use crate::init;
+
+    #[test]
+    fn shutdown_persists_the_node() {
+        let dir = tempfile::tempdir().unwrap();
+        init(dir.path(), None, false).unwrap();
+        let mut config = NodeConfig::default();
+        config.network.listen = "127.0.0.1:0".parse().unwrap();
+        config.network.mdns = false;
+        config.rpc.port = Some(0);
+        config.consensus.block_interval = 3600;
+
+        let node = Node::start(dir.path(), &config, None).unwrap();
+        let transaction = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("carol".into()),
+            0,
+        )
+        .with_chain_id("dev".into());
+        node.network.submit_transaction(transaction).unwrap();
+        let rpc_addr = node.rpc_addr.unwrap();
+        let rpc_addr = SocketAddr::from(([127, 0, 0, 1], rpc_addr.port()));
+        assert!(TcpStream::connect(rpc_addr).is_ok());
+        node.shutdown().unwrap();
+
+        // The RPC API stops accepting connections
+        for _ in 0..100 {
+            if TcpStream::connect(rpc_addr).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(TcpStream::connect(rpc_addr).is_err());
+
+        // ... whilst the pending transaction is submitted again when the node restarts
+        let node = Node::start(dir.path(), &config, None).unwrap();
+        assert_eq!(
+            1,
+            node.network
+                .chain()
+                .blocking_query(|chain| chain.pending_transactions().len())
+        );
+        node.shutdown().unwrap();
+    }
+
+    #[test]
+    fn dev_chains_produce_blocks_as_transactions_arrive() {
+        let dir = tempfile::tempdir().unwrap();
+        init(dir.path(), None, false).unwrap();
+        let mut config = NodeConfig::default();
+        config.network.listen = "127.0.0.1:0".parse().unwrap();
+        config.rpc.port = Some(0);
+        let config = config.dev();
+        assert!(!config.network.mdns);
+        assert_eq!(Some(0), config.rpc.port);
+
+        let node = Node::start(dir.path(), &config, None).unwrap();
+        let transaction = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("carol".into()),
+            0,
+        )
+        .with_chain_id("dev".into());
+        node.network.submit_transaction(transaction).unwrap();
+        let height = || node.network.chain().blocking_query(|chain| chain.len());
+        for _ in 0..100 {
+            if height() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(2, height());
+
+        // No empty blocks are produced meanwhile
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(2, height());
+        node.shutdown().unwrap();
+    }
+}
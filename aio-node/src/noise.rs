@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use snow::params::NoiseParams;
+use snow::{Builder, HandshakeState, TransportState};
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+
+/// The Noise protocol that connections are encrypted with: the XX handshake (both nodes
+/// transmit their static keys, encrypted) over Curve25519, ChaCha20-Poly1305 and BLAKE2s.
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+/// The size of the biggest Noise message, ciphertext and tag included.
+const MAX_FRAME_SIZE: usize = 65535;
+
+/// The size of the authentication tag of every encrypted frame.
+const TAG_SIZE: usize = 16;
+
+/// The identity of a node: the public (Curve25519) key of its static Noise key, which peers
+/// authenticate it by (hex encoded, e.g. in the configuration).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NodeId([u8; 32]);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for NodeId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|err| format!("Invalid node id: {}", err))?;
+        let key = bytes
+            .try_into()
+            .map_err(|_| "Invalid node id: expected 32 bytes".to_string())?;
+        Ok(NodeId(key))
+    }
+}
+
+impl TryFrom<String> for NodeId {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        hex.parse()
+    }
+}
+
+impl From<NodeId> for String {
+    fn from(id: NodeId) -> Self {
+        id.to_string()
+    }
+}
+
+/// The static key of the node, which every connection is encrypted with, along with the
+/// nodes that it may connect with.
+pub struct Identity {
+    private_key: Vec<u8>,
+
+    id: NodeId,
+
+    /// The nodes that may connect, all of them if empty
+    allowed: HashSet<NodeId>,
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Identity {{ id: {}, allowed: {} }}",
+            self.id,
+            self.allowed.len()
+        )
+    }
+}
+
+impl Identity {
+    /// Will generate a new (random) identity
+    pub fn generate() -> Self {
+        let keypair = builder()
+            .generate_keypair()
+            .expect("the keys of the supported protocol");
+        Self::from_private_key(keypair.private)
+    }
+
+    /// Will load the identity of the node from the file (holding the hex encoded private
+    /// key), generating (and storing) it if the file doesn't exist yet.<br/>
+    /// On unix, the generated file may only be read and written by its owner.
+    pub fn load_or_generate(path: &Path) -> io::Result<Self> {
+        if path.exists() {
+            let hex = fs::read_to_string(path)?;
+            let private_key = hex::decode(hex.trim())
+                .ok()
+                .filter(|key| key.len() == 32)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} doesn't hold a node key", path.display()),
+                    )
+                })?;
+            return Ok(Self::from_private_key(private_key));
+        }
+        let identity = Self::generate();
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(path)?
+            .write_all(hex::encode(&identity.private_key).as_bytes())?;
+        Ok(identity)
+    }
+
+    fn from_private_key(private_key: Vec<u8>) -> Self {
+        let scalar = private_key.clone().try_into().expect("a 32 bytes key");
+        Identity {
+            private_key,
+            id: NodeId(x25519(scalar, X25519_BASEPOINT_BYTES)),
+            allowed: HashSet::new(),
+        }
+    }
+
+    /// Will return the id of the node, which its peers authenticate it by
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Restricts the nodes that may connect (and be connected to) to the ones with the ids,
+    /// as permissioned networks require
+    pub fn allow(&mut self, ids: impl IntoIterator<Item = NodeId>) {
+        self.allowed.extend(ids);
+    }
+
+    /// Will encrypt the connection to a peer, as the initiator of the handshake
+    pub fn connect(&self, stream: TcpStream) -> io::Result<SecureStream> {
+        let builder = builder().local_private_key(&self.private_key);
+        let handshake = builder.build_initiator().map_err(noise_error)?;
+        self.handshake(stream, handshake, true)
+    }
+
+    /// Will encrypt the connection of a peer, as the responder of the handshake
+    pub fn accept(&self, stream: TcpStream) -> io::Result<SecureStream> {
+        let builder = builder().local_private_key(&self.private_key);
+        let handshake = builder.build_responder().map_err(noise_error)?;
+        self.handshake(stream, handshake, false)
+    }
+
+    /// Will exchange the three messages of the XX handshake and authenticate the peer by
+    /// its static key
+    fn handshake(
+        &self,
+        mut stream: TcpStream,
+        mut handshake: HandshakeState,
+        mut writing: bool,
+    ) -> io::Result<SecureStream> {
+        let mut buf = vec![0u8; MAX_FRAME_SIZE];
+        while !handshake.is_handshake_finished() {
+            if writing {
+                let len = handshake
+                    .write_message(&[], &mut buf)
+                    .map_err(noise_error)?;
+                write_frame(&mut stream, &buf[..len])?;
+            } else {
+                let frame = read_frame(&mut stream)?;
+                handshake
+                    .read_message(&frame, &mut buf)
+                    .map_err(noise_error)?;
+            }
+            writing = !writing;
+        }
+
+        let peer = handshake
+            .get_remote_static()
+            .and_then(|key| key.try_into().ok())
+            .map(NodeId)
+            .ok_or_else(|| noise_error("The peer sent no static key"))?;
+        if !self.allowed.is_empty() && !self.allowed.contains(&peer) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("The node {} is not allowed to connect", peer),
+            ));
+        }
+        Ok(SecureStream {
+            stream,
+            transport: handshake.into_transport_mode().map_err(noise_error)?,
+            peer,
+            buffer: Vec::new(),
+            pos: 0,
+        })
+    }
+}
+
+/// A connection encrypted (and authenticated) with Noise: whatever is written is sent as
+/// encrypted frames, prefixed by their (big endian) length.
+pub struct SecureStream {
+    stream: TcpStream,
+
+    transport: TransportState,
+
+    /// The id of the node at the other end
+    peer: NodeId,
+
+    /// The decrypted frame being read, up to the position
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl fmt::Debug for SecureStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecureStream {{ peer: {} }}", self.peer)
+    }
+}
+
+impl SecureStream {
+    /// Will return the id of the node at the other end, as authenticated by the handshake
+    pub fn peer_id(&self) -> NodeId {
+        self.peer
+    }
+}
+
+impl Read for SecureStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buffer.len() {
+            let frame = match read_frame(&mut self.stream) {
+                Ok(frame) => frame,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(0),
+                Err(err) => return Err(err),
+            };
+            self.buffer.resize(frame.len(), 0);
+            let len = self
+                .transport
+                .read_message(&frame, &mut self.buffer)
+                .map_err(noise_error)?;
+            self.buffer.truncate(len);
+            self.pos = 0;
+        }
+        let len = buf.len().min(self.buffer.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl Write for SecureStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(MAX_FRAME_SIZE - TAG_SIZE);
+        let mut frame = vec![0u8; len + TAG_SIZE];
+        let encrypted = self
+            .transport
+            .write_message(&buf[..len], &mut frame)
+            .map_err(noise_error)?;
+        write_frame(&mut self.stream, &frame[..encrypted])?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn builder() -> Builder<'static> {
+    let params: NoiseParams = NOISE_PARAMS.parse().expect("a supported protocol");
+    Builder::new(params)
+}
+
+fn write_frame<W: Write>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    writer.write_all(&(frame.len() as u16).to_be_bytes())?;
+    writer.write_all(frame)
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    reader.read_exact(&mut len)?;
+    let mut frame = vec![0u8; usize::from(u16::from_be_bytes(len))];
+    reader.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+fn noise_error<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::{self, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use super::Identity;
+
+    #[test]
+    fn identities_are_stored_for_the_owner_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.key");
+        let identity = Identity::load_or_generate(&path).unwrap();
+        assert_eq!(
+            identity.id(),
+            Identity::load_or_generate(&path).unwrap().id()
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+    }
+
+    #[test]
+    fn connections_are_encrypted_and_authenticated() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Identity::generate();
+        let server_id = server.id();
+        let server = thread::spawn(move || {
+            let mut echoed = Vec::new();
+            for stream in listener.incoming().take(2) {
+                let echo = server.accept(stream.unwrap()).and_then(|mut stream| {
+                    let mut message = vec![0u8; 100_000];
+                    stream.read_exact(&mut message)?;
+                    stream.write_all(&message)?;
+                    Ok(stream.peer_id())
+                });
+                echoed.extend(echo.ok());
+            }
+            echoed
+        });
+
+        // Messages bigger than a frame are split into several
+        let client = Identity::generate();
+        let mut stream = client.connect(TcpStream::connect(addr).unwrap()).unwrap();
+        assert_eq!(server_id, stream.peer_id());
+        let message: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+        stream.write_all(&message).unwrap();
+        let mut echo = vec![0u8; message.len()];
+        stream.read_exact(&mut echo).unwrap();
+        assert_eq!(message, echo);
+
+        // Only the allowed nodes may be connected to
+        let mut restricted = Identity::generate();
+        restricted.allow([client.id()]);
+        let err = restricted
+            .connect(TcpStream::connect(addr).unwrap())
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+
+        assert_eq!(vec![client.id()], server.join().unwrap());
+    }
+}
@@ -0,0 +1,1002 @@
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use bincode::Options;
+use blockchain::{Block, BlockchainError, Hash, Keypair, Transaction};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, debug_span, info, warn};
+
+use crate::actor::ChainClient;
+use crate::limits::{ConnectionLimiter, Limits, OpenConnection, RateLimiter};
+use crate::noise::{Identity, SecureStream};
+use crate::peers::{self, PeerManager, PeerSource, UNREACHABLE_PENALTY};
+use crate::shutdown::Shutdown;
+use crate::sync::{self, SyncStatus};
+
+/// Messages bigger than this are rejected, so that a peer can't exhaust our memory (unless
+/// configured otherwise, see `Limits`).
+pub const MAX_MESSAGE_SIZE: u32 = 16 * 1024 * 1024;
+
+/// How long we wait for a peer to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long we wait for a peer that we connected to, to take or send a message.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The maximum number of blocks sent in response to a `GetBlocks` request.
+const SYNC_BATCH_SIZE: usize = 100;
+
+/// The amount of transaction hashes remembered to stop the gossip loops, before the oldest
+/// ones are forgotten.
+const MAX_SEEN_TRANSACTIONS: usize = 10_000;
+
+/// The version of the protocol (the messages and how they are exchanged), which the nodes of
+/// a network have to share.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What nodes tell each other when they connect, before any other message (see `connect`):
+/// nodes of other networks or speaking other versions of the protocol are disconnected.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Handshake {
+    /// The version of the protocol the node speaks
+    pub version: u32,
+
+    /// The id of the network of the node's chain
+    pub chain_id: String,
+
+    /// The hash of the node's genesis block (`None` if it joined the network and didn't
+    /// sync the genesis block yet)
+    pub genesis_hash: Option<Hash>,
+
+    /// The height of the node's chain
+    pub height: usize,
+}
+
+impl Handshake {
+    /// Will check that the node of the other handshake may be talked to: it speaks the same
+    /// version of the protocol, on the same network, starting with the same genesis block
+    /// (unless either one doesn't have it yet).
+    pub fn check(&self, other: &Handshake) -> io::Result<()> {
+        let mismatch = if self.version != other.version {
+            format!(
+                "protocol version {}, expected {}",
+                other.version, self.version
+            )
+        } else if self.chain_id != other.chain_id {
+            format!("chain `{}`, expected `{}`", other.chain_id, self.chain_id)
+        } else if matches!((self.genesis_hash, other.genesis_hash), (Some(ours), Some(theirs)) if ours != theirs)
+        {
+            "another genesis block".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Incompatible peer: {}", mismatch),
+        ))
+    }
+}
+
+/// The messages that nodes exchange (gossip) with each other.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Message {
+    /// A block that was appended to the sender's chain
+    NewBlock(Block),
+
+    /// A transaction that was accepted into the sender's mempool
+    NewTransaction(Transaction),
+
+    /// Requests the blocks starting with the provided height
+    GetBlocks { from: usize },
+
+    /// The response to `GetBlocks`, carrying the sender's chain height.<br/>
+    /// Pruned blocks are not sent, hence there are none if the sender pruned the requested ones
+    Blocks { blocks: Vec<Block>, height: usize },
+
+    /// Requests the peers of the receiver, announcing the port that the sender accepts
+    /// connections on (if any), so that it becomes a peer of the receiver
+    GetPeers { port: Option<u16> },
+
+    /// The response to `GetPeers`
+    Peers(Vec<SocketAddr>),
+
+    /// Opens every connection, in both directions (see `connect`)
+    Handshake(Handshake),
+}
+
+impl Message {
+    /// Will return the name of the kind of the message (e.g. for logs)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Message::NewBlock(_) => "new_block",
+            Message::NewTransaction(_) => "new_transaction",
+            Message::GetBlocks { .. } => "get_blocks",
+            Message::Blocks { .. } => "blocks",
+            Message::GetPeers { .. } => "get_peers",
+            Message::Peers(_) => "peers",
+            Message::Handshake(_) => "handshake",
+        }
+    }
+}
+
+/// The networking part of a node: it broadcasts the blocks and transactions
+/// to the (configured and discovered) peers and feeds the ones received from them into the
+/// chain.<br/>
+/// Every message is sent over a new TCP connection, prefixed by its length, which is
+/// encrypted with Noise and authenticates the nodes by their keys (see `Identity`).
+pub struct Network {
+    /// The chain that is shared with the rest of the node
+    chain: ChainClient,
+
+    /// The key that connections are encrypted with, along with the nodes allowed to connect
+    identity: Identity,
+
+    /// The peers that we broadcast to
+    peers: Mutex<PeerManager>,
+
+    /// The hashes of the transactions we've already seen (prevents gossip loops)
+    seen_transactions: Mutex<SeenTransactions>,
+
+    /// The progress of the (last) synchronization with a peer
+    sync_status: Mutex<SyncStatus>,
+
+    /// The thresholds that peers (and the clients of the APIs) are held to
+    limits: Limits,
+
+    /// Limits the rate of the connections and messages of each peer (by its IP address)
+    limiter: RateLimiter,
+
+    /// Limits the open connections, of all peers and of each one (by its IP address)
+    connections: ConnectionLimiter,
+
+    /// Stops the servers of the node (and its block production) once requested
+    shutdown: Shutdown,
+}
+
+impl Network {
+    /// C'tor, gossiping with the static peers (and the ones discovered later on) as the
+    /// identity, whilst they are held to the limits
+    pub fn new(
+        chain: ChainClient,
+        peers: Vec<SocketAddr>,
+        limits: Limits,
+        identity: Identity,
+    ) -> Arc<Self> {
+        let mut manager = PeerManager::default();
+        for peer in peers {
+            manager.add(peer, PeerSource::Static);
+        }
+        Arc::new(Network {
+            chain,
+            identity,
+            peers: Mutex::new(manager),
+            seen_transactions: Mutex::new(SeenTransactions::new(MAX_SEEN_TRANSACTIONS)),
+            sync_status: Mutex::new(SyncStatus::Idle),
+            limiter: RateLimiter::new(limits.requests_per_second),
+            connections: ConnectionLimiter::default(),
+            limits,
+            shutdown: Shutdown::default(),
+        })
+    }
+
+    /// Will return the chain that is shared with the rest of the node
+    pub fn chain(&self) -> &ChainClient {
+        &self.chain
+    }
+
+    /// Will return the identity of the node
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// Will return the shutdown of the node, which its servers stop at
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
+    /// Will return the thresholds that peers and clients are held to
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Will return the peers (see `PeerManager`)
+    pub fn peers(&self) -> MutexGuard<'_, PeerManager> {
+        self.peers.lock().unwrap()
+    }
+
+    /// Will add the peer (see `PeerManager::add`) and return true if it wasn't known yet
+    pub fn add_peer(&self, addr: SocketAddr, source: PeerSource) -> bool {
+        self.peers().add(addr, source)
+    }
+
+    /// Will add the bootnodes and the peers they know, announcing the port that we accept
+    /// connections on to them (so that they pass us on to the nodes asking them later on).<br/>
+    /// Unreachable bootnodes are skipped.
+    pub fn bootstrap(&self, bootnodes: &[SocketAddr], port: u16) {
+        for bootnode in bootnodes {
+            self.add_peer(*bootnode, PeerSource::Bootnode);
+            let get_peers = Message::GetPeers { port: Some(port) };
+            match request(bootnode, &self.identity, &self.handshake(), &get_peers) {
+                Ok(Message::Peers(addrs)) => {
+                    let added = addrs
+                        .into_iter()
+                        .filter(|addr| self.add_peer(*addr, PeerSource::Exchange))
+                        .count();
+                    info!(%bootnode, added, "Learned the peers of the bootnode");
+                }
+                Ok(other) => warn!(%bootnode, kind = other.kind(), "Unexpected response"),
+                Err(err) => warn!(%bootnode, error = %err, "Could not reach the bootnode"),
+            }
+        }
+    }
+
+    /// Will catch up with the first (reachable) peer's chain, trying the best scored first.
+    pub fn sync(&self) -> Result<(), String> {
+        let mut result = Err("There are no peers to sync with".to_string());
+        // The peers aren't locked while they are contacted
+        let peers = self.peers().addrs();
+        for peer in peers {
+            result = self.sync_from(&peer);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    /// Will download (and validate) the blocks we're missing from the peer.
+    pub fn sync_from(&self, peer: &SocketAddr) -> Result<(), String> {
+        sync::sync(
+            &self.chain,
+            peer,
+            &self.identity,
+            &self.handshake(),
+            &self.sync_status,
+        )
+    }
+
+    /// Will return the handshake of the node (see `Handshake`)
+    pub fn handshake(&self) -> Handshake {
+        self.chain.blocking_query(|chain| Handshake {
+            version: PROTOCOL_VERSION,
+            chain_id: chain.chain_id().to_string(),
+            genesis_hash: chain.blocks().first().and_then(|genesis| genesis.hash()),
+            height: chain.len(),
+        })
+    }
+
+    /// Will return the progress of the (last) synchronization.
+    pub fn sync_status(&self) -> SyncStatus {
+        self.sync_status.lock().unwrap().clone()
+    }
+
+    /// Will start accepting connections from peers (in a background thread), until the node
+    /// is shut down, and return the address that it is listening on.<br/>
+    /// Every connection is served by a thread of its own, once admitted (see `admit`).
+    pub fn listen(self: &Arc<Self>, addr: SocketAddr) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let network = Arc::clone(self);
+        self.shutdown.watch(local_addr);
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if network.shutdown.is_requested() {
+                    break;
+                }
+                let connection = match network.admit(&stream) {
+                    Some(connection) => connection,
+                    None => continue,
+                };
+                let network = Arc::clone(&network);
+                thread::spawn(move || {
+                    network.serve(stream);
+                    drop(connection);
+                });
+            }
+        });
+
+        Ok(local_addr)
+    }
+
+    /// Will submit a transaction to the local mempool and broadcast it to the peers.
+    pub fn submit_transaction(&self, transaction: Transaction) -> Result<(), BlockchainError> {
+        self.seen_transactions
+            .lock()
+            .unwrap()
+            .insert(transaction.calculate_hash());
+        self.chain
+            .blocking_submit_transaction(transaction.clone())?;
+        self.broadcast(&Message::NewTransaction(transaction));
+        Ok(())
+    }
+
+    /// Will produce a block out of the local mempool and broadcast it to the peers.
+    pub fn produce_block(&self, max_txs: usize) -> Result<(), BlockchainError> {
+        self.produce(max_txs, None)
+    }
+
+    /// Will produce a block signed by the keypair (see `Blockchain::produce_signed_block`)
+    /// and broadcast it to the peers.
+    pub fn produce_signed_block(
+        &self,
+        max_txs: usize,
+        keypair: &Keypair,
+    ) -> Result<(), BlockchainError> {
+        self.produce(max_txs, Some(keypair.clone()))
+    }
+
+    /// Will produce a block (signed by the keypair, if set) and broadcast it
+    fn produce(&self, max_txs: usize, keypair: Option<Keypair>) -> Result<(), BlockchainError> {
+        if let Some(block) = self.chain.blocking_produce_block(max_txs, keypair)? {
+            self.broadcast(&Message::NewBlock(block));
+        }
+        Ok(())
+    }
+
+    /// Will send the message to all peers.<br/>
+    /// Unreachable peers are skipped (and penalized), since gossip is best effort, whilst
+    /// incompatible (or not allowed) ones are dropped.
+    pub fn broadcast(&self, message: &Message) {
+        let handshake = self.handshake();
+        // The peers aren't locked while they are contacted
+        let peers = self.peers().addrs();
+        for peer in peers {
+            match send(&peer, &self.identity, &handshake, message) {
+                Ok(()) => self.peers().reward(&peer),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::InvalidData | io::ErrorKind::PermissionDenied
+                    ) =>
+                {
+                    warn!(%peer, error = %err, "Dropped peer");
+                    self.peers().remove(&peer);
+                }
+                Err(err) => {
+                    warn!(%peer, error = %err, "Could not send message to peer");
+                    self.peers().penalize(&peer, UNREACHABLE_PENALTY);
+                }
+            }
+        }
+    }
+
+    /// Will count the connection against the limits and return it, unless it is refused (and
+    /// closed once dropped, before anything is exchanged): the connections of banned peers,
+    /// of peers exceeding the rate limit (every connection counts as a request) and the ones
+    /// beyond the maximum number of peers or of connections per IP address.
+    fn admit(&self, stream: &TcpStream) -> Option<OpenConnection> {
+        let ip = stream.peer_addr().ok()?.ip();
+        let refused = if self.peers().is_banned(ip) {
+            "a banned peer"
+        } else if !self.limiter.allow(ip) {
+            "a peer exceeding the rate limit"
+        } else {
+            let max_peers = self.peers().max_peers();
+            match self
+                .connections
+                .open(ip, max_peers, self.limits.max_connections_per_ip)
+            {
+                Some(connection) => return Some(connection),
+                None => "a peer exceeding the connection limits",
+            }
+        };
+        debug!(%ip, "Refused the connection of {}", refused);
+        None
+    }
+
+    /// Will read all messages of a (admitted) connection and handle them.<br/>
+    /// Connections of peers that aren't allowed (or incompatible) are closed after the
+    /// handshakes are exchanged, as are the ones of peers exceeding the limits (sending
+    /// messages too often or too big ones, or stalling for too long).
+    fn serve(&self, stream: TcpStream) {
+        let peer = match stream.peer_addr() {
+            Ok(peer) => peer,
+            Err(_) => return,
+        };
+        let _span = debug_span!("peer", %peer).entered();
+        let timeout = Some(self.limits.timeout);
+        if stream.set_read_timeout(timeout).is_err() || stream.set_write_timeout(timeout).is_err() {
+            return;
+        }
+        let mut stream = match self.identity.accept(stream) {
+            Ok(stream) => stream,
+            Err(err) => {
+                debug!(error = %err, "Closed the connection without Noise handshake");
+                return;
+            }
+        };
+        debug!(id = %stream.peer_id(), "Authenticated the peer");
+        if let Err(err) = self.accept_handshake(&mut stream) {
+            debug!(error = %err, "Closed the connection without handshake");
+            return;
+        }
+        while let Ok(message) = read_limited_message(&mut stream, self.limits.max_message_size) {
+            if !self.limiter.allow(peer.ip()) {
+                debug!("Closed the connection of a peer exceeding the rate limit");
+                return;
+            }
+            if let Some(response) = self.handle(message, peer.ip()) {
+                if write_message(&mut stream, &response).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Will read the peer's handshake, answer it with ours and check that they match
+    fn accept_handshake(&self, stream: &mut SecureStream) -> io::Result<()> {
+        let theirs = match read_limited_message(stream, self.limits.max_message_size)? {
+            Message::Handshake(handshake) => handshake,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Expected a handshake, got {}", other.kind()),
+                ))
+            }
+        };
+        let ours = self.handshake();
+        write_message(stream, &Message::Handshake(ours.clone()))?;
+        ours.check(&theirs)
+    }
+
+    /// Will validate the received message (of the peer at the IP address) against the chain
+    /// and relay it to the peers if it was accepted.<br/>
+    /// Requests are answered by returning the response, whilst peers sending invalid blocks
+    /// are banned.
+    fn handle(&self, message: Message, ip: IpAddr) -> Option<Message> {
+        let _span = debug_span!("message", kind = message.kind()).entered();
+        let accepted = match &message {
+            Message::NewBlock(block) => self.chain.blocking_append_block(block.clone()),
+            Message::NewTransaction(transaction) if transaction.performs_coinbase() => {
+                Err(BlockchainError::SubmittedCoinbase)
+            }
+            Message::NewTransaction(transaction) => {
+                if !self
+                    .seen_transactions
+                    .lock()
+                    .unwrap()
+                    .insert(transaction.calculate_hash())
+                {
+                    return None;
+                }
+                self.chain.blocking_submit_transaction(transaction.clone())
+            }
+            Message::GetBlocks { from } => {
+                let from = *from;
+                return Some(self.chain.blocking_query(move |chain| {
+                    let blocks = chain
+                        .blocks()
+                        .iter()
+                        .skip(from)
+                        .take_while(|block| !block.is_pruned())
+                        .take(SYNC_BATCH_SIZE)
+                        .cloned()
+                        .collect();
+                    Message::Blocks {
+                        blocks,
+                        height: chain.len(),
+                    }
+                }));
+            }
+            Message::GetPeers { port } => {
+                if let Some(port) = port {
+                    self.add_peer(SocketAddr::new(ip, *port), PeerSource::Exchange);
+                }
+                let peers = self.peers().addrs();
+                let others = peers
+                    .into_iter()
+                    .filter(|addr| Some(addr.port()) != *port || addr.ip() != ip)
+                    .collect();
+                return Some(Message::Peers(others));
+            }
+            Message::Blocks { .. } | Message::Peers(_) | Message::Handshake(_) => return None,
+        };
+
+        match accepted {
+            Ok(()) => {
+                debug!("Relaying accepted message");
+                self.broadcast(&message)
+            }
+            Err(BlockchainError::OrphanBlock { parent }) => {
+                debug!(%parent, "Kept block of unknown parent as an orphan")
+            }
+            Err(err)
+                if matches!(message, Message::NewBlock(_)) && peers::is_invalid_block(&err) =>
+            {
+                warn!(error = %err, code = err.code(), %ip, "Banned peer for an invalid block");
+                self.peers().ban(ip);
+            }
+            Err(err) => warn!(error = %err, code = err.code(), "Rejected message from peer"),
+        }
+        None
+    }
+}
+
+/// The hashes of the latest transactions seen, up to a capacity: once it is reached, the
+/// oldest hash is forgotten for every new one (a transaction seen again by then is
+/// rejected by the chain, as it is pending or included already).
+#[derive(Debug)]
+struct SeenTransactions {
+    hashes: HashSet<Hash>,
+
+    /// The hashes in the order they were seen
+    order: VecDeque<Hash>,
+
+    capacity: usize,
+}
+
+impl SeenTransactions {
+    /// C'tor, remembering up to `capacity` hashes
+    fn new(capacity: usize) -> Self {
+        SeenTransactions {
+            hashes: HashSet::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Will remember the hash and return true, unless it was seen already
+    fn insert(&mut self, hash: Hash) -> bool {
+        if !self.hashes.insert(hash) {
+            return false;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Will return the amount of remembered hashes
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+}
+
+/// Will connect to the peer as the identity and exchange the handshakes, failing (with
+/// `InvalidData`) if the peer is incompatible (see `Handshake::check`) or (with
+/// `PermissionDenied`) if it isn't allowed.<br/>
+/// The connection times out if the peer stalls, rather than blocking the caller.
+pub fn connect(
+    peer: &SocketAddr,
+    identity: &Identity,
+    handshake: &Handshake,
+) -> io::Result<SecureStream> {
+    let stream = TcpStream::connect_timeout(peer, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    stream.set_write_timeout(Some(RESPONSE_TIMEOUT))?;
+    let mut stream = identity.connect(stream)?;
+    write_message(&mut stream, &Message::Handshake(handshake.clone()))?;
+    match read_message(&mut stream)? {
+        Message::Handshake(theirs) => handshake.check(&theirs)?,
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected a handshake, got {}", other.kind()),
+            ))
+        }
+    }
+    Ok(stream)
+}
+
+/// Will connect to the peer and send a single message
+pub fn send(
+    peer: &SocketAddr,
+    identity: &Identity,
+    handshake: &Handshake,
+    message: &Message,
+) -> io::Result<()> {
+    let mut stream = connect(peer, identity, handshake)?;
+    write_message(&mut stream, message)
+}
+
+/// Will connect to the peer, send a request and wait for its response
+pub fn request(
+    peer: &SocketAddr,
+    identity: &Identity,
+    handshake: &Handshake,
+    message: &Message,
+) -> io::Result<Message> {
+    let mut stream = connect(peer, identity, handshake)?;
+    write_message(&mut stream, message)?;
+    read_message(&mut stream)
+}
+
+/// Will write the message, prefixed by its (big endian) length
+pub fn write_message<W: Write>(writer: &mut W, message: &Message) -> io::Result<()> {
+    let bytes = bincode::serialize(message).map_err(io::Error::other)?;
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()
+}
+
+/// Will read a length prefixed message.<br/>
+/// Malformed messages fail gracefully: the lengths they claim are checked against their size
+/// before anything is allocated and nested values are decoded up to a limited depth.
+pub fn read_message<R: Read>(reader: &mut R) -> io::Result<Message> {
+    read_limited_message(reader, MAX_MESSAGE_SIZE)
+}
+
+/// Will read a length prefixed message, failing if it is bigger than the size (in bytes)
+pub fn read_limited_message<R: Read>(reader: &mut R, max_size: u32) -> io::Result<Message> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message of {} bytes exceeds the maximum size", len),
+        ));
+    }
+
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    // The options of `bincode::deserialize`, limited to the size of the message
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(u64::from(len))
+        .deserialize(&bytes)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::Cell;
+    use std::io::Read;
+    use std::net::{SocketAddr, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    use blockchain::{Block, Blockchain, Hash, Transaction, TransactionData};
+
+    use super::{
+        read_limited_message, read_message, send, write_message, Message, Network, SeenTransactions,
+    };
+    use crate::actor::{self, ChainClient};
+    use crate::limits::Limits;
+    use crate::noise::Identity;
+    use crate::sync::SyncStatus;
+
+    fn create_account(id: &str) -> Transaction {
+        Transaction::new(id.into(), TransactionData::CreateUserAccount(id.into()), 0)
+    }
+
+    fn spawn_chain() -> ChainClient {
+        actor::spawn(Blockchain::new()).unwrap()
+    }
+
+    /// Gives the remote node some time to process the messages
+    fn wait_until<F: Fn() -> bool>(condition: F) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let sent = create_account("alice");
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &Message::NewTransaction(sent.clone())).unwrap();
+
+        match read_message(&mut bytes.as_slice()).unwrap() {
+            Message::NewTransaction(transaction) => {
+                assert_eq!(sent.calculate_hash(), transaction.calculate_hash())
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn seen_transactions_are_bounded() {
+        let mut seen = SeenTransactions::new(3);
+        let hashes: Vec<_> = (0u8..5).map(|i| Hash::digest(&[i])).collect();
+        for hash in &hashes {
+            assert!(seen.insert(*hash));
+            assert!(seen.len() <= 3);
+        }
+        assert!(!seen.insert(hashes[4]));
+
+        // The oldest hashes were forgotten
+        assert_eq!(3, seen.len());
+        assert!(seen.insert(hashes[0]));
+        assert!(!seen.insert(hashes[3]));
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected() {
+        let bytes = u32::MAX.to_be_bytes();
+        assert!(read_message(&mut &bytes[..]).is_err());
+
+        // The maximum size may be configured below the default one
+        let mut bytes = Vec::new();
+        write_message(
+            &mut bytes,
+            &Message::NewTransaction(create_account("alice")),
+        )
+        .unwrap();
+        assert!(read_limited_message(&mut bytes.as_slice(), 16).is_err());
+        assert!(read_limited_message(&mut bytes.as_slice(), bytes.len() as u32).is_ok());
+    }
+
+    #[test]
+    fn blocks_and_transactions_are_gossiped() {
+        let remote_chain = spawn_chain();
+        let remote = Network::new(
+            remote_chain.clone(),
+            Vec::new(),
+            Limits::default(),
+            Identity::generate(),
+        );
+        let remote_addr = remote.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let local_chain = spawn_chain();
+        let local = Network::new(
+            local_chain.clone(),
+            vec![remote_addr],
+            Limits::default(),
+            Identity::generate(),
+        );
+
+        local.submit_transaction(create_account("alice")).unwrap();
+        local.produce_block(1).unwrap();
+        wait_until(|| remote_chain.blocking_query(|chain| chain.len()) == 1);
+
+        local
+            .submit_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount("bob".into()),
+                0,
+            ))
+            .unwrap();
+
+        let pending =
+            |chain: &ChainClient| chain.blocking_query(|chain| chain.pending_transactions().len());
+        wait_until(|| pending(&remote_chain) == 1);
+
+        assert_eq!(1, remote_chain.blocking_query(|chain| chain.len()));
+        assert_eq!(
+            local_chain.blocking_query(|chain| chain.get_last_block_hash()),
+            remote_chain.blocking_query(|chain| chain.get_last_block_hash())
+        );
+        assert_eq!(1, pending(&remote_chain));
+    }
+
+    #[test]
+    fn fresh_node_syncs_with_peer() {
+        let remote_chain = spawn_chain();
+        let remote = Network::new(
+            remote_chain.clone(),
+            Vec::new(),
+            Limits::default(),
+            Identity::generate(),
+        );
+        let remote_addr = remote.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        remote.submit_transaction(create_account("alice")).unwrap();
+        remote.produce_block(1).unwrap();
+        for (nonce, user) in ["bob", "carol"].iter().enumerate() {
+            remote
+                .submit_transaction(Transaction::new(
+                    "alice".into(),
+                    TransactionData::CreateUserAccount((*user).into()),
+                    nonce as u128,
+                ))
+                .unwrap();
+            remote.produce_block(1).unwrap();
+        }
+
+        let local_chain = spawn_chain();
+        let local = Network::new(
+            local_chain.clone(),
+            vec![remote_addr],
+            Limits::default(),
+            Identity::generate(),
+        );
+        assert_eq!(SyncStatus::Idle, local.sync_status());
+
+        local.sync().unwrap();
+        assert_eq!(SyncStatus::Synced { height: 3 }, local.sync_status());
+        assert_eq!(
+            remote_chain.blocking_query(|chain| chain.get_last_block_hash()),
+            local_chain.blocking_query(|chain| chain.get_last_block_hash())
+        );
+    }
+
+    #[test]
+    fn pruned_blocks_are_not_synced() {
+        let remote_chain = spawn_chain();
+        let remote = Network::new(
+            remote_chain.clone(),
+            Vec::new(),
+            Limits::default(),
+            Identity::generate(),
+        );
+        let remote_addr = remote.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        remote.submit_transaction(create_account("alice")).unwrap();
+        remote.produce_block(1).unwrap();
+        remote
+            .submit_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount("bob".into()),
+                0,
+            ))
+            .unwrap();
+        remote.produce_block(1).unwrap();
+        remote_chain
+            .blocking_query(|chain| chain.set_pruning(Some(1)))
+            .unwrap();
+
+        let local = Network::new(
+            spawn_chain(),
+            vec![remote_addr],
+            Limits::default(),
+            Identity::generate(),
+        );
+        assert!(local.sync().unwrap_err().contains("pruned"));
+        assert!(matches!(local.sync_status(), SyncStatus::Failed(_)));
+    }
+
+    #[test]
+    fn peers_are_learned_from_bootnodes_and_banned_for_invalid_blocks() {
+        let other: SocketAddr = "10.0.0.1:7000".parse().unwrap();
+        let bootnode = Network::new(
+            spawn_chain(),
+            vec![other],
+            Limits::default(),
+            Identity::generate(),
+        );
+        let bootnode_addr = bootnode.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let local = Network::new(
+            spawn_chain(),
+            Vec::new(),
+            Limits::default(),
+            Identity::generate(),
+        );
+        local.bootstrap(&[bootnode_addr], 7001);
+        assert_eq!(vec![other, bootnode_addr], {
+            let mut addrs = local.peers().addrs();
+            addrs.sort();
+            addrs
+        });
+        let local_addr = SocketAddr::new(bootnode_addr.ip(), 7001);
+        assert!(bootnode.peers().addrs().contains(&local_addr));
+
+        // A block that wasn't even mined gets its sender banned
+        let block = Message::NewBlock(Block::new(None));
+        send(&bootnode_addr, local.identity(), &local.handshake(), &block).unwrap();
+        wait_until(|| bootnode.peers().is_banned(bootnode_addr.ip()));
+        assert!(bootnode.peers().is_banned(bootnode_addr.ip()));
+        assert!(!bootnode.peers().addrs().contains(&local_addr));
+    }
+
+    #[test]
+    fn incompatible_peers_are_disconnected() {
+        let remote_chain = spawn_chain();
+        let remote = Network::new(
+            remote_chain.clone(),
+            Vec::new(),
+            Limits::default(),
+            Identity::generate(),
+        );
+        let remote_addr = remote.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        remote.submit_transaction(create_account("alice")).unwrap();
+        remote.produce_block(1).unwrap();
+
+        // Another genesis block
+        let local = Network::new(
+            spawn_chain(),
+            vec![remote_addr],
+            Limits::default(),
+            Identity::generate(),
+        );
+        local.submit_transaction(create_account("bob")).unwrap();
+        local.produce_block(1).unwrap();
+        // The peer was dropped when the block was broadcast
+        assert!(local.peers().is_empty());
+        let err = local.sync_from(&remote_addr).unwrap_err();
+        assert!(err.contains("another genesis block"));
+        assert_eq!(1, remote_chain.blocking_query(|chain| chain.len()));
+
+        // Another version of the protocol
+        let mut handshake = remote.handshake();
+        handshake.version += 1;
+        let get_blocks = Message::GetBlocks { from: 0 };
+        let err = send(&remote_addr, local.identity(), &handshake, &get_blocks).unwrap_err();
+        assert!(err.to_string().contains("protocol version"));
+
+        // A node that isn't allowed to connect
+        let mut identity = Identity::generate();
+        identity.allow([local.identity().id()]);
+        let permissioned = Network::new(spawn_chain(), Vec::new(), Limits::default(), identity);
+        let permissioned_addr = permissioned.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        let handshake = permissioned.handshake();
+        assert!(send(
+            &permissioned_addr,
+            local.identity(),
+            &handshake,
+            &get_blocks
+        )
+        .is_ok());
+        assert!(send(
+            &permissioned_addr,
+            remote.identity(),
+            &handshake,
+            &get_blocks
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn connections_beyond_the_limits_are_refused_before_the_handshake() {
+        let limits = Limits {
+            max_connections_per_ip: 2,
+            ..Limits::default()
+        };
+        let remote = Network::new(spawn_chain(), Vec::new(), limits, Identity::generate());
+        let remote_addr = remote.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        let open = || {
+            let stream = TcpStream::connect(remote_addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(200)))
+                .unwrap();
+            stream
+        };
+        // A refused connection is closed without receiving a byte, whilst an admitted one
+        // waits for the Noise handshake of the peer
+        let is_refused = |mut stream: &TcpStream| matches!(stream.read(&mut [0u8; 1]), Ok(0));
+
+        let admitted = [open(), open()];
+        assert!(is_refused(&open()));
+        for stream in &admitted {
+            assert!(!is_refused(stream));
+        }
+
+        // The connections count until they are closed
+        drop(admitted);
+        let refused = Cell::new(true);
+        wait_until(|| {
+            refused.set(is_refused(&open()));
+            !refused.get()
+        });
+        assert!(!refused.get());
+
+        // So does the maximum number of peers
+        remote.peers().set_max_peers(0);
+        assert!(is_refused(&open()));
+    }
+
+    #[test]
+    fn sync_with_unreachable_peer_fails() {
+        // Bind and drop a listener to get a (most probably) unused port
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let local = Network::new(
+            spawn_chain(),
+            vec![addr],
+            Limits::default(),
+            Identity::generate(),
+        );
+
+        assert!(local.sync().is_err());
+        assert!(matches!(local.sync_status(), SyncStatus::Failed(_)));
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use blockchain::BlockchainError;
+
+/// The maximum number of peers a node gossips with, unless configured otherwise.
+pub const DEFAULT_MAX_PEERS: usize = 25;
+
+/// The score of a peer is capped, so that a long-known peer can't build up unlimited credit.
+const MAX_SCORE: i32 = 100;
+
+/// Discovered peers whose score drops below this are forgotten.
+const MIN_SCORE: i32 = -100;
+
+/// The penalty of a peer that couldn't be reached.
+pub const UNREACHABLE_PENALTY: i32 = 20;
+
+/// How long a peer that sent an invalid block is banned for.
+const BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How a peer became known to the node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerSource {
+    /// Configured with `--peer`
+    Static,
+
+    /// Configured with `--bootnode`, and asked for its peers when the node starts
+    Bootnode,
+
+    /// Announced on the local network (see `discovery`)
+    Mdns,
+
+    /// Learned from the peers of another peer (see `Message::Peers`)
+    Exchange,
+}
+
+impl PeerSource {
+    /// Will return true if the peer was configured, rather than discovered: configured peers
+    /// don't count against the limit and aren't forgotten (unless banned).
+    pub fn is_configured(self) -> bool {
+        matches!(self, PeerSource::Static | PeerSource::Bootnode)
+    }
+}
+
+/// A peer known to the node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Peer {
+    /// How the peer became known
+    pub source: PeerSource,
+
+    /// Rises as the peer is reached and falls as it isn't
+    pub score: i32,
+}
+
+/// Tracks the peers that the node gossips with: their scores, the limit of (discovered) peers
+/// and the IP addresses that are banned for sending invalid blocks.
+#[derive(Debug)]
+pub struct PeerManager {
+    peers: HashMap<SocketAddr, Peer>,
+
+    /// The IP addresses that are banned, until the instant
+    banned: HashMap<IpAddr, Instant>,
+
+    /// The maximum number of discovered peers
+    max_peers: usize,
+}
+
+impl Default for PeerManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PEERS)
+    }
+}
+
+impl PeerManager {
+    /// C'tor, accepting at most `max_peers` discovered peers
+    pub fn new(max_peers: usize) -> Self {
+        PeerManager {
+            peers: HashMap::new(),
+            banned: HashMap::new(),
+            max_peers,
+        }
+    }
+
+    /// Will add the peer and return true, unless it is known already, banned or (if it was
+    /// discovered) the limit of peers is reached.
+    pub fn add(&mut self, addr: SocketAddr, source: PeerSource) -> bool {
+        if self.peers.contains_key(&addr) || self.is_banned(addr.ip()) {
+            return false;
+        }
+        let discovered = self
+            .peers
+            .values()
+            .filter(|peer| !peer.source.is_configured())
+            .count();
+        if !source.is_configured() && discovered >= self.max_peers {
+            return false;
+        }
+        self.peers.insert(addr, Peer { source, score: 0 });
+        true
+    }
+
+    /// Will forget the peer, e.g. as it is incompatible
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        self.peers.remove(addr);
+    }
+
+    /// Will return the maximum number of peers: the configured ones and the discovered ones
+    /// up to their limit
+    pub fn max_peers(&self) -> usize {
+        let configured = self
+            .peers
+            .values()
+            .filter(|peer| peer.source.is_configured())
+            .count();
+        configured.saturating_add(self.max_peers)
+    }
+
+    /// Changes the maximum number of discovered peers (the known ones are kept)
+    pub fn set_max_peers(&mut self, max_peers: usize) {
+        self.max_peers = max_peers;
+    }
+
+    /// Will return the addresses of the peers, the best scored first
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        let mut peers: Vec<_> = self.peers.iter().collect();
+        peers.sort_by(|(a, peer_a), (b, peer_b)| peer_b.score.cmp(&peer_a.score).then(a.cmp(b)));
+        peers.into_iter().map(|(addr, _)| *addr).collect()
+    }
+
+    /// Will return the amount of peers
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Will return true if there are no peers
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Raises the score of the peer, e.g. as it was reached
+    pub fn reward(&mut self, addr: &SocketAddr) {
+        if let Some(peer) = self.peers.get_mut(addr) {
+            peer.score = (peer.score + 1).min(MAX_SCORE);
+        }
+    }
+
+    /// Lowers the score of the peer, forgetting it if it was discovered and its score
+    /// drops too low
+    pub fn penalize(&mut self, addr: &SocketAddr, penalty: i32) {
+        if let Some(peer) = self.peers.get_mut(addr) {
+            peer.score = peer.score.saturating_sub(penalty).max(MIN_SCORE);
+            if peer.score <= MIN_SCORE && !peer.source.is_configured() {
+                self.peers.remove(addr);
+            }
+        }
+    }
+
+    /// Will ban the IP address (e.g. as it sent an invalid block) for an hour, dropping
+    /// its peers and refusing its connections meanwhile
+    pub fn ban(&mut self, ip: IpAddr) {
+        self.peers.retain(|addr, _| addr.ip() != ip);
+        self.banned.insert(ip, Instant::now() + BAN_DURATION);
+    }
+
+    /// Will return true if the IP address is banned (bans expire after an hour)
+    pub fn is_banned(&mut self, ip: IpAddr) -> bool {
+        match self.banned.get(&ip) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                self.banned.remove(&ip);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Will return true if the error (of appending a block received from a peer) proves that the
+/// block is invalid, rather than not fitting the local chain (e.g. as it follows a fork or
+/// another block was appended meanwhile), which is worth banning the peer for.
+pub fn is_invalid_block(err: &BlockchainError) -> bool {
+    !matches!(
+        err,
+        BlockchainError::OrphanBlock { .. }
+            | BlockchainError::PrevHashMismatch
+            | BlockchainError::HeightMismatch { .. }
+            | BlockchainError::TimestampTooFarInFuture { .. }
+            | BlockchainError::BlockFinalized { .. }
+            | BlockchainError::Storage(_)
+    )
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use blockchain::BlockchainError;
+
+    use super::{is_invalid_block, PeerManager, PeerSource, UNREACHABLE_PENALTY};
+
+    #[test]
+    fn peers_are_scored_limited_and_banned() {
+        let addr = |port| ([10, 0, 0, 1], port).into();
+        let mut peers = PeerManager::new(1);
+        assert!(peers.add(addr(1), PeerSource::Static));
+        assert!(peers.add(addr(2), PeerSource::Mdns));
+        assert!(!peers.add(addr(2), PeerSource::Exchange));
+        // The discovered peers are limited, the configured ones aren't
+        assert!(!peers.add(addr(3), PeerSource::Exchange));
+        assert!(peers.add(addr(4), PeerSource::Bootnode));
+        assert_eq!(3, peers.len());
+
+        peers.reward(&addr(2));
+        assert_eq!(addr(2), peers.addrs()[0]);
+        for _ in 0..10 {
+            peers.penalize(&addr(1), UNREACHABLE_PENALTY);
+            peers.penalize(&addr(2), UNREACHABLE_PENALTY);
+        }
+        assert_eq!(vec![addr(4), addr(1)], peers.addrs());
+        assert!(peers.add(addr(3), PeerSource::Exchange));
+
+        peers.ban(addr(1).ip());
+        assert!(peers.is_empty());
+        assert!(!peers.add(addr(5), PeerSource::Static));
+
+        assert!(is_invalid_block(&BlockchainError::HashMismatch));
+        assert!(!is_invalid_block(&BlockchainError::PrevHashMismatch));
+    }
+}
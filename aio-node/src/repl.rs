@@ -0,0 +1,226 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use blockchain::{Blockchain, BlockchainError, Transaction, TransactionData};
+
+use crate::config::ConsensusConfig;
+use crate::io_error;
+
+/// The commands of the REPL, as listed by `help`.
+const HELP: &str = "\
+Commands:
+  balance <account>                 prints the tokens of the account
+  transfer <from> <to> <amount>     submits a transfer of tokens to the mempool
+  faucet <from> <to> <amount>       submits a minting of tokens (on development networks)
+  mine                              produces a block out of the mempool
+  validate                          checks the validity of the whole chain
+  dump block <height>               prints the block (as JSON)
+  dump account <account>            prints the account (as JSON)
+  pending                           prints the transactions of the mempool
+  help                              prints this help
+  exit                              leaves the REPL";
+
+/// Drives the chain interactively, one command per line of the input (see `HELP`), printing
+/// the outcome of every command to the output, until the input ends or `exit` is entered.<br/>
+/// Failed commands print their error, without ending the REPL.
+pub fn run<R: BufRead, W: Write>(
+    chain: &mut Blockchain,
+    input: R,
+    mut output: W,
+) -> Result<(), BlockchainError> {
+    writeln!(
+        output,
+        "Chain `{}` at height {} (type `help` for the commands)",
+        chain.chain_id(),
+        chain.len()
+    )
+    .map_err(io_error)?;
+
+    let mut lines = input.lines();
+    loop {
+        write!(output, "> ").map_err(io_error)?;
+        output.flush().map_err(io_error)?;
+        let line = match lines.next() {
+            Some(line) => line.map_err(io_error)?,
+            None => break,
+        };
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if let ["exit"] | ["quit"] = words.as_slice() {
+            break;
+        }
+        match execute(chain, &words) {
+            Ok(out) if out.is_empty() => {}
+            Ok(out) => writeln!(output, "{}", out).map_err(io_error)?,
+            Err(err) => writeln!(output, "Error: {}", err).map_err(io_error)?,
+        }
+    }
+    chain.flush()
+}
+
+/// The failure of a command: either it was mistyped or the chain refused it.
+#[derive(Debug)]
+enum CommandError {
+    Usage(String),
+    Chain(BlockchainError),
+}
+
+impl From<BlockchainError> for CommandError {
+    fn from(err: BlockchainError) -> Self {
+        CommandError::Chain(err)
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Usage(message) => write!(f, "{}", message),
+            CommandError::Chain(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Will execute the command (split into its words) against the chain and return its output
+fn execute(chain: &mut Blockchain, words: &[&str]) -> Result<String, CommandError> {
+    match words {
+        [] => Ok(String::new()),
+        ["help"] => Ok(HELP.into()),
+        ["balance", account] => chain
+            .get_account(account)
+            .map(|account| account.tokens().to_string())
+            .ok_or_else(|| BlockchainError::UnknownAccount(account.to_string()).into()),
+        ["transfer", from, to, amount] => {
+            let amount = amount
+                .parse()
+                .map_err(|_| usage(format!("Invalid amount: {}", amount)))?;
+            let transfer = TransactionData::TransferTokens {
+                to: to.to_string(),
+                amount,
+                asset: None,
+            };
+            submit(chain, from, transfer)
+        }
+        ["faucet", from, to, amount] => {
+            let amount = amount
+                .parse()
+                .map_err(|_| usage(format!("Invalid amount: {}", amount)))?;
+            let faucet = TransactionData::Faucet {
+                to: to.to_string(),
+                amount,
+            };
+            submit(chain, from, faucet)
+        }
+        ["mine"] => {
+            let max_txs = ConsensusConfig::default().max_block_transactions;
+            let result = chain.produce_block(max_txs)?;
+            chain.flush()?;
+            Ok(format!(
+                "Produced block {} with {} transactions ({} pending)",
+                chain.len() - 1,
+                result.receipts.len(),
+                chain.pending_transactions().len()
+            ))
+        }
+        ["validate"] => {
+            chain.check_validity()?;
+            Ok(format!("The chain of {} blocks is valid", chain.len()))
+        }
+        ["dump", "block", height] => {
+            let height = height
+                .parse()
+                .map_err(|_| usage(format!("Invalid height: {}", height)))?;
+            let block = chain
+                .get_block(height)?
+                .ok_or_else(|| usage(format!("There is no block at height {}", height)))?;
+            to_json(block)
+        }
+        ["dump", "account", account] => {
+            let account = chain
+                .get_account(account)
+                .ok_or_else(|| BlockchainError::UnknownAccount(account.to_string()))?;
+            to_json(account)
+        }
+        ["pending"] => to_json(&chain.pending_transactions()),
+        _ => Err(usage(format!(
+            "Unknown command `{}` (type `help` for the commands)",
+            words.join(" ")
+        ))),
+    }
+}
+
+/// Will submit a transaction of the operation from the sender, with its next nonce and the
+/// lowest fee the mempool accepts, to the mempool
+fn submit(
+    chain: &mut Blockchain,
+    from: &str,
+    operation: TransactionData,
+) -> Result<String, CommandError> {
+    let transaction = Transaction::new(from.to_string(), operation, chain.next_nonce(from))
+        .with_chain_id(chain.chain_id().into())
+        .with_version(chain.policy().version_at(chain.len()))
+        .with_fee(chain.minimum_fee());
+    let hash = transaction.calculate_hash();
+    chain.submit_transaction(transaction)?;
+    Ok(format!("Submitted transaction {}", hash))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()).into())
+}
+
+fn usage(message: String) -> CommandError {
+    CommandError::Usage(message)
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::run;
+    use crate::{init, open_chain};
+
+    #[test]
+    fn commands_drive_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        init(dir.path(), None, false).unwrap();
+        let mut chain = open_chain(dir.path()).unwrap();
+
+        let input = "\
+balance bob
+transfer alice bob 10
+transfer alice bob lots
+faucet alice dave 500
+mine
+balance bob
+balance dave
+validate
+dump block 1
+launch rockets
+exit
+balance bob
+";
+        let mut output = Vec::new();
+        run(&mut chain, input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("> 100000000\n"));
+        assert!(output.contains("Submitted transaction"));
+        assert!(output.contains("Error: Invalid amount: lots"));
+        assert!(output.contains("Produced block 1 with 2 transactions (0 pending)"));
+        assert!(output.contains("> 100000010\n"));
+        assert!(output.contains("> 500\n"));
+        assert!(output.contains("The chain of 2 blocks is valid"));
+        assert!(output.contains("\"transactions\""));
+        assert!(output.contains("Error: Unknown command `launch rockets`"));
+        // Nothing is executed after `exit`
+        assert_eq!(2, output.matches("> 1000000").count());
+
+        // The mined block is persisted
+        drop(chain);
+        assert_eq!(2, open_chain(dir.path()).unwrap().len());
+    }
+}
@@ -0,0 +1,325 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use blockchain::{Transaction, WorldState};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::explorer;
+use crate::limits::RateLimiter;
+use crate::metrics;
+use crate::p2p::Network;
+
+/// A (minimal) HTTP response carrying JSON, or HTML (or plain text) if it is rendered as such.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+    pub html: Option<String>,
+    pub text: Option<String>,
+}
+
+impl Response {
+    pub(crate) fn ok(body: Value) -> Self {
+        Response {
+            status: 200,
+            body,
+            html: None,
+            text: None,
+        }
+    }
+
+    pub(crate) fn error(status: u16, message: String) -> Self {
+        Response {
+            status,
+            body: json!({ "error": message }),
+            html: None,
+            text: None,
+        }
+    }
+}
+
+/// Will start serving the HTTP/JSON API of the node (in a background thread)
+/// and return the address that it is listening on.<br/>
+/// The API offers:
+/// - `GET /status` the id of the node (see `Identity`), the chain id, the height and the last block hash
+/// - `GET /accounts/<id>` the balance and the nonce of an account (or of an alias)
+/// - `POST /transactions` submits a (JSON encoded) transaction to the mempool
+///
+/// and the views of the (block) explorer (see `explorer::handle`).<br/>
+/// Clients are held to the limits of the network: the ones sending requests too often are
+/// answered with `429`, too big requests with `413` and stalling clients are dropped.<br/>
+/// No requests are accepted anymore once the node is shut down.
+pub fn serve(network: Arc<Network>, addr: SocketAddr) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let limiter = Arc::new(RateLimiter::new(network.limits().requests_per_second));
+    network.shutdown().watch(local_addr);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if network.shutdown().is_requested() {
+                break;
+            }
+            let network = Arc::clone(&network);
+            let limiter = Arc::clone(&limiter);
+            thread::spawn(move || {
+                if let Err(err) = serve_connection(&network, &limiter, stream) {
+                    warn!(error = %err, "RPC connection failed");
+                }
+            });
+        }
+    });
+
+    Ok(local_addr)
+}
+
+/// Will read a single request and write its response
+fn serve_connection(network: &Network, limiter: &RateLimiter, stream: TcpStream) -> io::Result<()> {
+    let limits = network.limits();
+    stream.set_read_timeout(Some(limits.timeout))?;
+    stream.set_write_timeout(Some(limits.timeout))?;
+    let ip = stream.peer_addr()?.ip();
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    // Only the length of the body is of interest
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let response = if !limiter.allow(ip) {
+        Response::error(429, "Too many requests".into())
+    } else if content_length > limits.max_body_size {
+        Response::error(413, "The request body is too large".into())
+    } else {
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body)?;
+        handle(network, &method, &path, &body)
+    };
+    write_response(stream, &response)
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> io::Result<()> {
+    let (content_type, body) = match (&response.html, &response.text) {
+        (Some(html), _) => ("text/html; charset=utf-8", html.clone()),
+        (None, Some(text)) => ("text/plain; version=0.0.4", text.clone()),
+        (None, None) => ("application/json", response.body.to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        _ => "Unknown",
+    }
+}
+
+/// Will answer a request of the API
+pub fn handle(network: &Network, method: &str, path: &str, body: &[u8]) -> Response {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if method == "GET" {
+        let owned: Vec<String> = segments.iter().map(|segment| segment.to_string()).collect();
+        let query = query.to_string();
+        let explored = network.chain().blocking_query(move |chain| {
+            let segments: Vec<&str> = owned.iter().map(String::as_str).collect();
+            explorer::handle(chain, &segments, &query)
+        });
+        if let Some(response) = explored {
+            return response;
+        }
+    }
+
+    match (method, segments.as_slice()) {
+        ("GET", ["metrics"]) => Response {
+            text: Some(
+                network
+                    .chain()
+                    .blocking_query(|chain| metrics::render(chain)),
+            ),
+            ..Response::ok(Value::Null)
+        },
+        ("GET", ["status"]) => {
+            let node_id = network.identity().id().to_string();
+            network.chain().blocking_query(move |chain| {
+                Response::ok(json!({
+                    "node_id": node_id,
+                    "chain_id": chain.chain_id(),
+                    "height": chain.len(),
+                    "last_block_hash": chain.get_last_block_hash(),
+                    "pending_transactions": chain.pending_transactions().len(),
+                    "pruned_height": chain.pruned_height(),
+                }))
+            })
+        }
+        ("GET", ["accounts", id]) => {
+            let id = id.to_string();
+            network.chain().blocking_query(move |chain| {
+                let id = chain.resolve_account_id(&id);
+                match chain.get_account_by_id(&id) {
+                    Some(account) => Response::ok(json!({
+                        "id": id,
+                        "balance": account.tokens().to_string(),
+                        "assets": explorer::asset_balances(account),
+                        "nonce": account.nonce().to_string(),
+                    })),
+                    None => Response::error(404, format!("Account `{}` does not exist", id)),
+                }
+            })
+        }
+        ("POST", ["transactions"]) => {
+            let transaction: Transaction = match serde_json::from_slice(body) {
+                Ok(transaction) => transaction,
+                Err(err) => return Response::error(400, err.to_string()),
+            };
+            let hash = transaction.calculate_hash();
+            match network.submit_transaction(transaction) {
+                Ok(()) => Response {
+                    status: 202,
+                    body: json!({ "hash": hash }),
+                    html: None,
+                    text: None,
+                },
+                Err(err) => Response::error(400, err.to_string()),
+            }
+        }
+        _ => Response::error(404, format!("No such endpoint: {} {}", method, path)),
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::Arc;
+
+    use blockchain::{Blockchain, GenesisConfig, Transaction, TransactionData};
+
+    use super::{handle, serve};
+    use crate::limits::Limits;
+    use crate::noise::Identity;
+    use crate::p2p::Network;
+
+    fn network(limits: Limits) -> Arc<Network> {
+        let config = GenesisConfig::from_toml(
+            r#"
+            chain_id = "test"
+            [[accounts]]
+            id = "alice"
+            balance = 10
+            "#,
+        )
+        .unwrap();
+        let chain = Blockchain::from_genesis(&config).unwrap();
+        Network::new(
+            crate::actor::spawn(chain).unwrap(),
+            Vec::new(),
+            limits,
+            Identity::generate(),
+        )
+    }
+
+    #[test]
+    fn queries_and_submissions() {
+        let network = network(Limits::default());
+
+        let status = handle(&network, "GET", "/status", &[]);
+        assert_eq!(200, status.status);
+        assert_eq!("test", status.body["chain_id"]);
+        assert_eq!(network.identity().id().to_string(), status.body["node_id"]);
+        assert_eq!(1, status.body["height"]);
+
+        let account = handle(&network, "GET", "/accounts/alice", &[]);
+        assert_eq!("10", account.body["balance"]);
+        assert_eq!(404, handle(&network, "GET", "/accounts/bob", &[]).status);
+
+        let transaction = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        )
+        .with_chain_id("test".into());
+        let body = serde_json::to_vec(&transaction).unwrap();
+        assert_eq!(202, handle(&network, "POST", "/transactions", &body).status);
+        assert_eq!(
+            1,
+            handle(&network, "GET", "/status", &[]).body["pending_transactions"]
+        );
+
+        // Transactions of another network are rejected
+        let body = serde_json::to_vec(&transaction.with_chain_id("dev".into())).unwrap();
+        assert_eq!(400, handle(&network, "POST", "/transactions", &body).status);
+        assert_eq!(400, handle(&network, "POST", "/transactions", b"{").status);
+        assert_eq!(404, handle(&network, "GET", "/unknown", &[]).status);
+
+        let metrics = handle(&network, "GET", "/metrics", &[]).text.unwrap();
+        assert!(metrics.contains("\naio_mempool_size 1\n"));
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_http() {
+        let addr = serve(network(Limits::default()), "127.0.0.1:0".parse().unwrap()).unwrap();
+        let response = get(addr, "/status");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\"chain_id\":\"test\""));
+    }
+
+    #[test]
+    fn clients_exceeding_the_rate_limit_are_refused() {
+        let limits = Limits {
+            requests_per_second: 2,
+            ..Limits::default()
+        };
+        let addr = serve(network(limits), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        assert!(get(addr, "/status").starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(get(addr, "/status").starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(get(addr, "/status").starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+    }
+}
@@ -0,0 +1,83 @@
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Tells the tasks of a node (the servers and the block production) to stop, once the node is
+/// shut down (see `Node::shutdown`).<br/>
+/// Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    requested: Mutex<bool>,
+    condvar: Condvar,
+
+    /// The addresses of the listeners to wake up, as they block until the next connection
+    listeners: Mutex<Vec<SocketAddr>>,
+}
+
+impl Shutdown {
+    /// Will request the shutdown, waking up whoever waits for it (see `wait_timeout`)
+    /// and the listeners (see `watch`)
+    pub fn request(&self) {
+        *self.inner.requested.lock().unwrap() = true;
+        self.inner.condvar.notify_all();
+        for addr in self.inner.listeners.lock().unwrap().drain(..) {
+            // The listener is woken up by a connection that it drops right away
+            let _ = TcpStream::connect_timeout(&local(addr), Duration::from_secs(1));
+        }
+    }
+
+    /// Will return true once the shutdown was requested
+    pub fn is_requested(&self) -> bool {
+        *self.inner.requested.lock().unwrap()
+    }
+
+    /// Will wait for the shutdown for at most the timeout, returning true if it was requested
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let requested = self.inner.requested.lock().unwrap();
+        let (requested, _) = self
+            .inner
+            .condvar
+            .wait_timeout_while(requested, timeout, |requested| !*requested)
+            .unwrap();
+        *requested
+    }
+
+    /// Will wait for the shutdown
+    pub fn wait(&self) {
+        let requested = self.inner.requested.lock().unwrap();
+        let _requested = self
+            .inner
+            .condvar
+            .wait_while(requested, |requested| !*requested)
+            .unwrap();
+    }
+
+    /// Registers the address of a listener, which checks `is_requested` after every
+    /// connection: it is woken up by a connection when the shutdown is requested
+    pub fn watch(&self, addr: SocketAddr) {
+        // The shutdown is requested before the listeners are woken up, so that either is seen
+        let mut listeners = self.inner.listeners.lock().unwrap();
+        if self.is_requested() {
+            let _ = TcpStream::connect_timeout(&local(addr), Duration::from_secs(1));
+        } else {
+            listeners.push(addr);
+        }
+    }
+}
+
+/// Will return the address to connect to a listener bound to the address (which may be the
+/// unspecified one) on this host
+fn local(mut addr: SocketAddr) -> SocketAddr {
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr {
+            SocketAddr::V4(_) => [127, 0, 0, 1].into(),
+            SocketAddr::V6(_) => std::net::Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    addr
+}
@@ -0,0 +1,81 @@
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use tracing::{info, info_span, warn};
+
+use crate::actor::ChainClient;
+use crate::noise::Identity;
+use crate::p2p::{request, Handshake, Message};
+
+/// The progress of synchronizing the chain with a peer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyncStatus {
+    /// No synchronization was started yet
+    Idle,
+
+    /// Blocks are being downloaded (and validated) from a peer
+    Syncing { height: usize, target: usize },
+
+    /// The chain caught up with the peer's tip
+    Synced { height: usize },
+
+    /// The synchronization stopped due to an error
+    Failed(String),
+}
+
+/// Will download the blocks (starting with the local height) from the peer,
+/// validating and appending them one by one, until the chain catches up with the peer's tip.
+pub fn sync(
+    chain: &ChainClient,
+    peer: &SocketAddr,
+    identity: &Identity,
+    handshake: &Handshake,
+    status: &Mutex<SyncStatus>,
+) -> Result<(), String> {
+    let _span = info_span!("sync", %peer).entered();
+    let result = download(chain, peer, identity, handshake, status);
+    if let Err(err) = &result {
+        warn!(error = %err, "Sync failed");
+        *status.lock().unwrap() = SyncStatus::Failed(err.clone());
+    }
+    result
+}
+
+fn download(
+    chain: &ChainClient,
+    peer: &SocketAddr,
+    identity: &Identity,
+    handshake: &Handshake,
+    status: &Mutex<SyncStatus>,
+) -> Result<(), String> {
+    loop {
+        let from = chain.blocking_query(|chain| chain.len());
+        let (blocks, target) =
+            match request(peer, identity, handshake, &Message::GetBlocks { from }) {
+                Ok(Message::Blocks { blocks, height }) => (blocks, height),
+                Ok(other) => return Err(format!("Unexpected sync response {:?}", other)),
+                Err(err) => return Err(format!("Could not sync with peer {}: {}", peer, err)),
+            };
+
+        if blocks.is_empty() {
+            if from < target {
+                return Err(format!(
+                    "The peer {} pruned the blocks starting with height {}",
+                    peer, from
+                ));
+            }
+            *status.lock().unwrap() = SyncStatus::Synced { height: from };
+            return Ok(());
+        }
+
+        let height = chain.blocking_query(|chain| {
+            for block in blocks {
+                // append_block validates the block against the current tip
+                chain.append_block(block).map_err(|err| err.to_string())?;
+            }
+            Ok::<_, String>(chain.len())
+        })?;
+        info!(height, target, "Downloaded blocks");
+        *status.lock().unwrap() = SyncStatus::Syncing { height, target };
+    }
+}
@@ -0,0 +1,114 @@
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use tracing::debug;
+use tungstenite::Message;
+
+use crate::p2p::Network;
+
+/// Will start serving the subscription API of the node (in a background thread)
+/// and return the address that it is listening on.<br/>
+/// Every WebSocket client gets all changes of the chain (see `blockchain::ChainEvent`)
+/// as JSON text messages, as soon as they happen, until the node is shut down.
+pub fn serve(network: Arc<Network>, addr: SocketAddr) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    network.shutdown().watch(local_addr);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if network.shutdown().is_requested() {
+                break;
+            }
+            let network = Arc::clone(&network);
+            thread::spawn(move || {
+                if let Err(err) = serve_subscriber(&network, stream) {
+                    debug!(error = %err, "Subscription ended");
+                }
+            });
+        }
+    });
+
+    Ok(local_addr)
+}
+
+/// Will forward the events of the chain to the client, until it goes away
+fn serve_subscriber(network: &Network, stream: TcpStream) -> io::Result<()> {
+    // A client that stalls the handshake, or stops reading the events, is dropped
+    stream.set_read_timeout(Some(network.limits().timeout))?;
+    stream.set_write_timeout(Some(network.limits().timeout))?;
+    // Subscribing before the handshake completes, so that the client misses no event
+    let events = network.chain().blocking_query(|chain| chain.subscribe());
+    let mut socket = tungstenite::accept(stream).map_err(ws_error)?;
+
+    for event in events {
+        let json = serde_json::to_string(&event).map_err(io::Error::other)?;
+        socket.send(Message::Text(json)).map_err(ws_error)?;
+    }
+    socket.close(None).map_err(ws_error)
+}
+
+fn ws_error<E: ToString>(err: E) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use blockchain::{Blockchain, GenesisConfig, Transaction, TransactionData};
+    use serde_json::Value;
+
+    use super::serve;
+    use crate::limits::Limits;
+    use crate::noise::Identity;
+    use crate::p2p::Network;
+
+    #[test]
+    fn events_are_pushed_to_subscribers() {
+        let config = GenesisConfig::from_toml(
+            r#"
+            chain_id = "test"
+            [[accounts]]
+            id = "alice"
+            balance = 10
+            "#,
+        )
+        .unwrap();
+        let chain = Blockchain::from_genesis(&config).unwrap();
+        let network = Network::new(
+            crate::actor::spawn(chain).unwrap(),
+            Vec::new(),
+            Limits::default(),
+            Identity::generate(),
+        );
+        let addr = serve(Arc::clone(&network), "127.0.0.1:0".parse().unwrap()).unwrap();
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}", addr)).unwrap();
+
+        network
+            .submit_transaction(
+                Transaction::new(
+                    "alice".into(),
+                    TransactionData::CreateUserAccount("bob".into()),
+                    0,
+                )
+                .with_chain_id("test".into()),
+            )
+            .unwrap();
+        network.produce_block(1).unwrap();
+
+        let mut read = || -> Value {
+            let message = socket.read().unwrap();
+            serde_json::from_str(message.to_text().unwrap()).unwrap()
+        };
+        assert_eq!(1, read()["BlockAppended"]["height"]);
+        assert_eq!(1, read()["TransactionExecuted"]["block_height"]);
+    }
+}
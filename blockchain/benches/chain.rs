@@ -0,0 +1,101 @@
+use blockchain::test_utils::{
+    account_id, mine_block, signed_transaction, ChainBuilder, TEST_CHAIN_ID,
+};
+use blockchain::{Block, Blockchain, Transaction, TransactionData};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+/// The amounts of transactions (per block or submitted) the benchmarks are run with.
+const TRANSACTIONS: [usize; 4] = [1, 10, 100, 1000];
+
+/// Will build a chain where `alice` holds enough tokens for any of the benchmarked transfers.
+fn funded_chain() -> Blockchain {
+    ChainBuilder::new()
+        .with_account("alice", u64::MAX.into())
+        .with_account("bob", 0)
+        .build()
+}
+
+/// Will return `count` transfers from `alice` to `bob`, signed and with consecutive nonces.
+fn transfers(count: usize) -> Vec<Transaction> {
+    (1..=count as u128)
+        .map(|nonce| {
+            let data = TransactionData::TransferTokens {
+                to: account_id("bob"),
+                amount: 1,
+                asset: None,
+            };
+            signed_transaction(TEST_CHAIN_ID, "alice", data, nonce)
+        })
+        .collect()
+}
+
+/// Measures hashing a block, which hashes its transactions into their Merkle root.
+fn calculate_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("calculate_hash");
+    for count in TRANSACTIONS {
+        let mut block = Block::new(None);
+        for transaction in transfers(count) {
+            block.add_transaction(transaction);
+        }
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("transactions", count),
+            &block,
+            |b, block| b.iter(|| block.calculate_hash()),
+        );
+    }
+    group.finish();
+}
+
+/// Measures appending a (mined) block, i.e. validating and executing its transactions.
+fn append_block(c: &mut Criterion) {
+    let mut chain = funded_chain();
+    let mut group = c.benchmark_group("append_block");
+    group.sample_size(10);
+    for count in TRANSACTIONS {
+        let block = mine_block(&mut chain, transfers(count)).unwrap();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("transactions", count),
+            &block,
+            |b, block| {
+                b.iter_batched(
+                    || (chain.clone(), block.clone()),
+                    |(mut chain, block)| chain.append_block(block).unwrap(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Measures submitting transactions to the mempool, which checks each of them.
+fn submit_transaction(c: &mut Criterion) {
+    let chain = funded_chain();
+    let mut group = c.benchmark_group("submit_transaction");
+    group.sample_size(10);
+    for count in TRANSACTIONS {
+        let transactions = transfers(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("transactions", count),
+            &transactions,
+            |b, transactions| {
+                b.iter_batched(
+                    || (chain.clone(), transactions.clone()),
+                    |(mut chain, transactions)| {
+                        for transaction in transactions {
+                            chain.submit_transaction(transaction).unwrap();
+                        }
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, calculate_hash, append_block, submit_transaction);
+criterion_main!(benches);
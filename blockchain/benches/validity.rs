@@ -0,0 +1,88 @@
+use blockchain::{Block, Blockchain, Keypair, Transaction, TransactionData, ValidationPolicy};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// The amount of blocks (after the genesis block) of the benchmarked chain.
+const BLOCKS: u128 = 200;
+
+/// The lengths (in blocks, after the genesis block) of the chains checked sequentially.
+const LENGTHS: [usize; 3] = [50, 100, 200];
+
+/// The amount of (signed) transactions per block.
+const TRANSACTIONS_PER_BLOCK: u128 = 20;
+
+/// Will build a chain whose blocks are full of signed transfers.
+fn signed_chain() -> Blockchain {
+    let keypair = Keypair::generate();
+    let sender = keypair.address().to_string();
+    let mut chain = Blockchain::with_policy(ValidationPolicy {
+        require_signatures: true,
+        ..ValidationPolicy::default()
+    });
+
+    let mut genesis = Block::new(None);
+    genesis.add_transaction(Transaction::new(
+        sender.clone(),
+        TransactionData::CreateUserAccount(sender.clone()),
+        0,
+    ));
+    genesis.add_transaction(Transaction::new(
+        sender.clone(),
+        TransactionData::CreateTokens {
+            receiver: sender.clone(),
+            amount: u64::MAX.into(),
+            asset: None,
+        },
+        0,
+    ));
+    chain.append_block(genesis).unwrap();
+
+    for block in 0..BLOCKS {
+        for index in 0..TRANSACTIONS_PER_BLOCK {
+            let data = TransactionData::TransferTokens {
+                to: sender.clone(),
+                amount: 1,
+                asset: None,
+            };
+            let mut transaction =
+                Transaction::new(sender.clone(), data, block * TRANSACTIONS_PER_BLOCK + index);
+            transaction.sign(&keypair);
+            chain.submit_transaction(transaction).unwrap();
+        }
+        chain
+            .produce_block(TRANSACTIONS_PER_BLOCK as usize)
+            .unwrap();
+    }
+    chain
+}
+
+/// Compares checking the validity of the chain sequentially with checking it in parallel,
+/// and measures how checking it sequentially scales with its length.
+fn check_validity(c: &mut Criterion) {
+    let full = signed_chain();
+    let mut chain = full.clone();
+    let mut group = c.benchmark_group("check_validity");
+    group.sample_size(10);
+    for threads in [1, 2, 4, 0] {
+        chain.set_validation_threads(threads);
+        group.bench_with_input(BenchmarkId::new("threads", threads), &chain, |b, chain| {
+            b.iter(|| chain.check_validity().unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("check_validity_length");
+    group.sample_size(10);
+    for blocks in LENGTHS {
+        // Rewinding replays the kept blocks, which is cheaper than mining them again
+        let mut chain = full.clone();
+        chain.rewind(blocks + 1).unwrap();
+        chain.set_validation_threads(1);
+        group.bench_with_input(BenchmarkId::new("blocks", blocks), &chain, |b, chain| {
+            b.iter(|| chain.check_validity().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, check_validity);
+criterion_main!(benches);
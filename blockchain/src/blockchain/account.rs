@@ -1,51 +1,176 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
+use crate::VestingTranche;
+
 /// Represents an account on the blockchain.<br/>
 /// This is basically the primary part of the "world state" of the blockchain.<br/>
 /// It is the final status after performing all blocks in order.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Account {
     /// We want the account to be able to store any information we want (Dictionary)
-    store: HashMap<String, String>,
+    #[serde(serialize_with = "super::serialization::ordered_map")]
+    pub(crate) store: HashMap<String, String>,
 
     /// store if this is a user account or sth else
-    acc_type: AccountType,
+    pub(crate) acc_type: AccountType,
 
-    /// Amount of tokens that account owns (like BTC or ETH)
+    /// Amount of (native) tokens that account owns (like BTC or ETH), which pay the fees,
+    /// the gas and the stakes
     pub(crate) tokens: u128,
+
+    /// The balances of the other assets (see `TransactionData::CreateAsset`), by their ids
+    #[serde(default, serialize_with = "super::serialization::ordered_map")]
+    pub(crate) assets: HashMap<String, u128>,
+
+    /// The amount of transactions the account has sent (and that were executed)
+    pub(crate) nonce: u128,
+
+    /// Set if an admin froze the account, which then can't transfer its tokens
+    #[serde(default)]
+    pub(crate) frozen: bool,
+
+    /// The (non-fungible) tokens the account owns, their metadata by their ids
+    #[serde(default, serialize_with = "super::serialization::ordered_map")]
+    pub(crate) nfts: HashMap<String, String>,
+
+    /// The amounts of the tokens that other accounts (spenders) may transfer on behalf of
+    /// this one, by the ids of the spenders
+    #[serde(default, serialize_with = "super::serialization::ordered_map")]
+    pub(crate) allowances: HashMap<String, u128>,
+
+    /// The profile of the account (e.g. its display name, its URL or the key it rotates to),
+    /// set by its `SetAccountMetadata` transactions
+    #[serde(default, serialize_with = "super::serialization::ordered_map")]
+    pub(crate) metadata: HashMap<String, String>,
+
+    /// The tranches of tokens that are locked until the chain reaches their locks (see
+    /// `TransactionData::CreateVestingAccount`)
+    #[serde(default)]
+    pub(crate) vesting: Vec<VestingTranche>,
 }
 
 /// We can support different types of accounts
-/// which could be used to represent different roles within the system.<br/>
-/// This is just for later extension, for now we will only use User accounts.
-#[derive(Clone, Debug)]
+/// which could be used to represent different roles within the system.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum AccountType {
     /// A common user account
     User,
 
     /// An account that technically does not represent an individual
-    /// Think of this like a SmartContract in Ethereum. We will not use it
-    /// in our implementation. It's just here if you want to go on implementing
-    /// to provide a starting point for more :)
-    Contract,
-
-    /// Add whatever roles you need.
-    /// Again, we will NOT make use of this for the example here
-    Validator {
-        // Again, enum members in rust may store additional data
-        correctly_validated_blocks: u128,
-        incorrectly_validated_blocks: u128,
-        you_get_the_idea: bool,
-    },
+    /// Think of this like a SmartContract in Ethereum.<br/>
+    /// It holds the (WASM) code, whilst its storage is the account's store.
+    Contract { code: Vec<u8> },
+
+    /// An account controlled by its owners (addresses): its transactions have to be
+    /// signed by at least `threshold` of them.
+    Multisig { owners: Vec<String>, threshold: u32 },
+
+    /// A user account that locked some of its tokens (stake), which entitles it to produce
+    /// blocks under Proof of Stake.
+    Validator { stake: u128 },
+
+    /// A user account that administrates the (permissioned) network, e.g. by freezing
+    /// accounts. Admins can only be created by the genesis block.
+    Admin,
 }
 
 impl Account {
     /// C'tor.
     pub fn new(account_type: AccountType) -> Self {
-        return Self {
+        Self {
             tokens: 0,
+            assets: HashMap::new(),
+            nonce: 0,
             acc_type: account_type,
             store: HashMap::new(),
-        };
+            frozen: false,
+            nfts: HashMap::new(),
+            allowances: HashMap::new(),
+            metadata: HashMap::new(),
+            vesting: Vec::new(),
+        }
+    }
+
+    /// Will return the amount of tokens the account owns
+    pub fn tokens(&self) -> u128 {
+        self.tokens
+    }
+
+    /// Will return the balances of the assets (other than the native tokens) the account owns
+    pub fn assets(&self) -> &HashMap<String, u128> {
+        &self.assets
+    }
+
+    /// Will return the (non-fungible) tokens the account owns, their metadata by their ids
+    pub fn nfts(&self) -> &HashMap<String, String> {
+        &self.nfts
+    }
+
+    /// Will return the amount of the asset that the account owns
+    pub fn asset_balance(&self, asset: &str) -> u128 {
+        self.assets.get(asset).copied().unwrap_or(0)
+    }
+
+    /// Will return the amount of transactions the account has sent
+    pub fn nonce(&self) -> u128 {
+        self.nonce
+    }
+
+    /// Will return the values stored by the account
+    pub fn store(&self) -> &HashMap<String, String> {
+        &self.store
+    }
+
+    /// Will return the metadata entries of the account (see `TransactionData::SetAccountMetadata`)
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// Will return the vesting schedule of the account (empty unless it was created by a
+    /// `CreateVestingAccount` transaction)
+    pub fn vesting(&self) -> &[VestingTranche] {
+        &self.vesting
+    }
+
+    /// Will return the amount of tokens that are still locked by the vesting schedule at the
+    /// block of the height, whose previous block has the timestamp
+    pub fn locked_tokens(&self, height: usize, timestamp: u64) -> u128 {
+        self.vesting
+            .iter()
+            .filter(|tranche| !tranche.unlocks_at.is_reached(height, timestamp))
+            .fold(0, |locked, tranche| locked.saturating_add(tranche.amount))
+    }
+
+    /// Will return the type of the account
+    pub fn account_type(&self) -> &AccountType {
+        &self.acc_type
+    }
+
+    /// Will return the amount of staked tokens (0 unless it is a validator account)
+    pub fn stake(&self) -> u128 {
+        match self.acc_type {
+            AccountType::Validator { stake } => stake,
+            _ => 0,
+        }
+    }
+
+    /// Will return true if an admin froze the account
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Will return the amount of the tokens that the spender may transfer on behalf of the account
+    pub fn allowance(&self, spender: &str) -> u128 {
+        self.allowances.get(spender).copied().unwrap_or(0)
+    }
+
+    /// Will return the code of a contract account (`None` for other accounts)
+    pub fn contract_code(&self) -> Option<&[u8]> {
+        match &self.acc_type {
+            AccountType::Contract { code } => Some(code),
+            _ => None,
+        }
     }
 }
@@ -0,0 +1,251 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{BlockchainError, Hash};
+
+/// The size (in bytes) of an address.
+pub const ADDRESS_BYTE_SIZE: usize = 20;
+
+/// The human-readable part of the (bech32 encoded) addresses.
+pub const ADDRESS_HRP: &str = "abc";
+
+/// The characters of the bech32 encoding (each one encodes 5 bits).
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The generator of the bech32 checksum (as of BIP173).
+const GENERATOR: [u32; 5] = [
+    0x3b6a_57b2,
+    0x2650_8e6d,
+    0x1ea1_19fa,
+    0x3d42_33dd,
+    0x2a14_62b3,
+];
+
+/// The address of an account, derived from the (Blake2b) hash of its public key.<br/>
+/// It is displayed and serialized in bech32 (e.g. `abc1...`), which includes a checksum.
+/// Parsing also accepts the plain hex representation (with or without a `0x` prefix).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address([u8; ADDRESS_BYTE_SIZE]);
+
+impl Address {
+    /// C'tor.
+    pub fn new(bytes: [u8; ADDRESS_BYTE_SIZE]) -> Self {
+        Address(bytes)
+    }
+
+    /// Will derive the address of a public key (32 bytes for Ed25519, 33 compressed bytes for
+    /// secp256k1)
+    pub fn from_public_key(public_key: &[u8]) -> Self {
+        let mut bytes = [0u8; ADDRESS_BYTE_SIZE];
+        bytes.copy_from_slice(&Hash::digest(public_key).as_bytes()[..ADDRESS_BYTE_SIZE]);
+        Address(bytes)
+    }
+
+    /// Will return the raw bytes of the address
+    pub fn as_bytes(&self) -> &[u8; ADDRESS_BYTE_SIZE] {
+        &self.0
+    }
+
+    /// Will return the lowercase hex representation of the address (without checksum)
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    /// Will parse the bech32 representation, verifying its checksum
+    fn from_bech32(s: &str) -> Result<Self, BlockchainError> {
+        let invalid = |reason: &str| BlockchainError::InvalidAddress(format!("{}: {}", s, reason));
+
+        let (hrp, values) = decode(s).ok_or_else(|| invalid("invalid checksum"))?;
+        if hrp != ADDRESS_HRP {
+            return Err(invalid("unexpected prefix"));
+        }
+        let bytes = convert_bits(&values, 5, 8, false).ok_or_else(|| invalid("invalid padding"))?;
+        if bytes.len() != ADDRESS_BYTE_SIZE {
+            return Err(invalid("invalid length"));
+        }
+        let mut address = [0u8; ADDRESS_BYTE_SIZE];
+        address.copy_from_slice(&bytes);
+        Ok(Address(address))
+    }
+
+    /// Will return the bech32 representation (including the checksum)
+    pub fn to_bech32(&self) -> String {
+        let values = convert_bits(&self.0, 8, 5, true).expect("Padding is allowed");
+        encode(ADDRESS_HRP, &values)
+    }
+}
+
+/// Will encode the (5 bit) values in bech32, appending the checksum
+fn encode(hrp: &str, values: &[u8]) -> String {
+    let mut checksum_input = [&expand_hrp(hrp), values].concat();
+    checksum_input.extend_from_slice(&[0; 6]);
+    let checksum = polymod(&checksum_input) ^ 1;
+
+    let checksum_values = (0..6).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8);
+    let data: String = values
+        .iter()
+        .copied()
+        .chain(checksum_values)
+        .map(|value| CHARSET[value as usize] as char)
+        .collect();
+    format!("{}1{}", hrp, data)
+}
+
+/// Will decode a (lowercase) bech32 string into its human-readable part and its (5 bit)
+/// values, if its checksum is valid
+fn decode(s: &str) -> Option<(&str, Vec<u8>)> {
+    let (hrp, data) = s.rsplit_once('1')?;
+    let values = data
+        .bytes()
+        .map(|c| CHARSET.iter().position(|&x| x == c).map(|v| v as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    if values.len() < 6 || polymod(&[&expand_hrp(hrp), &values[..]].concat()) != 1 {
+        return None;
+    }
+    Some((hrp, values[..values.len() - 6].to_vec()))
+}
+
+/// Will compute the bech32 checksum of the (5 bit) values
+fn polymod(values: &[u8]) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(*value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Will expand the human-readable part, so that it is covered by the checksum
+fn expand_hrp(hrp: &str) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 31));
+    values
+}
+
+/// Will regroup the bits of the values (e.g. from bytes to 5 bit values)
+fn convert_bits(values: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let max = (1 << to) - 1;
+    let mut result = Vec::new();
+    for value in values {
+        acc = (acc << from) | u32::from(*value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || ((acc << (to - bits)) & max) != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_bech32())
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address({})", self.to_bech32())
+    }
+}
+
+impl FromStr for Address {
+    type Err = BlockchainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex_str = s.strip_prefix("0x").unwrap_or(s);
+        if hex_str.len() == 2 * ADDRESS_BYTE_SIZE {
+            let mut bytes = [0u8; ADDRESS_BYTE_SIZE];
+            if hex::decode_to_slice(hex_str, &mut bytes).is_ok() {
+                return Ok(Address(bytes));
+            }
+        }
+        Self::from_bech32(s)
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_bech32())
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Address, ADDRESS_HRP};
+    use crate::Keypair;
+
+    #[test]
+    fn bech32_matches_bip173() {
+        let values: Vec<u8> = (0..32).collect();
+        let encoded = "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw";
+        assert_eq!(encoded, super::encode("abcdef", &values));
+        assert_eq!(Some(("abcdef", values)), super::decode(encoded));
+        assert_eq!(Some(("a", Vec::new())), super::decode("a12uel5l"));
+        assert_eq!(None, super::decode("a12uel5m"));
+    }
+
+    #[test]
+    fn round_trips() {
+        let address = Keypair::generate().address();
+        let bech32 = address.to_string();
+        assert!(bech32.starts_with(&format!("{}1", ADDRESS_HRP)));
+        assert_eq!(address, bech32.parse().unwrap());
+        assert_eq!(address, address.to_hex().parse().unwrap());
+        assert_eq!(address, format!("0x{}", address.to_hex()).parse().unwrap());
+
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(address, serde_json::from_str::<Address>(&json).unwrap());
+    }
+
+    #[test]
+    fn typos_are_detected() {
+        let bech32 = Keypair::generate().address().to_string();
+        let last = bech32.chars().last().unwrap();
+        let typo = format!(
+            "{}{}",
+            &bech32[..bech32.len() - 1],
+            if last == 'q' { 'p' } else { 'q' }
+        );
+        assert!(typo.parse::<Address>().is_err());
+        assert!("alice".parse::<Address>().is_err());
+        assert!(bech32.replacen("abc", "xyz", 1).parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn derived_from_public_key() {
+        let keypair = Keypair::generate();
+        let other = Keypair::generate();
+        assert_eq!(keypair.address(), keypair.address());
+        assert_ne!(keypair.address(), other.address());
+    }
+}
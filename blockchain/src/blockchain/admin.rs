@@ -0,0 +1,61 @@
+use crate::{AccountType, BlockchainError};
+
+use super::world_state::WorldState;
+
+/// Will freeze (or unfreeze) the account (which may be an alias) on behalf of the admin.<br/>
+/// Only admin accounts, which are created by the genesis block, may do so.
+/// Returns the id of the account.
+pub(crate) fn set_frozen<T: WorldState>(
+    world_state: &mut T,
+    admin: &str,
+    account: &str,
+    frozen: bool,
+) -> Result<String, BlockchainError> {
+    match world_state.get_account_by_id(admin).map(|a| &a.acc_type) {
+        Some(AccountType::Admin) => {}
+        _ => return Err(BlockchainError::NotAnAdmin(admin.to_string())),
+    }
+
+    let id = world_state.resolve_account_id(account);
+    let account = world_state
+        .get_account_by_id_mut(&id)
+        .ok_or_else(|| BlockchainError::UnknownAccount(id.clone()))?;
+    account.frozen = frozen;
+    Ok(id)
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::set_frozen;
+    use crate::{AccountType, Blockchain, BlockchainError, WorldState};
+
+    #[test]
+    fn only_admins_freeze_accounts() {
+        let mut chain = Blockchain::new();
+        chain
+            .create_account("admin".into(), AccountType::Admin)
+            .unwrap();
+        chain
+            .create_account("alice".into(), AccountType::User)
+            .unwrap();
+
+        assert_eq!(
+            Err(BlockchainError::NotAnAdmin("alice".into())),
+            set_frozen(&mut chain, "alice", "admin", true)
+        );
+        assert_eq!(
+            Err(BlockchainError::UnknownAccount("bob".into())),
+            set_frozen(&mut chain, "admin", "bob", true)
+        );
+
+        set_frozen(&mut chain, "admin", "alice", true).unwrap();
+        assert!(chain.get_account_by_id("alice").unwrap().is_frozen());
+        set_frozen(&mut chain, "admin", "alice", false).unwrap();
+        assert!(!chain.get_account_by_id("alice").unwrap().is_frozen());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{AccountType, Address, BlockchainError};
+
+use super::world_state::WorldState;
+
+/// The (reserved) account holding the alias registry, its store maps the aliases to addresses.
+pub const ALIAS_REGISTRY: &str = "@aliases";
+
+/// The minimum length of an alias.
+const MIN_ALIAS_LEN: usize = 3;
+
+/// The maximum length of an alias.
+const MAX_ALIAS_LEN: usize = 32;
+
+/// Will check if the alias meets the naming rules: 3 to 32 lowercase ASCII letters,
+/// digits, `-` or `_`, and not being an address itself
+pub(crate) fn validate_alias(alias: &str) -> Result<(), BlockchainError> {
+    let valid_chars = alias
+        .bytes()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-' || c == b'_');
+    if !valid_chars || alias.len() < MIN_ALIAS_LEN || alias.len() > MAX_ALIAS_LEN {
+        return Err(BlockchainError::InvalidAlias(alias.to_string()));
+    }
+    if alias.parse::<Address>().is_ok() {
+        return Err(BlockchainError::InvalidAlias(alias.to_string()));
+    }
+    Ok(())
+}
+
+/// Will register the alias for the owner (which has to be an address),
+/// creating the registry with the first alias
+pub(crate) fn register<T: WorldState>(
+    world_state: &mut T,
+    alias: &str,
+    owner: &str,
+) -> Result<(), BlockchainError> {
+    validate_alias(alias)?;
+    match owner.parse::<Address>() {
+        Ok(address) if address.to_string() == owner => {}
+        _ => return Err(BlockchainError::InvalidAlias(alias.to_string())),
+    }
+    if world_state.resolve_alias(alias).is_some() {
+        return Err(BlockchainError::AliasTaken(alias.to_string()));
+    }
+
+    if !world_state.account_exists(ALIAS_REGISTRY) {
+        world_state.create_account(ALIAS_REGISTRY.into(), AccountType::User)?;
+    }
+    world_state
+        .get_account_by_id_mut(ALIAS_REGISTRY)
+        .expect("The registry exists")
+        .store
+        .insert(alias.to_string(), owner.to_string());
+    Ok(())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::validate_alias;
+    use crate::Keypair;
+
+    #[test]
+    fn naming_rules() {
+        assert!(validate_alias("alice").is_ok());
+        assert!(validate_alias("bob_42-x").is_ok());
+        assert!(validate_alias("al").is_err());
+        assert!(validate_alias("Alice").is_err());
+        assert!(validate_alias("al ice").is_err());
+        assert!(validate_alias(&"a".repeat(33)).is_err());
+        assert!(validate_alias(&Keypair::generate().address().to_hex()).is_err());
+    }
+}
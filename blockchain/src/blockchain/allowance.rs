@@ -0,0 +1,119 @@
+use crate::BlockchainError;
+
+use super::vesting;
+use super::world_state::WorldState;
+
+/// Will allow the spender (which may be an alias) to transfer up to `amount` of the owner's
+/// tokens (see `transfer_from`), replacing the previous allowance.<br/>
+/// An allowance of zero revokes it. Returns the id of the spender.
+pub(crate) fn approve<T: WorldState>(
+    world_state: &mut T,
+    owner: &str,
+    spender: &str,
+    amount: u128,
+) -> Result<String, BlockchainError> {
+    let spender = world_state.resolve_account_id(spender);
+    if !world_state.account_exists(&spender) {
+        return Err(BlockchainError::UnknownAccount(spender));
+    }
+    let account = world_state
+        .get_account_by_id_mut(owner)
+        .ok_or_else(|| BlockchainError::UnknownAccount(owner.to_string()))?;
+    match amount {
+        0 => account.allowances.remove(&spender),
+        amount => account.allowances.insert(spender.clone(), amount),
+    };
+    Ok(spender)
+}
+
+/// Will transfer the amount of the owner's tokens to the receiver on behalf of the spender,
+/// spending the allowance the owner gave to it.<br/>
+/// The owner and the receiver may be aliases, their (resolved) ids are returned.
+pub(crate) fn transfer_from<T: WorldState>(
+    world_state: &mut T,
+    spender: &str,
+    owner: &str,
+    to: &str,
+    amount: u128,
+) -> Result<(String, String), BlockchainError> {
+    let owner = world_state.resolve_account_id(owner);
+    let to = world_state.resolve_account_id(to);
+    if !world_state.account_exists(&to) {
+        return Err(BlockchainError::UnknownAccount(to));
+    }
+
+    let account = world_state
+        .get_account_by_id_mut(&owner)
+        .ok_or_else(|| BlockchainError::UnknownAccount(owner.clone()))?;
+    if account.frozen {
+        return Err(BlockchainError::AccountFrozen(owner));
+    }
+    let allowance = account
+        .allowance(spender)
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientAllowance)?;
+    let remaining = account
+        .tokens
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    vesting::check_unlocked(world_state, &owner, remaining)?;
+
+    let account = world_state.get_account_by_id_mut(&owner).unwrap();
+    account.tokens = remaining;
+    match allowance {
+        0 => account.allowances.remove(spender),
+        allowance => account.allowances.insert(spender.to_string(), allowance),
+    };
+
+    let receiver = world_state.get_account_by_id_mut(&to).unwrap();
+    receiver.tokens = receiver
+        .tokens
+        .checked_add(amount)
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    Ok((owner, to))
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{approve, transfer_from};
+    use crate::{AccountType, Blockchain, BlockchainError, WorldState};
+
+    #[test]
+    fn spenders_transfer_within_their_allowance() {
+        let mut chain = Blockchain::new();
+        for id in ["alice", "bob", "carol"] {
+            chain.create_account(id.into(), AccountType::User).unwrap();
+        }
+        chain.get_account_by_id_mut("alice").unwrap().tokens = 100;
+
+        assert_eq!(
+            Err(BlockchainError::InsufficientAllowance),
+            transfer_from(&mut chain, "bob", "alice", "carol", 1)
+        );
+        approve(&mut chain, "alice", "bob", 30).unwrap();
+        assert_eq!(Some(30), chain.get_allowance("alice", "bob"));
+
+        transfer_from(&mut chain, "bob", "alice", "carol", 20).unwrap();
+        assert_eq!(Some(80), chain.get_balance("alice"));
+        assert_eq!(Some(20), chain.get_balance("carol"));
+        assert_eq!(Some(10), chain.get_allowance("alice", "bob"));
+        assert_eq!(
+            Err(BlockchainError::InsufficientAllowance),
+            transfer_from(&mut chain, "bob", "alice", "carol", 11)
+        );
+
+        // Approving replaces the allowance, approving zero revokes it
+        approve(&mut chain, "alice", "bob", 0).unwrap();
+        assert_eq!(Some(0), chain.get_allowance("alice", "bob"));
+        assert!(chain
+            .get_account_by_id("alice")
+            .unwrap()
+            .allowances
+            .is_empty());
+    }
+}
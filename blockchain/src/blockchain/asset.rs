@@ -0,0 +1,155 @@
+use crate::{AccountType, BlockchainError};
+
+use super::world_state::WorldState;
+
+/// The (reserved) account holding the asset registry, its store maps the asset ids to
+/// their issuers.
+pub const ASSET_REGISTRY: &str = "@assets";
+
+/// The minimum length of an asset id.
+const MIN_ASSET_LEN: usize = 2;
+
+/// The maximum length of an asset id.
+const MAX_ASSET_LEN: usize = 12;
+
+/// Will check if the asset id meets the naming rules: 2 to 12 uppercase ASCII letters or digits
+/// (like a ticker symbol)
+pub(crate) fn validate_asset(asset: &str) -> Result<(), BlockchainError> {
+    let valid_chars = asset
+        .bytes()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+    if !valid_chars || asset.len() < MIN_ASSET_LEN || asset.len() > MAX_ASSET_LEN {
+        return Err(BlockchainError::InvalidAsset(asset.to_string()));
+    }
+    Ok(())
+}
+
+/// Will register the asset for its issuer, who gets the whole (initial) supply,
+/// creating the registry with the first asset
+pub(crate) fn create<T: WorldState>(
+    world_state: &mut T,
+    asset: &str,
+    issuer: &str,
+    supply: u128,
+) -> Result<(), BlockchainError> {
+    validate_asset(asset)?;
+    if world_state.get_asset_issuer(asset).is_some() {
+        return Err(BlockchainError::AssetExists(asset.to_string()));
+    }
+
+    if !world_state.account_exists(ASSET_REGISTRY) {
+        world_state.create_account(ASSET_REGISTRY.into(), AccountType::User)?;
+    }
+    world_state
+        .get_account_by_id_mut(ASSET_REGISTRY)
+        .expect("The registry exists")
+        .store
+        .insert(asset.to_string(), issuer.to_string());
+    mint(world_state, asset, issuer, supply)
+}
+
+/// Will add the amount of the (existing) asset to the balance of the receiver
+pub(crate) fn mint<T: WorldState>(
+    world_state: &mut T,
+    asset: &str,
+    receiver: &str,
+    amount: u128,
+) -> Result<(), BlockchainError> {
+    if world_state.get_asset_issuer(asset).is_none() {
+        return Err(BlockchainError::UnknownAsset(asset.to_string()));
+    }
+    let account = world_state
+        .get_account_by_id_mut(receiver)
+        .ok_or_else(|| BlockchainError::UnknownAccount(receiver.to_string()))?;
+    let balance = account.assets.entry(asset.to_string()).or_insert(0);
+    *balance = balance
+        .checked_add(amount)
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+/// Will move the amount of the asset from the sender to the receiver (which may be an alias)
+/// and return the id of the receiver
+pub(crate) fn transfer<T: WorldState>(
+    world_state: &mut T,
+    asset: &str,
+    from: &str,
+    to: &str,
+    amount: u128,
+) -> Result<String, BlockchainError> {
+    if world_state.get_asset_issuer(asset).is_none() {
+        return Err(BlockchainError::UnknownAsset(asset.to_string()));
+    }
+    let to = world_state.resolve_account_id(to);
+    if !world_state.account_exists(&to) {
+        return Err(BlockchainError::UnknownAccount(to));
+    }
+
+    let sender = world_state
+        .get_account_by_id_mut(from)
+        .ok_or_else(|| BlockchainError::UnknownAccount(from.to_string()))?;
+    if sender.frozen {
+        return Err(BlockchainError::AccountFrozen(from.to_string()));
+    }
+    let remaining = sender
+        .asset_balance(asset)
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    match remaining {
+        0 => sender.assets.remove(asset),
+        remaining => sender.assets.insert(asset.to_string(), remaining),
+    };
+    mint(world_state, asset, &to, amount)?;
+    Ok(to)
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{create, transfer, validate_asset};
+    use crate::{AccountType, Blockchain, BlockchainError, WorldState};
+
+    #[test]
+    fn naming_rules() {
+        assert!(validate_asset("GOLD").is_ok());
+        assert!(validate_asset("USD2").is_ok());
+        assert!(validate_asset("G").is_err());
+        assert!(validate_asset("gold").is_err());
+        assert!(validate_asset("GO LD").is_err());
+        assert!(validate_asset(&"G".repeat(13)).is_err());
+    }
+
+    #[test]
+    fn create_and_transfer_assets() {
+        let mut chain = Blockchain::new();
+        for id in ["alice", "bob"] {
+            chain.create_account(id.into(), AccountType::User).unwrap();
+        }
+
+        create(&mut chain, "GOLD", "alice", 100).unwrap();
+        assert_eq!(Some(&"alice".to_string()), chain.get_asset_issuer("GOLD"));
+        assert_eq!(
+            Err(BlockchainError::AssetExists("GOLD".into())),
+            create(&mut chain, "GOLD", "bob", 1)
+        );
+        assert_eq!(
+            Err(BlockchainError::UnknownAsset("SILVER".into())),
+            transfer(&mut chain, "SILVER", "alice", "bob", 1)
+        );
+
+        transfer(&mut chain, "GOLD", "alice", "bob", 30).unwrap();
+        assert_eq!(Some(70), chain.get_asset_balance("alice", "GOLD"));
+        assert_eq!(Some(30), chain.get_asset_balance("bob", "GOLD"));
+        assert_eq!(
+            Err(BlockchainError::InsufficientBalance),
+            transfer(&mut chain, "GOLD", "bob", "alice", 31)
+        );
+
+        // The native tokens are not affected
+        assert_eq!(Some(0), chain.get_balance("alice"));
+    }
+}
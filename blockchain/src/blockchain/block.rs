@@ -1,28 +1,56 @@
 use std::fmt;
 
-use blake2::{Blake2b, Digest};
+use chrono::Utc;
+use ed25519_dalek::{PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use serde::{Deserialize, Serialize};
 
-use crate::Transaction;
+use super::header::transaction_leaf;
+use super::merkle::merkle_root;
+use super::storage::encoded_size;
+use super::transaction;
+use crate::{Address, BlockHeader, BlockchainError, ChainParams, Hash, Keypair, Transaction};
 
-const _HASH_BYTE_SIZE: usize = 32;
-
-pub type _Sha256Hash = [u8; _HASH_BYTE_SIZE];
-
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Block {
-    /// Actions that this block includes.<br/>
-    /// There has to be at least one.
-    pub(crate) transactions: Vec<Transaction>,
+    /// The fields that the hash of the block commits to
+    pub(crate) header: BlockHeader,
 
-    /// This actually connects the blocks together
-    pub(crate) prev_hash: Option<String>,
+    /// Actions that this block includes
+    pub(crate) body: BlockBody,
 
     /// We store the hash of the block here also in order to
     /// save the last block from being tampered with later on
-    pub(crate) hash: Option<String>,
+    pub(crate) hash: Option<Hash>,
+
+    /// Set if the body was dropped and only the header is kept.<br/>
+    /// The hash of such a block can't be checked against its transactions, it is trusted as
+    /// it is stored.
+    #[serde(default)]
+    pub(crate) pruned: bool,
+
+    /// The public key of the producer (hex encoded), if the block is signed by it
+    #[serde(default)]
+    pub(crate) public_key: Option<String>,
+
+    /// The producer's signature of the block hash (hex encoded), not part of the hash itself
+    #[serde(default)]
+    pub(crate) signature: Option<String>,
+}
+
+/// The transactions of a block, which its header only commits to through their Merkle root
+/// (see `Block::transactions_root`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BlockBody {
+    /// Actions that the block includes.<br/>
+    /// There has to be at least one.
+    pub(crate) transactions: Vec<Transaction>,
+}
 
-    /// Some arbitrary number which will be later used for Proof of Work
-    nonce: u128,
+impl BlockBody {
+    /// Will return the transactions
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
 }
 
 // Custom implementation of the `Debug` trait (insted of using
@@ -31,77 +59,271 @@ impl fmt::Debug for Block {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Block {{ hash: {:?}, phash: {:?}, nonce: {}, txns: {:?} }}",
-            &self.hash, &self.prev_hash, &self.nonce, &self.transactions
+            "Block {{ hash: {:?}, phash: {:?}, height: {}, nonce: {}, difficulty: {}, timestamp: {}, state_root: {:?}, txns: {:?} }}",
+            &self.hash,
+            &self.header.prev_hash,
+            &self.header.height,
+            &self.header.nonce,
+            &self.header.difficulty,
+            &self.header.timestamp,
+            &self.header.state_root,
+            &self.body.transactions
         )
     }
 }
 
 impl Block {
-    pub fn new(prev_hash: Option<String>) -> Self {
+    pub fn new(prev_hash: Option<Hash>) -> Self {
         Block {
-            nonce: 0,
+            header: BlockHeader {
+                prev_hash,
+                transactions_root: merkle_root(&[]),
+                state_root: None,
+                timestamp: now(),
+                nonce: 0,
+                difficulty: 0,
+                height: 0,
+                version: 0,
+            },
+            body: BlockBody::default(),
             hash: None,
-            prev_hash,
-            transactions: Vec::new(),
+            pruned: false,
+            public_key: None,
+            signature: None,
         }
     }
 
+    /// Will return the header of the block, which its hash commits to
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// Will return the body of the block (empty if it was pruned)
+    pub fn body(&self) -> &BlockBody {
+        &self.body
+    }
+
+    /// Will return a copy of the block without its body (only the header is kept)
+    pub fn without_body(&self) -> Block {
+        Block {
+            header: BlockHeader {
+                transactions_root: self.transactions_root(),
+                ..self.header.clone()
+            },
+            body: BlockBody::default(),
+            pruned: true,
+            ..self.clone()
+        }
+    }
+
+    /// Will return true if the body of the block was dropped (see `without_body`)
+    pub fn is_pruned(&self) -> bool {
+        self.pruned
+    }
+
+    /// Will return the hash of the block (`None` until it is mined)
+    pub fn hash(&self) -> Option<Hash> {
+        self.hash
+    }
+
+    /// Will return the hash of the previous block (`None` for the genesis block)
+    pub fn prev_hash(&self) -> Option<Hash> {
+        self.header.prev_hash
+    }
+
+    /// Will return the height of the block in its chain (0 for the genesis block)
+    pub fn height(&self) -> usize {
+        self.header.height as usize
+    }
+
+    /// Changes the height and updates the hash
+    pub fn set_height(&mut self, height: usize) {
+        self.header.height = height as u64;
+        self.update_hash();
+    }
+
+    /// Will return the transactions of the block
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.body.transactions
+    }
+
+    /// Will return the time (unix epoch, in milliseconds) the block was created at
+    pub fn timestamp(&self) -> u64 {
+        self.header.timestamp
+    }
+
+    /// Changes the timestamp and updates the hash
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.header.timestamp = timestamp;
+        self.update_hash();
+    }
+
+    /// Will return the version of the rules the block follows
+    pub fn version(&self) -> u32 {
+        self.header.version
+    }
+
+    /// Changes the version and updates the hash
+    pub fn set_version(&mut self, version: u32) {
+        self.header.version = version;
+        self.update_hash();
+    }
+
+    /// Will return the commitment over the world state after executing the block
+    pub fn state_root(&self) -> Option<Hash> {
+        self.header.state_root
+    }
+
+    /// Changes the state root and updates the hash
+    pub fn set_state_root(&mut self, state_root: Option<Hash>) {
+        self.header.state_root = state_root;
+        self.update_hash();
+    }
+
+    /// Will return the Proof of Work difficulty the block was mined with
+    pub fn difficulty(&self) -> u32 {
+        self.header.difficulty
+    }
+
+    /// Will return the nonce the block was mined with
+    pub fn nonce(&self) -> u128 {
+        self.header.nonce
+    }
+
     /// Changes the nonce number and updates the hash
     pub fn set_nonce(&mut self, nonce: u128) {
-        self.nonce = nonce;
+        self.header.nonce = nonce;
         self.update_hash();
     }
 
     /// Calculate the hash of the whole block including transactions.<br/>
-    /// It is using Blake2 hasher.
-    pub fn calculate_hash(&self) -> Vec<u8> {
-        let mut hasher = Blake2b::new();
+    /// It is the hash of the header (see `BlockHeader::hash`), which commits to the
+    /// transactions through their Merkle root (as calculated out of the body, unless it
+    /// was pruned).
+    pub fn calculate_hash(&self) -> Hash {
+        BlockHeader {
+            transactions_root: self.transactions_root(),
+            ..self.header.clone()
+        }
+        .hash()
+    }
 
-        for transaction in self.transactions.iter() {
-            hasher.update(transaction.calculate_hash())
+    /// Will return the root of the Merkle tree over the (hashes of the) transactions,
+    /// which proves their inclusion to light clients (see `HeaderChain`)
+    pub fn transactions_root(&self) -> Hash {
+        if self.pruned {
+            self.header.transactions_root
+        } else {
+            merkle_root(&self.transaction_leaves())
         }
+    }
 
-        let block_as_string = format!("{:?}", (&self.prev_hash, &self.nonce));
-        hasher.update(&block_as_string);
+    /// Will return the leaves of the Merkle tree over the transactions
+    pub(crate) fn transaction_leaves(&self) -> Vec<Hash> {
+        self.body
+            .transactions
+            .iter()
+            .map(|transaction| transaction_leaf(&transaction.calculate_hash()))
+            .collect()
+    }
 
-        return Vec::from(hasher.finalize().as_ref());
+    /// Will sign the hash of the block as its producer.<br/>
+    /// Since the signature isn't part of the hash, the block is signed after it is sealed and mined.
+    pub fn sign(&mut self, keypair: &Keypair) {
+        let hash = self.hash.unwrap_or_else(|| self.calculate_hash());
+        self.public_key = Some(keypair.public_key());
+        self.signature = Some(hex::encode(keypair.sign(hash.as_bytes())));
     }
 
-    /// Appends a transaction to the queue
+    /// Will return the address of the producer, if the block carries a valid signature of its hash
+    pub fn producer(&self) -> Option<Address> {
+        match (&self.hash, &self.public_key, &self.signature) {
+            (Some(hash), Some(public_key), Some(signature)) => {
+                transaction::verify(public_key, signature, hash.as_bytes())
+            }
+            _ => None,
+        }
+    }
+
+    /// Appends a transaction to the queue.<br/>
+    /// The limits of the chain are only checked once the block is appended,
+    /// use `try_add_transaction` to check them right away.
     pub fn add_transaction(&mut self, transaction: Transaction) {
-        self.transactions.push(transaction);
+        self.body.transactions.push(transaction);
         self.update_hash();
     }
 
+    /// Appends a transaction to the queue, unless the transaction or the resulting block
+    /// would exceed the limits of the chain (in which case the block is left untouched).
+    pub fn try_add_transaction(
+        &mut self,
+        transaction: Transaction,
+        params: &ChainParams,
+    ) -> Result<(), BlockchainError> {
+        params.check_transaction(&transaction)?;
+        if self.body.transactions.len() >= params.max_transactions_per_block {
+            return Err(BlockchainError::TooManyTransactions {
+                max: params.max_transactions_per_block,
+            });
+        }
+        let size = encoded_size(&transaction)?;
+        if self.sealed_size()?.saturating_add(size) > params.max_block_size {
+            return Err(BlockchainError::BlockTooLarge {
+                max: params.max_block_size,
+            });
+        }
+        self.add_transaction(transaction);
+        Ok(())
+    }
+
+    /// Will return the size (in bytes) of the block, serialized with the (bincode) binary format
+    pub fn size(&self) -> Result<u64, BlockchainError> {
+        encoded_size(self)
+    }
+
+    /// Will return the size the block will have once it is sealed and signed, i.e. a state root
+    /// and a signature are accounted for, even if the block doesn't carry them (yet)
+    pub fn sealed_size(&self) -> Result<u64, BlockchainError> {
+        let placeholder = Hash::digest(&[]);
+        let header = Block {
+            header: BlockHeader {
+                state_root: Some(placeholder),
+                ..self.header.clone()
+            },
+            body: BlockBody::default(),
+            hash: Some(placeholder),
+            pruned: self.pruned,
+            public_key: Some("0".repeat(PUBLIC_KEY_LENGTH * 2)),
+            signature: Some("0".repeat(SIGNATURE_LENGTH * 2)),
+        };
+        self.body
+            .transactions
+            .iter()
+            .try_fold(encoded_size(&header)?, |size, transaction| {
+                Ok(size.saturating_add(encoded_size(transaction)?))
+            })
+    }
+
     /// Will return the amount of transactions
     pub fn get_transaction_count(&self) -> usize {
-        self.transactions.len()
+        self.body.transactions.len()
     }
 
     /// Will update the hash field by including all transactions currently inside
     /// the public modifier is only for the demonstration of attacks
     pub(crate) fn update_hash(&mut self) {
-        self.hash = Some(byte_vector_to_string(&self.calculate_hash()));
+        self.header.transactions_root = self.transactions_root();
+        self.hash = Some(self.header.hash());
     }
 
     /// Checks if the hash is set and matches the blocks internals.
     pub fn verify_own_hash(&self) -> bool {
-        if self.hash.is_some() && // Hash is set.
-            self.hash.as_ref().unwrap().eq(
-                &byte_vector_to_string(
-                    &self.calculate_hash()))
-        {
-            // Hash equals calculated hash
-
-            return true;
-        }
-        false
+        // Hash is set and equals the calculated hash
+        self.hash == Some(self.calculate_hash())
     }
 }
 
-/// Will take an array of bytes and transform it into a string by interpreting every byte
-/// as an character
-fn byte_vector_to_string(arr: &Vec<u8>) -> String {
-    arr.iter().map(|&c| c as char).collect()
+/// Will return the current time (unix epoch, in milliseconds)
+pub(crate) fn now() -> u64 {
+    Utc::now().timestamp_millis() as u64
 }
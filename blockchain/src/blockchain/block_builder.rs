@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::overlay::Overlay;
+use super::state_root::state_root;
+use crate::{
+    Block, Blockchain, BlockchainError, ExecutionResult, Transaction, TransactionReceipt,
+    ValidationPolicy, WorldState,
+};
+
+/// Builds the block following the last one of a chain, validating every transaction as it
+/// is added: it is executed against an overlay of the world state (as left by the
+/// transactions added before it), so that a transaction the block can't include (e.g. as
+/// its sender can't afford it, its nonce isn't the next one or the block would exceed its
+/// gas limit) is rejected right away, instead of failing the whole block once it is
+/// appended.<br/>
+/// Rejected transactions leave the block (and the overlay) untouched.
+pub struct BlockBuilder<'a> {
+    chain: &'a Blockchain,
+
+    /// The policy of the built block's height
+    policy: Cow<'a, ValidationPolicy>,
+
+    block: Block,
+
+    /// The world state after executing the added transactions
+    overlay: Overlay<'a>,
+
+    result: ExecutionResult,
+}
+
+impl<'a> BlockBuilder<'a> {
+    /// C'tor, building the block following the last one of the chain
+    pub fn new(chain: &'a Blockchain) -> Self {
+        BlockBuilder {
+            chain,
+            policy: chain.policy().at(chain.len()),
+            block: chain.new_block(),
+            overlay: Overlay::new(chain),
+            result: ExecutionResult::default(),
+        }
+    }
+
+    /// Will add the transaction to the block, if the block may include it, and return its
+    /// receipt.<br/>
+    /// Besides executing it, the transaction is checked like the mempool does (its network,
+    /// its signature, its version and its validity window), its nonce has to be the next
+    /// one of its sender and the block has to stay within the limits of the chain.
+    pub fn add_transaction(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<&TransactionReceipt, BlockchainError> {
+        let height = self.chain.len();
+        let is_genesis = self.chain.is_empty();
+        self.chain.check_chain_id(&transaction)?;
+        if !self
+            .chain
+            .accepts_transaction_signature(&transaction, is_genesis)
+        {
+            return Err(BlockchainError::InvalidSignature);
+        }
+        self.chain
+            .policy()
+            .check_transaction_version(height, &transaction)?;
+        transaction.check_validity_window(height, self.block.header.timestamp)?;
+        if let Some(sender) = self.overlay.get_account_by_id(&transaction.from) {
+            if !is_genesis && !transaction.is_coinbase() && transaction.nonce != sender.nonce() {
+                return Err(BlockchainError::InvalidNonce {
+                    expected: sender.nonce(),
+                    found: transaction.nonce,
+                });
+            }
+        }
+
+        // The transaction is executed on top of the overlay, so that a failing one
+        // doesn't leave its partial changes behind
+        let mut executed = Overlay::new(&self.overlay);
+        let receipt = transaction.execute(&mut executed, &is_genesis)?;
+        let changes = executed.into_changes();
+        let gas_used = self
+            .result
+            .gas_used
+            .checked_add(receipt.gas_used)
+            .ok_or(BlockchainError::ArithmeticOverflow)?;
+        if gas_used > self.policy.block_gas_limit {
+            return Err(BlockchainError::BlockGasLimitExceeded {
+                limit: self.policy.block_gas_limit,
+            });
+        }
+        self.block
+            .try_add_transaction(transaction, &self.policy.params)?;
+
+        self.overlay.apply(changes);
+        self.result.add(receipt)?;
+        Ok(self.result.receipts.last().expect("the added receipt"))
+    }
+
+    /// Will return the transactions added so far
+    pub fn transactions(&self) -> &[Transaction] {
+        self.block.transactions()
+    }
+
+    /// Will return the outcome of executing the transactions added so far
+    pub fn result(&self) -> &ExecutionResult {
+        &self.result
+    }
+
+    /// Will seal the block (see `Blockchain::produce_block`): it commits to the world state
+    /// after its transactions, has the version active at its height and is mined with the
+    /// next difficulty, ready to be appended (or signed first, if the consensus requires it).
+    pub fn build(self) -> Block {
+        let difficulty = self.chain.next_difficulty();
+        let mut block = self.seal();
+        block.mine(difficulty);
+        block
+    }
+
+    /// Will seal the block like `build` does, except for mining it
+    pub(crate) fn seal(self) -> Block {
+        let accounts: HashMap<String, _> = self
+            .overlay
+            .accounts()
+            .map(|(id, account)| (id.clone(), account.clone()))
+            .collect();
+        let mut block = self.block;
+        block.set_state_root(Some(state_root(&accounts)));
+        block.header.height = self.chain.len() as u64;
+        block.header.version = self.chain.policy().version_at(self.chain.len());
+        block
+    }
+}
+
+impl fmt::Debug for BlockBuilder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BlockBuilder {{ transactions: {}, gas_used: {} }}",
+            self.block.transactions().len(),
+            self.result.gas_used
+        )
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::BlockBuilder;
+    use crate::test_utils::{account_id, keypair, signed_transaction, ChainBuilder, TEST_CHAIN_ID};
+    use crate::{BlockchainError, TransactionData, WorldState};
+
+    #[test]
+    fn invalid_transactions_are_rejected_when_added() {
+        let mut chain = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_account("bob", 0)
+            .build();
+        let nonce = chain.get_nonce(&account_id("alice")).unwrap();
+        let transfer = |amount, nonce| {
+            let transfer = TransactionData::TransferTokens {
+                to: account_id("bob"),
+                amount,
+                asset: None,
+            };
+            signed_transaction(TEST_CHAIN_ID, "alice", transfer, nonce)
+        };
+
+        let mut builder = BlockBuilder::new(&chain);
+        builder.add_transaction(transfer(60, nonce)).unwrap();
+        // Alice can't afford the second transfer anymore
+        assert_eq!(
+            Err(BlockchainError::InsufficientBalance),
+            builder.add_transaction(transfer(60, nonce + 1)).cloned()
+        );
+        assert_eq!(
+            Err(BlockchainError::InvalidNonce {
+                expected: nonce + 1,
+                found: nonce
+            }),
+            builder.add_transaction(transfer(10, nonce)).cloned()
+        );
+        let mut forged = transfer(10, nonce + 1);
+        forged.sign(&keypair("bob"));
+        assert_eq!(
+            Err(BlockchainError::InvalidSignature),
+            builder.add_transaction(forged).cloned()
+        );
+        builder.add_transaction(transfer(40, nonce + 1)).unwrap();
+        assert_eq!(2, builder.transactions().len());
+
+        // The rejected transactions left nothing behind
+        let block = builder.build();
+        chain.append_block(block).unwrap();
+        assert_eq!(Some(0), chain.get_balance(&account_id("alice")));
+        assert_eq!(Some(100), chain.get_balance(&account_id("bob")));
+        assert!(chain.check_validity().is_ok());
+    }
+}
@@ -0,0 +1,258 @@
+use std::io::{self, Read, Write};
+
+use super::storage::{decode, encode};
+use crate::{Block, Blockchain, BlockchainError, Hash};
+
+/// The bytes that block files start with
+const MAGIC: &[u8; 4] = b"AIOB";
+
+/// The version of the format of the block files (2 since blocks consist of a header and a body)
+const VERSION: u32 = 2;
+
+/// The length (in bytes) of the checksum following every record
+const CHECKSUM_LEN: usize = 8;
+
+impl Blockchain {
+    /// Will write the blocks of the chain to the writer, in a portable format (e.g. to move
+    /// the chain to another node, to archive it or to seed tests with it) and return their
+    /// amount:
+    /// - the magic bytes `AIOB` and the version of the format (a little endian `u32`)
+    /// - a record holding the chain id, followed by a record per block (in the binary storage
+    ///   format), starting with the genesis block
+    ///
+    /// Every record is prefixed by its length (a little endian `u64`) and followed by its
+    /// checksum (the first 8 bytes of its hash).<br/>
+    /// A pruned chain can't be exported, since its blocks can't be replayed.
+    pub fn export_blocks<W: Write>(&self, mut writer: W) -> Result<usize, BlockchainError> {
+        if self.pruned_height() > 0 {
+            return Err(BlockchainError::BlockPruned { height: 0 });
+        }
+        writer.write_all(MAGIC).map_err(io_error)?;
+        writer.write_all(&VERSION.to_le_bytes()).map_err(io_error)?;
+        write_record(&mut writer, self.chain_id().as_bytes())?;
+        for block in self.blocks.iter() {
+            write_record(&mut writer, &encode(block)?)?;
+        }
+        writer.flush().map_err(io_error)?;
+        Ok(self.blocks.len())
+    }
+
+    /// Will read the blocks written by `export_blocks` and append the ones the chain doesn't
+    /// have yet (validating them, like the blocks received from peers), returning their
+    /// amount.<br/>
+    /// The blocks the chain has already have to match the ones of the file.
+    pub fn import_blocks<R: Read>(&mut self, mut reader: R) -> Result<usize, BlockchainError> {
+        let mut magic = [0; 4];
+        read_exact(&mut reader, &mut magic)?;
+        if &magic != MAGIC {
+            return Err(BlockchainError::InvalidBlockFile("Not a block file".into()));
+        }
+        let mut version = [0; 4];
+        read_exact(&mut reader, &mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(BlockchainError::InvalidBlockFile(format!(
+                "Unsupported version {}",
+                version
+            )));
+        }
+        let chain_id = read_record(&mut reader)?
+            .ok_or_else(truncated)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())?;
+        if chain_id != self.chain_id() {
+            return Err(BlockchainError::ChainIdMismatch {
+                expected: self.chain_id().into(),
+                found: chain_id,
+            });
+        }
+
+        let known = self.len();
+        let mut height = 0;
+        while let Some(bytes) = read_record(&mut reader)? {
+            let block: Block = decode(&bytes)?;
+            if height < known {
+                if block.hash != self.blocks[height].hash {
+                    return Err(BlockchainError::InvalidBlockFile(format!(
+                        "The block at height {} differs from the one of the chain",
+                        height
+                    )));
+                }
+            } else {
+                self.append_block(block)
+                    .map_err(|err| err.in_block(height))?;
+            }
+            height += 1;
+        }
+        Ok(self.len() - known)
+    }
+}
+
+fn write_record<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), BlockchainError> {
+    writer
+        .write_all(&(bytes.len() as u64).to_le_bytes())
+        .map_err(io_error)?;
+    writer.write_all(bytes).map_err(io_error)?;
+    writer.write_all(&checksum(bytes)).map_err(io_error)
+}
+
+/// Will read the next record, `None` if the file ends (which it may only do between records)
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, BlockchainError> {
+    let mut len = [0; 8];
+    let mut read = 0;
+    while read < len.len() {
+        match reader.read(&mut len[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(truncated()),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(io_error(err)),
+        }
+    }
+    let len = u64::from_le_bytes(len);
+
+    // Not allocating the (untrusted) length upfront
+    let mut bytes = Vec::new();
+    reader
+        .by_ref()
+        .take(len)
+        .read_to_end(&mut bytes)
+        .map_err(io_error)?;
+    if bytes.len() as u64 != len {
+        return Err(truncated());
+    }
+    let mut sum = [0; CHECKSUM_LEN];
+    read_exact(reader, &mut sum)?;
+    if sum != checksum(&bytes) {
+        return Err(BlockchainError::InvalidBlockFile(
+            "Checksum mismatch".into(),
+        ));
+    }
+    Ok(Some(bytes))
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), BlockchainError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => truncated(),
+        _ => io_error(err),
+    })
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut sum = [0; CHECKSUM_LEN];
+    sum.copy_from_slice(&Hash::digest(bytes).as_bytes()[..CHECKSUM_LEN]);
+    sum
+}
+
+fn truncated() -> BlockchainError {
+    BlockchainError::InvalidBlockFile("The file is truncated".into())
+}
+
+fn io_error(err: io::Error) -> BlockchainError {
+    BlockchainError::Storage(err.to_string())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        Blockchain, BlockchainError, GenesisConfig, Transaction, TransactionData, WorldState,
+    };
+
+    fn chain(chain_id: &str) -> Blockchain {
+        let config = GenesisConfig::from_toml(&format!(
+            r#"
+            chain_id = "{}"
+            timestamp = 1000
+            [[accounts]]
+            id = "alice"
+            balance = 10
+            "#,
+            chain_id
+        ))
+        .unwrap();
+        Blockchain::from_genesis(&config).unwrap()
+    }
+
+    #[test]
+    fn blocks_round_trip() {
+        let mut source = chain("test");
+        for (nonce, id) in ["bob", "carol"].iter().enumerate() {
+            source
+                .submit_transaction(
+                    Transaction::new(
+                        "alice".into(),
+                        TransactionData::CreateUserAccount((*id).into()),
+                        nonce as u128,
+                    )
+                    .with_chain_id("test".into()),
+                )
+                .unwrap();
+            source.produce_block(1).unwrap();
+        }
+        let mut file = Vec::new();
+        assert_eq!(3, source.export_blocks(&mut file).unwrap());
+
+        let mut target = chain("test");
+        assert_eq!(2, target.import_blocks(file.as_slice()).unwrap());
+        assert_eq!(source.get_last_block_hash(), target.get_last_block_hash());
+        assert!(target.get_account_by_id("carol").is_some());
+        // Importing again appends nothing
+        assert_eq!(0, target.import_blocks(file.as_slice()).unwrap());
+
+        assert!(matches!(
+            chain("other").import_blocks(file.as_slice()).unwrap_err(),
+            BlockchainError::ChainIdMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn corrupted_files_are_rejected() {
+        let mut file = Vec::new();
+        chain("test").export_blocks(&mut file).unwrap();
+        let import = |bytes: &[u8]| chain("test").import_blocks(bytes).unwrap_err().code();
+
+        let mut flipped = file.clone();
+        let last = flipped.len() - 20;
+        flipped[last] ^= 1;
+        assert_eq!("invalid_block_file", import(&flipped));
+        assert_eq!("invalid_block_file", import(&file[..file.len() - 1]));
+        assert_eq!("invalid_block_file", import(&file[..6]));
+        assert_eq!("invalid_block_file", import(b"JSON[]"));
+    }
+
+    #[test]
+    fn conflicting_blocks_are_rejected() {
+        let create = |id: &str| {
+            let mut chain = chain("test");
+            chain
+                .submit_transaction(
+                    Transaction::new(
+                        "alice".into(),
+                        TransactionData::CreateUserAccount(id.into()),
+                        0,
+                    )
+                    .with_chain_id("test".into()),
+                )
+                .unwrap();
+            chain.produce_block(1).unwrap();
+            chain
+        };
+        let mut file = Vec::new();
+        create("bob").export_blocks(&mut file).unwrap();
+        assert!(matches!(
+            create("carol").import_blocks(file.as_slice()).unwrap_err(),
+            BlockchainError::InvalidBlockFile(reason) if reason.contains("height 1")
+        ));
+
+        let mut pruned = create("bob");
+        pruned.set_pruning(Some(0)).unwrap();
+        assert!(matches!(
+            pruned.export_blocks(Vec::new()).unwrap_err(),
+            BlockchainError::BlockPruned { .. }
+        ));
+    }
+}
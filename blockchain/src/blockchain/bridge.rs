@@ -0,0 +1,368 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::canonical;
+use super::header::transaction_leaf;
+use super::transaction::verify;
+use super::vesting;
+use super::world_state::{is_reserved_account, WorldState};
+use crate::{
+    AccountType, Block, BlockHeader, Blockchain, BlockchainError, Cosignature, Hash, Keypair,
+    MerkleProof, Transaction, TransactionData,
+};
+
+/// The (reserved) account holding the registry of the bridge: its store maps the hashes of
+/// the `BridgeOut` transactions (of other chains) that were relayed to their source chains,
+/// so that none of them is relayed twice.
+pub const BRIDGE_REGISTRY: &str = "@bridge";
+
+/// The tag that the headers attested for the bridge are hashed with, so that their signatures
+/// can't be mistaken for the signatures of anything else (e.g. of a checkpoint)
+const BRIDGE_DOMAIN: &str = "abc/bridge";
+
+/// What a message passed to another chain carries (see `TransactionData::BridgeOut`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgePayload {
+    /// Tokens burnt by the sender on the source chain and minted for the receiver on the
+    /// target chain, so that their supply across both chains doesn't change
+    Transfer { to: String, amount: u128 },
+}
+
+/// A chain that messages are accepted from (see `ValidationPolicy::bridges`): its headers
+/// have to be attested by at least `threshold` of the `attesters` (addresses).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BridgeConfig {
+    /// The chain id of the source chain
+    pub chain_id: String,
+
+    pub attesters: Vec<String>,
+
+    pub threshold: usize,
+}
+
+/// The proof that a message was sent by a source chain: its `BridgeOut` transaction, the
+/// header of the block including it (along with the Merkle proof of the inclusion) and the
+/// signatures of the attesters of the source chain over the header.<br/>
+/// It is delivered to the target chain by a `BridgeIn` transaction, see `Relayer`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BridgeProof {
+    pub transaction: Transaction,
+
+    pub header: BlockHeader,
+
+    pub proof: MerkleProof,
+
+    pub signatures: Vec<Cosignature>,
+}
+
+impl BridgeProof {
+    /// Will return the (unsigned) proof of the `BridgeOut` transaction with the hash, if it is
+    /// included by the (not pruned) chain
+    pub fn new(chain: &Blockchain, hash: &Hash) -> Option<Self> {
+        let (location, proof) = chain.transaction_proof(hash)?;
+        let block = chain.blocks().get(location.block_height)?;
+        let transaction = block.transactions().get(location.index)?;
+        if !matches!(transaction.record, TransactionData::BridgeOut { .. }) {
+            return None;
+        }
+        Some(BridgeProof {
+            transaction: transaction.clone(),
+            header: block.header().clone(),
+            proof,
+            signatures: Vec::new(),
+        })
+    }
+
+    /// Will return the chain id of the source chain
+    pub fn source_chain(&self) -> &str {
+        self.transaction.chain_id()
+    }
+
+    /// Will return the hash that the attesters sign
+    pub fn signing_hash(&self) -> Hash {
+        Hash::digest(&canonical::encode(&(
+            BRIDGE_DOMAIN,
+            self.source_chain(),
+            self.header.hash(),
+        )))
+    }
+
+    /// Will add the signature of an attester
+    pub fn sign(&mut self, keypair: &Keypair) {
+        let signature = keypair.sign(self.signing_hash().as_bytes());
+        self.signatures.push(Cosignature {
+            public_key: keypair.public_key(),
+            signature: hex::encode(signature),
+        });
+    }
+
+    /// Will return the addresses of the (valid) signers
+    pub fn signers(&self) -> HashSet<String> {
+        let hash = self.signing_hash();
+        self.signatures
+            .iter()
+            .filter_map(|signature| {
+                verify(&signature.public_key, &signature.signature, hash.as_bytes())
+            })
+            .map(|address| address.to_string())
+            .collect()
+    }
+
+    /// Will check that the proof carries a message of a known source chain for the target
+    /// chain, included by a block attested by enough of the source chain's attesters, and
+    /// return its payload
+    fn verify<T: WorldState>(&self, world_state: &T) -> Result<&BridgePayload, BlockchainError> {
+        let invalid = |reason: &str| Err(BlockchainError::InvalidBridgeProof(reason.into()));
+        let payload = match &self.transaction.record {
+            TransactionData::BridgeOut {
+                target_chain,
+                payload,
+            } if *target_chain == world_state.chain_id() => payload,
+            _ => return invalid("It carries no message for this chain"),
+        };
+        let config = world_state
+            .bridge(self.source_chain())
+            .ok_or_else(|| BlockchainError::UnknownBridge(self.source_chain().to_string()))?;
+        let leaf = transaction_leaf(&self.transaction.calculate_hash());
+        if !self.proof.verify(&leaf, &self.header.transactions_root) {
+            return invalid("The message is not included by the block");
+        }
+        let signers = self.signers();
+        let attested = config
+            .attesters
+            .iter()
+            .filter(|attester| signers.contains(*attester))
+            .count();
+        if config.threshold == 0 || attested < config.threshold {
+            return invalid(&format!(
+                "{} of the {} required attesters signed the block",
+                attested, config.threshold
+            ));
+        }
+        Ok(payload)
+    }
+}
+
+/// Will send the message of the sender: the tokens of a transfer are burnt (until they are
+/// minted by the target chain)
+pub(crate) fn send<T: WorldState>(
+    world_state: &mut T,
+    from: &str,
+    payload: &BridgePayload,
+) -> Result<(), BlockchainError> {
+    match payload {
+        BridgePayload::Transfer { amount, .. } => {
+            let sender = world_state
+                .get_account_by_id(from)
+                .ok_or_else(|| BlockchainError::UnknownAccount(from.to_string()))?;
+            if sender.frozen {
+                return Err(BlockchainError::AccountFrozen(from.to_string()));
+            }
+            let remaining = sender
+                .tokens
+                .checked_sub(*amount)
+                .ok_or(BlockchainError::InsufficientBalance)?;
+            vesting::check_unlocked(world_state, from, remaining)?;
+            world_state.get_account_by_id_mut(from).unwrap().tokens = remaining;
+        }
+    }
+    Ok(())
+}
+
+/// Will check the proof and execute the message it carries, returning the hash of its
+/// `BridgeOut` transaction and its payload.<br/>
+/// The receiver of transferred tokens is created if it doesn't exist yet.
+pub(crate) fn receive<T: WorldState>(
+    world_state: &mut T,
+    proof: &BridgeProof,
+) -> Result<(String, BridgePayload), BlockchainError> {
+    let payload = proof.verify(world_state)?.clone();
+    let id = proof.transaction.calculate_hash().to_string();
+    if world_state.get_store_value(BRIDGE_REGISTRY, &id).is_some() {
+        return Err(BlockchainError::BridgeMessageRelayed(id));
+    }
+
+    match &payload {
+        BridgePayload::Transfer { to, amount } => {
+            if !world_state.account_exists(to) {
+                if is_reserved_account(to) {
+                    return Err(BlockchainError::ReservedAccount(to.clone()));
+                }
+                world_state.create_account(to.clone(), AccountType::User)?;
+            }
+            let receiver = world_state.get_account_by_id_mut(to).unwrap();
+            receiver.tokens = receiver
+                .tokens
+                .checked_add(*amount)
+                .ok_or(BlockchainError::ArithmeticOverflow)?;
+        }
+    }
+
+    if !world_state.account_exists(BRIDGE_REGISTRY) {
+        world_state.create_account(BRIDGE_REGISTRY.into(), AccountType::User)?;
+    }
+    world_state
+        .get_account_by_id_mut(BRIDGE_REGISTRY)
+        .expect("The registry exists")
+        .store
+        .insert(id.clone(), proof.source_chain().to_string());
+    Ok((id, payload))
+}
+
+/// The hooks of a relayer: an external process passing the messages of a source chain to a
+/// target chain (e.g. over the RPC APIs of their nodes), such as the transfers of tokens
+/// between two instances of this chain.<br/>
+/// The relayer doesn't have to be trusted: the target chain verifies the proofs it delivers.
+pub trait Relayer {
+    /// Will return the (attested) proofs of the messages sent to the target chain by the
+    /// blocks of the source chain from the height on (see `Blockchain::bridge_proofs`)
+    fn fetch(&mut self, from_height: usize) -> Result<Vec<BridgeProof>, BlockchainError>;
+
+    /// Will deliver the proof to the target chain, e.g. by submitting a `BridgeIn` transaction
+    fn deliver(&mut self, proof: BridgeProof) -> Result<(), BlockchainError>;
+
+    /// Will pass the messages sent from the height on, returning how many were delivered
+    fn relay(&mut self, from_height: usize) -> Result<usize, BlockchainError> {
+        let proofs = self.fetch(from_height)?;
+        let count = proofs.len();
+        for proof in proofs {
+            self.deliver(proof)?;
+        }
+        Ok(count)
+    }
+}
+
+impl Blockchain {
+    /// Will return the (unsigned) proofs of the messages sent to the target chain by the
+    /// blocks from the height on, which the attesters sign (see `BridgeProof::sign`)
+    pub fn bridge_proofs(&self, target_chain: &str, from_height: usize) -> Vec<BridgeProof> {
+        self.blocks_range(from_height, self.blocks.len())
+            .iter()
+            .flat_map(Block::transactions)
+            .filter(|transaction| {
+                matches!(
+                    &transaction.record,
+                    TransactionData::BridgeOut { target_chain: target, .. } if target == target_chain
+                )
+            })
+            .filter_map(|transaction| BridgeProof::new(self, &transaction.calculate_hash()))
+            .collect()
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{BridgeConfig, BridgePayload, BridgeProof, Relayer};
+    use crate::test_utils::{account_id, keypair, mine_block, signed_transaction, ChainBuilder};
+    use crate::{Blockchain, BlockchainError, TransactionData, ValidationPolicy, WorldState};
+
+    /// Passes the messages of one chain to another, as an external process would
+    struct TestRelayer<'a> {
+        source: &'a Blockchain,
+        target: &'a mut Blockchain,
+        nonce: u128,
+    }
+
+    impl Relayer for TestRelayer<'_> {
+        fn fetch(&mut self, from_height: usize) -> Result<Vec<BridgeProof>, BlockchainError> {
+            let target_chain = self.target.policy().chain_id.clone();
+            let mut proofs = self.source.bridge_proofs(&target_chain, from_height);
+            for proof in proofs.iter_mut() {
+                proof.sign(&keypair("attester"));
+            }
+            Ok(proofs)
+        }
+
+        fn deliver(&mut self, proof: BridgeProof) -> Result<(), BlockchainError> {
+            let chain_id = self.target.policy().chain_id.clone();
+            let data = TransactionData::BridgeIn {
+                proof: Box::new(proof),
+            };
+            let transaction = signed_transaction(&chain_id, "relayer", data, self.nonce);
+            self.nonce += 1;
+            let block = mine_block(self.target, vec![transaction])?;
+            self.target.append_block(block).map(|_| ())
+        }
+    }
+
+    #[test]
+    fn tokens_are_bridged_between_chains() {
+        let source = ChainBuilder::new()
+            .with_chain_id("source")
+            .with_account("alice", 1_000)
+            .transaction(
+                "alice",
+                TransactionData::BridgeOut {
+                    target_chain: "target".into(),
+                    payload: BridgePayload::Transfer {
+                        to: account_id("bob"),
+                        amount: 400,
+                    },
+                },
+            )
+            .build();
+        assert_eq!(Some(600), source.get_balance(&account_id("alice")));
+
+        let bridge = |threshold| BridgeConfig {
+            chain_id: "source".into(),
+            attesters: vec![account_id("attester")],
+            threshold,
+        };
+        let target = |bridges| {
+            ChainBuilder::new()
+                .with_chain_id("target")
+                .with_policy(ValidationPolicy {
+                    require_signatures: true,
+                    bridges,
+                    ..ValidationPolicy::default()
+                })
+                .with_account("relayer", 0)
+                .build()
+        };
+        let mut bc = target(vec![bridge(1)]);
+        let mut relayer = TestRelayer {
+            source: &source,
+            target: &mut bc,
+            nonce: 0,
+        };
+        assert_eq!(1, relayer.relay(0).unwrap());
+        assert_eq!(Some(400), bc.get_balance(&account_id("bob")));
+
+        // A message is only relayed once
+        let mut relayer = TestRelayer {
+            source: &source,
+            target: &mut bc,
+            nonce: 1,
+        };
+        assert!(matches!(
+            relayer.relay(0).unwrap_err(),
+            BlockchainError::InvalidTransaction { source, .. }
+                if matches!(*source, BlockchainError::BridgeMessageRelayed(_))
+        ));
+
+        // ... whilst unknown source chains and blocks lacking attestations are rejected
+        for (bridges, rejected) in [
+            (vec![], "unknown_bridge"),
+            (vec![bridge(2)], "invalid_bridge_proof"),
+        ] {
+            let mut bc = target(bridges);
+            let mut relayer = TestRelayer {
+                source: &source,
+                target: &mut bc,
+                nonce: 0,
+            };
+            assert!(matches!(
+                relayer.relay(0).unwrap_err(),
+                BlockchainError::InvalidTransaction { source, .. } if source.code() == rejected
+            ));
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroUsize;
+
+use elsa::FrozenMap;
+use lru::LruCache;
+use tracing::warn;
+
+use super::tombstone;
+use crate::storage::AccountStore;
+use crate::{Account, AccountType, BlockchainError, WorldState};
+
+/// A world state kept in an AccountStore (e.g. `RocksAccountStore`) rather than in memory,
+/// so that large account sets don't have to fit into the memory and restarts don't have to
+/// load (or replay) them.<br/>
+/// The accounts are read from the store on demand, through a cache of (at most) `capacity`
+/// recently used accounts, whilst the changes are held back until `commit` writes the ones
+/// of a block as a single batch (or `rollback` discards them, e.g. when the block fails).
+/// Since accounts are read through shared references, the cache is only trimmed to its
+/// capacity by `commit`: until then, it holds the accounts read while executing the block.
+pub struct CachedWorldState {
+    store: Box<dyn AccountStore>,
+    capacity: NonZeroUsize,
+
+    /// The height of the committed world state (the amount of committed blocks)
+    height: usize,
+
+    /// The accounts read from the store, along with their ids
+    cache: FrozenMap<String, Box<(String, Account)>>,
+
+    /// The ids of the cached accounts, least recently used first
+    recency: RefCell<LruCache<String, ()>>,
+
+    /// The uncommitted changes; `None` marks a deleted account
+    changes: HashMap<String, Option<Account>>,
+}
+
+impl CachedWorldState {
+    /// C'tor, caching up to `capacity` accounts of the store (at least one).
+    pub fn new(store: Box<dyn AccountStore>, capacity: usize) -> Result<Self, BlockchainError> {
+        Ok(CachedWorldState {
+            height: store.height()?,
+            store,
+            capacity: NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+            cache: FrozenMap::new(),
+            recency: RefCell::new(LruCache::unbounded()),
+            changes: HashMap::new(),
+        })
+    }
+
+    /// Will write the uncommitted changes to the store (as the ones of the next block),
+    /// and trim the cache to its capacity
+    pub fn commit(&mut self) -> Result<(), BlockchainError> {
+        self.store.write_batch(self.height + 1, &self.changes)?;
+        self.height += 1;
+
+        let cache = self.cache.as_mut();
+        let recency = self.recency.get_mut();
+        for (id, change) in self.changes.drain() {
+            match change {
+                Some(account) => {
+                    recency.put(id.clone(), ());
+                    cache.insert(id.clone(), Box::new((id, account)));
+                }
+                None => {
+                    recency.pop(&id);
+                    cache.remove(&id);
+                }
+            }
+        }
+        while cache.len() > self.capacity.get() {
+            match recency.pop_lru() {
+                Some((id, _)) => cache.remove(&id),
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    /// Will discard the uncommitted changes
+    pub fn rollback(&mut self) {
+        self.changes.clear();
+    }
+
+    /// Will return the amount of cached accounts
+    pub fn cached_accounts(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Will return the (committed or cached) account, reading it from the store if needed.<br/>
+    /// A failing read is logged and treated like a missing account, as the world state
+    /// can't report it.
+    fn load(&self, id: &str) -> Option<&(String, Account)> {
+        let entry = match self.cache.get(id) {
+            Some(entry) => entry,
+            None => match self.store.get_account(id) {
+                Ok(Some(account)) => self
+                    .cache
+                    .insert(id.to_string(), Box::new((id.to_string(), account))),
+                Ok(None) => return None,
+                Err(err) => {
+                    warn!(error = %err, account = id, "Failed to read the account");
+                    return None;
+                }
+            },
+        };
+        self.recency.borrow_mut().put(id.to_string(), ());
+        Some(entry)
+    }
+}
+
+impl fmt::Debug for CachedWorldState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CachedWorldState {{ height: {}, cached: {}, changed: {} }}",
+            self.height,
+            self.cache.len(),
+            self.changes.len()
+        )
+    }
+}
+
+impl WorldState for CachedWorldState {
+    fn get_user_ids(&self) -> Vec<String> {
+        self.accounts().map(|(id, _)| id.clone()).collect()
+    }
+
+    fn get_account_by_id_mut(&mut self, id: &str) -> Option<&mut Account> {
+        if !self.changes.contains_key(id) {
+            let account = self.load(id)?.1.clone();
+            self.changes.insert(id.to_string(), Some(account));
+        }
+        self.changes.get_mut(id).and_then(Option::as_mut)
+    }
+
+    fn get_account_by_id(&self, id: &str) -> Option<&Account> {
+        match self.changes.get(id) {
+            Some(account) => account.as_ref(),
+            None => self.load(id).map(|(_, account)| account),
+        }
+    }
+
+    fn accounts(&self) -> Box<dyn Iterator<Item = (&String, &Account)> + '_> {
+        let ids = self.store.account_ids().unwrap_or_else(|err| {
+            warn!(error = %err, "Failed to list the accounts");
+            Vec::new()
+        });
+        let changes = &self.changes;
+        let unchanged = ids
+            .into_iter()
+            .filter(move |id| !changes.contains_key(id))
+            .filter_map(move |id| self.load(&id).map(|(id, account)| (id, account)));
+        let changed = changes
+            .iter()
+            .filter_map(|(id, account)| account.as_ref().map(|account| (id, account)));
+        Box::new(unchanged.chain(changed))
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn create_account(
+        &mut self,
+        id: String,
+        account_type: AccountType,
+    ) -> Result<(), BlockchainError> {
+        if self.account_exists(&id) {
+            return Err(BlockchainError::AccountExists(id));
+        }
+        let account = tombstone::new_account(self, &id, account_type);
+        self.changes.insert(id, Some(account));
+        Ok(())
+    }
+
+    fn delete_account(&mut self, id: &str) -> Result<Account, BlockchainError> {
+        let account = self
+            .get_account_by_id(id)
+            .cloned()
+            .ok_or_else(|| BlockchainError::UnknownAccount(id.to_string()))?;
+        self.changes.insert(id.to_string(), None);
+        Ok(account)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::CachedWorldState;
+    use crate::storage::SledAccountStore;
+    use crate::{AccountType, Transaction, TransactionData, WorldState};
+
+    #[test]
+    fn accounts_are_committed_to_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        // sled may hold its lock for a moment after being dropped, hence the restarted
+        // world state shares the store instead of reopening the database
+        let store = SledAccountStore::open(dir.path()).unwrap();
+        let open = || CachedWorldState::new(Box::new(store.clone()), 2).unwrap();
+
+        let mut state = open();
+        for id in ["alice", "bob", "carol"].iter() {
+            state
+                .create_account(id.to_string(), AccountType::User)
+                .unwrap();
+        }
+        state.get_account_by_id_mut("alice").unwrap().tokens = 100;
+        state.commit().unwrap();
+        assert_eq!(1, state.height());
+        assert_eq!(2, state.cached_accounts());
+
+        // Executed transactions change the world state, until they are rolled back
+        let transfer = |amount| {
+            let transfer = TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount,
+                asset: None,
+            };
+            Transaction::new("alice".into(), transfer, 0)
+        };
+        transfer(10).execute(&mut state, &false).unwrap();
+        assert_eq!(Some(10), state.get_balance("bob"));
+        state.rollback();
+        assert_eq!(Some(0), state.get_balance("bob"));
+
+        transfer(30).execute(&mut state, &false).unwrap();
+        state.delete_account("carol").unwrap();
+        state.commit().unwrap();
+        drop(state);
+
+        // The restarted world state reads the accounts from the store
+        let state = open();
+        assert_eq!(2, state.height());
+        assert_eq!(0, state.cached_accounts());
+        assert_eq!(Some(70), state.get_balance("alice"));
+        assert_eq!(Some(30), state.get_balance("bob"));
+        assert!(!state.account_exists("carol"));
+        let mut ids = state.get_user_ids();
+        ids.sort();
+        assert_eq!(vec!["alice".to_string(), "bob".to_string()], ids);
+    }
+}
@@ -0,0 +1,60 @@
+use bincode::Options;
+use serde::Serialize;
+
+/// Will encode the value canonically, as it is hashed (see `Transaction::calculate_hash` and
+/// `Block::calculate_hash`):
+/// - integers have fixed widths and are little endian (e.g. a `u128` always takes 16 bytes)
+/// - strings, byte slices and sequences are prefixed by their length (as a `u64`)
+/// - options are prefixed by a byte (0 for `None`, 1 for `Some`), enum variants by their index
+///   (as a `u32`)
+/// - the fields of structs (and the elements of tuples) follow each other in the order of
+///   their declaration, without their names
+///
+/// Unlike the storage format, the encoding is part of the consensus: changing it (e.g. by
+/// reordering the variants of `TransactionData`) changes the hashes of every block.
+pub(crate) fn encode<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .serialize(value)
+        .expect("values without maps of unknown length are always encodable")
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::encode;
+    use crate::{Block, Hash, TransactionData};
+
+    #[test]
+    fn encoding_is_locked() {
+        assert_eq!(
+            "2a0000000000000002000000000000006869010700000000000000000000000000000000",
+            hex::encode(encode(&(42u64, "hi", Some(7u128), None::<u32>)))
+        );
+        assert_eq!(
+            "0900000003000000000000006269620500000000000000000000000000000000",
+            hex::encode(encode(&TransactionData::TransferTokens {
+                to: "bib".into(),
+                amount: 5,
+                asset: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn block_hashes_are_locked() {
+        let mut block = Block::new(Some(Hash::from_slice(&[7; 64]).unwrap()));
+        block.header.timestamp = 1_600_000_000_000;
+        block.header.nonce = 42;
+        assert_eq!(
+            "acd12371dd57025d19a1754395f19712a6add4115ca4a6e90d90c2d2ae9bb7f1\
+             b20c2fa9dfe39d292e8fce4ca8e3530f85acadb22908ce39ea36ba684dee621a",
+            block.calculate_hash().to_hex()
+        );
+    }
+}
@@ -1,297 +1,4200 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use crate::{Account, Block, Transaction};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, debug_span, info, info_span, trace_span, warn};
 
-#[derive(Debug, Clone)]
+use super::block::now;
+use super::consensus::ConsensusHandle;
+use super::index::ChainIndex;
+use super::journal::Journal;
+use super::mempool::{max_cost, order_by_fee};
+use super::orphans::OrphanPool;
+use super::overlay::Overlay;
+use super::snapshot::Snapshot;
+use super::storage::wal::{self, WalEntry};
+use super::storage::StorageHandle;
+use super::subscription::Subscribers;
+use crate::storage::FileStorage;
+use crate::{
+    Account, AccountChange, Block, BlockBuilder, BlockHeader, BlockchainError, ChainEvent,
+    ChainMetrics, Checkpoint, Consensus, ExecutionResult, Hash, Keypair, MempoolPolicy,
+    MerkleProof, OrphanPolicy, Storage, Transaction, TransactionData, TransactionKind,
+    TransactionLocation, TransactionReceipt, ValidationPolicy,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Blockchain {
-    /// The blocks which were accepted in the blockchain.
-    pub blocks: Vec<Block>,
+    /// The blocks which were accepted in the blockchain (see `blocks`).<br/>
+    /// Like the accounts, they are only changed by the chain itself, so that its invariants
+    /// (the indexes, the receipts, the persisted blocks) hold; see `tamper_block` for
+    /// demonstrating attacks.
+    pub(crate) blocks: Vec<Block>,
 
-    /// Lookup from AccountID (will be a public key later) to Account.
-    /// Effectively, this represents the WorldState
-    pub accounts: HashMap<String, Account>,
+    /// Lookup from AccountID (the address of the owner, for signed accounts) to Account.
+    /// Effectively, this represents the WorldState (query it through the `WorldState` trait)
+    #[serde(serialize_with = "super::serialization::ordered_map")]
+    pub(crate) accounts: HashMap<String, Account>,
 
     /// Will store transactions which should be added to the chain
-    /// but aren't yet
+    /// but aren't yet (the mempool)
     pending_transactions: Vec<Transaction>,
+
+    /// Limits the size of the mempool and how long transactions may wait in it
+    #[serde(skip)]
+    mempool_policy: MempoolPolicy,
+
+    /// When the pending transactions arrived in the mempool, by their senders and nonces
+    #[serde(skip)]
+    arrivals: HashMap<(String, u128), u64>,
+
+    /// The blocks whose parents are unknown (yet)
+    #[serde(skip)]
+    orphans: OrphanPool,
+
+    /// Limits the size of the orphan pool and how long blocks are kept in it
+    #[serde(skip)]
+    orphan_policy: OrphanPolicy,
+
+    /// The rules that blocks have to follow in order to be accepted
+    policy: ValidationPolicy,
+
+    /// The latest checkpoint signed by the validators (see `finalize`)
+    #[serde(default)]
+    finalized: Option<Checkpoint>,
+
+    /// Decides who may produce the blocks and how they prove it
+    #[serde(skip)]
+    consensus: ConsensusHandle,
+
+    /// The storage the accepted blocks are persisted to (if any)
+    #[serde(skip)]
+    storage: StorageHandle,
+
+    /// Records the changes of the block being executed (used for rolling it back)
+    #[serde(skip)]
+    pub(crate) journal: Option<Journal>,
+
+    /// The positions of the blocks and the transactions, by their hashes
+    #[serde(skip)]
+    index: ChainIndex,
+
+    /// The receipts of the transactions, per block
+    #[serde(skip)]
+    receipts: Vec<Vec<TransactionReceipt>>,
+
+    /// The channels that the changes of the chain are published to
+    #[serde(skip)]
+    subscribers: Subscribers,
+
+    /// The amount of (latest) blocks whose transactions are kept, all if not set
+    #[serde(skip)]
+    keep_blocks: Option<usize>,
+
+    /// If set, `check_validity` only checks the blocks appended since its last successful run
+    #[serde(skip)]
+    incremental_checks: bool,
+
+    /// The height and the hash of the last block that `check_validity` verified
+    #[serde(skip)]
+    verified: VerifiedTip,
+
+    /// The amount of threads that `check_validity` verifies the blocks with (0 for all cores)
+    #[serde(skip)]
+    validation_threads: usize,
+
+    /// Counts the appended and the rejected blocks (see `metrics`)
+    #[serde(skip)]
+    metrics: ChainMetrics,
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Blockchain {
     /// C'tor.
     pub fn new() -> Self {
+        Self::with_policy(ValidationPolicy::default())
+    }
+
+    /// C'tor using a custom validation policy (and the consensus mechanism of its engine).
+    pub fn with_policy(policy: ValidationPolicy) -> Self {
+        Self::with_consensus_handle(policy, ConsensusHandle(None))
+    }
+
+    /// C'tor using a custom validation policy and a custom consensus mechanism,
+    /// which replaces the one of the policy's engine.
+    pub fn with_consensus(policy: ValidationPolicy, consensus: Arc<dyn Consensus>) -> Self {
+        Self::with_consensus_handle(policy, ConsensusHandle(Some(consensus)))
+    }
+
+    /// C'tor of an empty chain following the rules of this one (its policy and its consensus
+    /// mechanism), e.g. to replay its blocks
+    fn empty_replica(&self) -> Self {
+        Self::with_consensus_handle(
+            self.policy.clone(),
+            ConsensusHandle(self.consensus.0.clone()),
+        )
+    }
+
+    fn with_consensus_handle(policy: ValidationPolicy, consensus: ConsensusHandle) -> Self {
         Blockchain {
             blocks: Vec::new(),
             accounts: HashMap::new(),
             pending_transactions: Vec::new(),
+            mempool_policy: MempoolPolicy::default(),
+            arrivals: HashMap::new(),
+            orphans: OrphanPool::default(),
+            orphan_policy: OrphanPolicy::default(),
+            policy,
+            finalized: None,
+            consensus,
+            storage: StorageHandle::default(),
+            journal: None,
+            index: ChainIndex::default(),
+            receipts: Vec::new(),
+            subscribers: Subscribers::default(),
+            keep_blocks: None,
+            incremental_checks: false,
+            verified: VerifiedTip::default(),
+            validation_threads: 0,
+            metrics: ChainMetrics::default(),
+        }
+    }
+
+    /// Will open the Blockchain persisted (as files) in the provided directory,
+    /// creating an empty one if there is none yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        Self::open_with(
+            Box::new(FileStorage::open(path)?),
+            ValidationPolicy::default(),
+        )
+    }
+
+    /// Will load the Blockchain from the provided storage and keep it attached to it,
+    /// so that all accepted blocks get persisted.<br/>
+    /// If the stored world state is not up to date, the blocks are re-executed.
+    pub fn open_with(
+        storage: Box<dyn Storage>,
+        policy: ValidationPolicy,
+    ) -> Result<Self, BlockchainError> {
+        Self::with_policy(policy).load(storage)
+    }
+
+    /// Will load the Blockchain from the provided storage like `open_with` does,
+    /// using a custom consensus mechanism (see `with_consensus`).
+    pub fn open_with_consensus(
+        storage: Box<dyn Storage>,
+        policy: ValidationPolicy,
+        consensus: Arc<dyn Consensus>,
+    ) -> Result<Self, BlockchainError> {
+        Self::with_consensus(policy, consensus).load(storage)
+    }
+
+    /// Will load the blocks and the world state of the (empty) chain from the storage
+    /// and attach the chain to it
+    fn load(self, mut storage: Box<dyn Storage>) -> Result<Self, BlockchainError> {
+        let mut chain = self;
+
+        // A block application interrupted by a crash is completed (or rolled back) first
+        wal::recover(storage.as_mut())?;
+        let block_count = storage.block_count()?;
+
+        match storage.get_state()? {
+            Some((height, accounts)) if height == block_count => {
+                chain.blocks = storage.blocks().collect::<Result<_, _>>()?;
+                chain.accounts = accounts;
+                for (height, block) in chain.blocks.iter().enumerate() {
+                    let receipts = storage.get_receipts(height)?.unwrap_or_default();
+                    chain.index.add_block(height, block, &receipts);
+                    chain.receipts.push(receipts);
+                }
+            }
+            _ => {
+                for (height, block) in storage.blocks().enumerate() {
+                    chain
+                        .append_block(block?)
+                        .map_err(|err| err.in_block(height))?;
+                }
+            }
+        }
+
+        chain.storage = StorageHandle(Some(storage));
+        Ok(chain)
+    }
+
+    /// Will return a snapshot of the chain at its current height: the headers of the blocks
+    /// and the world state (but neither the transactions nor the mempool).
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            chain_id: self.chain_id().to_string(),
+            headers: self.blocks.iter().map(Block::without_body).collect(),
+            accounts: self.accounts.clone(),
         }
     }
 
-    /// Will add a block to the Blockchain
+    /// Will restore a chain out of a snapshot (see `snapshot`), without replaying its blocks.<br/>
+    /// The headers are validated like the blocks of `check_validity`, whilst the world state
+    /// has to match the state root the last header commits to.
+    pub fn restore_from_snapshot(
+        snapshot: Snapshot,
+        policy: ValidationPolicy,
+    ) -> Result<Self, BlockchainError> {
+        let mut chain = Self::with_policy(policy);
+        if snapshot.chain_id != chain.policy.chain_id {
+            return Err(BlockchainError::ChainIdMismatch {
+                expected: chain.policy.chain_id,
+                found: snapshot.chain_id,
+            });
+        }
+
+        chain.blocks = snapshot
+            .headers
+            .into_iter()
+            .map(|block| block.without_body())
+            .collect();
+        chain.accounts = snapshot.accounts;
+        for (height, block) in chain.blocks.iter().enumerate() {
+            chain.index.add_block(height, block, &[]);
+        }
+        chain.receipts = vec![Vec::new(); chain.blocks.len()];
+        chain.check_validity()?;
+        Ok(chain)
+    }
+
+    /// Will restore a chain out of a snapshot like `restore_from_snapshot` does, persisting it
+    /// to the (empty) storage and keeping it attached to it.
+    pub fn restore_from_snapshot_with(
+        mut storage: Box<dyn Storage>,
+        snapshot: Snapshot,
+        policy: ValidationPolicy,
+    ) -> Result<Self, BlockchainError> {
+        if storage.block_count()? != 0 {
+            return Err(BlockchainError::Storage(
+                "A snapshot can only be restored into an empty storage".into(),
+            ));
+        }
+
+        let mut chain = Self::restore_from_snapshot(snapshot, policy)?;
+        for (height, block) in chain.blocks.iter().enumerate() {
+            storage.put_block(height, block)?;
+        }
+        storage.put_state(chain.len(), &chain.accounts)?;
+        storage.flush()?;
+
+        chain.storage = StorageHandle(Some(storage));
+        Ok(chain)
+    }
+
+    /// Will persist the world state and flush the storage (if the chain is attached to one)
+    pub fn flush(&mut self) -> Result<(), BlockchainError> {
+        if let Some(storage) = self.storage.0.as_mut() {
+            storage.put_state(self.blocks.len(), &self.accounts)?;
+            storage.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Will return the validation policy in use
+    pub fn policy(&self) -> &ValidationPolicy {
+        &self.policy
+    }
+
+    /// Will return the validation policy that the block at the height is validated with,
+    /// i.e. with the upgrades activated up to the height (see `ValidationPolicy::at`)
+    pub fn policy_at(&self, height: usize) -> Cow<'_, ValidationPolicy> {
+        self.policy.at(height)
+    }
+
+    /// Will return the validation policy that the next block is validated with
+    fn next_policy(&self) -> Cow<'_, ValidationPolicy> {
+        self.policy.at(self.blocks.len())
+    }
+
+    /// Will return the consensus mechanism in use (for the next block)
+    pub fn consensus(&self) -> Arc<dyn Consensus> {
+        self.consensus_at(self.blocks.len())
+    }
+
+    /// Will return the consensus mechanism that the block at the height is validated with:
+    /// the custom one (see `with_consensus`) or the one of the policy's engine at the height
+    fn consensus_at(&self, height: usize) -> Arc<dyn Consensus> {
+        self.consensus.resolve(&self.policy.at(height))
+    }
+
+    /// Will return the id of the network the chain belongs to
+    pub fn chain_id(&self) -> &str {
+        &self.policy.chain_id
+    }
+
+    /// Will add a block to the Blockchain and return the result of executing it (gas used)
     /// @TODO every simple step could be refactored into a separate function for
     /// better testability and code-reusability
-    pub fn append_block(&mut self, block: Block) -> Result<(), String> {
+    /// <br/>
+    /// A block whose parent is unknown (yet) is kept in the orphan pool (see
+    /// `set_orphan_policy`) and rejected with `OrphanBlock`, it is appended as soon as its
+    /// parent is (along with the orphans following it).
+    pub fn append_block(&mut self, block: Block) -> Result<ExecutionResult, BlockchainError> {
+        let appended = self.append_recorded(block);
+        if appended.is_ok() {
+            self.attach_orphans();
+        }
+        appended
+    }
+
+    /// Will append the block (or keep it as an orphan) like `append_block` does,
+    /// recording the outcome in the metrics and the logs
+    fn append_recorded(&mut self, block: Block) -> Result<ExecutionResult, BlockchainError> {
+        let _span = info_span!("append_block", height = self.len(), hash = ?block.hash).entered();
+        let started = Instant::now();
+        let appended = match self.unknown_parent(&block) {
+            Some(parent) => self.keep_orphan(block, parent),
+            None => self.append(block, false),
+        };
+        self.metrics.record_block(&appended, started.elapsed());
+        match &appended {
+            Ok(result) => info!(
+                transactions = result.receipts.len(),
+                gas_used = result.gas_used,
+                "Block appended"
+            ),
+            Err(BlockchainError::OrphanBlock { parent }) => {
+                debug!(%parent, orphans = self.orphans.blocks().count(), "Orphan block kept")
+            }
+            Err(err) => warn!(error = %err, code = err.code(), "Block rejected"),
+        }
+        appended
+    }
+
+    /// Will return the parent of the block, if it is not part of the chain
+    fn unknown_parent(&self, block: &Block) -> Option<Hash> {
+        block
+            .header
+            .prev_hash
+            .filter(|parent| self.index.block_height(parent).is_none())
+    }
+
+    /// Will keep the (intact) block in the orphan pool, until its parent arrives
+    fn keep_orphan(
+        &mut self,
+        block: Block,
+        parent: Hash,
+    ) -> Result<ExecutionResult, BlockchainError> {
+        if !block.verify_own_hash() {
+            return Err(BlockchainError::HashMismatch);
+        }
+        self.orphans.insert(block, now(), &self.orphan_policy);
+        Err(BlockchainError::OrphanBlock { parent })
+    }
+
+    /// Will append the orphans following the last block (and the ones following them).<br/>
+    /// Of competing orphans, the first valid one is appended, whilst the others are dropped.
+    fn attach_orphans(&mut self) {
+        while let Some(tip) = self.get_last_block_hash() {
+            let children = self.orphans.take_children(&tip, now(), &self.orphan_policy);
+            let mut attached = false;
+            for child in children {
+                attached = attached || self.append_recorded(child).is_ok();
+            }
+            if !attached {
+                return;
+            }
+        }
+    }
+
+    /// Will limit the orphan pool (its size and how long blocks are kept in it)
+    pub fn set_orphan_policy(&mut self, policy: OrphanPolicy) {
+        self.orphan_policy = policy;
+    }
+
+    /// Will return the blocks whose parents are unknown (yet), in the order they arrived in
+    pub fn orphan_blocks(&self) -> impl Iterator<Item = &Block> {
+        self.orphans.blocks()
+    }
+
+    /// Will append the block like `append_block` does.<br/>
+    /// If `is_verified` is set, the checks of `verify_block` are skipped, since the block was
+    /// verified before (e.g. by `check_validity`, which then replays the blocks).
+    fn append(
+        &mut self,
+        block: Block,
+        is_verified: bool,
+    ) -> Result<ExecutionResult, BlockchainError> {
         // The genesis block may create user out of nowhere,
         // and also may do some other things
-        let is_genesis = self.len() == 0;
+        let is_genesis = self.is_empty();
 
         // Check if the hash matches the transactions
-        if !block.verify_own_hash() {
-            return Err("The block hash is mismatching! (Code: 93820394)".into());
+        if !is_verified && !block.verify_own_hash() {
+            return Err(BlockchainError::HashMismatch);
         }
 
         // Check if the newly added block is meant to be appended onto the last block
-        if !(block.prev_hash == self.get_last_block_hash()) {
-            return Err("The new block has to point to the previous block (Code: 3948230)".into());
+        if block.header.prev_hash != self.get_last_block_hash() {
+            return Err(BlockchainError::PrevHashMismatch);
+        }
+        check_height(&block, self.blocks.last())?;
+
+        // Check if the block is the one of the checkpoint at its height (if any)
+        if let Some(checkpoint) = self.policy.checkpoint_at(self.blocks.len()) {
+            if block.hash != Some(checkpoint.hash) {
+                return Err(BlockchainError::CheckpointMismatch {
+                    height: checkpoint.height,
+                });
+            }
+        }
+
+        // Check if the block was produced as the consensus demands (e.g. mined or signed),
+        // by someone entitled to produce it
+        let consensus = self.consensus();
+        if !is_verified {
+            consensus.check_header(&block, &self.blocks)?;
+        }
+        if !is_genesis {
+            consensus.check_producer(&block, &self.blocks, self)?;
+        }
+
+        // Check if the block is neither older than the previous blocks nor from the far future
+        self.check_timestamp(&block, &self.blocks)?;
+        let max = now().saturating_add(self.policy.max_future_drift);
+        if block.header.timestamp > max {
+            return Err(BlockchainError::TimestampTooFarInFuture { max });
         }
 
         // There has to be at least one transaction inside the queue
         if block.get_transaction_count() == 0 {
-            return Err("There has to be at least one transaction \
-                inside the block! (Code: 9482930)"
-                .into());
+            return Err(BlockchainError::EmptyBlock);
         }
 
-        // Reject block having nonces that are already used (Prevent reply attacks etc.)
-        // @Todo (Will skip that for simplicity)
+        // Check if the block (and its transactions) stays within the limits of the chain
+        let policy = self.next_policy();
+        policy.params.check_block(&block)?;
 
-        // This is expensive and just used for rollback if some transactions succeed whilst
-        // others don't (prevent inconsistent states)
-        // Arguably, that could be implemented more resource-aware
-        let old_state = self.accounts.clone();
+        // Check if the block (and its transactions) follows the version of the rules
+        self.policy.check_versions(self.blocks.len(), &block)?;
 
-        // Execute each transaction
-        for (i, transaction) in block.transactions.iter().enumerate() {
-            // Execute the transaction
-            if let Err(err) = transaction.execute(self, &is_genesis) {
-                // Recover state on failure.
-                self.accounts = old_state;
+        // Check if the transactions are meant for this network (prevents replays across networks)
+        for (i, transaction) in block.body.transactions.iter().enumerate() {
+            self.check_chain_id(transaction)
+                .and_then(|_| policy.params.check_transaction(transaction))
+                .and_then(|_| transaction.check_validity_window(self.len(), block.header.timestamp))
+                .map_err(|err| err.in_transaction(i))?;
+        }
 
-                // ... and reject the block
-                return Err(format!(
-                    "Could not execute transaction {} due to `{}`. Rolling back \
-                    (Code: 38203984)",
-                    i + 1,
-                    err
-                ));
+        // Check if the transactions are signed as required by the policy
+        if !is_verified {
+            let rejected = policy.first_rejected_transaction(&block.body.transactions, is_genesis);
+            if let Some(i) = rejected {
+                return Err(BlockchainError::InvalidSignature.in_transaction(i));
             }
         }
 
-        // Everything went fine... append the block
-        self.blocks.push(block);
+        // Check if the block producer rewarded itself correctly
+        check_coinbase(&policy, &block)?;
+        let require_state_root = policy.require_state_root;
 
-        Ok(())
-    }
+        // Execute each transaction (rejecting the ones whose nonces are used already, which
+        // prevents replay attacks, see `Transaction::execute`)
+        let result = self.execute_block(&block, is_genesis)?;
 
-    /// Will return the amount of blocks currently stored
-    pub fn len(&self) -> usize {
-        self.blocks.len()
-    }
+        // Check if the block commits to the resulting world state
+        let state_matches = match block.header.state_root {
+            Some(state_root) => state_root == self.state_root(),
+            None => !require_state_root,
+        };
+        if !state_matches {
+            self.rollback();
+            return Err(BlockchainError::StateRootMismatch);
+        }
 
-    /// Will return the hash of the last block
-    pub fn get_last_block_hash(&self) -> Option<String> {
-        if self.len() == 0 {
-            return None;
+        // Persist the block and its receipts (if the chain is attached to a storage), logging
+        // them (with the changed accounts) ahead, so that a crash can't interrupt them
+        if let Some(storage) = self.storage.0.as_mut() {
+            let height = self.blocks.len();
+            let entry = WalEntry {
+                height,
+                block: block.clone(),
+                receipts: result.receipts.clone(),
+                changes: match &self.journal {
+                    Some(journal) => journal.changes(&self.accounts),
+                    None => HashMap::new(),
+                },
+            };
+            let persisted = storage
+                .put_wal(&entry)
+                .and_then(|_| storage.put_receipts(height, &result.receipts))
+                .and_then(|_| storage.put_block(height, &block))
+                .and_then(|_| storage.clear_wal());
+            if let Err(err) = persisted {
+                self.rollback();
+                return Err(err);
+            }
+        }
+        self.journal = None;
+
+        // Pending transactions included by the block (e.g. received from a peer)
+        // must not be included again
+        if !self.pending_transactions.is_empty() {
+            let included: HashSet<Hash> = block
+                .body
+                .transactions
+                .iter()
+                .map(|transaction| transaction.calculate_hash())
+                .collect();
+            self.pending_transactions
+                .retain(|transaction| !included.contains(&transaction.calculate_hash()));
+        }
+
+        // Everything went fine... append the block
+        let height = self.blocks.len();
+        self.index.add_block(height, &block, &result.receipts);
+        self.receipts.push(result.receipts.clone());
+        if let Some(hash) = block.hash {
+            self.subscribers
+                .publish(ChainEvent::BlockAppended { height, hash });
+        }
+        self.blocks.push(block);
+
+        for (index, receipt) in result.receipts.iter().enumerate() {
+            self.subscribers.publish(ChainEvent::TransactionExecuted {
+                block_height: height,
+                index,
+                receipt: receipt.clone(),
+            });
         }
 
-        self.blocks[self.len() - 1].hash.clone()
+        // The block is appended already, a failed pruning is retried with the next block
+        if let Err(err) = self.prune() {
+            warn!(error = %err, "Could not prune the blocks");
+        }
+        Ok(result)
     }
 
-    /// Checks if the blockchain was tempered with
-    /// It will check until the first error happens and return a description of the problem
-    /// if everything is fine it will return Ok
-    pub fn check_validity(&self) -> Result<(), String> {
-        for (block_num, block) in self.blocks.iter().enumerate() {
-            // Check if block saved hash matches to calculated hash
-            if !block.verify_own_hash() {
-                return Err(format!(
-                    "Stored hash for Block #{} \
-                        does not match calculated hash (Code: 665234234)",
-                    block_num + 1
-                )
-                .into());
-            }
+    /// Will keep the transactions (and receipts) of only the last `keep_blocks` blocks, all if
+    /// `None`. Older blocks are pruned right away and whenever a block is appended, only their
+    /// headers and the world state are kept.<br/>
+    /// Pruned blocks can't be re-executed, hence a pruned chain can't be rewound.
+    pub fn set_pruning(&mut self, keep_blocks: Option<usize>) -> Result<(), BlockchainError> {
+        self.keep_blocks = keep_blocks;
+        self.prune()
+    }
 
-            // Check previous black hash points to actual previous block
-            if block_num == 0 {
-                // Genesis block should point to nowhere
-                if block.prev_hash.is_some() {
-                    return Err("The genesis block has a previous hash set which \
-                         it shouldn't Code :394823098"
-                        .into());
-                }
-            } else {
-                // Non genesis blocks should point to previous blocks hash (which is validated before)
-                if block.prev_hash.is_none() {
-                    return Err(format!("Block #{} has no previous hash set", block_num + 1).into());
-                }
+    /// Will return the amount of (latest) blocks whose transactions are kept, if pruning
+    pub fn pruning(&self) -> Option<usize> {
+        self.keep_blocks
+    }
 
-                // Store the values locally to use them within the error message on failure
-                let prev_hash_proposed = block.prev_hash.as_ref().unwrap();
-                let prev_hash_actual = self.blocks[block_num - 1].hash.as_ref().unwrap();
-
-                if !(&block.prev_hash == &self.blocks[block_num - 1].hash) {
-                    return Err(format!(
-                        "Block #{} is not connected to previous block (Hashes do \
-                        not match. Should be `{}` but is `{}`)",
-                        block_num, prev_hash_proposed, prev_hash_actual
-                    )
-                    .into());
-                }
-            }
+    /// Will return the height of the first block that was not pruned
+    /// (the blocks before it only consist of their headers)
+    pub fn pruned_height(&self) -> usize {
+        // Blocks are pruned in order, so they are at the start of the chain
+        self.blocks.partition_point(Block::is_pruned)
+    }
 
-            // Check if transactions are signed correctly
-            for (transaction_num, transaction) in block.transactions.iter().enumerate() {
-                // Careful! With that implementation an unsigned message will always
-                // be valid! You may remove the first check to only accept signed transactions
-                if transaction.is_signed() && !transaction.check_signature() {
-                    return Err(format!(
-                        "Transaction #{} for Block #{} has an invalid signature \
-                        (Code: 4398239048)",
-                        transaction_num + 1,
-                        block_num + 1
-                    ));
-                }
-            }
+    /// Will return the block at the height, `None` if there is no such block
+    /// or `BlockPruned` if only its header is kept (see `get_header`).
+    pub fn get_block(&self, height: usize) -> Result<Option<&Block>, BlockchainError> {
+        match self.blocks.get(height) {
+            Some(block) if block.is_pruned() => Err(BlockchainError::BlockPruned { height }),
+            block => Ok(block),
         }
-        Ok(())
     }
-}
-
-// -------------------------
-//        unit tests
-// -------------------------
 
-#[cfg(test)]
-mod tests {
+    /// Will return the blocks of the chain (the pruned ones only consist of their headers)
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
 
-    use std::borrow::BorrowMut;
+    /// Will return the amount of blocks (like `len`)
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
 
-    use crate::{Block, Blockchain, Transaction, TransactionData};
+    /// Will return the account with the id (use `WorldState::resolve_account_id` for aliases)
+    pub fn get_account(&self, id: &str) -> Option<&Account> {
+        self.accounts.get(id)
+    }
 
-    #[test]
-    fn creation() {
-        let bc = Blockchain::new();
-        assert_eq!(0, bc.blocks.len());
+    /// Will return the amount of accounts
+    pub fn accounts_len(&self) -> usize {
+        self.accounts.len()
     }
 
-    #[test]
-    fn create_and_alter() {
-        // Create a new Blockchain
-        let mut bc = Blockchain::new();
+    /// Will return the block at the height to tamper with, bypassing every check of the chain,
+    /// e.g. to demonstrate that tampering is detected (see `check_validity`).
+    #[cfg(any(test, feature = "tamper"))]
+    pub fn tamper_block(&mut self, height: usize) -> Option<&mut Block> {
+        self.blocks.get_mut(height)
+    }
 
-        // Create an empty block (first block has no prev_block)
-        let mut genesis = Block::new(None);
+    /// Will return the account with the id to tamper with, bypassing every check of the
+    /// chain (see `tamper_block`).
+    #[cfg(any(test, feature = "tamper"))]
+    pub fn tamper_account(&mut self, id: &str) -> Option<&mut Account> {
+        self.accounts.get_mut(id)
+    }
 
-        let initial_users = vec!["alice", "bob"];
+    /// Will return the header of the block at the height (even if it was pruned)
+    pub fn get_header(&self, height: usize) -> Option<&BlockHeader> {
+        self.blocks.get(height).map(Block::header)
+    }
 
-        for user in initial_users {
-            let create_account_txn = Transaction::new(
-                user.into(),
-                TransactionData::CreateUserAccount(user.into()),
-                0,
-            );
+    /// Will return the headers of the blocks starting with the height, e.g. for peers (or
+    /// light clients, see `HeaderChain`) syncing the headers before the bodies.
+    pub fn headers(&self, from: usize) -> impl Iterator<Item = &BlockHeader> {
+        self.blocks.iter().skip(from).map(Block::header)
+    }
 
-            let create_token_txn = Transaction::new(
-                user.into(),
-                TransactionData::CreateTokens {
-                    receiver: user.into(),
-                    amount: 100_000_000,
-                },
-                0,
-            );
+    /// Will drop the transactions of the blocks that are not kept by the pruning configuration
+    fn prune(&mut self) -> Result<(), BlockchainError> {
+        let keep_blocks = match self.keep_blocks {
+            Some(keep_blocks) => keep_blocks,
+            None => return Ok(()),
+        };
+        let from = self.pruned_height();
+        let until = self.len().saturating_sub(keep_blocks);
+        if from >= until {
+            return Ok(());
+        }
 
-            genesis.add_transaction(create_account_txn);
+        // The world state is persisted first, since the pruned blocks can't be re-executed
+        if let Some(storage) = self.storage.0.as_mut() {
+            storage.put_state(self.blocks.len(), &self.accounts)?;
+            for height in from..until {
+                storage.put_receipts(height, &[])?;
+                storage.put_block(height, &self.blocks[height].without_body())?;
+            }
+        }
 
-            genesis.add_transaction(create_token_txn);
+        for height in from..until {
+            self.index.remove_transactions(height, &self.blocks[height]);
+            self.blocks[height] = self.blocks[height].without_body();
+            self.receipts[height] = Vec::new();
         }
+        debug!(from, until, "Blocks pruned");
+        Ok(())
+    }
 
-        let mut res = bc.append_block(genesis);
-        println!("Genesis block successfully added: {:?}", res);
-        println!("Full blockchain printout");
-        println!("{:#?}", bc);
+    /// Will return a channel receiving all future changes of the chain
+    /// (appended blocks, executed transactions and reorgs).<br/>
+    /// The subscription ends when the receiver is dropped.
+    pub fn subscribe(&mut self) -> Receiver<ChainEvent> {
+        self.subscribers.subscribe()
+    }
 
-        // Transfer 1 token from alice to bob
-        let mut block2 = Block::new(bc.get_last_block_hash());
-        block2.add_transaction(Transaction::new(
-            "alice".into(),
-            TransactionData::TransferTokens {
-                to: "bob".into(),
-                amount: 1,
-            },
-            0,
-        ));
+    /// Will return what happened to the chain so far (e.g. the amount of appended blocks and
+    /// the reasons that blocks were rejected for)
+    pub fn metrics(&self) -> &ChainMetrics {
+        &self.metrics
+    }
 
-        res = bc.append_block(block2);
-        println!("Block added: {:?}", res);
-        println!("Full blockchain printout");
-        println!("{:#?}", bc);
-        println!("Blockchain valid: {:?}", bc.check_validity());
+    /// Will remove the blocks starting with `height` (e.g. to switch to a competing fork)
+    /// and return them.<br/>
+    /// The world state is rebuilt by re-executing the remaining blocks, whilst the
+    /// transactions of the removed blocks are not put back into the mempool.
+    pub fn rewind(&mut self, height: usize) -> Result<Vec<Block>, BlockchainError> {
+        if height >= self.len() {
+            return Ok(Vec::new());
+        }
+        if let Some(finalized) = self
+            .finalized_height()
+            .filter(|finalized| height <= *finalized)
+        {
+            return Err(BlockchainError::BlockFinalized { height: finalized });
+        }
+        if self.pruned_height() > 0 {
+            return Err(BlockchainError::BlockPruned { height: 0 });
+        }
 
-        // Everything is fine until here
+        let mut replayed = self.empty_replica();
+        for (block_height, block) in self.blocks[..height].iter().enumerate() {
+            replayed
+                .append_block(block.clone())
+                .map_err(|err| err.in_block(block_height))?;
+        }
 
-        // Attack I: changing a transaction
-        // Let's tamper the block chain. Maybe bob was not satisfied with the amount of coins alice sent
-        // him, so he will tamper the blockchains transaction to transmit 100 Coins instead of 1
+        if let Some(storage) = self.storage.0.as_mut() {
+            storage.truncate(height)?;
+            storage.put_state(height, &replayed.accounts)?;
+        }
 
-        // let's clone the current blockchain before tempering
-        let mut bc_attack_1 = bc.clone();
-        // get the transaction as mutable (second block, first transaction; the token transfer)
-        let transaction_data = bc_attack_1.blocks[1].transactions[0].borrow_mut();
+        let removed = self.blocks.split_off(height);
+        info!(height, removed = removed.len(), "Chain rewound");
+        self.accounts = replayed.accounts;
+        self.index = replayed.index;
+        self.receipts = replayed.receipts;
+        self.subscribers.publish(ChainEvent::Reorg {
+            height,
+            removed: removed.iter().filter_map(|block| block.hash).collect(),
+        });
+        Ok(removed)
+    }
 
-        // change the amount value of the transaction INSIDE the chain
-        match transaction_data.record.borrow_mut() {
-            &mut TransactionData::TransferTokens {
-                to: _,
-                ref mut amount,
-            } => {
-                *amount = 100; // Actually change the value in place
+    /// Will switch to a competing fork: the blocks starting with `height` are replaced by the
+    /// provided ones, which are validated like `append_block` does, and the removed blocks are
+    /// returned.<br/>
+    /// If one of the provided blocks is rejected, the removed blocks are restored and the
+    /// error is returned. Which fork to follow (fork choice) is up to the caller.
+    pub fn reorganize(
+        &mut self,
+        height: usize,
+        blocks: Vec<Block>,
+    ) -> Result<Vec<Block>, BlockchainError> {
+        let removed = self.rewind(height)?;
+        for (offset, block) in blocks.into_iter().enumerate() {
+            if let Err(err) = self.append_block(block) {
+                self.rewind(height)?;
+                for block in removed {
+                    // The removed blocks were verified when they were appended
+                    self.append(block, true)?;
+                }
+                return Err(err.in_block(height + offset));
             }
-
-            _ => {} // We know that that recors is a TransferToken Action so we ignore the rest
         }
+        Ok(removed)
+    }
 
-        println!("Changed transaction: {:?}", transaction_data.record);
+    /// Will return the height of the latest finalized block (see `Checkpoint`), if any: the
+    /// chain can't be reorganized at or below it.
+    pub fn finalized_height(&self) -> Option<usize> {
+        let hard_coded = self
+            .policy
+            .checkpoints
+            .iter()
+            .map(|checkpoint| checkpoint.height)
+            .filter(|height| *height < self.len());
+        let signed = self.finalized.as_ref().map(|checkpoint| checkpoint.height);
+        hard_coded.chain(signed).max()
+    }
 
-        // Will print an error, since the blocks hash changes for the
-        println!(
-            "Is the Blockchain still valid? {:#?}",
-            bc_attack_1.check_validity()
-        );
+    /// Will finalize the block of the checkpoint, which has to be part of the chain and signed
+    /// by validators holding more than two thirds of the (current) stake.<br/>
+    /// A checkpoint below the finalized height is ignored.
+    pub fn finalize(&mut self, checkpoint: Checkpoint) -> Result<(), BlockchainError> {
+        let height = checkpoint.height;
+        if self.blocks.get(height).and_then(Block::hash) != Some(checkpoint.hash) {
+            return Err(BlockchainError::CheckpointMismatch { height });
+        }
+        checkpoint.check_quorum(self)?;
+        if self
+            .finalized_height()
+            .is_none_or(|finalized| finalized < height)
+        {
+            info!(height, hash = %checkpoint.hash, "Block finalized");
+            self.finalized = Some(checkpoint);
+        }
+        Ok(())
+    }
 
-        // Attack II: Changing transaction + updating the hash (increasing initial tokens in create
-        // user action)
-        let mut bc_attack_2 = bc.clone();
+    /// Will validate a transaction and store it in the mempool,
+    /// so that it gets included by one of the next produced blocks
+    pub fn submit_transaction(&mut self, transaction: Transaction) -> Result<(), BlockchainError> {
+        let hash = transaction.calculate_hash();
+        let _span = debug_span!("submit_transaction", tx_hash = %hash).entered();
+        let submitted = self.add_pending_transaction(transaction, hash);
+        match &submitted {
+            Ok(()) => debug!(
+                pending = self.pending_transactions.len(),
+                "Transaction accepted"
+            ),
+            Err(err) => debug!(error = %err, code = err.code(), "Transaction rejected"),
+        }
+        submitted
+    }
 
-        // Alice tokens
-        let transaction_data = bc_attack_2.blocks[0].transactions[1].borrow_mut();
+    /// Will execute the transaction on top of the current world state, as the next block
+    /// would, and return its receipt without changing anything, e.g. for wallets to estimate
+    /// its outcome and the gas it uses (see `WorldState::gas_price`) before submitting it.<br/>
+    /// Its signatures aren't checked, so that it can be simulated before being signed.
+    pub fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<TransactionReceipt, BlockchainError> {
+        self.check_chain_id(transaction)?;
+        self.next_policy().params.check_transaction(transaction)?;
+        let mut overlay = Overlay::new(self);
+        transaction.execute(&mut overlay, &self.is_empty())
+    }
 
-        // change tokens
-        match transaction_data.record.borrow_mut() {
-            &mut TransactionData::CreateTokens {
-                receiver: _,
-                ref mut amount,
-            } => {
-                *amount = 100_000_000_000; // Let's dont be small on that
+    /// Will execute the transactions of the block on top of the current world state, as
+    /// appending it would, and return the result without changing anything.<br/>
+    /// Only the execution is simulated: neither the hash, the Proof of Work nor the
+    /// signatures of the block are checked.
+    pub fn simulate_block(&self, block: &Block) -> Result<ExecutionResult, BlockchainError> {
+        let is_genesis = self.is_empty();
+        let policy = self.next_policy();
+        let mut overlay = Overlay::new(self);
+        let mut result = ExecutionResult::default();
+        for (i, transaction) in block.body.transactions.iter().enumerate() {
+            self.check_chain_id(transaction)
+                .and_then(|_| policy.params.check_transaction(transaction))
+                .and_then(|_| transaction.execute(&mut overlay, &is_genesis))
+                .and_then(|receipt| result.add(receipt))
+                .map_err(|err| err.in_transaction(i))?;
+            if result.gas_used > policy.block_gas_limit {
+                return Err(BlockchainError::BlockGasLimitExceeded {
+                    limit: policy.block_gas_limit,
+                });
             }
-            _ => {} // We know that that record is a Token Create Action so we ignore the rest
         }
+        Ok(result)
+    }
 
-        // If we execute now, we'll see the same error as above, hashes dont match (this time 1st block)
+    /// Will validate the transaction (with the provided hash) and add it to the mempool,
+    /// as part of `submit_transaction`
+    fn add_pending_transaction(
+        &mut self,
+        transaction: Transaction,
+        hash: Hash,
+    ) -> Result<(), BlockchainError> {
+        let is_genesis = self.is_empty();
 
-        // Will print an error, since the blocks hash changes for the
-        println!(
-            "Is the Blockchain still valid? {:#?}",
+        self.check_chain_id(&transaction)?;
+        // Coinbases are only created by the block producers (see `produce_block_with_reward`)
+        if transaction.performs_coinbase() {
+            return Err(BlockchainError::SubmittedCoinbase);
+        }
+        if !self.accepts_transaction_signature(&transaction, is_genesis) {
+            return Err(BlockchainError::InvalidSignature);
+        }
+
+        // An expired transaction would never be included, unlike one that isn't valid yet
+        if let Err(err @ BlockchainError::TransactionExpired(_)) =
+            transaction.check_validity_window(self.len(), now())
+        {
+            return Err(err);
+        }
+
+        // A transaction created for a newer version of the rules isn't understood yet
+        self.policy
+            .check_transaction_version(self.len(), &transaction)?;
+
+        // A transaction that doesn't even fit into an empty block would never be included
+        let mut block = self.new_block();
+        block.try_add_transaction(transaction.clone(), &self.next_policy().params)?;
+
+        // Transactions may arrive again after being included (e.g. gossiped by a slow peer)
+        if self.index.transaction_location(&hash).is_some() {
+            return Err(BlockchainError::TransactionAlreadyIncluded(hash));
+        }
+
+        // Only the genesis block may contain transactions from accounts not on the chain
+        if !is_genesis && !self.accounts.contains_key(&transaction.from) {
+            return Err(BlockchainError::UnknownAccount(transaction.from));
+        }
+
+        // A used nonce would never be included again (whilst a gap may still be filled)
+        let nonce = self
+            .accounts
+            .get(&transaction.from)
+            .map_or(0, Account::nonce);
+        if !is_genesis && transaction.nonce < nonce {
+            return Err(BlockchainError::InvalidNonce {
+                expected: nonce,
+                found: transaction.nonce,
+            });
+        }
+
+        let now = now();
+        self.evict_expired_transactions(now);
+        if !is_genesis {
+            self.check_conflicts(&transaction)?;
+            self.make_room_for(&transaction)?;
+        }
+        self.arrivals
+            .insert((transaction.from.clone(), transaction.nonce), now);
+        self.pending_transactions.push(transaction);
+        Ok(())
+    }
+
+    /// Will check that the transaction doesn't conflict with the mempool, so that producing a
+    /// block out of it doesn't routinely fail: the sender has to be able to afford it along with
+    /// its other pending transactions (except for the one it replaces, if any, see
+    /// `make_room_for`).<br/>
+    /// The tokens the sender receives by pending transactions aren't counted, since the
+    /// transactions may end up in any order (or in no block at all).
+    fn check_conflicts(&self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        let sender = &self.accounts[&transaction.from];
+        let gas_price = self.next_policy().gas_price;
+        let required = self
+            .pending_transactions
+            .iter()
+            .filter(|pending| {
+                pending.from == transaction.from && pending.nonce != transaction.nonce
+            })
+            .fold(max_cost(transaction, gas_price), |required, pending| {
+                required.saturating_add(max_cost(pending, gas_price))
+            });
+        if required > sender.tokens {
+            return Err(BlockchainError::PendingBalanceExceeded {
+                balance: sender.tokens,
+                required,
+            });
+        }
+        Ok(())
+    }
+
+    /// Will make room in the mempool for the transaction: a pending transaction with the same
+    /// sender and nonce is replaced if the new one pays a higher fee (replace-by-fee), whilst
+    /// a full mempool evicts the transaction paying the lowest fee if the new one pays more.
+    fn make_room_for(&mut self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        let duplicate = self.pending_transactions.iter().position(|pending| {
+            pending.from == transaction.from && pending.nonce == transaction.nonce
+        });
+        if let Some(position) = duplicate {
+            let fee = self.pending_transactions[position].fee;
+            if transaction.fee <= fee {
+                return Err(BlockchainError::ReplacementFeeTooLow { fee });
+            }
+            self.pending_transactions.remove(position);
+            self.metrics.record_eviction("replaced", 1);
+            return Ok(());
+        }
+
+        if self.pending_transactions.len() < self.mempool_policy.max_transactions {
+            return Ok(());
+        }
+        // The latest of the lowest-fee transactions goes first
+        let lowest = self
+            .pending_transactions
+            .iter()
+            .enumerate()
+            .rev()
+            .min_by_key(|(_, pending)| pending.fee)
+            .map(|(position, pending)| (position, pending.fee));
+        match lowest {
+            Some((position, fee)) if fee < transaction.fee => {
+                let evicted = self.pending_transactions.remove(position);
+                self.arrivals.remove(&(evicted.from, evicted.nonce));
+                self.metrics.record_eviction("evicted", 1);
+                Ok(())
+            }
+            _ => Err(BlockchainError::MempoolFull),
+        }
+    }
+
+    /// Will drop the pending transactions that waited longer than the TTL of the mempool
+    /// (at the time `now`) and forget the arrivals of the ones that left it.
+    fn evict_expired_transactions(&mut self, now: u64) {
+        let ttl = self.mempool_policy.ttl;
+        let arrivals = &self.arrivals;
+        let pending = self.pending_transactions.len();
+        self.pending_transactions.retain(|transaction| {
+            arrivals
+                .get(&(transaction.from.clone(), transaction.nonce))
+                .is_none_or(|arrival| now.saturating_sub(*arrival) < ttl)
+        });
+        self.metrics
+            .record_eviction("expired", pending - self.pending_transactions.len());
+
+        let pending: HashSet<(&str, u128)> = self
+            .pending_transactions
+            .iter()
+            .map(|transaction| (transaction.from.as_str(), transaction.nonce))
+            .collect();
+        self.arrivals
+            .retain(|(from, nonce), _| pending.contains(&(from.as_str(), *nonce)));
+    }
+
+    /// Will limit the mempool (its size and how long transactions may wait in it).<br/>
+    /// The size applies to the transactions submitted from now on, whilst the TTL applies to
+    /// the pending ones as well.
+    pub fn set_mempool_policy(&mut self, policy: MempoolPolicy) {
+        self.mempool_policy = policy;
+    }
+
+    /// Will build a block like `produce_block` does, rewarding the `producer` account
+    /// with the block subsidy plus the fees of the included transactions (coinbase).
+    pub fn produce_block_with_reward(
+        &mut self,
+        max_txs: usize,
+        producer: String,
+    ) -> Result<ExecutionResult, BlockchainError> {
+        let mut block = self.fill_block(max_txs, Some(&producer))?;
+        block.mine(self.next_difficulty());
+        self.append_block(block)
+    }
+
+    /// Will return the transactions that wait to be included into a block
+    pub fn pending_transactions(&self) -> &[Transaction] {
+        &self.pending_transactions
+    }
+
+    /// Will return the nonce that the next transaction of the account should have: the
+    /// nonce of the account (0 if it doesn't exist yet), past the ones of its pending
+    /// transactions.
+    pub fn next_nonce(&self, id: &str) -> u128 {
+        let nonce = self.accounts.get(id).map_or(0, Account::nonce);
+        self.pending_transactions
+            .iter()
+            .filter(|pending| pending.from == id)
+            .fold(nonce, |nonce, pending| {
+                nonce.max(pending.nonce.saturating_add(1))
+            })
+    }
+
+    /// Will return the lowest fee that a new transaction is accepted by the mempool with:
+    /// 0, unless the mempool is full, when it has to pay more than the lowest pending fee
+    /// (see `set_mempool_policy`).
+    pub fn minimum_fee(&self) -> u128 {
+        if self.pending_transactions.len() < self.mempool_policy.max_transactions {
+            return 0;
+        }
+        self.pending_transactions
+            .iter()
+            .map(Transaction::fee)
+            .min()
+            .map_or(0, |fee| fee.saturating_add(1))
+    }
+
+    /// Will build a block out of (at most `max_txs`) pending transactions,
+    /// linked to the last block, and append it to the Blockchain.<br/>
+    /// The transactions paying the highest fees per byte are included first (in the order of
+    /// the nonces of their senders), as many as the limits of the chain (see `ChainParams`)
+    /// and its gas limit allow, the others are left in the mempool.<br/>
+    /// The used transactions are removed from the mempool, even if the block gets rejected.
+    pub fn produce_block(&mut self, max_txs: usize) -> Result<ExecutionResult, BlockchainError> {
+        let block = self.build_block(max_txs)?;
+        self.append_block(block)
+    }
+
+    /// Will build a block like `produce_block` does and sign it as its producer
+    /// (required by consensus mechanisms like Proof of Stake) before appending it.<br/>
+    /// The mempool is left untouched if the key is not entitled to produce the next block.
+    pub fn produce_signed_block(
+        &mut self,
+        max_txs: usize,
+        keypair: &Keypair,
+    ) -> Result<ExecutionResult, BlockchainError> {
+        if let Some(expected) = self.next_producer() {
+            if expected != keypair.address().to_string() {
+                return Err(BlockchainError::InvalidProducer { expected });
+            }
+        }
+        let mut block = self.build_block(max_txs)?;
+        block.sign(keypair);
+        self.append_block(block)
+    }
+
+    /// Will build and seal a block out of (at most `max_txs`) pending transactions
+    fn build_block(&mut self, max_txs: usize) -> Result<Block, BlockchainError> {
+        let mut block = self.assemble_block(max_txs)?;
+        block.mine(self.next_difficulty());
+        Ok(block)
+    }
+
+    /// Will build a block out of (at most `max_txs`) pending transactions like `build_block`
+    /// does, except for mining it (with `next_difficulty`), which is left to the caller
+    /// (e.g. `ChainHandle`, which mines without locking the chain).
+    pub(crate) fn assemble_block(&mut self, max_txs: usize) -> Result<Block, BlockchainError> {
+        if self.pending_transactions.is_empty() || max_txs == 0 {
+            return Err(BlockchainError::EmptyMempool);
+        }
+        self.fill_block(max_txs, None)
+    }
+
+    /// Will build a block out of (at most `max_txs`) ready pending transactions, followed by
+    /// the coinbase rewarding the producer (if set), except for mining it.<br/>
+    /// The transactions are added through a `BlockBuilder`, so that the ones the block can't
+    /// include (e.g. whose nonces follow a gap, or that their senders can't afford anymore)
+    /// are skipped and keep waiting in the mempool, which only the included ones leave.
+    fn fill_block(
+        &mut self,
+        max_txs: usize,
+        producer: Option<&str>,
+    ) -> Result<Block, BlockchainError> {
+        let ready = self.ready_transactions(now())?;
+        let candidates = self.pending_transactions[..ready].to_vec();
+        let max_txs = self
+            .next_policy()
+            .params
+            .max_transactions_per_block
+            .saturating_sub(usize::from(producer.is_some()))
+            .min(max_txs);
+
+        let mut builder = BlockBuilder::new(self);
+        let mut included = HashSet::new();
+        for transaction in candidates {
+            if included.len() == max_txs {
+                break;
+            }
+            let key = (transaction.from.clone(), transaction.nonce);
+            match builder.add_transaction(transaction) {
+                Ok(_) => {
+                    included.insert(key);
+                }
+                Err(err) => debug!(error = %err, "Pending transaction skipped"),
+            }
+        }
+        if let Some(producer) = producer {
+            let fees = builder
+                .transactions()
+                .iter()
+                .try_fold(0u128, |fees, transaction| fees.checked_add(transaction.fee))
+                .ok_or(BlockchainError::ArithmeticOverflow)?;
+            let amount = self
+                .policy
+                .block_reward
+                .checked_add(fees)
+                .ok_or(BlockchainError::ArithmeticOverflow)?;
+            let coinbase = TransactionData::Coinbase {
+                receiver: producer.to_string(),
+                amount,
+            };
+            builder.add_transaction(
+                Transaction::new(producer.to_string(), coinbase, 0)
+                    .with_chain_id(self.chain_id().into()),
+            )?;
+        } else if included.is_empty() {
+            return Err(BlockchainError::EmptyMempool);
+        }
+        let block = builder.seal();
+
+        self.pending_transactions.retain(|transaction| {
+            !included.contains(&(transaction.from.clone(), transaction.nonce))
+        });
+        debug!(
+            transactions = included.len(),
+            pending = self.pending_transactions.len(),
+            "Block built out of the mempool"
+        );
+        Ok(block)
+    }
+
+    /// Will drop the expired pending transactions (and the ones whose nonces were used by
+    /// now) and move the ones that the next block (with the timestamp) may include to the front
+    /// of the mempool, returning their amount.<br/>
+    /// The transactions that aren't valid yet keep waiting (see `Transaction::with_valid_after`),
+    /// whilst the ready ones are ordered by their fees (see `order_by_fee`), except for the
+    /// genesis block, whose transactions depend on each other.
+    fn ready_transactions(&mut self, timestamp: u64) -> Result<usize, BlockchainError> {
+        self.evict_expired_transactions(timestamp);
+        let accounts = &self.accounts;
+        let pending = self.pending_transactions.len();
+        self.pending_transactions.retain(|transaction| {
+            accounts
+                .get(&transaction.from)
+                .is_none_or(|sender| transaction.nonce >= sender.nonce())
+        });
+        self.metrics
+            .record_eviction("stale", pending - self.pending_transactions.len());
+
+        let height = self.len();
+        let pending = self.pending_transactions.len();
+        let (mut ready, waiting): (Vec<Transaction>, Vec<Transaction>) = self
+            .pending_transactions
+            .drain(..)
+            .filter(|transaction| {
+                !matches!(
+                    transaction.check_validity_window(height, timestamp),
+                    Err(BlockchainError::TransactionExpired(_))
+                )
+            })
+            .partition(|transaction| transaction.check_validity_window(height, timestamp).is_ok());
+        if !self.is_empty() {
+            ready = order_by_fee(ready)?;
+        }
+        let count = ready.len();
+        ready.extend(waiting);
+        self.pending_transactions = ready;
+        self.metrics
+            .record_eviction("expired", pending - self.pending_transactions.len());
+        Ok(count)
+    }
+
+    /// Will make the block ready to be appended: it commits to the world state after
+    /// executing the block (which is reverted again) and is mined with the next difficulty.
+    pub(crate) fn seal_block(&mut self, block: &mut Block) -> Result<(), BlockchainError> {
+        self.prepare_block(block)?;
+        block.mine(self.next_difficulty());
+        Ok(())
+    }
+
+    /// Will seal the block like `seal_block` does, except for mining it.<br/>
+    /// The block is placed at the height following the tip, at the version active there.
+    fn prepare_block(&mut self, block: &mut Block) -> Result<(), BlockchainError> {
+        let is_genesis = self.is_empty();
+        let state_root = self
+            .execute_block(block, is_genesis)
+            .map(|_| self.state_root());
+        self.rollback();
+        block.set_state_root(Some(state_root?));
+
+        block.header.height = self.blocks.len() as u64;
+        block.header.version = self.policy.version_at(self.blocks.len());
+        Ok(())
+    }
+
+    /// Will return the account that is entitled to produce the next block
+    /// (`None` if anyone may produce it, like under Proof of Work)
+    pub fn next_producer(&self) -> Option<String> {
+        self.consensus().next_producer(&self.blocks, self)
+    }
+
+    /// Will return the Proof of Work difficulty the next block has to be mined with
+    pub fn next_difficulty(&self) -> u32 {
+        self.consensus().next_difficulty(&self.blocks)
+    }
+
+    /// Will return the amount of blocks currently stored
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Will return true if no block was stored yet
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Will return the hash of the last block
+    pub fn get_last_block_hash(&self) -> Option<Hash> {
+        if self.is_empty() {
+            return None;
+        }
+
+        self.blocks[self.len() - 1].hash
+    }
+
+    /// Will return an empty block following the last one: pointing to it and at the height
+    /// after it (see `append_block`)
+    pub fn new_block(&self) -> Block {
+        let mut block = Block::new(self.get_last_block_hash());
+        block.header.height = self.blocks.len() as u64;
+        block
+    }
+
+    /// Will return the block at the height (pruned blocks only consist of their headers),
+    /// looked up directly, since blocks are kept in the order of their heights
+    pub fn get_block_by_height(&self, height: usize) -> Option<&Block> {
+        self.blocks.get(height)
+    }
+
+    /// Will return the height of the block with the hash (if it is on the chain)
+    pub fn block_height(&self, hash: &Hash) -> Option<usize> {
+        self.index.block_height(hash)
+    }
+
+    /// Will return where the transaction with the hash is stored (if it is on the chain)
+    pub fn transaction_location(&self, hash: &Hash) -> Option<TransactionLocation> {
+        self.index.transaction_location(hash)
+    }
+
+    /// Will return the receipt of the (included) transaction with the hash
+    pub fn get_receipt(&self, hash: &Hash) -> Option<&TransactionReceipt> {
+        let location = self.transaction_location(hash)?;
+        self.receipts
+            .get(location.block_height)?
+            .get(location.index)
+    }
+
+    /// Will return the block with the hash (if it is on the chain)
+    pub fn get_block_by_hash(&self, hash: &Hash) -> Option<&Block> {
+        self.blocks.get(self.block_height(hash)?)
+    }
+
+    /// Will return the (included) transaction with the hash (if it is on the chain)
+    pub fn get_transaction(&self, hash: &Hash) -> Option<&Transaction> {
+        let location = self.transaction_location(hash)?;
+        self.blocks
+            .get(location.block_height)?
+            .body
+            .transactions
+            .get(location.index)
+    }
+
+    /// Will return where the (included) transaction with the hash is stored, along with the
+    /// proof of its inclusion in the Merkle root of the transactions of its block, which light
+    /// clients verify against the block header (see `HeaderChain::verify_transaction`).<br/>
+    /// There is no proof once the transactions of the block were pruned.
+    pub fn transaction_proof(&self, hash: &Hash) -> Option<(TransactionLocation, MerkleProof)> {
+        let location = self.transaction_location(hash)?;
+        let leaves = self.blocks.get(location.block_height)?.transaction_leaves();
+        let proof = MerkleProof::new(&leaves, location.index)?;
+        Some((location, proof))
+    }
+
+    /// Will return the blocks from the height `from` up to (excluding) the height `to`,
+    /// as far as they exist
+    pub fn blocks_range(&self, from: usize, to: usize) -> &[Block] {
+        let to = to.min(self.blocks.len());
+        &self.blocks[from.min(to)..to]
+    }
+
+    /// Will return a page (of up to `limit` transactions, the first page being 0) of the
+    /// transactions sent by the account, in the order of the chain.<br/>
+    /// The transactions of pruned blocks are not listed.
+    pub fn transactions_by_account(
+        &self,
+        id: &str,
+        page: usize,
+        limit: usize,
+    ) -> Vec<(TransactionLocation, &Transaction)> {
+        self.index
+            .sender_transactions(id)
+            .iter()
+            .skip(page.saturating_mul(limit))
+            .take(limit)
+            .filter_map(|location| self.transaction_at(*location))
+            .collect()
+    }
+
+    /// Will return a page (of up to `limit` entries, the first page being 0) of the history of
+    /// the account: the transactions that touched it, along with how they changed its
+    /// balance, in the order of the chain (e.g. to audit how its balance evolved).<br/>
+    /// The transactions of pruned blocks (and of blocks restored from a snapshot) are not listed.
+    pub fn account_history(&self, id: &str, page: usize, limit: usize) -> Vec<AccountChange> {
+        self.index
+            .account_changes(id)
+            .iter()
+            .skip(page.saturating_mul(limit))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Will return the transactions of the kind, in the order of the chain (e.g. to be
+    /// paginated with `skip` and `take`).<br/>
+    /// Batches are listed as such, not by the kinds of their operations.
+    pub fn transactions_by_type(
+        &self,
+        kind: TransactionKind,
+    ) -> impl Iterator<Item = (TransactionLocation, &Transaction)> + '_ {
+        self.index
+            .kind_transactions(kind)
+            .iter()
+            .filter_map(move |location| self.transaction_at(*location))
+    }
+
+    fn transaction_at(
+        &self,
+        location: TransactionLocation,
+    ) -> Option<(TransactionLocation, &Transaction)> {
+        let transaction = self
+            .blocks
+            .get(location.block_height)?
+            .body
+            .transactions
+            .get(location.index)?;
+        Some((location, transaction))
+    }
+
+    /// Will set the amount of threads that `check_validity` verifies the blocks with
+    /// (their hashes and the signatures of their transactions): 0 for one thread per CPU core
+    /// (the default), 1 for verifying them sequentially.
+    pub fn set_validation_threads(&mut self, threads: usize) {
+        self.validation_threads = threads;
+    }
+
+    /// Will turn the incremental mode of `check_validity` on or off.<br/>
+    /// In incremental mode, the blocks that were verified by a previous (successful) check
+    /// are trusted, so that only the blocks appended since then are checked.
+    pub fn set_incremental_checks(&mut self, enabled: bool) {
+        self.incremental_checks = enabled;
+        self.verified.set(None);
+    }
+
+    /// Checks if the blockchain was tempered with
+    /// It will check until the first error happens and return a description of the problem
+    /// (wrapped into `InvalidBlock`), if everything is fine it will return Ok.<br/>
+    /// If some blocks were pruned, the blocks can't be re-executed: the world state is
+    /// checked against the state root the last block commits to instead.<br/>
+    /// In incremental mode (see `set_incremental_checks`), only the blocks after the last
+    /// verified one are checked (like `check_validity_from` does).
+    pub fn check_validity(&self) -> Result<(), BlockchainError> {
+        let from = match self.verified.get() {
+            // The verified blocks may have been replaced (e.g. by a rewind) in the meantime
+            Some((height, hash))
+                if self.incremental_checks
+                    && self.blocks.get(height - 1).and_then(Block::hash) == Some(hash) =>
+            {
+                height
+            }
+            _ => 0,
+        };
+        self.check_validity_from(from)?;
+
+        let last_hash = self.blocks.last().and_then(Block::hash);
+        self.verified.set(last_hash.map(|hash| (self.len(), hash)));
+        Ok(())
+    }
+
+    /// Will check the blocks starting with `height` like `check_validity` does, each one
+    /// against the blocks before it (e.g. for auditing the latest blocks).<br/>
+    /// The blocks are only re-executed if they are checked from the genesis block on,
+    /// otherwise the world state is checked against the state root that the last block
+    /// commits to (if it does).
+    pub fn check_validity_from(&self, height: usize) -> Result<(), BlockchainError> {
+        let _span = info_span!("check_validity", from = height, height = self.len()).entered();
+        let checked = self.check_blocks_from(height);
+        match &checked {
+            Ok(()) => debug!("Chain is valid"),
+            Err(err) => warn!(error = %err, code = err.code(), "Chain is invalid"),
+        }
+        checked
+    }
+
+    /// Will check the blocks starting with `height`, as part of `check_validity_from`
+    fn check_blocks_from(&self, height: usize) -> Result<(), BlockchainError> {
+        // The blocks are verified on their own (in parallel) first, then against each other
+        let blocks = &self.blocks[height.min(self.len())..];
+        let verified = self.verify_blocks(height, blocks);
+        for (offset, (block, verified)) in blocks.iter().zip(verified).enumerate() {
+            let block_num = height + offset;
+            verified
+                .and_then(|_| self.check_stored_block(block_num, block))
+                .map_err(|err| err.in_block(block_num))?;
+        }
+
+        let is_pruned = self.pruned_height() > 0;
+        if height > 0 || is_pruned {
+            return match self.blocks.last().and_then(Block::state_root) {
+                Some(state_root) if state_root != self.state_root() => {
+                    Err(BlockchainError::StateRootMismatch)
+                }
+                // A pruned chain has to commit to its world state, it can't be replayed
+                None if is_pruned => Err(BlockchainError::StateRootMismatch),
+                _ => Ok(()),
+            };
+        }
+
+        // Re-execute the blocks, which checks their state roots,
+        // and compare the resulting world state with ours
+        let mut replayed = self.empty_replica();
+        for (block_num, block) in self.blocks.iter().enumerate() {
+            replayed
+                .append(block.clone(), true)
+                .map_err(|err| err.in_block(block_num))?;
+        }
+        if replayed.state_root() != self.state_root() {
+            return Err(BlockchainError::StateRootMismatch);
+        }
+        Ok(())
+    }
+
+    /// Will verify what the blocks (starting with `height`) prove on their own, as part of
+    /// `check_validity`: their hashes, their headers and the signatures of their transactions.<br/>
+    /// The blocks are verified in parallel, unless a single thread is configured
+    /// (see `set_validation_threads`).
+    fn verify_blocks(&self, height: usize, blocks: &[Block]) -> Vec<Result<(), BlockchainError>> {
+        let (all_blocks, base_policy, handle) = (&self.blocks, &self.policy, &self.consensus);
+        let verify = |(offset, block): (usize, &Block)| {
+            let block_num = height + offset;
+            let policy = base_policy.at(block_num);
+            let consensus = handle.resolve(&policy);
+            verify_block(&policy, consensus.as_ref(), block, &all_blocks[..block_num])
+        };
+
+        let pool = match self.validation_threads {
+            0 => None,
+            1 => return blocks.iter().enumerate().map(verify).collect(),
+            threads => ThreadPoolBuilder::new().num_threads(threads).build().ok(),
+        };
+        let verify_all = || blocks.par_iter().enumerate().map(verify).collect();
+        match pool {
+            Some(pool) => pool.install(verify_all),
+            None => verify_all(),
+        }
+    }
+
+    /// Will check a block of the chain against the blocks before it, as part of
+    /// `check_validity` (which verifies the block on its own before, see `verify_blocks`)
+    fn check_stored_block(&self, block_num: usize, block: &Block) -> Result<(), BlockchainError> {
+        // Check if the block is not older than the blocks before it
+        self.check_timestamp(block, &self.blocks[..block_num])?;
+
+        // Check previous black hash points to actual previous block
+        if block_num == 0 {
+            // Genesis block should point to nowhere
+            if block.header.prev_hash.is_some() {
+                return Err(BlockchainError::PrevHashMismatch);
+            }
+        } else {
+            // Non genesis blocks should point to previous blocks hash (which is validated before)
+            if block.header.prev_hash.is_none()
+                || block.header.prev_hash != self.blocks[block_num - 1].hash
+            {
+                return Err(BlockchainError::PrevHashMismatch);
+            }
+        }
+        check_height(
+            block,
+            block_num.checked_sub(1).map(|parent| &self.blocks[parent]),
+        )?;
+
+        // Check if the block producer rewarded itself correctly
+        check_coinbase(&self.policy.at(block_num), block)
+    }
+
+    /// Will check if the block is not older than the median time past of the previous blocks.<br/>
+    /// Blocks created within the same millisecond are accepted, hence the median itself is.
+    fn check_timestamp(&self, block: &Block, prev_blocks: &[Block]) -> Result<(), BlockchainError> {
+        match self.policy.median_time_past(prev_blocks) {
+            Some(median_time_past) if block.header.timestamp < median_time_past => {
+                Err(BlockchainError::TimestampTooOld { median_time_past })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Will execute the transactions of the block, recording the changed accounts
+    /// for a rollback if some transactions succeed whilst others don't
+    /// (prevent inconsistent states).<br/>
+    /// The changes are reverted on failure, whilst on success they can be reverted using `rollback`.
+    fn execute_block(
+        &mut self,
+        block: &Block,
+        is_genesis: bool,
+    ) -> Result<ExecutionResult, BlockchainError> {
+        self.journal = Some(Journal::default());
+
+        let mut result = ExecutionResult::default();
+        for (i, transaction) in block.body.transactions.iter().enumerate() {
+            let _span = trace_span!("execute", index = i, tx_hash = %transaction.calculate_hash())
+                .entered();
+
+            // Execute the transaction, noting how it changed the balances of the accounts
+            let executed = transaction
+                .execute(self, &is_genesis)
+                .and_then(|mut receipt| {
+                    if let Some(journal) = self.journal.as_mut() {
+                        receipt.balance_changes = journal.take_balance_changes(&self.accounts);
+                    }
+                    result.add(receipt)
+                });
+            if let Err(err) = executed {
+                debug!(error = %err, code = err.code(), "Transaction failed");
+
+                // Recover state on failure.
+                self.rollback();
+
+                // ... and reject the block
+                return Err(err.in_transaction(i));
+            }
+
+            // The block may not contain unbounded work
+            let block_gas_limit = self.policy.at(self.blocks.len()).block_gas_limit;
+            if result.gas_used > block_gas_limit {
+                self.rollback();
+                return Err(BlockchainError::BlockGasLimitExceeded {
+                    limit: block_gas_limit,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Will check if the transaction is meant for the network of this chain
+    pub(crate) fn check_chain_id(&self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        if transaction.chain_id != self.policy.chain_id {
+            return Err(BlockchainError::ChainIdMismatch {
+                expected: self.policy.chain_id.clone(),
+                found: transaction.chain_id.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Will undo the changes recorded while executing the current block
+    fn rollback(&mut self) {
+        if let Some(journal) = self.journal.take() {
+            journal.rollback(&mut self.accounts);
+        }
+    }
+
+    /// Will check the signature of a transaction against the validation policy
+    pub(crate) fn accepts_transaction_signature(
+        &self,
+        transaction: &Transaction,
+        is_genesis: bool,
+    ) -> bool {
+        self.next_policy()
+            .accepts_transaction(transaction, is_genesis)
+    }
+}
+
+/// Will verify what the block proves on its own (given the blocks before it): its hash,
+/// its header (e.g. its Proof of Work) and the signatures of its transactions.
+fn verify_block(
+    policy: &ValidationPolicy,
+    consensus: &dyn Consensus,
+    block: &Block,
+    prev_blocks: &[Block],
+) -> Result<(), BlockchainError> {
+    // Check if block saved hash matches to calculated hash (unless it can't be calculated)
+    if !block.is_pruned() && !block.verify_own_hash() {
+        return Err(BlockchainError::HashMismatch);
+    }
+
+    // Check if the block proves what the consensus demands (e.g. its Proof of Work)
+    consensus.check_header(block, prev_blocks)?;
+
+    // Check if transactions are signed correctly
+    // Careful! Unless the policy requires signatures, an unsigned message will always be valid!
+    let is_genesis = prev_blocks.is_empty();
+    match policy.first_rejected_transaction(&block.body.transactions, is_genesis) {
+        Some(transaction_num) => {
+            Err(BlockchainError::InvalidSignature.in_transaction(transaction_num))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Will check that the block is at the height following its parent's (0 for the genesis block)
+fn check_height(block: &Block, parent: Option<&Block>) -> Result<(), BlockchainError> {
+    let expected = parent.map_or(0, |parent| parent.height() + 1);
+    if block.height() != expected {
+        return Err(BlockchainError::HeightMismatch {
+            expected,
+            found: block.height(),
+        });
+    }
+    Ok(())
+}
+
+/// The height and the hash of the last block verified by `check_validity`, which (as it
+/// only reads the chain) updates it through a shared reference.<br/>
+/// It is kept behind a Mutex rather than a Cell, so that the chain may be shared between
+/// threads (see `ChainHandle`).
+#[derive(Debug, Default)]
+struct VerifiedTip(Mutex<Option<(usize, Hash)>>);
+
+impl VerifiedTip {
+    fn get(&self) -> Option<(usize, Hash)> {
+        *self.0.lock().unwrap()
+    }
+
+    fn set(&self, tip: Option<(usize, Hash)>) {
+        *self.0.lock().unwrap() = tip;
+    }
+}
+
+impl Clone for VerifiedTip {
+    fn clone(&self) -> Self {
+        VerifiedTip(Mutex::new(self.get()))
+    }
+}
+
+/// Will check that the block contains at most one coinbase, which pays
+/// exactly the block subsidy (of the policy) plus the fees of the other transactions.<br/>
+/// The coinbase of a signed block (e.g. under Proof of Stake or Proof of Authority) has to
+/// pay its producer.
+fn check_coinbase(policy: &ValidationPolicy, block: &Block) -> Result<(), BlockchainError> {
+    let mut coinbases = block.body.transactions.iter().filter(|t| t.is_coinbase());
+    let coinbase = match (coinbases.next(), coinbases.next()) {
+        (None, _) => return Ok(()),
+        (Some(coinbase), None) => coinbase,
+        (Some(_), Some(_)) => return Err(BlockchainError::MultipleCoinbases),
+    };
+
+    let fees = block
+        .body
+        .transactions
+        .iter()
+        .filter(|t| !t.is_coinbase())
+        .try_fold(0u128, |fees, transaction| fees.checked_add(transaction.fee))
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    let expected = policy.block_reward.checked_add(fees);
+
+    match &coinbase.record {
+        TransactionData::Coinbase { amount, receiver }
+            if coinbase.fee == 0 && Some(*amount) == expected =>
+        {
+            match block.producer() {
+                Some(producer) if producer.to_string() != *receiver => {
+                    Err(BlockchainError::CoinbaseReceiverMismatch)
+                }
+                _ => Ok(()),
+            }
+        }
+        _ => Err(BlockchainError::InvalidCoinbase { expected }),
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::borrow::BorrowMut;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use crate::storage::{FileStorage, Storage, WalEntry};
+    use crate::{
+        contract_address, gas, AccountType, Block, Blockchain, BlockchainError, ChainEvent,
+        ChainParams, Checkpoint, Consensus, DifficultyParams, Event, Evidence, GenesisConfig, Hash,
+        Keypair, MempoolPolicy, OrphanPolicy, PolicyUpgrade, Snapshot, TimeLock, Transaction,
+        TransactionData, TransactionKind, ValidationPolicy, WorldState, TOMBSTONE_REGISTRY,
+    };
+
+    /// Creates a genesis block with a funded account for the given id
+    fn genesis_for(id: &str) -> Block {
+        let mut genesis = Block::new(None);
+        genesis.add_transaction(Transaction::new(
+            id.into(),
+            TransactionData::CreateUserAccount(id.into()),
+            0,
+        ));
+        genesis.add_transaction(Transaction::new(
+            id.into(),
+            TransactionData::CreateTokens {
+                receiver: id.into(),
+                amount: 100,
+                asset: None,
+            },
+            0,
+        ));
+        genesis
+    }
+
+    #[test]
+    fn creation() {
+        let bc = Blockchain::new();
+        assert_eq!(0, bc.blocks.len());
+    }
+
+    #[test]
+    fn create_and_alter() {
+        // Create a new Blockchain
+        let mut bc = Blockchain::new();
+
+        // Create an empty block (first block has no prev_block)
+        let mut genesis = Block::new(None);
+
+        let initial_users = vec!["alice", "bob"];
+
+        for user in initial_users {
+            let create_account_txn = Transaction::new(
+                user.into(),
+                TransactionData::CreateUserAccount(user.into()),
+                0,
+            );
+
+            let create_token_txn = Transaction::new(
+                user.into(),
+                TransactionData::CreateTokens {
+                    receiver: user.into(),
+                    amount: 100_000_000,
+                    asset: None,
+                },
+                0,
+            );
+
+            genesis.add_transaction(create_account_txn);
+
+            genesis.add_transaction(create_token_txn);
+        }
+
+        let mut res = bc.append_block(genesis);
+        println!("Genesis block successfully added: {:?}", res);
+        println!("Full blockchain printout");
+        println!("{:#?}", bc);
+
+        // Transfer 1 token from alice to bob
+        let mut block2 = bc.new_block();
+        block2.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 1,
+                asset: None,
+            },
+            0,
+        ));
+
+        res = bc.append_block(block2);
+        println!("Block added: {:?}", res);
+        println!("Full blockchain printout");
+        println!("{:#?}", bc);
+        println!("Blockchain valid: {:?}", bc.check_validity());
+
+        // Everything is fine until here
+
+        // Attack I: changing a transaction
+        // Let's tamper the block chain. Maybe bob was not satisfied with the amount of coins alice sent
+        // him, so he will tamper the blockchains transaction to transmit 100 Coins instead of 1
+
+        // let's clone the current blockchain before tempering
+        let mut bc_attack_1 = bc.clone();
+        // get the transaction as mutable (second block, first transaction; the token transfer)
+        let transaction_data =
+            bc_attack_1.tamper_block(1).unwrap().body.transactions[0].borrow_mut();
+
+        // change the amount value of the transaction INSIDE the chain
+        // (We know that that record is a TransferToken Action so we ignore the rest)
+        if let TransactionData::TransferTokens { amount, .. } = transaction_data.record.borrow_mut()
+        {
+            *amount = 100; // Actually change the value in place
+        }
+
+        println!("Changed transaction: {:?}", transaction_data.record);
+
+        // Will print an error, since the blocks hash changes for the
+        println!(
+            "Is the Blockchain still valid? {:#?}",
+            bc_attack_1.check_validity()
+        );
+
+        // Attack II: Changing transaction + updating the hash (increasing initial tokens in create
+        // user action)
+        let mut bc_attack_2 = bc.clone();
+
+        // Alice tokens
+        let transaction_data =
+            bc_attack_2.tamper_block(0).unwrap().body.transactions[1].borrow_mut();
+
+        // change tokens
+        // (We know that that record is a Token Create Action so we ignore the rest)
+        if let TransactionData::CreateTokens { amount, .. } = transaction_data.record.borrow_mut() {
+            *amount = 100_000_000_000; // Let's dont be small on that
+        }
+
+        // If we execute now, we'll see the same error as above, hashes dont match (this time 1st block)
+
+        // Will print an error, since the blocks hash changes for the
+        println!(
+            "Is the Blockchain still valid? {:#?}",
+            bc_attack_2.check_validity()
+        );
+
+        // But alice was smart, she also updated the first blocks' hash
+        bc_attack_2.tamper_block(0).unwrap().update_hash();
+
+        // So the hash is correct now, however, block2 points now to sth which does not exists
+        // Again, the blockchain is invalid but for a different reason
+        println!(
+            "Is the Blockchain still valid? {:#?}",
             bc_attack_2.check_validity()
         );
+    }
+
+    #[test]
+    fn policy_rejects_unsigned_transactions() {
+        let alice = Keypair::generate();
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            require_signatures: true,
+            ..Default::default()
+        });
+        bc.append_block(genesis_for(&alice.address().to_string()))
+            .unwrap();
+
+        let mut block = bc.new_block();
+        block.add_transaction(Transaction::new(
+            alice.address().to_string(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ));
+        assert!(bc.append_block(block.clone()).is_err());
+
+        block.body.transactions[0].sign(&alice);
+        block.update_hash();
+        assert!(bc.append_block(block).is_ok());
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn policy_may_reject_unsigned_genesis() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            require_signatures: true,
+            allow_unsigned_genesis: false,
+            ..Default::default()
+        });
+        assert!(bc.append_block(genesis_for("alice")).is_err());
+        assert!(bc.is_empty());
+    }
+
+    #[test]
+    fn invalid_signatures_are_always_rejected() {
+        let alice = Keypair::generate();
+        let mallory = Keypair::generate();
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for(&alice.address().to_string()))
+            .unwrap();
+
+        let mut txn = Transaction::new(
+            alice.address().to_string(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        txn.sign(&mallory);
+        let mut block = bc.new_block();
+        block.add_transaction(txn);
+        assert!(bc.append_block(block).is_err());
+    }
+
+    #[test]
+    fn produce_blocks_from_mempool() {
+        let mut bc = Blockchain::new();
+        for txn in genesis_for("alice").body.transactions {
+            bc.submit_transaction(txn).unwrap();
+        }
+        bc.produce_block(10).unwrap();
+        assert_eq!(1, bc.len());
+        assert!(bc.pending_transactions().is_empty());
+
+        // Unknown senders are not accepted into the mempool
+        assert!(bc
+            .submit_transaction(Transaction::new(
+                "mallory".into(),
+                TransactionData::CreateUserAccount("mallory".into()),
+                0,
+            ))
+            .is_err());
+
+        for (nonce, user) in ["bob", "carol", "dave"].iter().enumerate() {
+            bc.submit_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount((*user).into()),
+                nonce as u128,
+            ))
+            .unwrap();
+        }
+        bc.produce_block(2).unwrap();
+        assert_eq!(2, bc.blocks[1].get_transaction_count());
+        assert_eq!(1, bc.pending_transactions().len());
+
+        bc.produce_block(2).unwrap();
+        assert_eq!(3, bc.len());
+        assert!(bc.produce_block(2).is_err());
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn proof_of_work_is_required() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            difficulty: DifficultyParams {
+                initial_difficulty: 6,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let mut genesis = genesis_for("alice");
+        assert!(bc.append_block(genesis.clone()).is_err());
+
+        // Mined with a too low difficulty
+        genesis.mine(2);
+        assert!(bc.append_block(genesis.clone()).is_err());
+
+        genesis.mine(6);
+        assert!(bc.append_block(genesis).is_ok());
+
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+        assert_eq!(6, bc.blocks[1].difficulty());
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn gas_is_charged_and_limited() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            gas_price: 2,
+            // Exactly what the genesis block uses
+            block_gas_limit: 3 * gas::TRANSACTION_GAS + gas::ACCOUNT_GAS,
+            ..Default::default()
+        });
+        let mut genesis = genesis_for("alice");
+        genesis.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateTokens {
+                receiver: "alice".into(),
+                amount: 100_000,
+                asset: None,
+            },
+            0,
+        ));
+        bc.append_block(genesis).unwrap();
+
+        let transfer = |nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::TransferTokens {
+                    to: "alice".into(),
+                    amount: 0,
+                    asset: None,
+                },
+                nonce,
+            )
+        };
+        bc.submit_transaction(transfer(0)).unwrap();
+        bc.submit_transaction(transfer(1)).unwrap();
+        let result = bc.produce_block(2).unwrap();
+        assert_eq!(2 * gas::TRANSACTION_GAS, result.gas_used);
+        assert_eq!(
+            vec![gas::TRANSACTION_GAS, gas::TRANSACTION_GAS],
+            result.transactions_gas_used
+        );
+        // The genesis block is free
+        assert_eq!(
+            100_100 - 2 * 2 * gas::TRANSACTION_GAS as u128,
+            bc.accounts["alice"].tokens
+        );
+
+        // Nine transfers exceed the limit
+        let mut block = bc.new_block();
+        for nonce in 2..11 {
+            block.add_transaction(transfer(nonce));
+        }
+        block.update_hash();
+        assert_eq!(
+            Err(BlockchainError::BlockGasLimitExceeded {
+                limit: 3 * gas::TRANSACTION_GAS + gas::ACCOUNT_GAS
+            }),
+            bc.append_block(block.clone())
+        );
+        assert_eq!(2, bc.len());
+
+        // ... so that produced blocks leave the last one in the mempool
+        for transaction in block.body.transactions {
+            bc.submit_transaction(transaction).unwrap();
+        }
+        assert_eq!(8, bc.produce_block(9).unwrap().receipts.len());
+        assert_eq!(1, bc.pending_transactions().len());
+    }
+
+    #[test]
+    fn state_tampering_is_detected() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            require_state_root: true,
+            ..Default::default()
+        });
+
+        // The genesis block doesn't commit to the state
+        assert_eq!(
+            Err(BlockchainError::StateRootMismatch),
+            bc.append_block(genesis_for("alice"))
+        );
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("alice".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+        assert_eq!(Some(bc.state_root()), bc.blocks[0].state_root());
+
+        // A block committing to another state is rejected
+        let mut block = bc.new_block();
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ));
+        block.set_state_root(Some(bc.state_root()));
+        assert_eq!(
+            Err(BlockchainError::StateRootMismatch),
+            bc.append_block(block)
+        );
+        assert!(bc.check_validity().is_ok());
+
+        // Changing the world state without a block is detected
+        bc.tamper_account("alice").unwrap().tokens = 1_000;
+        assert_eq!(Err(BlockchainError::StateRootMismatch), bc.check_validity());
+    }
+
+    #[test]
+    fn transactions_of_other_networks_are_rejected() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            chain_id: "main".into(),
+            ..Default::default()
+        });
+        let create = |chain_id: &str| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount("alice".into()),
+                0,
+            )
+            .with_chain_id(chain_id.into())
+        };
+        let mismatch = BlockchainError::ChainIdMismatch {
+            expected: "main".into(),
+            found: "dev".into(),
+        };
+
+        // The chain id is part of the (signed) hash
+        assert_ne!(
+            create("main").calculate_hash(),
+            create("dev").calculate_hash()
+        );
+
+        let mut genesis = Block::new(None);
+        genesis.add_transaction(create("dev"));
+        assert_eq!(
+            Err(mismatch.clone().in_transaction(0)),
+            bc.append_block(genesis)
+        );
+        assert_eq!(Err(mismatch), bc.submit_transaction(create("dev")));
+
+        bc.submit_transaction(create("main")).unwrap();
+        bc.produce_block_with_reward(1, "alice".into()).unwrap();
+        assert_eq!(1, bc.len());
+    }
+
+    #[test]
+    fn block_timestamps_are_validated() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            median_time_span: 3,
+            ..Default::default()
+        });
+        let mut genesis = genesis_for("alice");
+        genesis.set_timestamp(1_000);
+        bc.append_block(genesis).unwrap();
+
+        for (i, user) in ["bob", "carol"].iter().enumerate() {
+            let mut block = bc.new_block();
+            block.add_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount((*user).into()),
+                i as u128,
+            ));
+            block.set_timestamp(2_000 + i as u64 * 1_000);
+            bc.append_block(block).unwrap();
+        }
+
+        // The median of 1000, 2000 and 3000 is 2000
+        let mut block = bc.new_block();
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("dave".into()),
+            2,
+        ));
+        block.set_timestamp(1_999);
+        assert_eq!(
+            Err(BlockchainError::TimestampTooOld {
+                median_time_past: 2_000
+            }),
+            bc.append_block(block.clone())
+        );
+
+        // Far in the future
+        block.set_timestamp(Block::new(None).timestamp() + 3 * 60 * 60 * 1000);
+        assert!(matches!(
+            bc.append_block(block.clone()),
+            Err(BlockchainError::TimestampTooFarInFuture { .. })
+        ));
+
+        block.set_timestamp(2_000);
+        assert!(bc.append_block(block).is_ok());
+        assert!(bc.check_validity().is_ok());
+
+        // A tampered (stored) timestamp is detected as well
+        bc.tamper_block(3).unwrap().set_timestamp(0);
+        assert!(bc.check_validity().is_err());
+    }
+
+    #[test]
+    fn blocks_follow_the_height_of_their_parent() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create_bob = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+
+        let mut block = bc.new_block();
+        block.add_transaction(create_bob);
+        assert_eq!(1, block.height());
+        block.set_height(2);
+        assert_eq!(
+            Err(BlockchainError::HeightMismatch {
+                expected: 1,
+                found: 2
+            }),
+            bc.append_block(block.clone()).map(|_| ())
+        );
+        block.set_height(1);
+        bc.append_block(block).unwrap();
+
+        for height in 0..2 {
+            let block = bc.get_block_by_height(height).unwrap();
+            assert_eq!(height, block.height());
+            assert_eq!(bc.get_header(height), Some(block.header()));
+        }
+        assert!(bc.get_block_by_height(2).is_none());
+
+        // A tampered (stored) height is detected as well
+        bc.tamper_block(1).unwrap().set_height(5);
+        assert_eq!(
+            Err(BlockchainError::InvalidBlock {
+                height: 1,
+                source: Box::new(BlockchainError::HeightMismatch {
+                    expected: 1,
+                    found: 5
+                })
+            }),
+            bc.check_validity()
+        );
+    }
+
+    #[test]
+    fn persist_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut bc = Blockchain::open(dir.path()).unwrap();
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+        let last_hash = bc.get_last_block_hash();
+        drop(bc);
+
+        // Without a flushed state, the blocks are re-executed
+        let mut bc = Blockchain::open(dir.path()).unwrap();
+        assert_eq!(2, bc.len());
+        assert_eq!(last_hash, bc.get_last_block_hash());
+        assert_eq!(100, bc.accounts["alice"].tokens);
+        assert!(bc.accounts.contains_key("bob"));
+        bc.flush().unwrap();
+        drop(bc);
+
+        let bc = Blockchain::open(dir.path()).unwrap();
+        assert_eq!(2, bc.len());
+        assert!(bc.accounts.contains_key("bob"));
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn interrupted_block_applications_are_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut bc = Blockchain::open(dir.path()).unwrap();
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.flush().unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ))
+        .unwrap();
+        let receipts = bc.produce_block(1).unwrap().receipts;
+        let block = bc.blocks[1].clone();
+        let mut changes = HashMap::new();
+        changes.insert("bob".to_string(), bc.accounts.get("bob").cloned());
+        drop(bc);
+
+        // A crash after logging the block, but before persisting it
+        let entry = WalEntry {
+            height: 1,
+            block: block.clone(),
+            receipts,
+            changes,
+        };
+        let mut storage = FileStorage::open(dir.path()).unwrap();
+        storage.truncate(1).unwrap();
+        storage.put_wal(&entry).unwrap();
+        drop(storage);
+
+        // ... is completed on startup, along with the stored world state
+        let bc = Blockchain::open(dir.path()).unwrap();
+        assert_eq!(2, bc.len());
+        assert_eq!(block.hash, bc.get_last_block_hash());
+        assert!(bc.accounts.contains_key("bob"));
+        let storage = FileStorage::open(dir.path()).unwrap();
+        assert!(storage.get_wal().unwrap().is_none());
+        let (height, accounts) = storage.get_state().unwrap().unwrap();
+        assert_eq!(2, height);
+        assert!(accounts.contains_key("bob"));
+        drop((bc, storage));
+
+        // A logged block that doesn't follow the stored ones is rolled back
+        let mut storage = FileStorage::open(dir.path()).unwrap();
+        storage.put_wal(&WalEntry { height: 2, ..entry }).unwrap();
+        drop(storage);
+        let bc = Blockchain::open(dir.path()).unwrap();
+        assert_eq!(2, bc.len());
+        assert!(FileStorage::open(dir.path())
+            .unwrap()
+            .get_wal()
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn lookups_by_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut bc = Blockchain::open(dir.path()).unwrap();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let transaction = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        let hash = transaction.calculate_hash();
+        bc.submit_transaction(transaction.clone()).unwrap();
+        assert!(bc.get_transaction(&hash).is_none());
+        bc.produce_block(1).unwrap();
+        assert_eq!(
+            Err(BlockchainError::TransactionAlreadyIncluded(hash)),
+            bc.submit_transaction(transaction)
+        );
+        bc.flush().unwrap();
+        drop(bc);
+
+        // The indexes are rebuilt when the chain is reopened
+        let bc = Blockchain::open(dir.path()).unwrap();
+        let last_hash = bc.get_last_block_hash().unwrap();
+        assert_eq!(Some(1), bc.block_height(&last_hash));
+        assert_eq!(
+            Some(last_hash),
+            bc.get_block_by_hash(&last_hash).and_then(Block::hash)
+        );
+        assert_eq!(
+            Some(hash),
+            bc.get_transaction(&hash).map(Transaction::calculate_hash)
+        );
+        let location = bc.transaction_location(&hash).unwrap();
+        assert_eq!((1, 0), (location.block_height, location.index));
+        assert!(bc.get_block_by_hash(&hash).is_none());
+
+        // ... and so are the receipts
+        let receipt = bc.get_receipt(&hash).unwrap();
+        assert!(receipt.is_success());
+        assert_eq!(
+            vec![Event::AccountCreated { id: "bob".into() }],
+            receipt.events
+        );
+    }
+
+    #[test]
+    fn fees_are_paid_to_the_block_producer() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            block_reward: 50,
+            ..Default::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("miner".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+
+        bc.submit_transaction(
+            Transaction::new(
+                "alice".into(),
+                TransactionData::TransferTokens {
+                    to: "miner".into(),
+                    amount: 10,
+                    asset: None,
+                },
+                1,
+            )
+            .with_fee(5),
+        )
+        .unwrap();
+        bc.produce_block_with_reward(10, "miner".into()).unwrap();
+
+        assert_eq!(85, bc.accounts["alice"].tokens);
+        assert_eq!(10 + 50 + 5, bc.accounts["miner"].tokens);
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn producers_are_rewarded_for_consecutive_blocks() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            block_reward: 50,
+            ..Default::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        for nonce in 0..2 {
+            bc.submit_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(format!("user{}", nonce)),
+                nonce,
+            ))
+            .unwrap();
+            bc.produce_block_with_reward(1, "alice".into()).unwrap();
+        }
+        assert_eq!(3, bc.len());
+        assert_eq!(100 + 2 * 50, bc.accounts["alice"].tokens);
+
+        // The coinbases don't use the nonces of the producer
+        assert_eq!(Some(2), bc.get_nonce("alice"));
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn coinbases_follow_the_selected_transactions() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            block_reward: 50,
+            ..Default::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("miner".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+
+        // More transactions are pending than the block may include
+        for (nonce, fee) in [(1, 3), (2, 2), (3, 1)] {
+            bc.submit_transaction(
+                Transaction::new(
+                    "alice".into(),
+                    TransactionData::TransferTokens {
+                        to: "miner".into(),
+                        amount: 10,
+                        asset: None,
+                    },
+                    nonce,
+                )
+                .with_fee(fee),
+            )
+            .unwrap();
+        }
+        bc.produce_block_with_reward(2, "miner".into()).unwrap();
+
+        let block = bc.blocks().last().unwrap();
+        let nonces: Vec<_> = block.transactions().iter().map(|t| t.nonce).collect();
+        assert_eq!(vec![1, 2, 0], nonces);
+        assert!(matches!(
+            block.transactions()[2].record,
+            TransactionData::Coinbase { amount: 55, .. }
+        ));
+        assert_eq!(20 + 50 + 5, bc.accounts["miner"].tokens);
+
+        // The remaining transaction keeps waiting, without a coinbase
+        assert_eq!(1, bc.pending_transactions().len());
+        assert_eq!(3, bc.pending_transactions()[0].nonce);
+    }
+
+    #[test]
+    fn invalid_coinbases_are_rejected() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            block_reward: 50,
+            ..Default::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+
+        let coinbase = |amount| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::Coinbase {
+                    receiver: "alice".into(),
+                    amount,
+                },
+                0,
+            )
+        };
+
+        // Wrong reward amount
+        let mut block = bc.new_block();
+        block.add_transaction(coinbase(51));
+        assert_eq!(
+            Err(BlockchainError::InvalidCoinbase { expected: Some(50) }),
+            bc.append_block(block)
+        );
+
+        // More than one coinbase
+        let mut block = bc.new_block();
+        block.add_transaction(coinbase(50));
+        block.add_transaction(coinbase(50));
+        assert_eq!(
+            Err(BlockchainError::MultipleCoinbases),
+            bc.append_block(block)
+        );
+
+        // A fee that the sender can't afford
+        let mut block = bc.new_block();
+        block.add_transaction(
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount("bob".into()),
+                0,
+            )
+            .with_fee(1_000),
+        );
+        block.add_transaction(coinbase(1_050));
+        assert_eq!(
+            Err(BlockchainError::InsufficientBalance.in_transaction(0)),
+            bc.append_block(block)
+        );
+
+        let mut block = bc.new_block();
+        block.add_transaction(coinbase(50));
+        assert!(bc.append_block(block).is_ok());
+        assert_eq!(150, bc.accounts["alice"].tokens);
+    }
+
+    #[test]
+    fn submitted_coinbases_are_rejected() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            block_reward: 50,
+            ..Default::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        let coinbase = TransactionData::Coinbase {
+            receiver: "alice".into(),
+            amount: 50,
+        };
+
+        assert_eq!(
+            Err(BlockchainError::SubmittedCoinbase),
+            bc.submit_transaction(Transaction::new("alice".into(), coinbase.clone(), 0))
+        );
+        let batch = TransactionData::Batch(vec![
+            TransactionData::CreateUserAccount("bob".into()),
+            coinbase,
+        ]);
+        assert_eq!(
+            Err(BlockchainError::SubmittedCoinbase),
+            bc.submit_transaction(Transaction::new("alice".into(), batch, 0))
+        );
+        assert!(bc.pending_transactions().is_empty());
+
+        // Only the producer's own coinbase pays the reward
+        bc.produce_block_with_reward(10, "alice".into()).unwrap();
+        assert_eq!(150, bc.accounts["alice"].tokens);
+    }
+
+    #[test]
+    fn failed_block_leaves_state_untouched() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let state_before = bc.accounts.clone();
+
+        // The first transactions succeed, the last one overspends
+        let mut block = bc.new_block();
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ));
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 60,
+                asset: None,
+            },
+            0,
+        ));
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 60,
+                asset: None,
+            },
+            0,
+        ));
+        assert!(bc.append_block(block).is_err());
+
+        assert_eq!(state_before, bc.accounts);
+        assert!(bc.journal.is_none());
+    }
+
+    #[test]
+    fn subscribers_are_notified() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut bc = Blockchain::open(dir.path()).unwrap();
+        let events = bc.subscribe();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let genesis_hash = bc.get_last_block_hash().unwrap();
+        let appended: Vec<_> = events.try_iter().collect();
+        assert_eq!(
+            ChainEvent::BlockAppended {
+                height: 0,
+                hash: genesis_hash
+            },
+            appended[0]
+        );
+        assert_eq!(bc.blocks[0].body.transactions.len() + 1, appended.len());
+        assert!(matches!(
+            appended[1],
+            ChainEvent::TransactionExecuted {
+                block_height: 0,
+                index: 0,
+                ..
+            }
+        ));
+
+        let transaction = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        let hash = transaction.calculate_hash();
+        bc.submit_transaction(transaction).unwrap();
+        bc.produce_block(1).unwrap();
+        let block_hash = bc.get_last_block_hash().unwrap();
+        let executed: Vec<_> = events.try_iter().collect();
+        assert_eq!(2, executed.len());
+        assert!(matches!(
+            &executed[1],
+            ChainEvent::TransactionExecuted { receipt, .. } if receipt.transaction_hash == hash
+        ));
+
+        // Rewinding reverts the removed blocks
+        let removed = bc.rewind(1).unwrap();
+        assert_eq!(1, removed.len());
+        assert_eq!(
+            ChainEvent::Reorg {
+                height: 1,
+                removed: vec![block_hash]
+            },
+            events.try_recv().unwrap()
+        );
+        assert_eq!(Some(genesis_hash), bc.get_last_block_hash());
+        assert!(!bc.accounts.contains_key("bob"));
+        assert!(bc.get_transaction(&hash).is_none());
+        assert!(bc.check_validity().is_ok());
+
+        // ... also in the storage
+        bc.flush().unwrap();
+        drop(bc);
+        let bc = Blockchain::open(dir.path()).unwrap();
+        assert_eq!(1, bc.len());
+        assert!(!bc.accounts.contains_key("bob"));
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+
+        let bytes = bc.snapshot().to_bytes().unwrap();
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(2, snapshot.height());
+        assert_eq!(bc.get_last_block_hash(), snapshot.last_block_hash());
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Box::new(FileStorage::open(dir.path()).unwrap());
+        let mut restored =
+            Blockchain::restore_from_snapshot_with(storage, snapshot, bc.policy().clone()).unwrap();
+        assert_eq!(bc.len(), restored.len());
+        assert_eq!(bc.state_root(), restored.state_root());
+        assert!(restored.blocks.iter().all(Block::is_pruned));
+
+        // The restored chain follows the original one
+        bc.submit_transaction(Transaction::new(
+            "bob".into(),
+            TransactionData::CreateUserAccount("carol".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+        restored
+            .append_block(bc.blocks.last().cloned().unwrap())
+            .unwrap();
+        assert!(restored.check_validity().is_ok());
+        restored.flush().unwrap();
+        drop(restored);
+
+        let reopened = Blockchain::open(dir.path()).unwrap();
+        assert_eq!(bc.get_last_block_hash(), reopened.get_last_block_hash());
+        assert_eq!(bc.state_root(), reopened.state_root());
+    }
+
+    #[test]
+    fn tampered_snapshots_are_rejected() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+
+        let mut snapshot = bc.snapshot();
+        snapshot.accounts.get_mut("alice").unwrap().tokens = 1_000;
+        assert_eq!(
+            Err(BlockchainError::StateRootMismatch),
+            Blockchain::restore_from_snapshot(snapshot, bc.policy().clone()).map(|_| ())
+        );
+
+        let mut snapshot = bc.snapshot();
+        snapshot.headers.swap(0, 1);
+        assert!(Blockchain::restore_from_snapshot(snapshot, bc.policy().clone()).is_err());
+
+        let policy = ValidationPolicy {
+            chain_id: "other".into(),
+            ..bc.policy().clone()
+        };
+        assert!(matches!(
+            Blockchain::restore_from_snapshot(bc.snapshot(), policy),
+            Err(BlockchainError::ChainIdMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn pruning_keeps_the_latest_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut bc = Blockchain::open(dir.path()).unwrap();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let mut hashes = Vec::new();
+        for (nonce, id) in ["bob", "carol", "dave"].iter().enumerate() {
+            let transaction = Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount((*id).into()),
+                nonce as u128,
+            );
+            hashes.push(transaction.calculate_hash());
+            bc.submit_transaction(transaction).unwrap();
+            bc.produce_block(1).unwrap();
+        }
+        let state_root = bc.state_root();
+
+        bc.set_pruning(Some(2)).unwrap();
+        assert_eq!(Some(2), bc.pruning());
+        assert_eq!(2, bc.pruned_height());
+        assert_eq!(
+            Err(BlockchainError::BlockPruned { height: 1 }),
+            bc.get_block(1).map(|_| ())
+        );
+        assert!(bc.blocks()[1].is_pruned());
+        assert!(bc.get_block(2).unwrap().is_some());
+        assert!(bc.get_block(4).unwrap().is_none());
+        assert!(bc.get_transaction(&hashes[0]).is_none());
+        assert!(bc.get_transaction(&hashes[1]).is_some());
+        assert_eq!(state_root, bc.state_root());
+        assert!(bc.check_validity().is_ok());
+        assert_eq!(
+            Err(BlockchainError::BlockPruned { height: 0 }),
+            bc.rewind(1).map(|_| ())
+        );
+
+        // Appending prunes the next block
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("erin".into()),
+            3,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+        assert_eq!(3, bc.pruned_height());
+        drop(bc);
+
+        let bc = Blockchain::open(dir.path()).unwrap();
+        assert_eq!(5, bc.len());
+        assert_eq!(3, bc.pruned_height());
+        assert!(bc.accounts.contains_key("erin"));
+        assert!(bc.check_validity().is_ok());
+    }
+
+    /// Creates a Proof of Stake chain whose validators stake 60 and 40 tokens
+    fn proof_of_stake_chain(addresses: &[String]) -> Blockchain {
+        let config = GenesisConfig::from_toml(&format!(
+            r#"
+            chain_id = "pos"
+            [[accounts]]
+            id = "{}"
+            balance = 100
+            stake = 60
+            [[accounts]]
+            id = "{}"
+            balance = 100
+            stake = 40
+            [consensus.engine.proof_of_stake]
+            min_stake = 10
+            "#,
+            addresses[0], addresses[1]
+        ))
+        .unwrap();
+        Blockchain::from_genesis(&config).unwrap()
+    }
+
+    #[test]
+    fn proof_of_stake_producers() {
+        let keypairs = [Keypair::generate(), Keypair::generate()];
+        let addresses: Vec<String> = keypairs.iter().map(|k| k.address().to_string()).collect();
+        let mut bc = proof_of_stake_chain(&addresses);
+        assert_eq!(60, bc.accounts[&addresses[0]].stake());
+        assert_eq!(60, bc.accounts[&addresses[1]].tokens);
+
+        let create_account = |id: &str, nonce| {
+            Transaction::new(
+                addresses[0].clone(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+            .with_chain_id("pos".into())
+        };
+        for (nonce, id) in ["bob", "carol", "dave"].iter().enumerate() {
+            let producer = bc.next_producer().unwrap();
+            let (chosen, other) = if producer == addresses[0] {
+                (&keypairs[0], &keypairs[1])
+            } else {
+                (&keypairs[1], &keypairs[0])
+            };
+
+            // Neither unsigned blocks nor the ones of other validators are accepted
+            bc.submit_transaction(create_account(id, nonce as u128))
+                .unwrap();
+            assert_eq!(
+                Err(BlockchainError::InvalidBlockSignature),
+                bc.produce_block(1).map(|_| ())
+            );
+            let mut block = bc.new_block();
+            block.add_transaction(create_account(id, nonce as u128));
+            bc.seal_block(&mut block).unwrap();
+            block.sign(other);
+            assert_eq!(
+                Err(BlockchainError::InvalidProducer {
+                    expected: producer.clone()
+                }),
+                bc.append_block(block).map(|_| ())
+            );
+
+            // Other validators don't even take the transactions out of the mempool
+            bc.submit_transaction(create_account(id, nonce as u128))
+                .unwrap();
+            assert!(bc.produce_signed_block(1, other).is_err());
+            bc.produce_signed_block(1, chosen).unwrap();
+            assert_eq!(
+                Some(producer),
+                bc.blocks.last().unwrap().producer().map(|a| a.to_string())
+            );
+        }
+        assert_eq!(4, bc.len());
+        assert!(bc.check_validity().is_ok());
+
+        // Validators below the minimum stake are not chosen anymore
+        for (keypair, (amount, nonce)) in keypairs.iter().zip([(55, 3), (40, 0)]) {
+            let mut unstake = Transaction::new(
+                keypair.address().to_string(),
+                TransactionData::Unstake { amount },
+                nonce,
+            )
+            .with_chain_id("pos".into());
+            unstake.sign(keypair);
+            bc.submit_transaction(unstake).unwrap();
+        }
+        let producer = bc.next_producer().unwrap();
+        let keypair = keypairs
+            .iter()
+            .find(|k| k.address().to_string() == producer)
+            .unwrap();
+        bc.produce_signed_block(2, keypair).unwrap();
+        assert_eq!(None, bc.next_producer());
+        assert_eq!(95, bc.accounts[&addresses[0]].tokens());
+    }
+
+    #[test]
+    fn custom_consensus_replaces_the_engine() {
+        /// Accepts no block but the genesis block
+        struct Closed;
+
+        impl Consensus for Closed {
+            fn check_header(
+                &self,
+                _block: &Block,
+                prev_blocks: &[Block],
+            ) -> Result<(), BlockchainError> {
+                match prev_blocks.len() {
+                    0 => Ok(()),
+                    _ => Err(BlockchainError::InvalidProducer {
+                        expected: "nobody".into(),
+                    }),
+                }
+            }
+        }
+
+        let mut bc = Blockchain::with_consensus(ValidationPolicy::default(), Arc::new(Closed));
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ))
+        .unwrap();
+        assert!(matches!(
+            bc.clone().produce_block(1),
+            Err(BlockchainError::InvalidProducer { .. })
+        ));
+        assert!(bc.check_validity().is_ok());
+
+        // The engine of the policy is used otherwise
+        let mut bc = Blockchain::with_policy(bc.policy().clone());
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ))
+        .unwrap();
+        assert!(bc.produce_block(1).is_ok());
+    }
+
+    #[test]
+    fn misbehaving_validators_are_slashed() {
+        let keypairs = [Keypair::generate(), Keypair::generate()];
+        let addresses: Vec<String> = keypairs.iter().map(|k| k.address().to_string()).collect();
+        let mut bc = proof_of_stake_chain(&addresses);
+        let transaction = |from: &str, data: TransactionData| {
+            Transaction::new(from.into(), data, 0).with_chain_id("pos".into())
+        };
+
+        // The chosen validator signs two blocks for the same height
+        let producer = bc.next_producer().unwrap();
+        let offender = keypairs
+            .iter()
+            .find(|keypair| keypair.address().to_string() == producer)
+            .unwrap();
+        let reporter = addresses.iter().find(|id| **id != producer).unwrap();
+        let conflicting: Vec<Block> = ["bob", "carol"]
+            .iter()
+            .map(|id| {
+                let mut fork = bc.clone();
+                let data = TransactionData::CreateUserAccount(id.to_string());
+                fork.submit_transaction(transaction(&producer, data))
+                    .unwrap();
+                fork.produce_signed_block(1, offender).unwrap();
+                fork.blocks.pop().unwrap()
+            })
+            .collect();
+        bc.append_block(conflicting[0].clone()).unwrap();
+
+        let evidence = Evidence::new(conflicting[0].clone(), conflicting[1].clone());
+        let report = TransactionData::ReportMisbehavior {
+            evidence: Box::new(evidence),
+        };
+        bc.submit_transaction(transaction(reporter, report.clone()))
+            .unwrap();
+        let next = bc.next_producer().unwrap();
+        let keypair = keypairs
+            .iter()
+            .find(|keypair| keypair.address().to_string() == next)
+            .unwrap();
+        let result = bc.produce_signed_block(1, keypair).unwrap();
+        let stake = if producer == addresses[0] { 60 } else { 40 };
+        assert_eq!(
+            vec![Event::Slashed {
+                validator: producer.clone(),
+                amount: stake
+            }],
+            result.receipts[0].events
+        );
+        assert_eq!(0, bc.accounts[&producer].stake());
+        assert_eq!(Some(reporter.clone()), bc.next_producer());
 
-        // But alice was smart, she also updated the first blocks' hash
-        bc_attack_2.blocks[0].update_hash();
+        // The stake can't be slashed twice
+        let retry = Transaction::new(reporter.clone(), report, 1).with_chain_id("pos".into());
+        assert_eq!(
+            Err(BlockchainError::InsufficientStake),
+            bc.simulate_transaction(&retry).map(|_| ())
+        );
+        bc.submit_transaction(retry).unwrap();
+        let reporter_keypair = keypairs
+            .iter()
+            .find(|keypair| keypair.address().to_string() == *reporter)
+            .unwrap();
+        assert_eq!(
+            Err(BlockchainError::EmptyMempool),
+            bc.produce_signed_block(1, reporter_keypair).map(|_| ())
+        );
+    }
 
-        // So the hash is correct now, however, block2 points now to sth which does not exists
-        // Again, the blockchain is invalid but for a different reason
-        println!(
-            "Is the Blockchain still valid? {:#?}",
-            bc_attack_2.check_validity()
+    #[test]
+    fn incremental_validity_checks() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let transfer = |nonce: u128| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::TransferTokens {
+                    to: "alice".into(),
+                    amount: 1,
+                    asset: None,
+                },
+                nonce,
+            )
+        };
+        for nonce in 0..3 {
+            bc.submit_transaction(transfer(nonce)).unwrap();
+            bc.produce_block(1).unwrap();
+        }
+        bc.set_incremental_checks(true);
+        assert!(bc.check_validity().is_ok());
+
+        // The verified blocks are trusted from now on, the new ones are checked
+        bc.tamper_block(1).unwrap().header.nonce += 1;
+        assert!(bc.check_validity().is_ok());
+        bc.submit_transaction(transfer(3)).unwrap();
+        bc.produce_block(1).unwrap();
+        bc.tamper_block(4).unwrap().header.nonce += 1;
+        assert!(matches!(
+            bc.check_validity(),
+            Err(BlockchainError::InvalidBlock { height: 4, .. })
+        ));
+        assert!(matches!(
+            bc.check_validity_from(0),
+            Err(BlockchainError::InvalidBlock { height: 1, .. })
+        ));
+
+        // Without the incremental mode, every block is checked again
+        bc.tamper_block(4).unwrap().header.nonce -= 1;
+        assert!(bc.check_validity_from(2).is_ok());
+        assert!(bc.check_validity().is_ok());
+        bc.set_incremental_checks(false);
+        assert!(matches!(
+            bc.check_validity(),
+            Err(BlockchainError::InvalidBlock { height: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn parallel_validation_finds_the_first_invalid_block() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        for nonce in 0..6 {
+            bc.submit_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(format!("user{}", nonce)),
+                nonce,
+            ))
+            .unwrap();
+            bc.produce_block(1).unwrap();
+        }
+
+        for threads in [0, 1, 3] {
+            bc.set_validation_threads(threads);
+            assert!(bc.check_validity().is_ok());
+
+            let mut tampered = bc.clone();
+            tampered.tamper_block(5).unwrap().header.nonce += 1;
+            tampered.tamper_block(2).unwrap().header.timestamp += 1;
+            let err = tampered.check_validity().unwrap_err();
+            assert!(matches!(
+                err,
+                BlockchainError::InvalidBlock { height: 2, .. }
+            ));
+            assert_eq!("hash_mismatch", err.code());
+        }
+    }
+
+    #[test]
+    fn blocks_stay_within_the_chain_params() {
+        let params = ChainParams {
+            max_transactions_per_block: 2,
+            max_store_value_length: 8,
+            ..ChainParams::default()
+        };
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            params: params.clone(),
+            ..ValidationPolicy::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create_account = |id: &str, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+        };
+
+        // Produced blocks leave the transactions that exceed the limits in the mempool
+        for (nonce, id) in ["bob", "carol", "dave"].iter().enumerate() {
+            bc.submit_transaction(create_account(id, nonce as u128))
+                .unwrap();
+        }
+        assert_eq!(2, bc.produce_block(10).unwrap().receipts.len());
+        assert_eq!(1, bc.pending_transactions().len());
+
+        let store = |value: &str| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::ChangeStoreValue {
+                    key: "key".into(),
+                    value: value.into(),
+                },
+                3,
+            )
+        };
+        assert_eq!(
+            Err(BlockchainError::StoreValueTooLong { max: 8 }),
+            bc.submit_transaction(store("123456789"))
+        );
+        bc.submit_transaction(store("12345678")).unwrap();
+
+        // Blocks exceeding the limits are neither built nor accepted
+        let mut block = bc.new_block();
+        block
+            .try_add_transaction(create_account("erin", 4), &params)
+            .unwrap();
+        block
+            .try_add_transaction(create_account("fred", 5), &params)
+            .unwrap();
+        assert_eq!(
+            Err(BlockchainError::TooManyTransactions { max: 2 }),
+            block.try_add_transaction(create_account("gina", 6), &params)
+        );
+        block.add_transaction(create_account("gina", 6));
+        assert_eq!(
+            Err(BlockchainError::TooManyTransactions { max: 2 }),
+            bc.append_block(block).map(|_| ())
+        );
+
+        let mut block = bc.new_block();
+        block.add_transaction(create_account("erin", 4));
+        let params = ChainParams {
+            max_block_size: block.sealed_size().unwrap(),
+            ..params
+        };
+        assert_eq!(
+            Err(BlockchainError::BlockTooLarge {
+                max: params.max_block_size
+            }),
+            block.try_add_transaction(create_account("fred", 5), &params)
+        );
+        assert_eq!(1, block.get_transaction_count());
+    }
+
+    #[test]
+    fn admins_freeze_accounts() {
+        let config = GenesisConfig::from_toml(
+            r#"
+            chain_id = "permissioned"
+            [[accounts]]
+            id = "admin"
+            admin = true
+            [[accounts]]
+            id = "alice"
+            balance = 100
+            "#,
+        )
+        .unwrap();
+        let mut bc = Blockchain::from_genesis(&config).unwrap();
+        assert_eq!(&AccountType::Admin, bc.accounts["admin"].account_type());
+        let transaction = |from: &str, data, nonce| {
+            Transaction::new(from.into(), data, nonce).with_chain_id("permissioned".into())
+        };
+        let transfer = || TransactionData::TransferTokens {
+            to: "admin".into(),
+            amount: 10,
+            asset: None,
+        };
+        let freeze = |frozen| match frozen {
+            true => TransactionData::FreezeAccount {
+                account: "alice".into(),
+            },
+            false => TransactionData::UnfreezeAccount {
+                account: "alice".into(),
+            },
+        };
+
+        // Only admins may freeze accounts, and only the genesis block may create them
+        assert_eq!(
+            Err(BlockchainError::NotAnAdmin("alice".into())),
+            bc.simulate_transaction(&transaction("alice", freeze(true), 0))
+                .map(|_| ())
+        );
+        let create_admin = TransactionData::CreateAdminAccount("mallory".into());
+        assert!(bc
+            .simulate_transaction(&transaction("alice", create_admin, 0))
+            .is_err());
+
+        bc.submit_transaction(transaction("admin", freeze(true), 0))
+            .unwrap();
+        let result = bc.produce_block(1).unwrap();
+        assert_eq!(
+            vec![Event::AccountFrozen { id: "alice".into() }],
+            result.receipts[0].events
+        );
+        assert!(bc.accounts["alice"].is_frozen());
+
+        // Frozen accounts can't transfer their tokens (which keep waiting), until they are
+        // unfrozen
+        let frozen_transfer = transaction("alice", transfer(), 0);
+        assert_eq!(
+            Err(BlockchainError::AccountFrozen("alice".into())),
+            bc.simulate_transaction(&frozen_transfer).map(|_| ())
+        );
+        bc.submit_transaction(frozen_transfer).unwrap();
+        assert_eq!(
+            Err(BlockchainError::EmptyMempool),
+            bc.produce_block(1).map(|_| ())
+        );
+        assert_eq!(1, bc.pending_transactions().len());
+        bc.submit_transaction(transaction("admin", freeze(false), 1))
+            .unwrap();
+        bc.produce_block(1).unwrap();
+        bc.produce_block(1).unwrap();
+        assert_eq!(90, bc.accounts["alice"].tokens());
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn batches_are_executed_atomically() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create_and_fund = |id: &str, amount| {
+            TransactionData::Batch(vec![
+                TransactionData::CreateUserAccount(id.into()),
+                TransactionData::TransferTokens {
+                    to: id.into(),
+                    amount,
+                    asset: None,
+                },
+            ])
+        };
+
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            create_and_fund("bob", 10),
+            0,
+        ))
+        .unwrap();
+        let result = bc.produce_block(1).unwrap();
+        assert_eq!(2, result.receipts[0].events.len());
+        assert_eq!(10, bc.accounts["bob"].tokens());
+
+        // A batch the sender can't afford doesn't even get into the mempool
+        assert_eq!(
+            Err(BlockchainError::PendingBalanceExceeded {
+                balance: 90,
+                required: 1_000
+            }),
+            bc.submit_transaction(Transaction::new(
+                "alice".into(),
+                create_and_fund("carol", 1_000),
+                1,
+            ))
+        );
+
+        // If an operation fails, none of them is applied
+        let mut failing = create_and_fund("carol", 10);
+        if let TransactionData::Batch(operations) = &mut failing {
+            operations.push(TransactionData::CreateUserAccount("bob".into()));
+        }
+        let failing = Transaction::new("alice".into(), failing, 1);
+        assert_eq!(
+            Err(BlockchainError::AccountExists("bob".into())),
+            bc.simulate_transaction(&failing).map(|_| ())
+        );
+        bc.submit_transaction(failing).unwrap();
+        assert_eq!(
+            Err(BlockchainError::EmptyMempool),
+            bc.produce_block(1).map(|_| ())
+        );
+        assert!(!bc.accounts.contains_key("carol"));
+        assert_eq!(90, bc.accounts["alice"].tokens());
+
+        let nested = TransactionData::Batch(vec![create_and_fund("carol", 1)]);
+        let nested = Transaction::new("alice".into(), nested, 1);
+        assert_eq!(
+            "invalid_batch",
+            bc.simulate_transaction(&nested).unwrap_err().code()
+        );
+    }
+
+    #[test]
+    fn batches_create_contracts_and_multisig_accounts() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let code =
+            wat::parse_str(r#"(module (memory (export "memory") 1) (func (export "call")))"#)
+                .unwrap();
+        let deploy = TransactionData::DeployContract { code };
+        let create_multisig = TransactionData::CreateMultisigAccount {
+            owners: vec![Keypair::generate().address().to_string()],
+            threshold: 1,
+        };
+
+        let batch = Transaction::new(
+            "alice".into(),
+            TransactionData::Batch(vec![deploy.clone(), create_multisig]),
+            0,
+        );
+        bc.submit_transaction(batch.clone()).unwrap();
+        bc.produce_block(1).unwrap();
+        let contract = batch.contract_address().unwrap();
+        assert!(bc.accounts[&contract].contract_code().is_some());
+        assert!(bc.accounts.contains_key(&batch.multisig_address().unwrap()));
+
+        // The contracts of a batch get addresses of their own (see `contract_address`)
+        let twice = TransactionData::Batch(vec![deploy.clone(), deploy]);
+        let twice = Transaction::new("alice".into(), twice, 1);
+        bc.submit_transaction(twice.clone()).unwrap();
+        bc.produce_block(1).unwrap();
+        let contracts = twice.contract_addresses();
+        assert_eq!(
+            vec![
+                contract_address("alice", 1, 0),
+                contract_address("alice", 1, 1)
+            ],
+            contracts
+        );
+        assert_ne!(contracts[0], contracts[1]);
+        for contract in &contracts {
+            assert!(bc.accounts[contract].contract_code().is_some());
+        }
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn time_locked_transactions_wait_for_their_window() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create = |id: &str, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+        };
+        let create_bob = create("bob", 1).with_valid_after(TimeLock::Height(2));
+
+        // Blocks may not include it before its window opens, so it keeps waiting in the mempool
+        let mut block = bc.new_block();
+        block.add_transaction(create_bob.clone());
+        block.update_hash();
+        assert_eq!(
+            "transaction_not_yet_valid",
+            bc.append_block(block).unwrap_err().code()
+        );
+        bc.submit_transaction(create_bob).unwrap();
+        assert!(bc.produce_block(1).is_err());
+        bc.submit_transaction(create("carol", 0)).unwrap();
+        bc.produce_block(2).unwrap();
+        assert!(bc.accounts.contains_key("carol"));
+        assert_eq!(1, bc.pending_transactions().len());
+
+        bc.produce_block(1).unwrap();
+        assert!(bc.accounts.contains_key("bob"));
+
+        // Expired transactions are rejected right away
+        let expired = create("dave", 2).with_valid_before(TimeLock::Height(3));
+        assert_eq!(
+            "transaction_expired",
+            bc.submit_transaction(expired).unwrap_err().code()
+        );
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn mempool_evicts_and_replaces_transactions() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        bc.set_mempool_policy(MempoolPolicy {
+            max_transactions: 2,
+            ..Default::default()
+        });
+        let create = |id: &str, nonce, fee| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+            .with_fee(fee)
+        };
+
+        // Replace-by-fee: the same sender and nonce only get in again paying a higher fee
+        bc.submit_transaction(create("bob", 0, 1)).unwrap();
+        assert_eq!(
+            Err(BlockchainError::ReplacementFeeTooLow { fee: 1 }),
+            bc.submit_transaction(create("carol", 0, 1))
+        );
+        bc.submit_transaction(create("carol", 0, 2)).unwrap();
+        assert_eq!(1, bc.pending_transactions().len());
+
+        // A full mempool evicts its lowest-fee transaction for one paying more
+        bc.submit_transaction(create("dave", 1, 0)).unwrap();
+        assert_eq!(
+            Err(BlockchainError::MempoolFull),
+            bc.submit_transaction(create("erin", 2, 0))
+        );
+        bc.submit_transaction(create("erin", 2, 5)).unwrap();
+        let fees: Vec<u128> = bc.pending_transactions().iter().map(|t| t.fee()).collect();
+        assert_eq!(vec![2, 5], fees);
+
+        // Transactions waiting longer than the TTL expire
+        bc.set_mempool_policy(MempoolPolicy {
+            ttl: 0,
+            ..Default::default()
+        });
+        bc.submit_transaction(create("frank", 3, 0)).unwrap();
+        assert_eq!(1, bc.pending_transactions().len());
+
+        let evicted = &bc.metrics().transactions_evicted;
+        assert_eq!(Some(&1), evicted.get("replaced"));
+        assert_eq!(Some(&1), evicted.get("evicted"));
+        assert_eq!(Some(&2), evicted.get("expired"));
+    }
+
+    #[test]
+    fn upgrades_apply_from_their_heights() {
+        let params = ChainParams {
+            max_transactions_per_block: 1,
+            ..ChainParams::default()
+        };
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            upgrades: vec![PolicyUpgrade {
+                height: 2,
+                version: 1,
+                params: Some(params),
+                ..PolicyUpgrade::default()
+            }],
+            ..ValidationPolicy::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        for (nonce, id) in ["bob", "carol", "dave", "eve"].iter().enumerate() {
+            bc.submit_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.to_string()),
+                nonce as u128,
+            ))
+            .unwrap();
+        }
+
+        // Before the upgrade, blocks include several transactions, afterwards just one
+        assert_eq!(0, bc.policy().version_at(1));
+        assert_eq!(2, bc.produce_block(2).unwrap().receipts.len());
+        assert_eq!(1, bc.policy().version_at(2));
+        assert_eq!(1, bc.policy_at(2).params.max_transactions_per_block);
+        assert_eq!(1, bc.produce_block(2).unwrap().receipts.len());
+        assert_eq!(1, bc.pending_transactions().len());
+
+        // The blocks before the upgrade stay valid
+        assert_eq!(Ok(()), bc.check_validity());
+    }
+
+    #[test]
+    fn blocks_and_transactions_follow_the_versions() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            upgrades: vec![PolicyUpgrade {
+                height: 2,
+                version: 1,
+                ..PolicyUpgrade::default()
+            }],
+            ..ValidationPolicy::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create_account = |id: &str, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+            .with_version(1)
+        };
+
+        // Transactions of a version that isn't active yet are rejected
+        assert_eq!(
+            Err(BlockchainError::UnsupportedTransactionVersion { max: 0, found: 1 }),
+            bc.submit_transaction(create_account("bob", 0))
+        );
+        bc.submit_transaction(create_account("bob", 0).with_version(0))
+            .unwrap();
+        bc.produce_block(1).unwrap();
+        assert_eq!(0, bc.blocks.last().unwrap().version());
+
+        // From the upgrade on, the blocks have its version
+        bc.submit_transaction(create_account("carol", 1)).unwrap();
+        bc.produce_block(1).unwrap();
+        assert_eq!(1, bc.blocks.last().unwrap().version());
+
+        let mut block = bc.new_block();
+        block.add_transaction(create_account("dave", 2));
+        bc.seal_block(&mut block).unwrap();
+        block.set_version(0);
+        block.mine(bc.next_difficulty());
+        let err = bc.append_block(block).unwrap_err();
+        assert_eq!(
+            BlockchainError::UnexpectedBlockVersion {
+                expected: 1,
+                found: 0
+            },
+            err
+        );
+        assert_eq!("unexpected_block_version", err.code());
+
+        // The blocks before the upgrade stay valid
+        assert_eq!(Ok(()), bc.check_validity());
+    }
+
+    #[test]
+    fn names_expire_unless_renewed() {
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            name_registration_period: 2,
+            ..ValidationPolicy::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        let (carol, dave) = (
+            Keypair::generate().address().to_string(),
+            Keypair::generate().address().to_string(),
+        );
+        for (nonce, id) in [&carol, &dave].iter().enumerate() {
+            bc.submit_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.to_string()),
+                nonce as u128,
+            ))
+            .unwrap();
+        }
+        bc.produce_block(2).unwrap();
+        let register = |from: &str, nonce| {
+            Transaction::new(
+                from.into(),
+                TransactionData::RegisterName {
+                    name: "carol".into(),
+                },
+                nonce,
+            )
+        };
+        let next_block = |bc: &mut Blockchain, transaction| {
+            bc.submit_transaction(transaction)?;
+            bc.produce_block(1)
+        };
+
+        // Registered at the height 2 until the height 4, then renewed until the height 6
+        next_block(&mut bc, register(&carol, 0)).unwrap();
+        assert_eq!(carol, bc.resolve_account_id("carol"));
+        assert_eq!(
+            "name_taken",
+            bc.simulate_transaction(&register(&dave, 0))
+                .unwrap_err()
+                .code()
+        );
+        let result = next_block(&mut bc, register(&carol, 1)).unwrap();
+        assert!(matches!(
+            &result.receipts[0].events[0],
+            Event::NameRegistered { expires_at: 6, .. }
+        ));
+
+        // Once expired, anyone may register the name
+        for nonce in 2..4 {
+            let store = TransactionData::ChangeStoreValue {
+                key: "key".into(),
+                value: nonce.to_string(),
+            };
+            next_block(&mut bc, Transaction::new("alice".into(), store, nonce)).unwrap();
+        }
+        assert_eq!(6, bc.len());
+        assert_eq!(None, bc.resolve_name("carol"));
+        assert_eq!(6, bc.get_name("carol").unwrap().expires_at);
+        next_block(&mut bc, register(&dave, 0)).unwrap();
+        assert_eq!(dave, bc.resolve_account_id("carol"));
+    }
+
+    #[test]
+    fn faucets_only_fund_development_networks() {
+        let faucet = |amount| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::Faucet {
+                    to: "carol".into(),
+                    amount,
+                },
+                0,
+            )
+        };
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        assert_eq!(
+            Err(BlockchainError::FaucetDisabled),
+            bc.submit_transaction(faucet(10))
+        );
+
+        let mut bc = Blockchain::with_policy(ValidationPolicy {
+            params: ChainParams {
+                dev_mode: true,
+                max_faucet_amount: 100,
+                ..ChainParams::default()
+            },
+            ..ValidationPolicy::default()
+        });
+        bc.append_block(genesis_for("alice")).unwrap();
+        assert_eq!(
+            Err(BlockchainError::FaucetLimitExceeded { max: 100 }),
+            bc.submit_transaction(faucet(101))
+        );
+
+        // The receiver's account is created on the fly
+        bc.submit_transaction(faucet(100)).unwrap();
+        let result = bc.produce_block(1).unwrap();
+        assert_eq!(Some(100), bc.get_balance("carol"));
+        assert!(matches!(
+            &result.receipts[0].events[..],
+            [
+                Event::AccountCreated { .. },
+                Event::TokensCreated { amount: 100, .. }
+            ]
+        ));
+        assert_eq!(Ok(()), bc.check_validity());
+    }
+
+    #[test]
+    fn account_histories_list_the_balance_changes() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        let transfer = Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 30,
+                asset: None,
+            },
+            1,
+        )
+        .with_fee(2);
+        bc.submit_transaction(create.clone()).unwrap();
+        bc.submit_transaction(transfer.clone()).unwrap();
+        bc.produce_block(2).unwrap();
+
+        let deltas = |id: &str, page, limit| -> Vec<(usize, i128)> {
+            bc.account_history(id, page, limit)
+                .iter()
+                .map(|change| (change.block_height, change.delta))
+                .collect()
+        };
+        assert_eq!(
+            vec![(0, 0), (0, 100), (1, 0), (1, -32)],
+            deltas("alice", 0, 10)
+        );
+        assert_eq!(vec![(1, 0), (1, -32)], deltas("alice", 1, 2));
+        assert_eq!(vec![(1, 0), (1, 30)], deltas("bob", 0, 10));
+        assert!(deltas("carol", 0, 10).is_empty());
+
+        let history = bc.account_history("bob", 0, 10);
+        assert_eq!(create.calculate_hash(), history[0].transaction_hash);
+        assert_eq!(transfer.calculate_hash(), history[1].transaction_hash);
+        let receipt = bc.get_receipt(&transfer.calculate_hash()).unwrap();
+        assert_eq!(2, receipt.balance_changes.len());
+
+        // The histories are rebuilt along with the world state
+        let mut rewound = bc.clone();
+        rewound.rewind(1).unwrap();
+        assert!(rewound.account_history("bob", 0, 10).is_empty());
+        assert_eq!(2, rewound.account_history("alice", 0, 10).len());
+    }
+
+    #[test]
+    fn replayed_transactions_are_rejected() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create = |id: &str, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+        };
+        let mut block = bc.new_block();
+        block.add_transaction(create("bob", 0));
+        bc.append_block(block).unwrap();
+        assert_eq!(Some(1), bc.get_nonce("alice"));
+
+        // Neither a used nonce nor one skipping the next is accepted
+        for (nonce, id) in [(0, "bob"), (0, "carol"), (2, "carol")] {
+            let mut block = bc.new_block();
+            block.add_transaction(create(id, nonce));
+            assert_eq!(
+                Err(BlockchainError::InvalidNonce {
+                    expected: 1,
+                    found: nonce
+                }
+                .in_transaction(0)),
+                bc.append_block(block).map(|_| ())
+            );
+        }
+        assert_eq!(2, bc.len());
+        assert!(!bc.accounts.contains_key("carol"));
+    }
+
+    #[test]
+    fn blocks_skip_transactions_with_gapped_nonces() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let create = |id: &str, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+        };
+        bc.submit_transaction(create("bob", 0)).unwrap();
+        bc.produce_block(1).unwrap();
+
+        // Used nonces don't get into the mempool
+        assert_eq!(
+            Err(BlockchainError::InvalidNonce {
+                expected: 1,
+                found: 0
+            }),
+            bc.submit_transaction(create("carol", 0))
+        );
+
+        // ... whilst a gapped one keeps waiting, without failing the block
+        bc.submit_transaction(create("dave", 7)).unwrap();
+        bc.submit_transaction(create("carol", 1)).unwrap();
+        let result = bc.produce_block(10).unwrap();
+        assert_eq!(1, result.receipts.len());
+        assert_eq!(3, bc.len());
+        assert!(bc.accounts.contains_key("carol"));
+        assert_eq!(1, bc.pending_transactions().len());
+        assert_eq!(7, bc.pending_transactions()[0].nonce);
+        assert_eq!(
+            Err(BlockchainError::EmptyMempool),
+            bc.produce_block(10).map(|_| ())
+        );
+        assert_eq!(1, bc.pending_transactions().len());
+
+        // ... until the earlier nonces arrive
+        for nonce in 2..7 {
+            bc.submit_transaction(create(&format!("user{}", nonce), nonce))
+                .unwrap();
+        }
+        bc.produce_block(10).unwrap();
+        assert!(bc.accounts.contains_key("dave"));
+        assert!(bc.pending_transactions().is_empty());
+    }
+
+    #[test]
+    fn simulations_leave_the_chain_untouched() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let transfer = |amount, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::TransferTokens {
+                    to: "alice".into(),
+                    amount,
+                    asset: None,
+                },
+                nonce,
+            )
+        };
+        let create = |nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount("bob".into()),
+                nonce,
+            )
+        };
+        let state_root = bc.state_root();
+
+        let receipt = bc.simulate_transaction(&create(0)).unwrap();
+        assert!(receipt.is_success());
+        assert_eq!(create(0).record.gas_cost(), receipt.gas_used);
+        assert_eq!(
+            Err(BlockchainError::InsufficientBalance),
+            bc.simulate_transaction(&transfer(1_000, 0))
+        );
+
+        // The transactions of a block are executed on top of each other
+        let mut block = bc.new_block();
+        block.add_transaction(create(0));
+        block.add_transaction(create(1));
+        assert!(matches!(
+            bc.simulate_block(&block).unwrap_err(),
+            BlockchainError::InvalidTransaction { index: 1, .. }
+        ));
+        let mut block = bc.new_block();
+        block.add_transaction(transfer(10, 0));
+        assert_eq!(1, bc.simulate_block(&block).unwrap().receipts.len());
+
+        assert!(!bc.accounts.contains_key("bob"));
+        assert_eq!(state_root, bc.state_root());
+    }
+
+    #[test]
+    fn mempool_rejects_transactions_exceeding_the_pending_balance() {
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis_for("alice")).unwrap();
+        let transfer = |amount, nonce, fee| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::TransferTokens {
+                    to: "bob".into(),
+                    amount,
+                    asset: None,
+                },
+                nonce,
+            )
+            .with_fee(fee)
+        };
+
+        // Alice's 100 tokens cover the amounts and fees of her first two transfers only
+        bc.submit_transaction(transfer(50, 0, 5)).unwrap();
+        bc.submit_transaction(transfer(40, 1, 5)).unwrap();
+        assert_eq!(
+            Err(BlockchainError::PendingBalanceExceeded {
+                balance: 100,
+                required: 101
+            }),
+            bc.submit_transaction(transfer(0, 2, 1))
+        );
+
+        // A replacement doesn't count the transaction it replaces
+        bc.submit_transaction(transfer(30, 1, 6)).unwrap();
+        bc.submit_transaction(transfer(0, 2, 1)).unwrap();
+        assert_eq!(3, bc.pending_transactions().len());
+    }
+
+    #[test]
+    fn higher_fees_are_included_first() {
+        let mut bc = Blockchain::new();
+        let mut genesis = genesis_for("alice");
+        genesis
+            .body
+            .transactions
+            .extend(genesis_for("bob").body.transactions);
+        genesis.update_hash();
+        bc.append_block(genesis).unwrap();
+        let store = |from: &str, nonce, fee| {
+            Transaction::new(
+                from.into(),
+                TransactionData::ChangeStoreValue {
+                    key: "key".into(),
+                    value: from.into(),
+                },
+                nonce,
+            )
+            .with_fee(fee)
+        };
+
+        let transactions = [
+            store("alice", 0, 1),
+            store("bob", 0, 5),
+            store("alice", 1, 9),
+        ];
+        for transaction in &transactions {
+            bc.submit_transaction(transaction.clone()).unwrap();
+        }
+        // Bob outbids the first transaction of Alice, which her second one has to wait for
+        let result = bc.produce_block(2).unwrap();
+        let included: Vec<Hash> = result
+            .receipts
+            .iter()
+            .map(|receipt| receipt.transaction_hash)
+            .collect();
+        assert_eq!(
+            vec![
+                transactions[1].calculate_hash(),
+                transactions[0].calculate_hash()
+            ],
+            included
+        );
+        assert_eq!(1, bc.pending_transactions()[0].nonce);
+    }
+
+    #[test]
+    fn histories_are_queried_by_range_account_and_kind() {
+        let mut bc = Blockchain::new();
+        let mut genesis = genesis_for("alice");
+        genesis
+            .body
+            .transactions
+            .extend(genesis_for("bob").body.transactions);
+        genesis.update_hash();
+        bc.append_block(genesis).unwrap();
+        let transfer = |from: &str, to: &str, nonce| {
+            Transaction::new(
+                from.into(),
+                TransactionData::TransferTokens {
+                    to: to.into(),
+                    amount: 1,
+                    asset: None,
+                },
+                nonce,
+            )
+        };
+        for nonce in 0..3 {
+            bc.submit_transaction(transfer("alice", "bob", nonce))
+                .unwrap();
+            bc.submit_transaction(transfer("bob", "alice", nonce))
+                .unwrap();
+            bc.produce_block(2).unwrap();
+        }
+
+        assert_eq!(4, bc.len());
+        assert_eq!(2, bc.blocks_range(1, 3).len());
+        assert_eq!(bc.blocks[1].hash, bc.blocks_range(1, 3)[0].hash);
+        assert_eq!(1, bc.blocks_range(3, 10).len());
+        assert!(bc.blocks_range(5, 2).is_empty());
+
+        // The genesis transactions of Alice come first, then her transfers
+        let first = bc.transactions_by_account("alice", 0, 3);
+        assert_eq!(3, first.len());
+        assert_eq!(0, first[0].0.block_height);
+        assert_eq!(1, first[2].0.block_height);
+        let second = bc.transactions_by_account("alice", 1, 3);
+        let heights: Vec<usize> = second
+            .iter()
+            .map(|(location, _)| location.block_height)
+            .collect();
+        assert_eq!(vec![2, 3], heights);
+        assert!(second
+            .iter()
+            .all(|(_, transaction)| transaction.from == "alice"));
+        assert!(bc.transactions_by_account("alice", 2, 3).is_empty());
+        assert!(bc.transactions_by_account("carol", 0, 3).is_empty());
+
+        assert_eq!(
+            6,
+            bc.transactions_by_type(TransactionKind::TransferTokens)
+                .count()
+        );
+        assert_eq!(
+            2,
+            bc.transactions_by_type(TransactionKind::CreateTokens)
+                .count()
+        );
+        assert_eq!(0, bc.transactions_by_type(TransactionKind::Stake).count());
+
+        // Pruned blocks aren't listed anymore
+        bc.set_pruning(Some(2)).unwrap();
+        assert_eq!(2, bc.transactions_by_account("alice", 0, 10).len());
+        assert_eq!(
+            4,
+            bc.transactions_by_type(TransactionKind::TransferTokens)
+                .count()
+        );
+        assert_eq!(
+            0,
+            bc.transactions_by_type(TransactionKind::CreateTokens)
+                .count()
+        );
+    }
+
+    #[test]
+    fn reorganize_switches_to_valid_forks_only() {
+        use crate::test_utils::{account_id, mine_block, signed_transaction, TEST_CHAIN_ID};
+
+        let transfer = |amount, nonce| {
+            let data = TransactionData::TransferTokens {
+                to: account_id("bob"),
+                amount,
+                asset: None,
+            };
+            signed_transaction(TEST_CHAIN_ID, "alice", data, nonce)
+        };
+        let mut bc = crate::test_utils::ChainBuilder::new()
+            .with_account("alice", 100)
+            .transfer("alice", "bob", 1)
+            .build();
+
+        let mut fork = bc.clone();
+        fork.rewind(1).unwrap();
+        let first = mine_block(&mut fork, vec![transfer(10, 0)]).unwrap();
+        fork.append_block(first.clone()).unwrap();
+        let second = mine_block(&mut fork, vec![transfer(20, 1)]).unwrap();
+
+        // A rejected fork leaves the chain untouched
+        let mut invalid = second.clone();
+        invalid.hash = Some(Hash::digest(b"tampered"));
+        let tip = bc.get_last_block_hash();
+        assert!(bc.reorganize(1, vec![first.clone(), invalid]).is_err());
+        assert_eq!(tip, bc.get_last_block_hash());
+        assert_eq!(1, bc.accounts[&account_id("bob")].tokens);
+
+        let removed = bc.reorganize(1, vec![first, second]).unwrap();
+        assert_eq!(
+            vec![tip],
+            removed.iter().map(|block| block.hash).collect::<Vec<_>>()
+        );
+        assert_eq!(3, bc.len());
+        assert_eq!(30, bc.accounts[&account_id("bob")].tokens);
+    }
+
+    #[test]
+    fn finalized_blocks_are_never_reorganized() {
+        use crate::test_utils::{keypair, ChainBuilder};
+
+        let builder = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_validator("alice", 40)
+            .with_validator("bob", 30)
+            .with_validator("carol", 30)
+            .transfer("alice", "dave", 1)
+            .next_block()
+            .transfer("alice", "dave", 2);
+        let mut bc = builder.clone().build();
+        assert_eq!(None, bc.finalized_height());
+
+        // Hard-coded checkpoints pin the blocks at their heights
+        let pinned = |hash| ValidationPolicy {
+            require_signatures: true,
+            checkpoints: vec![Checkpoint::new(1, hash)],
+            ..ValidationPolicy::default()
+        };
+        let mismatch = builder
+            .clone()
+            .with_policy(pinned(Hash::digest(b"other")))
+            .try_build();
+        assert!(matches!(
+            mismatch,
+            Err(BlockchainError::CheckpointMismatch { height: 1 })
+        ));
+        let mut pinned = builder
+            .with_policy(pinned(bc.blocks[1].hash.unwrap()))
+            .build();
+        assert_eq!(Some(1), pinned.finalized_height());
+        assert_eq!(
+            BlockchainError::BlockFinalized { height: 1 },
+            pinned.rewind(1).unwrap_err()
+        );
+        assert_eq!(1, pinned.rewind(2).unwrap().len());
+
+        // Signed checkpoints need more than two thirds of the stake
+        let mut checkpoint = Checkpoint::new(1, bc.blocks[1].hash.unwrap());
+        checkpoint.sign(&keypair("bob"));
+        checkpoint.sign(&keypair("carol"));
+        assert!(matches!(
+            bc.finalize(checkpoint.clone()),
+            Err(BlockchainError::CheckpointNotApproved { .. })
+        ));
+        let mut wrong = Checkpoint::new(1, bc.blocks[2].hash.unwrap());
+        wrong.sign(&keypair("alice"));
+        wrong.sign(&keypair("bob"));
+        assert_eq!(
+            BlockchainError::CheckpointMismatch { height: 1 },
+            bc.finalize(wrong).unwrap_err()
+        );
+        checkpoint.sign(&keypair("alice"));
+        bc.finalize(checkpoint).unwrap();
+        assert_eq!(Some(1), bc.finalized_height());
+        assert_eq!(
+            BlockchainError::BlockFinalized { height: 1 },
+            bc.reorganize(1, Vec::new()).unwrap_err()
+        );
+        assert!(bc.reorganize(2, Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn orphans_are_appended_once_their_parents_arrive() {
+        use crate::test_utils::{
+            account_id, mine_block, signed_transaction, ChainBuilder, TEST_CHAIN_ID,
+        };
+
+        let mut bc = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_account("bob", 0)
+            .build();
+        let mut source = bc.clone();
+        let mut blocks = Vec::new();
+        for amount in 1..=3 {
+            let data = TransactionData::TransferTokens {
+                to: account_id("bob"),
+                amount,
+                asset: None,
+            };
+            let transfer = signed_transaction(TEST_CHAIN_ID, "alice", data, amount - 1);
+            let block = mine_block(&mut source, vec![transfer]).unwrap();
+            source.append_block(block.clone()).unwrap();
+            blocks.push(block);
+        }
+
+        // Blocks arriving before their parents are kept, not appended
+        for block in [&blocks[2], &blocks[1]] {
+            assert!(matches!(
+                bc.append_block(block.clone()),
+                Err(BlockchainError::OrphanBlock { .. })
+            ));
+        }
+        assert_eq!(2, bc.orphan_blocks().count());
+        assert_eq!(1, bc.len());
+
+        bc.append_block(blocks[0].clone()).unwrap();
+        assert_eq!(4, bc.len());
+        assert_eq!(source.get_last_block_hash(), bc.get_last_block_hash());
+        assert_eq!(0, bc.orphan_blocks().count());
+
+        // Expired orphans are dropped
+        let mut bc = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_account("bob", 0)
+            .build();
+        bc.set_orphan_policy(OrphanPolicy {
+            ttl: 0,
+            ..OrphanPolicy::default()
+        });
+        assert!(bc.append_block(blocks[1].clone()).is_err());
+        bc.append_block(blocks[0].clone()).unwrap();
+        assert_eq!(2, bc.len());
+    }
+
+    #[test]
+    fn recreated_accounts_keep_their_nonces() {
+        use crate::test_utils::{
+            account_id, mine_block, signed_transaction, ChainBuilder, TEST_CHAIN_ID,
+        };
+
+        let mut bc = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_account("bob", 100)
+            .build();
+        let transfer = |from, to, nonce| {
+            let data = TransactionData::TransferTokens {
+                to: account_id(to),
+                amount: 10,
+                asset: None,
+            };
+            signed_transaction(TEST_CHAIN_ID, from, data, nonce)
+        };
+        let old_transfer = transfer("alice", "bob", 0);
+        bc.submit_transaction(old_transfer.clone()).unwrap();
+        let delete = TransactionData::DeleteAccount {
+            beneficiary: account_id("bob"),
+        };
+        bc.submit_transaction(signed_transaction(TEST_CHAIN_ID, "alice", delete, 1))
+            .unwrap();
+        bc.produce_block(2).unwrap();
+        assert!(!bc.account_exists(&account_id("alice")));
+        assert_eq!(
+            Some(&"2".to_string()),
+            bc.get_store_value(TOMBSTONE_REGISTRY, &account_id("alice"))
+        );
+
+        // The re-created account continues with the nonce it was deleted with
+        let create = TransactionData::CreateUserAccount(account_id("alice"));
+        bc.submit_transaction(signed_transaction(TEST_CHAIN_ID, "bob", create, 0))
+            .unwrap();
+        bc.submit_transaction(transfer("bob", "alice", 1)).unwrap();
+        bc.produce_block(2).unwrap();
+        assert_eq!(Some(10), bc.get_balance(&account_id("alice")));
+        assert_eq!(Some(2), bc.get_nonce(&account_id("alice")));
+
+        // Hence the old transactions of the account can't be replayed
+        assert!(bc.submit_transaction(old_transfer.clone()).is_err());
+        assert_eq!(
+            Err(BlockchainError::InvalidNonce {
+                expected: 2,
+                found: 0
+            }),
+            bc.simulate_transaction(&old_transfer).map(|_| ())
         );
+        let replayed = mine_block(&mut bc, vec![old_transfer]);
+        assert!(replayed.and_then(|block| bc.append_block(block)).is_err());
+        assert_eq!(Some(10), bc.get_balance(&account_id("alice")));
+        assert!(bc.check_validity().is_ok());
     }
 }
@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::canonical;
+use super::transaction::verify;
+use crate::{BlockchainError, Cosignature, Hash, Keypair, WorldState};
+
+/// The tag that the signed checkpoints are hashed with, so that their signatures can't be
+/// mistaken for the signatures of anything else (e.g. of a transaction)
+const CHECKPOINT_DOMAIN: &str = "abc/checkpoint";
+
+/// Finalizes the block at its height: the chain can't be reorganized at or below it.<br/>
+/// A checkpoint is either hard-coded (see `ValidationPolicy::checkpoints`), in which case
+/// the block at its height has to have its hash, or signed by validators holding more than
+/// two thirds of the stake (see `Blockchain::finalize`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The height of the finalized block
+    pub height: usize,
+
+    /// The hash of the finalized block
+    pub hash: Hash,
+
+    /// The signatures of the validators (not needed by hard-coded checkpoints)
+    #[serde(default)]
+    pub signatures: Vec<Cosignature>,
+}
+
+impl Checkpoint {
+    /// C'tor.
+    pub fn new(height: usize, hash: Hash) -> Self {
+        Checkpoint {
+            height,
+            hash,
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Will return the hash that the validators sign
+    pub fn signing_hash(&self) -> Hash {
+        Hash::digest(&canonical::encode(&(
+            CHECKPOINT_DOMAIN,
+            self.height as u64,
+            self.hash,
+        )))
+    }
+
+    /// Will add the signature of a validator
+    pub fn sign(&mut self, keypair: &Keypair) {
+        let signature = keypair.sign(self.signing_hash().as_bytes());
+        self.signatures.push(Cosignature {
+            public_key: keypair.public_key(),
+            signature: hex::encode(signature),
+        });
+    }
+
+    /// Will return the addresses of the (valid) signers
+    pub fn signers(&self) -> HashSet<String> {
+        let hash = self.signing_hash();
+        self.signatures
+            .iter()
+            .filter_map(|signature| {
+                verify(&signature.public_key, &signature.signature, hash.as_bytes())
+            })
+            .map(|address| address.to_string())
+            .collect()
+    }
+
+    /// Will check that validators holding more than two thirds of the stake (in the world
+    /// state) signed the checkpoint
+    pub(crate) fn check_quorum(&self, world_state: &dyn WorldState) -> Result<(), BlockchainError> {
+        let signers = self.signers();
+        let (signed, total) =
+            world_state
+                .accounts()
+                .fold((0u128, 0u128), |(signed, total), (id, account)| {
+                    let stake = account.stake();
+                    match signers.contains(id) {
+                        true => (signed.saturating_add(stake), total.saturating_add(stake)),
+                        false => (signed, total.saturating_add(stake)),
+                    }
+                });
+        if total == 0 || signed.saturating_mul(3) <= total.saturating_mul(2) {
+            return Err(BlockchainError::CheckpointNotApproved { signed, total });
+        }
+        Ok(())
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::Checkpoint;
+    use crate::test_utils::{keypair, ChainBuilder};
+    use crate::{BlockchainError, Hash};
+
+    #[test]
+    fn validators_holding_two_thirds_of_the_stake_finalize() {
+        let state = ChainBuilder::new()
+            .with_validator("alice", 40)
+            .with_validator("bob", 30)
+            .with_validator("carol", 30)
+            .build();
+
+        let mut checkpoint = Checkpoint::new(3, Hash::digest(b"block"));
+        checkpoint.sign(&keypair("alice"));
+        checkpoint.sign(&keypair("bob"));
+        assert_eq!(2, checkpoint.signers().len());
+        assert!(checkpoint.check_quorum(&state).is_ok());
+
+        // Exactly two thirds are not enough, nor are forged signatures
+        let mut checkpoint = Checkpoint::new(3, Hash::digest(b"block"));
+        checkpoint.sign(&keypair("bob"));
+        checkpoint.sign(&keypair("carol"));
+        let mut forged = checkpoint.clone();
+        forged.hash = Hash::digest(b"other");
+        assert!(forged.signers().is_empty());
+        checkpoint.sign(&keypair("mallory"));
+        assert_eq!(
+            Err(BlockchainError::CheckpointNotApproved {
+                signed: 60,
+                total: 100
+            }),
+            checkpoint.check_quorum(&state)
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::staking;
+use super::world_state::WorldState;
+use crate::{Block, BlockchainError, DifficultyParams, Hash, ValidationPolicy};
+
+/// The consensus mechanisms a network may use (see `Consensus`).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusEngine {
+    /// Every block is accepted, as far as the consensus is concerned (see `NoOpConsensus`)
+    NoOp,
+
+    /// Blocks are mined with the Proof of Work difficulty (`ValidationPolicy::difficulty`)
+    #[default]
+    ProofOfWork,
+
+    /// Every block is signed by a validator, chosen with a probability proportional to its
+    /// stake. Validators need to have staked at least `min_stake` tokens.
+    ProofOfStake {
+        #[serde(default)]
+        min_stake: u128,
+    },
+
+    /// The authorities (addresses) of the network take turns signing the blocks
+    ProofOfAuthority { authorities: Vec<String> },
+}
+
+/// Decides who may append a block to the chain (the producer of the block)
+/// and how this is proven by the block.<br/>
+/// The genesis block is not checked against `check_producer`, since nobody could
+/// be entitled to produce it.<br/>
+/// A Blockchain uses the mechanism of its policy's engine (see `ValidationPolicy::consensus`),
+/// unless it is constructed with another one (see `Blockchain::with_consensus`).
+pub trait Consensus: Send + Sync {
+    /// Will check what the block proves on its own (e.g. its Proof of Work or its signature),
+    /// given the blocks before it
+    fn check_header(&self, block: &Block, prev_blocks: &[Block]) -> Result<(), BlockchainError>;
+
+    /// Will check if the producer of the block was entitled to produce it,
+    /// given the world state before executing the block
+    fn check_producer(
+        &self,
+        _block: &Block,
+        _prev_blocks: &[Block],
+        _world_state: &dyn WorldState,
+    ) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+
+    /// Will return the Proof of Work difficulty the next block has to be mined with
+    fn next_difficulty(&self, _prev_blocks: &[Block]) -> u32 {
+        0
+    }
+
+    /// Will return the account that is entitled to produce the next block,
+    /// `None` if anyone may produce it
+    fn next_producer(
+        &self,
+        _prev_blocks: &[Block],
+        _world_state: &dyn WorldState,
+    ) -> Option<String> {
+        None
+    }
+}
+
+/// Holds the consensus mechanism of a Blockchain.<br/>
+/// A deserialized Blockchain holds none, so it falls back to the one of its policy.
+#[derive(Clone, Default)]
+pub(crate) struct ConsensusHandle(pub(crate) Option<Arc<dyn Consensus>>);
+
+impl fmt::Debug for ConsensusHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ConsensusHandle {{ custom: {} }}", self.0.is_some())
+    }
+}
+
+impl ConsensusHandle {
+    /// Will return the custom consensus mechanism, or the one of the policy's engine
+    pub(crate) fn resolve(&self, policy: &ValidationPolicy) -> Arc<dyn Consensus> {
+        match &self.0 {
+            Some(consensus) => Arc::clone(consensus),
+            None => Arc::from(policy.consensus()),
+        }
+    }
+}
+
+/// Every block is accepted, as long as it doesn't claim a Proof of Work.
+#[derive(Clone, Debug, Default)]
+pub struct NoOpConsensus;
+
+impl Consensus for NoOpConsensus {
+    fn check_header(&self, block: &Block, _prev_blocks: &[Block]) -> Result<(), BlockchainError> {
+        if block.header.difficulty != 0 {
+            return Err(BlockchainError::InvalidProofOfWork { expected: 0 });
+        }
+        Ok(())
+    }
+}
+
+/// Blocks have to be mined, so that their hashes meet the difficulty.
+#[derive(Clone, Debug)]
+pub struct ProofOfWork {
+    pub params: DifficultyParams,
+}
+
+impl Consensus for ProofOfWork {
+    fn check_header(&self, block: &Block, prev_blocks: &[Block]) -> Result<(), BlockchainError> {
+        let difficulty = self.next_difficulty(prev_blocks);
+        if block.header.difficulty != difficulty || !block.verify_proof_of_work() {
+            return Err(BlockchainError::InvalidProofOfWork {
+                expected: difficulty,
+            });
+        }
+        Ok(())
+    }
+
+    fn next_difficulty(&self, prev_blocks: &[Block]) -> u32 {
+        self.params.next_difficulty(prev_blocks)
+    }
+}
+
+/// Blocks have to be signed by the validator chosen for them (see `ProofOfStake::select`).
+#[derive(Clone, Debug)]
+pub struct ProofOfStake {
+    pub min_stake: u128,
+}
+
+impl ProofOfStake {
+    /// Will choose the validator of the block following the last one, with a probability
+    /// proportional to its stake.<br/>
+    /// The choice is seeded by the hash of the last block, so every node makes the same one.
+    pub fn select(&self, last_hash: Option<Hash>, world_state: &dyn WorldState) -> Option<String> {
+        let validators = staking::validators(world_state, self.min_stake);
+        let total = validators
+            .iter()
+            .try_fold(0u128, |total, (_, stake)| total.checked_add(*stake))?;
+        if total == 0 {
+            return None;
+        }
+
+        let seed = Hash::digest(last_hash.as_ref().map_or(&[][..], |hash| hash.as_ref()));
+        let bytes = <[u8; 16]>::try_from(&seed.as_bytes()[..16]).expect("Hashes have 64 bytes");
+        let mut ticket = u128::from_be_bytes(bytes) % total;
+        for (id, stake) in validators {
+            if ticket < stake {
+                return Some(id);
+            }
+            ticket -= stake;
+        }
+        None
+    }
+}
+
+impl Consensus for ProofOfStake {
+    fn check_header(&self, block: &Block, prev_blocks: &[Block]) -> Result<(), BlockchainError> {
+        if block.header.difficulty != 0 {
+            return Err(BlockchainError::InvalidProofOfWork { expected: 0 });
+        }
+        // The genesis block can't be signed by a validator, since there are none yet
+        if !prev_blocks.is_empty() && block.producer().is_none() {
+            return Err(BlockchainError::InvalidBlockSignature);
+        }
+        Ok(())
+    }
+
+    fn check_producer(
+        &self,
+        block: &Block,
+        prev_blocks: &[Block],
+        world_state: &dyn WorldState,
+    ) -> Result<(), BlockchainError> {
+        let expected = self
+            .next_producer(prev_blocks, world_state)
+            .ok_or(BlockchainError::NoValidators)?;
+        match block.producer() {
+            Some(producer) if producer.to_string() == expected => Ok(()),
+            Some(_) => Err(BlockchainError::InvalidProducer { expected }),
+            None => Err(BlockchainError::InvalidBlockSignature),
+        }
+    }
+
+    fn next_producer(&self, prev_blocks: &[Block], world_state: &dyn WorldState) -> Option<String> {
+        self.select(prev_blocks.last().and_then(Block::hash), world_state)
+    }
+}
+
+/// Blocks have to be signed by the authorities, a fixed set of addresses that are trusted
+/// to produce the blocks of a (private) network.<br/>
+/// The authorities take turns in the configured order (round robin), starting with
+/// the block following the genesis block.
+#[derive(Clone, Debug)]
+pub struct ProofOfAuthority {
+    pub authorities: Vec<String>,
+}
+
+impl ProofOfAuthority {
+    /// Will return the authority whose turn it is to sign the block at `height`
+    /// (`None` for the genesis block or if there are no authorities)
+    pub fn authority_at(&self, height: usize) -> Option<&String> {
+        match (height, self.authorities.len()) {
+            (0, _) | (_, 0) => None,
+            (height, count) => self.authorities.get((height - 1) % count),
+        }
+    }
+}
+
+impl Consensus for ProofOfAuthority {
+    fn check_header(&self, block: &Block, prev_blocks: &[Block]) -> Result<(), BlockchainError> {
+        if block.header.difficulty != 0 {
+            return Err(BlockchainError::InvalidProofOfWork { expected: 0 });
+        }
+        // The genesis block is created out of the configuration, not by an authority
+        if prev_blocks.is_empty() {
+            return Ok(());
+        }
+
+        let expected = self
+            .authority_at(prev_blocks.len())
+            .ok_or(BlockchainError::NoValidators)?;
+        let producer = block
+            .producer()
+            .ok_or(BlockchainError::InvalidBlockSignature)?
+            .to_string();
+        if !self.authorities.contains(&producer) {
+            return Err(BlockchainError::UnknownAuthority(producer));
+        }
+        if &producer != expected {
+            return Err(BlockchainError::InvalidProducer {
+                expected: expected.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn next_producer(
+        &self,
+        prev_blocks: &[Block],
+        _world_state: &dyn WorldState,
+    ) -> Option<String> {
+        self.authority_at(prev_blocks.len()).cloned()
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use super::ProofOfStake;
+    use crate::{
+        AccountType, Block, Blockchain, BlockchainError, ConsensusEngine, Hash, Keypair,
+        Transaction, TransactionData, ValidationPolicy, WorldState,
+    };
+
+    #[test]
+    fn selection_is_weighted_by_stake() {
+        let mut chain = Blockchain::new();
+        let small = Keypair::generate().address().to_string();
+        let large = Keypair::generate().address().to_string();
+        chain
+            .create_account(small.clone(), AccountType::Validator { stake: 1 })
+            .unwrap();
+        chain
+            .create_account(large.clone(), AccountType::Validator { stake: 9 })
+            .unwrap();
+        chain
+            .create_account("user".into(), AccountType::User)
+            .unwrap();
+
+        let consensus = ProofOfStake { min_stake: 0 };
+        let mut selected = HashMap::new();
+        for i in 0u32..1_000 {
+            let last_hash = Hash::digest(&i.to_be_bytes());
+            let validator = consensus.select(Some(last_hash), &chain).unwrap();
+            *selected.entry(validator).or_insert(0) += 1;
+        }
+        assert_eq!(2, selected.len());
+        assert!(selected[&large] > 800 && selected[&small] > 50);
+
+        // The selection is deterministic and respects the minimum stake
+        let last_hash = Some(Hash::digest(b"block"));
+        assert_eq!(
+            consensus.select(last_hash, &chain),
+            consensus.select(last_hash, &chain)
+        );
+        let consensus = ProofOfStake { min_stake: 5 };
+        assert_eq!(Some(large), consensus.select(last_hash, &chain));
+        let consensus = ProofOfStake { min_stake: 10 };
+        assert_eq!(None, consensus.select(last_hash, &chain));
+    }
+
+    #[test]
+    fn authorities_take_turns() {
+        let authorities = [Keypair::generate(), Keypair::generate()];
+        let policy = ValidationPolicy {
+            engine: ConsensusEngine::ProofOfAuthority {
+                authorities: authorities
+                    .iter()
+                    .map(|keypair| keypair.address().to_string())
+                    .collect(),
+            },
+            ..ValidationPolicy::default()
+        };
+        let mut chain = Blockchain::with_policy(policy);
+        let mut genesis = Block::new(None);
+        genesis.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("alice".into()),
+            0,
+        ));
+        chain.append_block(genesis).unwrap();
+
+        let create_account = |id: &str, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                nonce,
+            )
+        };
+        chain.submit_transaction(create_account("bob", 0)).unwrap();
+        assert_eq!(
+            Err(BlockchainError::InvalidBlockSignature),
+            chain.produce_block(1).map(|_| ())
+        );
+        let stranger = Keypair::generate();
+        let mut block = chain.new_block();
+        block.add_transaction(create_account("bob", 0));
+        chain.seal_block(&mut block).unwrap();
+        block.sign(&stranger);
+        assert_eq!(
+            Err(BlockchainError::UnknownAuthority(
+                stranger.address().to_string()
+            )),
+            chain.append_block(block).map(|_| ())
+        );
+        chain.submit_transaction(create_account("bob", 0)).unwrap();
+        chain.produce_signed_block(1, &authorities[0]).unwrap();
+
+        // The second authority is next, the first one has to wait for its turn
+        let second = authorities[1].address().to_string();
+        assert_eq!(Some(second.clone()), chain.next_producer());
+        chain
+            .submit_transaction(create_account("carol", 1))
+            .unwrap();
+        assert_eq!(
+            Err(BlockchainError::InvalidProducer { expected: second }),
+            chain.produce_signed_block(1, &authorities[0]).map(|_| ())
+        );
+        assert_eq!(1, chain.pending_transactions().len());
+        chain.produce_signed_block(1, &authorities[1]).unwrap();
+        chain.submit_transaction(create_account("dave", 2)).unwrap();
+        chain.produce_signed_block(1, &authorities[0]).unwrap();
+
+        assert_eq!(4, chain.len());
+        assert!(chain.check_validity().is_ok());
+    }
+
+    #[test]
+    fn coinbases_pay_the_producer() {
+        let authority = Keypair::generate();
+        let address = authority.address().to_string();
+        let policy = ValidationPolicy {
+            engine: ConsensusEngine::ProofOfAuthority {
+                authorities: vec![address.clone()],
+            },
+            block_reward: 50,
+            ..ValidationPolicy::default()
+        };
+        let mut chain = Blockchain::with_policy(policy);
+        let mut genesis = Block::new(None);
+        for id in ["alice", address.as_str()].iter() {
+            genesis.add_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.to_string()),
+                0,
+            ));
+        }
+        chain.append_block(genesis).unwrap();
+
+        let signed_block = |chain: &mut Blockchain, receiver: &str| {
+            let mut block = chain.new_block();
+            block.add_transaction(Transaction::new(
+                receiver.into(),
+                TransactionData::Coinbase {
+                    receiver: receiver.into(),
+                    amount: 50,
+                },
+                0,
+            ));
+            chain.seal_block(&mut block).unwrap();
+            block.sign(&authority);
+            block
+        };
+
+        // The authority may not reward somebody else
+        let block = signed_block(&mut chain, "alice");
+        assert_eq!(
+            Err(BlockchainError::CoinbaseReceiverMismatch),
+            chain.append_block(block).map(|_| ())
+        );
+        assert_eq!(Some(0), chain.get_balance("alice"));
+
+        let block = signed_block(&mut chain, &address);
+        chain.append_block(block).unwrap();
+        assert_eq!(Some(50), chain.get_balance(&address));
+        assert!(chain.check_validity().is_ok());
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use wasmi::core::TrapCode;
+use wasmi::{Caller, Config, Engine, Extern, Linker, Memory, Module, Store};
+
+use super::address::ADDRESS_BYTE_SIZE;
+use super::canonical;
+use crate::{Address, BlockchainError, Hash};
+
+/// The amount of gas (wasmi fuel) a single contract call may consume.
+pub const CALL_GAS_LIMIT: u64 = 1_000_000;
+
+/// The tag that the addresses of the contracts are derived with, so that they can't collide
+/// with the addresses derived of anything else
+const CONTRACT_ADDRESS_DOMAIN: &str = "abc/contract";
+
+/// The module the host functions are imported from (by the contracts).
+const HOST_MODULE: &str = "env";
+
+/// The function a contract has to export, it is invoked on every call.
+const ENTRY_POINT: &str = "call";
+
+/// The (linear) memory a contract has to export, the host functions read from and write to it.
+const MEMORY: &str = "memory";
+
+/// Everything a contract call can access: the contract's storage, the caller and the input
+/// (and the events it emits).
+struct HostState {
+    storage: HashMap<String, String>,
+    caller: String,
+    input: Vec<u8>,
+    events: Vec<(String, Vec<u8>)>,
+}
+
+/// The outcome of a contract call: the gas is used even if the call fails.
+#[derive(Debug)]
+pub(crate) struct CallOutcome {
+    pub(crate) gas_used: u64,
+
+    /// The (topic, data) of the emitted events, only kept if the call succeeds
+    pub(crate) events: Vec<(String, Vec<u8>)>,
+
+    pub(crate) result: Result<(), BlockchainError>,
+}
+
+/// Will derive the address of the contract deployed by the deployer at the nonce (the one of
+/// the deploying transaction, which is the deployer's next one) and the index (of the
+/// deploying operation within its batch, 0 for a `DeployContract` transaction), so that
+/// clients know it before the deployment is included.<br/>
+/// Since every nonce is used once, so is every address: deploying to an address that another
+/// account took already is rejected (see `TransactionData::DeployContract`).
+pub fn contract_address(deployer: &str, nonce: u128, index: usize) -> String {
+    let hash = Hash::digest(&canonical::encode(&(
+        CONTRACT_ADDRESS_DOMAIN,
+        deployer,
+        nonce,
+        index as u64,
+    )));
+    let mut bytes = [0u8; ADDRESS_BYTE_SIZE];
+    bytes.copy_from_slice(&hash.as_bytes()[..ADDRESS_BYTE_SIZE]);
+    Address::new(bytes).to_string()
+}
+
+/// Will check if the code is a valid WASM module, before it is deployed.
+pub(crate) fn validate(code: &[u8]) -> Result<(), BlockchainError> {
+    Module::new(&engine(), code)
+        .map(|_| ())
+        .map_err(|err| BlockchainError::InvalidContract(err.to_string()))
+}
+
+/// Will execute the contract's entry point and return the gas it used (and its events).<br/>
+/// The storage is only changed if the call succeeds.
+///
+/// Contracts import the following host functions from the `env` module:
+/// - `input_len() -> i32` and `input_read(ptr: i32)` to access the input of the call
+/// - `caller_len() -> i32` and `caller_read(ptr: i32)` to access the caller's account id
+/// - `storage_read(key_ptr, key_len, value_ptr, value_cap: i32) -> i32` returns the length
+///   of the stored value (or -1 if there is none), writing at most `value_cap` bytes of it
+/// - `storage_write(key_ptr, key_len, value_ptr, value_len: i32)`
+/// - `emit_event(topic_ptr, topic_len, data_ptr, data_len: i32)` emits an event (the topic
+///   has to be valid UTF-8, the data may be any bytes)
+///
+/// Keys and values have to be valid UTF-8.
+pub(crate) fn call(
+    code: &[u8],
+    storage: &mut HashMap<String, String>,
+    caller: &str,
+    input: &[u8],
+) -> CallOutcome {
+    let engine = engine();
+    let host = HostState {
+        storage: storage.clone(),
+        caller: caller.to_string(),
+        input: input.to_vec(),
+        events: Vec::new(),
+    };
+    let mut store = Store::new(&engine, host);
+
+    let result = run(&engine, &mut store, code);
+    // Running out of gas uses all of it (even if some fuel is left, too little for the next step)
+    let gas_used = match result {
+        Err(BlockchainError::OutOfGas) => CALL_GAS_LIMIT,
+        _ => CALL_GAS_LIMIT - store.get_fuel().unwrap_or(0),
+    };
+    let mut events = Vec::new();
+    if result.is_ok() {
+        let host = store.into_data();
+        *storage = host.storage;
+        events = host.events;
+    }
+    CallOutcome {
+        gas_used,
+        events,
+        result,
+    }
+}
+
+/// Will instantiate the contract and invoke its entry point
+fn run(engine: &Engine, store: &mut Store<HostState>, code: &[u8]) -> Result<(), BlockchainError> {
+    let module = Module::new(engine, code)
+        .map_err(|err| BlockchainError::InvalidContract(err.to_string()))?;
+    store.set_fuel(CALL_GAS_LIMIT).map_err(contract_error)?;
+
+    let linker = linker(engine).map_err(contract_error)?;
+    let instance = linker
+        .instantiate(&mut *store, &module)
+        .and_then(|instance| instance.start(&mut *store))
+        .map_err(contract_error)?;
+    let entry_point = instance
+        .get_typed_func::<(), ()>(&*store, ENTRY_POINT)
+        .map_err(contract_error)?;
+
+    entry_point.call(&mut *store, ()).map_err(|err| {
+        if err.as_trap_code() == Some(TrapCode::OutOfFuel) {
+            BlockchainError::OutOfGas
+        } else {
+            contract_error(err)
+        }
+    })
+}
+
+fn engine() -> Engine {
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    Engine::new(&config)
+}
+
+/// Will define the host functions the contracts may import
+fn linker(engine: &Engine) -> Result<Linker<HostState>, wasmi::Error> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap(HOST_MODULE, "input_len", |caller: Caller<'_, HostState>| {
+        caller.data().input.len() as i32
+    })?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "input_read",
+        |mut caller: Caller<'_, HostState>, ptr: i32| {
+            let input = caller.data().input.clone();
+            write_memory(&mut caller, ptr, &input)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "caller_len",
+        |caller: Caller<'_, HostState>| caller.data().caller.len() as i32,
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "caller_read",
+        |mut caller: Caller<'_, HostState>, ptr: i32| {
+            let id = caller.data().caller.clone();
+            write_memory(&mut caller, ptr, id.as_bytes())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "storage_read",
+        |mut caller: Caller<'_, HostState>,
+         key_ptr: i32,
+         key_len: i32,
+         value_ptr: i32,
+         value_cap: i32|
+         -> Result<i32, wasmi::Error> {
+            let key = read_string(&caller, key_ptr, key_len)?;
+            let value = match caller.data().storage.get(&key) {
+                Some(value) => value.clone().into_bytes(),
+                None => return Ok(-1),
+            };
+            let len = value.len().min(value_cap.max(0) as usize);
+            write_memory(&mut caller, value_ptr, &value[..len])?;
+            Ok(value.len() as i32)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "storage_write",
+        |mut caller: Caller<'_, HostState>,
+         key_ptr: i32,
+         key_len: i32,
+         value_ptr: i32,
+         value_len: i32|
+         -> Result<(), wasmi::Error> {
+            let key = read_string(&caller, key_ptr, key_len)?;
+            let value = read_string(&caller, value_ptr, value_len)?;
+            caller.data_mut().storage.insert(key, value);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "emit_event",
+        |mut caller: Caller<'_, HostState>,
+         topic_ptr: i32,
+         topic_len: i32,
+         data_ptr: i32,
+         data_len: i32|
+         -> Result<(), wasmi::Error> {
+            let topic = read_string(&caller, topic_ptr, topic_len)?;
+            let data = read_memory(&caller, data_ptr, data_len)?;
+            caller.data_mut().events.push((topic, data));
+            Ok(())
+        },
+    )?;
+
+    Ok(linker)
+}
+
+fn memory(caller: &Caller<'_, HostState>) -> Result<Memory, wasmi::Error> {
+    caller
+        .get_export(MEMORY)
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("The contract does not export its memory"))
+}
+
+fn read_memory(
+    caller: &Caller<'_, HostState>,
+    ptr: i32,
+    len: i32,
+) -> Result<Vec<u8>, wasmi::Error> {
+    let mut bytes = vec![0u8; len.max(0) as usize];
+    memory(caller)?
+        .read(caller, ptr as u32 as usize, &mut bytes)
+        .map_err(|err| wasmi::Error::new(err.to_string()))?;
+    Ok(bytes)
+}
+
+fn read_string(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String, wasmi::Error> {
+    let bytes = read_memory(caller, ptr, len)?;
+    String::from_utf8(bytes).map_err(|err| wasmi::Error::new(err.to_string()))
+}
+
+fn write_memory(
+    caller: &mut Caller<'_, HostState>,
+    ptr: i32,
+    bytes: &[u8],
+) -> Result<(), wasmi::Error> {
+    memory(caller)?
+        .write(caller, ptr as u32 as usize, bytes)
+        .map_err(|err| wasmi::Error::new(err.to_string()))
+}
+
+fn contract_error<E: ToString>(err: E) -> BlockchainError {
+    BlockchainError::ContractFailed(err.to_string())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use super::CALL_GAS_LIMIT;
+    use crate::BlockchainError;
+
+    /// Stores the input of the call under the caller's account id
+    const REGISTRY: &str = r#"
+        (module
+            (import "env" "input_len" (func $input_len (result i32)))
+            (import "env" "input_read" (func $input_read (param i32)))
+            (import "env" "caller_len" (func $caller_len (result i32)))
+            (import "env" "caller_read" (func $caller_read (param i32)))
+            (import "env" "storage_write" (func $storage_write (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "call")
+                (call $caller_read (i32.const 0))
+                (call $input_read (i32.const 1024))
+                (call $storage_write
+                    (i32.const 0) (call $caller_len)
+                    (i32.const 1024) (call $input_len))))
+    "#;
+
+    /// Emits the input as the data of a "called" event
+    const NOTIFIER: &str = r#"
+        (module
+            (import "env" "input_len" (func $input_len (result i32)))
+            (import "env" "input_read" (func $input_read (param i32)))
+            (import "env" "emit_event" (func $emit_event (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "called")
+            (func (export "call")
+                (call $input_read (i32.const 16))
+                (call $emit_event
+                    (i32.const 0) (i32.const 6)
+                    (i32.const 16) (call $input_len))))
+    "#;
+
+    const ENDLESS_LOOP: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "call") (loop $forever (br $forever))))
+    "#;
+
+    #[test]
+    fn call_writes_storage() {
+        let code = wat::parse_str(REGISTRY).unwrap();
+        super::validate(&code).unwrap();
+
+        let mut storage = HashMap::new();
+        let outcome = super::call(&code, &mut storage, "alice", b"hello");
+        outcome.result.unwrap();
+        assert!(outcome.gas_used > 0);
+        assert_eq!(Some(&"hello".to_string()), storage.get("alice"));
+    }
+
+    #[test]
+    fn call_emits_events() {
+        let code = wat::parse_str(NOTIFIER).unwrap();
+        let outcome = super::call(&code, &mut HashMap::new(), "alice", &[1, 2]);
+        outcome.result.unwrap();
+        assert_eq!(vec![("called".to_string(), vec![1, 2])], outcome.events);
+    }
+
+    #[test]
+    fn invalid_code_is_rejected() {
+        assert!(matches!(
+            super::validate(b"not wasm"),
+            Err(BlockchainError::InvalidContract(_))
+        ));
+    }
+
+    #[test]
+    fn gas_is_limited() {
+        let code = wat::parse_str(ENDLESS_LOOP).unwrap();
+        let mut storage = HashMap::new();
+        let outcome = super::call(&code, &mut storage, "alice", &[]);
+        assert_eq!(Err(BlockchainError::OutOfGas), outcome.result);
+        assert_eq!(CALL_GAS_LIMIT, outcome.gas_used);
+    }
+
+    #[test]
+    fn invalid_utf8_leaves_storage_untouched() {
+        let code = wat::parse_str(REGISTRY).unwrap();
+        let mut storage = HashMap::new();
+        assert!(super::call(&code, &mut storage, "alice", &[0xff])
+            .result
+            .is_err());
+        assert!(storage.is_empty());
+    }
+}
@@ -0,0 +1,530 @@
+use thiserror::Error;
+
+use crate::{Hash, TimeLock};
+
+/// The errors that the blockchain operations may fail with.<br/>
+/// Errors caused by a specific block or transaction are wrapped into
+/// `InvalidBlock`/`InvalidTransaction`, which carry its position.
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum BlockchainError {
+    /// The stored hash of a block does not match its contents
+    #[error("The block hash does not match its contents")]
+    HashMismatch,
+
+    /// A block does not point to the (current) last block
+    #[error("The block does not point to the previous block")]
+    PrevHashMismatch,
+
+    /// A block is not at the height following its parent's
+    #[error("The block has height {found}, expected height {expected}")]
+    HeightMismatch { expected: usize, found: usize },
+
+    /// The parent of a block is unknown (yet), the block is kept until it arrives
+    #[error("The parent {parent} of the block is unknown")]
+    OrphanBlock { parent: Hash },
+
+    /// A block was not mined with the required Proof of Work difficulty
+    #[error("The block does not meet the Proof of Work difficulty of {expected}")]
+    InvalidProofOfWork { expected: u32 },
+
+    /// A block is older than the median timestamp of the previous blocks
+    #[error("The block timestamp is older than the median time past ({median_time_past})")]
+    TimestampTooOld { median_time_past: u64 },
+
+    /// A block is too far ahead of the local clock
+    #[error("The block timestamp is too far in the future (at most {max} is accepted)")]
+    TimestampTooFarInFuture { max: u64 },
+
+    /// The transactions of a block use more gas than a block may use
+    #[error("The block exceeds the gas limit of {limit}")]
+    BlockGasLimitExceeded { limit: u64 },
+
+    /// The world state after executing a block does not match its state root
+    #[error("The state root does not match the world state")]
+    StateRootMismatch,
+
+    /// A block contains more transactions than a block may contain
+    #[error("The block contains more than {max} transactions")]
+    TooManyTransactions { max: usize },
+
+    /// A block is larger than a block may be
+    #[error("The block is larger than {max} bytes")]
+    BlockTooLarge { max: u64 },
+
+    /// A block doesn't follow the version of the rules active at its height
+    #[error("The block has the version {found}, instead of {expected}")]
+    UnexpectedBlockVersion { expected: u32, found: u32 },
+
+    /// A transaction was created for a version of the rules that isn't active yet
+    #[error("The transaction has the version {found}, newer than {max}")]
+    UnsupportedTransactionVersion { max: u32, found: u32 },
+
+    /// A transaction can't be built, as a part of it is missing (see `TransactionBuilder`)
+    #[error("The transaction has no {0}")]
+    IncompleteTransaction(&'static str),
+
+    /// A transaction doesn't have the next nonce of its sender (see `BlockBuilder`)
+    #[error("The transaction has the nonce {found}, instead of {expected}")]
+    InvalidNonce { expected: u128, found: u128 },
+
+    /// A transaction stores a value that is longer than a stored value may be
+    #[error("The stored value is longer than {max} bytes")]
+    StoreValueTooLong { max: usize },
+
+    /// A block does not contain any transaction
+    #[error("There has to be at least one transaction inside the block")]
+    EmptyBlock,
+
+    /// A block contains more than one coinbase
+    #[error("The block contains more than one coinbase")]
+    MultipleCoinbases,
+
+    /// The coinbase does not pay the block subsidy plus the fees (or carries a fee itself)
+    #[error("The coinbase has to pay exactly {expected:?} tokens, without a fee")]
+    InvalidCoinbase { expected: Option<u128> },
+
+    /// The coinbase is not paid to its sender, or not to the producer of its signed block
+    #[error("The coinbase has to be paid to its sender (the block producer)")]
+    CoinbaseReceiverMismatch,
+
+    /// A coinbase (or a batch containing one) was submitted, whilst only block producers
+    /// may create coinbases
+    #[error("Coinbases may only be created by block producers")]
+    SubmittedCoinbase,
+
+    /// The block at the provided height (of a stored chain) is invalid
+    #[error("Block at height {height} is invalid: {source}")]
+    InvalidBlock {
+        height: usize,
+        source: Box<BlockchainError>,
+    },
+
+    /// The transaction at the provided index (within its block) is invalid
+    #[error("Transaction at index {index} is invalid: {source}")]
+    InvalidTransaction {
+        index: usize,
+        source: Box<BlockchainError>,
+    },
+
+    /// A transaction is unsigned (but signatures are required) or its signature is invalid
+    #[error("The transaction is unsigned or has an invalid signature")]
+    InvalidSignature,
+
+    /// A transaction is meant for another network
+    #[error("The transaction is meant for chain `{found}` instead of `{expected}`")]
+    ChainIdMismatch { expected: String, found: String },
+
+    /// The account does not exist
+    #[error("Account `{0}` does not exist")]
+    UnknownAccount(String),
+
+    /// The account already exists
+    #[error("Account `{0}` already exists")]
+    AccountExists(String),
+
+    /// The account still owns stored values (so it can't be deleted)
+    #[error("Account `{0}` still owns stored values")]
+    AccountHasStorage(String),
+
+    /// An account can't be the beneficiary of its own deletion
+    #[error("An account can't be its own beneficiary")]
+    InvalidBeneficiary,
+
+    /// The sender can't afford the transaction (amount and/or fee)
+    #[error("Insufficient balance")]
+    InsufficientBalance,
+
+    /// The asset id doesn't meet the naming rules
+    #[error("Invalid asset id `{0}`")]
+    InvalidAsset(String),
+
+    /// An asset with the id is already registered
+    #[error("Asset `{0}` already exists")]
+    AssetExists(String),
+
+    /// No asset with the id is registered
+    #[error("Asset `{0}` does not exist")]
+    UnknownAsset(String),
+
+    /// The chain didn't reach the lock the transaction waits for
+    #[error("The transaction is not valid before {0:?}")]
+    TransactionNotYetValid(TimeLock),
+
+    /// The chain reached the lock the transaction expires at
+    #[error("The transaction expired at {0:?}")]
+    TransactionExpired(TimeLock),
+
+    /// A batch of operations is empty or contains operations it can't contain
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+
+    /// The id of an NFT is empty or too long
+    #[error("Invalid NFT id `{0}`")]
+    InvalidNft(String),
+
+    /// An NFT with the id was already minted
+    #[error("NFT `{0}` already exists")]
+    NftExists(String),
+
+    /// No NFT with the id was minted
+    #[error("NFT `{0}` does not exist")]
+    UnknownNft(String),
+
+    /// The NFT is owned by another account than the sender
+    #[error("NFT `{0}` is not owned by the sender")]
+    NotNftOwner(String),
+
+    /// An escrow with the id was already opened
+    #[error("Escrow `{0}` already exists")]
+    EscrowExists(String),
+
+    /// No escrow with the id is open
+    #[error("Escrow `{0}` does not exist")]
+    UnknownEscrow(String),
+
+    /// The sender may not release (or refund) the escrow
+    #[error("The sender may not settle escrow `{0}`")]
+    NotEscrowParty(String),
+
+    /// The hash lock (or the preimage) of a hashed timelock contract is invalid
+    #[error("Invalid hashed timelock contract: {0}")]
+    InvalidHtlc(String),
+
+    /// No hashed timelock contract with the id is open
+    #[error("Hashed timelock contract `{0}` does not exist")]
+    UnknownHtlc(String),
+
+    /// The timeout of a hashed timelock contract is reached, it may only be refunded
+    #[error("Hashed timelock contract `{0}` expired")]
+    HtlcExpired(String),
+
+    /// The timeout of a hashed timelock contract is not reached, it may only be claimed
+    #[error("Hashed timelock contract `{0}` did not expire yet")]
+    HtlcNotExpired(String),
+
+    /// The proof of a message of another chain is invalid
+    #[error("Invalid bridge proof: {0}")]
+    InvalidBridgeProof(String),
+
+    /// Messages of the chain are not accepted (see `ValidationPolicy::bridges`)
+    #[error("There is no bridge to chain `{0}`")]
+    UnknownBridge(String),
+
+    /// The message of another chain (the hash of its transaction) was already relayed
+    #[error("The bridge message {0} was already relayed")]
+    BridgeMessageRelayed(String),
+
+    /// The spender transfers more tokens than the owner allowed it to
+    #[error("Insufficient allowance")]
+    InsufficientAllowance,
+
+    /// An amount of tokens overflows
+    #[error("Arithmetic overflow")]
+    ArithmeticOverflow,
+
+    /// The operation is only available in the genesis block
+    #[error("The operation is only available in the genesis block")]
+    GenesisOnly,
+
+    /// The faucet is only available on development networks (see `ChainParams::dev_mode`)
+    #[error("The faucet is only available on development networks")]
+    FaucetDisabled,
+
+    /// The faucet mints at most the amount per transaction
+    #[error("The faucet mints at most {max} tokens per transaction")]
+    FaucetLimitExceeded { max: u128 },
+
+    /// There are no pending transactions to produce a block from
+    #[error("There are no pending transactions to produce a block from")]
+    EmptyMempool,
+
+    /// A pending transaction of the sender with the same nonce pays at least the same fee
+    #[error("A pending transaction with the same nonce pays a fee of {fee} already")]
+    ReplacementFeeTooLow { fee: u128 },
+
+    /// The mempool is full of transactions paying at least the same fee
+    #[error("The mempool is full of transactions paying at least the same fee")]
+    MempoolFull,
+
+    /// The sender can't afford the transaction along with its pending transactions
+    #[error("The pending transactions require {required} tokens, the balance is {balance}")]
+    PendingBalanceExceeded { balance: u128, required: u128 },
+
+    /// The storage backend failed
+    #[error("Storage error: {0}")]
+    Storage(String),
+
+    /// A value could not be encoded or decoded
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+
+    /// The code of a contract is not a valid WASM module
+    #[error("Invalid contract: {0}")]
+    InvalidContract(String),
+
+    /// The account called is not a contract
+    #[error("Account `{0}` is not a contract")]
+    NotAContract(String),
+
+    /// The execution of a contract failed (trapped)
+    #[error("Contract execution failed: {0}")]
+    ContractFailed(String),
+
+    /// The execution of a contract used more gas than it is allowed to
+    #[error("Out of gas")]
+    OutOfGas,
+
+    /// A configuration (e.g. of the genesis) could not be loaded
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    /// A hash could not be parsed from its hex representation
+    #[error("Invalid hash: {0}")]
+    InvalidHash(String),
+
+    /// A key file of the keystore could not be read or written
+    #[error("Keystore error: {0}")]
+    Keystore(String),
+
+    /// A key file could not be decrypted with the provided password
+    #[error("Wrong password for the key of {0}")]
+    WrongPassword(String),
+
+    /// A mnemonic seed phrase is malformed
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+
+    /// An address could not be parsed (or its checksum is wrong)
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
+    /// An alias doesn't meet the naming rules (or the sender isn't an address)
+    #[error("Invalid alias: {0}")]
+    InvalidAlias(String),
+
+    /// An alias is already registered
+    #[error("The alias {0} is already taken")]
+    AliasTaken(String),
+
+    /// A name doesn't meet the naming rules of the aliases (or the sender isn't an address)
+    #[error("Invalid name: {0}")]
+    InvalidName(String),
+
+    /// A name is registered by another owner, whose registration didn't expire
+    #[error("The name {0} is already taken")]
+    NameTaken(String),
+
+    /// The account ID is reserved for (on-chain) registries
+    #[error("The account {0} is reserved")]
+    ReservedAccount(String),
+
+    /// The owners or the threshold of a multisignature account are invalid
+    #[error("Invalid multisignature account: {0}")]
+    InvalidMultisig(String),
+
+    /// A metadata entry of an account is too large, or the account has too many of them
+    #[error("Invalid account metadata: {0}")]
+    InvalidMetadata(String),
+
+    /// The vesting schedule of a new account is invalid
+    #[error("Invalid vesting schedule: {0}")]
+    InvalidVesting(String),
+
+    /// The account would be left with less tokens than the ones that are still locked by its
+    /// vesting schedule
+    #[error("{locked} tokens of the account are still locked")]
+    TokensLocked { locked: u128 },
+
+    /// A transaction of a multisignature account lacks signatures of its owners
+    #[error("{approvals} of the {threshold} required owner signatures are present")]
+    MultisigThresholdNotMet { threshold: u32, approvals: usize },
+
+    /// The transactions of a block were pruned, only its header is kept
+    #[error("The block at height {height} was pruned")]
+    BlockPruned { height: usize },
+
+    /// A transaction is already included in the chain
+    #[error("The transaction {0} is already included")]
+    TransactionAlreadyIncluded(Hash),
+
+    /// Only user (and validator) accounts may stake tokens
+    #[error("Account `{0}` can't stake tokens")]
+    NotStakeable(String),
+
+    /// More tokens are unstaked than the validator has staked
+    #[error("Insufficient stake")]
+    InsufficientStake,
+
+    /// The account still has staked tokens (so it can't be deleted)
+    #[error("Account `{0}` still has staked tokens")]
+    AccountHasStake(String),
+
+    /// An operation reserved to admins was sent by another account
+    #[error("Account `{0}` is not an admin")]
+    NotAnAdmin(String),
+
+    /// The (frozen) account can't transfer its tokens
+    #[error("Account `{0}` is frozen")]
+    AccountFrozen(String),
+
+    /// A block is unsigned (but has to be signed by its producer) or its signature is invalid
+    #[error("The block is unsigned or has an invalid signature")]
+    InvalidBlockSignature,
+
+    /// A block was produced by someone who wasn't entitled to produce it
+    #[error("The block has to be produced by {expected}")]
+    InvalidProducer { expected: String },
+
+    /// There is no validator that is entitled to produce the next block
+    #[error("There are no validators to produce the block")]
+    NoValidators,
+
+    /// A block was signed by someone who is not an authority of the network
+    #[error("{0} is not an authority of the network")]
+    UnknownAuthority(String),
+
+    /// The evidence of a misbehavior doesn't prove it
+    #[error("Invalid evidence: {0}")]
+    InvalidEvidence(String),
+
+    /// A block file (see `Blockchain::import_blocks`) is malformed, truncated or corrupted
+    #[error("Invalid block file: {0}")]
+    InvalidBlockFile(String),
+
+    /// A block doesn't have the hash of the checkpoint at its height
+    #[error("The block at height {height} doesn't match the checkpoint")]
+    CheckpointMismatch { height: usize },
+
+    /// The blocks up to the height are finalized, so they can't be reorganized
+    #[error("The chain is finalized up to height {height}")]
+    BlockFinalized { height: usize },
+
+    /// The signers of a checkpoint don't hold more than two thirds of the stake
+    #[error("Validators holding {signed} of the {total} staked tokens signed the checkpoint, more than two thirds are required")]
+    CheckpointNotApproved { signed: u128, total: u128 },
+}
+
+impl BlockchainError {
+    /// Will return a stable identifier (snake case) of the kind of the error, e.g. for logs
+    /// and metrics.<br/>
+    /// Wrapped errors (see `in_block`) are identified by the error that caused them.
+    pub fn code(&self) -> &'static str {
+        match self {
+            BlockchainError::InvalidBlock { source, .. }
+            | BlockchainError::InvalidTransaction { source, .. } => source.code(),
+            BlockchainError::HashMismatch => "hash_mismatch",
+            BlockchainError::PrevHashMismatch => "prev_hash_mismatch",
+            BlockchainError::HeightMismatch { .. } => "height_mismatch",
+            BlockchainError::OrphanBlock { .. } => "orphan_block",
+            BlockchainError::InvalidProofOfWork { .. } => "invalid_proof_of_work",
+            BlockchainError::TimestampTooOld { .. } => "timestamp_too_old",
+            BlockchainError::TimestampTooFarInFuture { .. } => "timestamp_too_far_in_future",
+            BlockchainError::BlockGasLimitExceeded { .. } => "block_gas_limit_exceeded",
+            BlockchainError::StateRootMismatch => "state_root_mismatch",
+            BlockchainError::TooManyTransactions { .. } => "too_many_transactions",
+            BlockchainError::BlockTooLarge { .. } => "block_too_large",
+            BlockchainError::UnexpectedBlockVersion { .. } => "unexpected_block_version",
+            BlockchainError::UnsupportedTransactionVersion { .. } => {
+                "unsupported_transaction_version"
+            }
+            BlockchainError::IncompleteTransaction(_) => "incomplete_transaction",
+            BlockchainError::InvalidNonce { .. } => "invalid_nonce",
+            BlockchainError::StoreValueTooLong { .. } => "store_value_too_long",
+            BlockchainError::EmptyBlock => "empty_block",
+            BlockchainError::MultipleCoinbases => "multiple_coinbases",
+            BlockchainError::InvalidCoinbase { .. } => "invalid_coinbase",
+            BlockchainError::CoinbaseReceiverMismatch => "coinbase_receiver_mismatch",
+            BlockchainError::SubmittedCoinbase => "submitted_coinbase",
+            BlockchainError::InvalidSignature => "invalid_signature",
+            BlockchainError::ChainIdMismatch { .. } => "chain_id_mismatch",
+            BlockchainError::UnknownAccount(_) => "unknown_account",
+            BlockchainError::AccountExists(_) => "account_exists",
+            BlockchainError::AccountHasStorage(_) => "account_has_storage",
+            BlockchainError::InvalidBeneficiary => "invalid_beneficiary",
+            BlockchainError::InsufficientBalance => "insufficient_balance",
+            BlockchainError::InsufficientAllowance => "insufficient_allowance",
+            BlockchainError::InvalidAsset(_) => "invalid_asset",
+            BlockchainError::AssetExists(_) => "asset_exists",
+            BlockchainError::UnknownAsset(_) => "unknown_asset",
+            BlockchainError::InvalidBatch(_) => "invalid_batch",
+            BlockchainError::TransactionNotYetValid(_) => "transaction_not_yet_valid",
+            BlockchainError::TransactionExpired(_) => "transaction_expired",
+            BlockchainError::InvalidNft(_) => "invalid_nft",
+            BlockchainError::NftExists(_) => "nft_exists",
+            BlockchainError::UnknownNft(_) => "unknown_nft",
+            BlockchainError::NotNftOwner(_) => "not_nft_owner",
+            BlockchainError::EscrowExists(_) => "escrow_exists",
+            BlockchainError::UnknownEscrow(_) => "unknown_escrow",
+            BlockchainError::NotEscrowParty(_) => "not_escrow_party",
+            BlockchainError::InvalidHtlc(_) => "invalid_htlc",
+            BlockchainError::UnknownHtlc(_) => "unknown_htlc",
+            BlockchainError::HtlcExpired(_) => "htlc_expired",
+            BlockchainError::HtlcNotExpired(_) => "htlc_not_expired",
+            BlockchainError::InvalidBridgeProof(_) => "invalid_bridge_proof",
+            BlockchainError::UnknownBridge(_) => "unknown_bridge",
+            BlockchainError::BridgeMessageRelayed(_) => "bridge_message_relayed",
+            BlockchainError::ArithmeticOverflow => "arithmetic_overflow",
+            BlockchainError::GenesisOnly => "genesis_only",
+            BlockchainError::FaucetDisabled => "faucet_disabled",
+            BlockchainError::FaucetLimitExceeded { .. } => "faucet_limit_exceeded",
+            BlockchainError::EmptyMempool => "empty_mempool",
+            BlockchainError::ReplacementFeeTooLow { .. } => "replacement_fee_too_low",
+            BlockchainError::MempoolFull => "mempool_full",
+            BlockchainError::PendingBalanceExceeded { .. } => "pending_balance_exceeded",
+            BlockchainError::Storage(_) => "storage",
+            BlockchainError::Serialization(_) => "serialization",
+            BlockchainError::InvalidContract(_) => "invalid_contract",
+            BlockchainError::NotAContract(_) => "not_a_contract",
+            BlockchainError::ContractFailed(_) => "contract_failed",
+            BlockchainError::OutOfGas => "out_of_gas",
+            BlockchainError::InvalidConfig(_) => "invalid_config",
+            BlockchainError::InvalidHash(_) => "invalid_hash",
+            BlockchainError::Keystore(_) => "keystore",
+            BlockchainError::WrongPassword(_) => "wrong_password",
+            BlockchainError::InvalidMnemonic(_) => "invalid_mnemonic",
+            BlockchainError::InvalidAddress(_) => "invalid_address",
+            BlockchainError::InvalidAlias(_) => "invalid_alias",
+            BlockchainError::AliasTaken(_) => "alias_taken",
+            BlockchainError::InvalidName(_) => "invalid_name",
+            BlockchainError::NameTaken(_) => "name_taken",
+            BlockchainError::ReservedAccount(_) => "reserved_account",
+            BlockchainError::InvalidMultisig(_) => "invalid_multisig",
+            BlockchainError::InvalidMetadata(_) => "invalid_metadata",
+            BlockchainError::InvalidVesting(_) => "invalid_vesting",
+            BlockchainError::TokensLocked { .. } => "tokens_locked",
+            BlockchainError::MultisigThresholdNotMet { .. } => "multisig_threshold_not_met",
+            BlockchainError::BlockPruned { .. } => "block_pruned",
+            BlockchainError::TransactionAlreadyIncluded(_) => "transaction_already_included",
+            BlockchainError::NotStakeable(_) => "not_stakeable",
+            BlockchainError::InsufficientStake => "insufficient_stake",
+            BlockchainError::AccountHasStake(_) => "account_has_stake",
+            BlockchainError::NotAnAdmin(_) => "not_an_admin",
+            BlockchainError::AccountFrozen(_) => "account_frozen",
+            BlockchainError::InvalidBlockSignature => "invalid_block_signature",
+            BlockchainError::InvalidProducer { .. } => "invalid_producer",
+            BlockchainError::NoValidators => "no_validators",
+            BlockchainError::UnknownAuthority(_) => "unknown_authority",
+            BlockchainError::InvalidEvidence(_) => "invalid_evidence",
+            BlockchainError::InvalidBlockFile(_) => "invalid_block_file",
+            BlockchainError::CheckpointMismatch { .. } => "checkpoint_mismatch",
+            BlockchainError::BlockFinalized { .. } => "block_finalized",
+            BlockchainError::CheckpointNotApproved { .. } => "checkpoint_not_approved",
+        }
+    }
+
+    /// Wraps the error as caused by the block at the provided height
+    pub(crate) fn in_block(self, height: usize) -> Self {
+        BlockchainError::InvalidBlock {
+            height,
+            source: Box::new(self),
+        }
+    }
+
+    /// Wraps the error as caused by the transaction at the provided index
+    pub(crate) fn in_transaction(self, index: usize) -> Self {
+        BlockchainError::InvalidTransaction {
+            index,
+            source: Box::new(self),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{AccountType, BlockchainError};
+
+use super::vesting;
+use super::world_state::WorldState;
+
+/// The (reserved) account holding the escrow registry: its store maps the ids of the open
+/// escrows to their records (see `EscrowRecord`), whilst its tokens are the escrowed ones.
+pub const ESCROW_REGISTRY: &str = "@escrows";
+
+/// An open escrow: the amount of tokens the sender put aside for the receiver, until they
+/// are either released (to the receiver) or refunded (to the sender).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscrowRecord {
+    pub from: String,
+
+    pub to: String,
+
+    /// The account settling disputes: it may both release and refund the escrow
+    pub arbiter: String,
+
+    pub amount: u128,
+}
+
+impl EscrowRecord {
+    /// Will parse the record as stored by the registry (JSON)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        serde_json::from_str(value).ok()
+    }
+}
+
+/// Will open the escrow of the id, moving the amount of the sender's tokens to the registry
+/// (which is created with the first escrow), and return its record.<br/>
+/// The receiver and the arbiter (which may be aliases) have to exist.
+pub(crate) fn create<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    from: &str,
+    to: &str,
+    arbiter: &str,
+    amount: u128,
+) -> Result<EscrowRecord, BlockchainError> {
+    if world_state.get_escrow(id).is_some() {
+        return Err(BlockchainError::EscrowExists(id.to_string()));
+    }
+    let to = world_state.resolve_account_id(to);
+    let arbiter = world_state.resolve_account_id(arbiter);
+    for party in [&to, &arbiter] {
+        if !world_state.account_exists(party) {
+            return Err(BlockchainError::UnknownAccount(party.clone()));
+        }
+    }
+
+    let sender = world_state
+        .get_account_by_id(from)
+        .ok_or_else(|| BlockchainError::UnknownAccount(from.to_string()))?;
+    if sender.frozen {
+        return Err(BlockchainError::AccountFrozen(from.to_string()));
+    }
+    let remaining = sender
+        .tokens
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    vesting::check_unlocked(world_state, from, remaining)?;
+    world_state.get_account_by_id_mut(from).unwrap().tokens = remaining;
+
+    if !world_state.account_exists(ESCROW_REGISTRY) {
+        world_state.create_account(ESCROW_REGISTRY.into(), AccountType::User)?;
+    }
+    let record = EscrowRecord {
+        from: from.to_string(),
+        to,
+        arbiter,
+        amount,
+    };
+    let json = serde_json::to_string(&record)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()))?;
+    let registry = world_state
+        .get_account_by_id_mut(ESCROW_REGISTRY)
+        .expect("The registry exists");
+    registry.tokens = registry
+        .tokens
+        .checked_add(amount)
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    registry.store.insert(id.to_string(), json);
+    Ok(record)
+}
+
+/// Will close the escrow, paying its tokens to the receiver if it is released (which the
+/// sender or the arbiter may do), or back to the sender if it is refunded (which the
+/// receiver or the arbiter may do), and return its record
+pub(crate) fn settle<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    sender: &str,
+    release: bool,
+) -> Result<EscrowRecord, BlockchainError> {
+    let record = world_state
+        .get_escrow(id)
+        .ok_or_else(|| BlockchainError::UnknownEscrow(id.to_string()))?;
+    let (party, beneficiary) = match release {
+        true => (&record.from, &record.to),
+        false => (&record.to, &record.from),
+    };
+    if sender != party && sender != record.arbiter {
+        return Err(BlockchainError::NotEscrowParty(id.to_string()));
+    }
+    if !world_state.account_exists(beneficiary) {
+        return Err(BlockchainError::UnknownAccount(beneficiary.clone()));
+    }
+
+    let registry = world_state
+        .get_account_by_id_mut(ESCROW_REGISTRY)
+        .expect("The registry holds the escrow");
+    registry.store.remove(id);
+    registry.tokens = registry
+        .tokens
+        .checked_sub(record.amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    let beneficiary = world_state.get_account_by_id_mut(beneficiary).unwrap();
+    beneficiary.tokens = beneficiary
+        .tokens
+        .checked_add(record.amount)
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    Ok(record)
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{create, settle, ESCROW_REGISTRY};
+    use crate::{AccountType, Blockchain, BlockchainError, WorldState};
+
+    #[test]
+    fn escrows_are_released_or_refunded() {
+        let mut chain = Blockchain::new();
+        for id in ["alice", "bob", "judge"] {
+            chain.create_account(id.into(), AccountType::User).unwrap();
+        }
+        chain.get_account_by_id_mut("alice").unwrap().tokens = 100;
+
+        let record = create(&mut chain, "e1", "alice", "bob", "judge", 30).unwrap();
+        assert_eq!(Some(record), chain.get_escrow("e1"));
+        create(&mut chain, "e2", "alice", "bob", "judge", 50).unwrap();
+        assert_eq!(Some(20), chain.get_balance("alice"));
+        assert_eq!(Some(80), chain.get_balance(ESCROW_REGISTRY));
+        assert_eq!(
+            Err(BlockchainError::EscrowExists("e1".into())),
+            create(&mut chain, "e1", "alice", "bob", "judge", 1).map(|_| ())
+        );
+        assert_eq!(
+            Err(BlockchainError::UnknownAccount("carol".into())),
+            create(&mut chain, "e3", "alice", "bob", "carol", 1).map(|_| ())
+        );
+        assert_eq!(
+            Err(BlockchainError::InsufficientBalance),
+            create(&mut chain, "e3", "alice", "bob", "judge", 21).map(|_| ())
+        );
+
+        // The receiver can't release the escrow to itself, the sender can
+        assert_eq!(
+            Err(BlockchainError::NotEscrowParty("e1".into())),
+            settle(&mut chain, "e1", "bob", true).map(|_| ())
+        );
+        settle(&mut chain, "e1", "alice", true).unwrap();
+        assert_eq!(Some(30), chain.get_balance("bob"));
+        assert_eq!(None, chain.get_escrow("e1"));
+        assert_eq!(
+            Err(BlockchainError::UnknownEscrow("e1".into())),
+            settle(&mut chain, "e1", "alice", true).map(|_| ())
+        );
+
+        // The sender can't refund the escrow to itself, the arbiter can
+        assert_eq!(
+            Err(BlockchainError::NotEscrowParty("e2".into())),
+            settle(&mut chain, "e2", "alice", false).map(|_| ())
+        );
+        settle(&mut chain, "e2", "judge", false).unwrap();
+        assert_eq!(Some(70), chain.get_balance("alice"));
+        assert_eq!(Some(0), chain.get_balance(ESCROW_REGISTRY));
+    }
+}
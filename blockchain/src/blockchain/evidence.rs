@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Address, Block, BlockchainError};
+
+/// Proves that a validator misbehaved by signing two conflicting blocks: different
+/// blocks that follow the same block (so are meant for the same height).<br/>
+/// It is reported through a `TransactionData::ReportMisbehavior` transaction,
+/// which slashes the stake of the offender.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Evidence {
+    /// One of the conflicting blocks (including its transactions)
+    pub(crate) first: Block,
+
+    /// The other conflicting block (including its transactions)
+    pub(crate) second: Block,
+}
+
+impl Evidence {
+    /// C'tor.
+    pub fn new(first: Block, second: Block) -> Self {
+        Evidence { first, second }
+    }
+
+    /// Will check that both blocks are intact, conflict with each other and are signed
+    /// by the same producer, returning the address of that producer (the offender).
+    pub fn verify(&self) -> Result<Address, BlockchainError> {
+        let invalid = |reason: &str| Err(BlockchainError::InvalidEvidence(reason.into()));
+
+        // Pruned blocks are rejected, since their hashes can't be recalculated
+        for block in [&self.first, &self.second] {
+            if block.is_pruned() || !block.verify_own_hash() {
+                return invalid("The hash of a block doesn't match its contents");
+            }
+        }
+        if self.first.hash == self.second.hash {
+            return invalid("The blocks are the same");
+        }
+        if self.first.header.prev_hash != self.second.header.prev_hash {
+            return invalid("The blocks don't follow the same block");
+        }
+
+        match (self.first.producer(), self.second.producer()) {
+            (Some(first), Some(second)) if first == second => Ok(first),
+            (Some(_), Some(_)) => invalid("The blocks are signed by different producers"),
+            _ => invalid("A block is unsigned or has an invalid signature"),
+        }
+    }
+}
+
+// Evidences are compared by the blocks they consist of
+impl PartialEq for Evidence {
+    fn eq(&self, other: &Self) -> bool {
+        let signed = |block: &Block| (block.hash, block.signature.clone());
+        signed(&self.first) == signed(&other.first) && signed(&self.second) == signed(&other.second)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::Evidence;
+    use crate::{Block, BlockchainError, Hash, Keypair, Transaction, TransactionData};
+
+    /// Creates a block following the previous block, signed by the keypair
+    fn signed_block(prev_hash: Option<Hash>, id: &str, keypair: &Keypair) -> Block {
+        let mut block = Block::new(prev_hash);
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount(id.into()),
+            0,
+        ));
+        block.sign(keypair);
+        block
+    }
+
+    #[test]
+    fn conflicting_blocks_prove_misbehavior() {
+        let validator = Keypair::generate();
+        let prev_hash = Some(Hash::digest(b"genesis"));
+        let first = signed_block(prev_hash, "bob", &validator);
+        let second = signed_block(prev_hash, "carol", &validator);
+
+        let evidence = Evidence::new(first.clone(), second.clone());
+        assert_eq!(Ok(validator.address()), evidence.verify());
+
+        let invalid = |evidence: Evidence| {
+            matches!(evidence.verify(), Err(BlockchainError::InvalidEvidence(_)))
+        };
+        assert!(invalid(Evidence::new(first.clone(), first.clone())));
+        assert!(invalid(Evidence::new(first.without_body(), second.clone())));
+        let other = signed_block(None, "carol", &validator);
+        assert!(invalid(Evidence::new(first.clone(), other)));
+        let other = signed_block(prev_hash, "carol", &Keypair::generate());
+        assert!(invalid(Evidence::new(first.clone(), other)));
+
+        // Re-signing a tampered block doesn't make it conflicting
+        let mut tampered = first.clone();
+        tampered.header.nonce += 1;
+        tampered.sign(&validator);
+        assert!(invalid(Evidence::new(first, tampered)));
+    }
+}
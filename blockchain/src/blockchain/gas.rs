@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockchainError, TransactionData, TransactionReceipt};
+
+/// The gas every transaction costs, on top of the cost of its operation.
+pub const TRANSACTION_GAS: u64 = 1_000;
+
+/// The gas it costs to create an account (user or contract).
+pub const ACCOUNT_GAS: u64 = 5_000;
+
+/// The gas it costs to store a byte (of a store value or of the code of a contract).
+pub const BYTE_GAS: u64 = 10;
+
+/// The gas it costs to call a contract, on top of the gas used by executing its code.
+pub const CALL_GAS: u64 = 2_000;
+
+/// The outcome of executing the transactions of a block.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    /// The gas used by each transaction (in the order of the block)
+    pub transactions_gas_used: Vec<u64>,
+
+    /// The gas used by the whole block
+    pub gas_used: u64,
+
+    /// The receipts of the transactions (in the order of the block)
+    pub receipts: Vec<TransactionReceipt>,
+}
+
+impl ExecutionResult {
+    /// Will add the receipt of the next transaction of the block
+    pub(crate) fn add(&mut self, receipt: TransactionReceipt) -> Result<(), BlockchainError> {
+        self.gas_used = self
+            .gas_used
+            .checked_add(receipt.gas_used)
+            .ok_or(BlockchainError::ArithmeticOverflow)?;
+        self.transactions_gas_used.push(receipt.gas_used);
+        self.receipts.push(receipt);
+        Ok(())
+    }
+}
+
+impl TransactionData {
+    /// Will return the gas the operation costs, without the gas used by
+    /// executing contract code (which is only known after the execution).<br/>
+    /// The coinbase is free, since it is part of producing the block.
+    pub fn gas_cost(&self) -> u64 {
+        let bytes = |len: usize| (len as u64).saturating_mul(BYTE_GAS);
+        let operation = match self {
+            TransactionData::Coinbase { .. } => return 0,
+            TransactionData::CreateUserAccount(_) | TransactionData::CreateAdminAccount(_) => {
+                ACCOUNT_GAS
+            }
+            TransactionData::CreateVestingAccount { .. }
+            | TransactionData::EscrowCreate { .. }
+            | TransactionData::HtlcLock { .. }
+            | TransactionData::BridgeIn { .. } => ACCOUNT_GAS,
+            TransactionData::CreateUserAccounts(accounts) => {
+                ACCOUNT_GAS.saturating_mul(accounts.len() as u64)
+            }
+            TransactionData::ChangeStoreValue { key, value }
+            | TransactionData::SetAccountMetadata { key, value } => bytes(key.len() + value.len()),
+            TransactionData::Approve { spender, .. } => bytes(spender.len()),
+            TransactionData::MintNft { id, metadata } => {
+                ACCOUNT_GAS.saturating_add(bytes(id.len() + metadata.len()))
+            }
+            TransactionData::CreateAsset { asset, .. } => {
+                ACCOUNT_GAS.saturating_add(bytes(asset.len()))
+            }
+            TransactionData::TransferTokens { .. }
+            | TransactionData::TransferFrom { .. }
+            | TransactionData::CreateTokens { .. }
+            | TransactionData::DeleteAccount { .. }
+            | TransactionData::Stake { .. }
+            | TransactionData::Unstake { .. }
+            | TransactionData::TransferNft { .. }
+            | TransactionData::FreezeAccount { .. }
+            | TransactionData::UnfreezeAccount { .. }
+            | TransactionData::Faucet { .. }
+            | TransactionData::EscrowRelease { .. }
+            | TransactionData::EscrowRefund { .. }
+            | TransactionData::HtlcClaim { .. }
+            | TransactionData::HtlcRefund { .. }
+            | TransactionData::BridgeOut { .. } => 0,
+            TransactionData::DeployContract { code } => {
+                ACCOUNT_GAS.saturating_add(bytes(code.len()))
+            }
+            TransactionData::ReportMisbehavior { .. } => CALL_GAS,
+            TransactionData::CallContract { input, .. } => {
+                CALL_GAS.saturating_add(bytes(input.len()))
+            }
+            TransactionData::RegisterAlias { alias: name }
+            | TransactionData::RegisterName { name } => {
+                ACCOUNT_GAS.saturating_add(bytes(name.len()))
+            }
+            TransactionData::CreateMultisigAccount { owners, .. } => {
+                ACCOUNT_GAS.saturating_add(bytes(owners.iter().map(String::len).sum()))
+            }
+            // The operations of a batch share the gas of the transaction
+            TransactionData::Batch(operations) => operations
+                .iter()
+                .map(|operation| operation.gas_cost().saturating_sub(TRANSACTION_GAS))
+                .fold(0, u64::saturating_add),
+        };
+        TRANSACTION_GAS.saturating_add(operation)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{ExecutionResult, ACCOUNT_GAS, BYTE_GAS, TRANSACTION_GAS};
+    use crate::{Hash, ReceiptStatus, TransactionData, TransactionReceipt};
+
+    fn receipt(gas_used: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: Hash::digest(&[]),
+            status: ReceiptStatus::Success,
+            gas_used,
+            events: Vec::new(),
+            balance_changes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn gas_cost_per_operation() {
+        assert_eq!(
+            TRANSACTION_GAS + ACCOUNT_GAS,
+            TransactionData::CreateUserAccount("alice".into()).gas_cost()
+        );
+        assert_eq!(
+            TRANSACTION_GAS + 8 * BYTE_GAS,
+            TransactionData::ChangeStoreValue {
+                key: "color".into(),
+                value: "red".into()
+            }
+            .gas_cost()
+        );
+        assert_eq!(
+            0,
+            TransactionData::Coinbase {
+                receiver: "alice".into(),
+                amount: 1
+            }
+            .gas_cost()
+        );
+    }
+
+    #[test]
+    fn execution_result_sums_gas() {
+        let mut result = ExecutionResult::default();
+        result.add(receipt(10)).unwrap();
+        result.add(receipt(5)).unwrap();
+        assert_eq!(15, result.gas_used);
+        assert_eq!(vec![10, 5], result.transactions_gas_used);
+        assert_eq!(2, result.receipts.len());
+        assert!(result.add(receipt(u64::MAX)).is_err());
+    }
+}
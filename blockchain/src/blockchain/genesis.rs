@@ -0,0 +1,334 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::policy::DEFAULT_CHAIN_ID;
+use crate::{Block, Blockchain, BlockchainError, Transaction, TransactionData, ValidationPolicy};
+
+/// The time (unix epoch, in milliseconds) of the genesis blocks created by
+/// `Blockchain::create_genesis`: 2020-01-01
+const CANONICAL_GENESIS_TIMESTAMP: u64 = 1_577_836_800_000;
+
+/// Describes a network (dev, test, main, ...): its initial accounts and the
+/// consensus parameters, so that new networks don't need code changes.<br/>
+/// It can be loaded from TOML or JSON.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    /// Identifies the network (it overrides the one of the consensus parameters)
+    pub chain_id: String,
+
+    /// The time (unix epoch, in milliseconds) of the genesis block (the current time if not set)
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+
+    /// The accounts that exist from the beginning
+    #[serde(default)]
+    pub accounts: Vec<GenesisAccount>,
+
+    /// If set, the plain user accounts are all created by a single transaction (see
+    /// `TransactionData::CreateUserAccounts`), rather than by a couple of transactions each,
+    /// which keeps the genesis blocks of large initial allocations small.<br/>
+    /// It changes the genesis block, so it can't be turned on for an existing network.
+    #[serde(default)]
+    pub batch_accounts: bool,
+
+    /// The consensus (validation) parameters of the network
+    #[serde(default)]
+    pub consensus: ValidationPolicy,
+}
+
+/// An account created by the genesis block
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    /// The id of the account
+    pub id: String,
+
+    /// The amount of tokens the account starts with
+    #[serde(default)]
+    pub balance: u128,
+
+    /// The amount of its tokens the account stakes (as a validator, the id has to be an address)
+    #[serde(default)]
+    pub stake: u128,
+
+    /// If set, the account is an admin of the network (see `AccountType::Admin`)
+    #[serde(default)]
+    pub admin: bool,
+}
+
+impl GenesisConfig {
+    /// Will parse the configuration from TOML.<br/>
+    /// TOML integers are 64 bit (signed), so larger amounts have to be configured using JSON.
+    pub fn from_toml(toml: &str) -> Result<Self, BlockchainError> {
+        // The TOML deserializer doesn't support u128, so the document is converted to JSON first
+        let value: toml::Value = toml::from_str(toml).map_err(config_error)?;
+        serde_json::to_value(value)
+            .and_then(serde_json::from_value)
+            .map_err(config_error)
+    }
+
+    /// Will parse the configuration from JSON
+    pub fn from_json(json: &str) -> Result<Self, BlockchainError> {
+        serde_json::from_str(json).map_err(config_error)
+    }
+
+    /// Will load the configuration from a file, which is parsed as JSON
+    /// if it has the `.json` extension and as TOML otherwise
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|err| {
+            BlockchainError::InvalidConfig(format!("{}: {}", path.display(), err))
+        })?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&content),
+            _ => Self::from_toml(&content),
+        }
+    }
+
+    /// Will return the validation policy of the network
+    pub fn policy(&self) -> ValidationPolicy {
+        ValidationPolicy {
+            chain_id: self.chain_id.clone(),
+            ..self.consensus.clone()
+        }
+    }
+
+    /// Will build the (unsealed) genesis block, creating and funding the accounts (the plain
+    /// user accounts all at once, if `batch_accounts` is set)
+    fn genesis_block(&self) -> Block {
+        let mut genesis = Block::new(None);
+        if let Some(timestamp) = self.timestamp {
+            genesis.set_timestamp(timestamp);
+        }
+        // Stamping the transactions like the block, so that the genesis block is reproducible
+        let created_at = genesis.timestamp();
+
+        let (users, others): (Vec<_>, Vec<_>) = self
+            .accounts
+            .iter()
+            .partition(|account| self.batch_accounts && !account.admin && account.stake == 0);
+        if let Some(first) = users.first() {
+            let accounts = users
+                .iter()
+                .map(|account| (account.id.clone(), account.balance))
+                .collect();
+            genesis.add_transaction(
+                Transaction::new(
+                    first.id.clone(),
+                    TransactionData::CreateUserAccounts(accounts),
+                    0,
+                )
+                .with_chain_id(self.chain_id.clone())
+                .with_created_at(created_at),
+            );
+        }
+
+        for account in others {
+            let create_account = match account.admin {
+                true => TransactionData::CreateAdminAccount(account.id.clone()),
+                false => TransactionData::CreateUserAccount(account.id.clone()),
+            };
+            genesis.add_transaction(
+                Transaction::new(account.id.clone(), create_account, 0)
+                    .with_chain_id(self.chain_id.clone())
+                    .with_created_at(created_at),
+            );
+            if account.balance > 0 {
+                genesis.add_transaction(
+                    Transaction::new(
+                        account.id.clone(),
+                        TransactionData::CreateTokens {
+                            receiver: account.id.clone(),
+                            amount: account.balance,
+                            asset: None,
+                        },
+                        0,
+                    )
+                    .with_chain_id(self.chain_id.clone())
+                    .with_created_at(created_at),
+                );
+            }
+            if account.stake > 0 {
+                genesis.add_transaction(
+                    Transaction::new(
+                        account.id.clone(),
+                        TransactionData::Stake {
+                            amount: account.stake,
+                        },
+                        0,
+                    )
+                    .with_chain_id(self.chain_id.clone())
+                    .with_created_at(created_at),
+                );
+            }
+        }
+        genesis
+    }
+}
+
+fn config_error<E: ToString>(err: E) -> BlockchainError {
+    BlockchainError::InvalidConfig(err.to_string())
+}
+
+impl Blockchain {
+    /// Will create a Blockchain for the network described by the configuration,
+    /// starting with its genesis block.
+    pub fn from_genesis(config: &GenesisConfig) -> Result<Self, BlockchainError> {
+        let mut chain = Blockchain::with_policy(config.policy());
+        let mut genesis = config.genesis_block();
+        chain.seal_block(&mut genesis)?;
+        chain.append_block(genesis)?;
+        Ok(chain)
+    }
+
+    /// Will create the canonical genesis block of the development network (see
+    /// `Blockchain::new`), creating and funding the accounts (ids and balances).<br/>
+    /// It is reproducible: the accounts are created in the order of their ids, at a fixed
+    /// time (2020-01-01), so that every node given the same accounts gets the same block,
+    /// down to the byte (and the hash).
+    pub fn create_genesis(accounts: &[(&str, u128)]) -> Result<Block, BlockchainError> {
+        let mut accounts = accounts.to_vec();
+        accounts.sort();
+        let config = GenesisConfig {
+            chain_id: DEFAULT_CHAIN_ID.into(),
+            timestamp: Some(CANONICAL_GENESIS_TIMESTAMP),
+            accounts: accounts
+                .into_iter()
+                .map(|(id, balance)| GenesisAccount {
+                    id: id.into(),
+                    balance,
+                    stake: 0,
+                    admin: false,
+                })
+                .collect(),
+            batch_accounts: false,
+            consensus: ValidationPolicy::default(),
+        };
+        let mut genesis = config.genesis_block();
+        Blockchain::with_policy(config.policy()).seal_block(&mut genesis)?;
+        Ok(genesis)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::GenesisConfig;
+    use crate::storage::encode;
+    use crate::{Blockchain, BlockchainError, Transaction, TransactionData, WorldState};
+
+    const TOML: &str = r#"
+        chain_id = "test"
+        timestamp = 1000
+
+        [[accounts]]
+        id = "alice"
+        balance = 500
+
+        [[accounts]]
+        id = "bob"
+
+        [consensus]
+        block_reward = 5
+
+        [consensus.difficulty]
+        initial_difficulty = 2
+    "#;
+
+    #[test]
+    fn chain_from_toml_genesis() {
+        let config = GenesisConfig::from_toml(TOML).unwrap();
+        let bc = Blockchain::from_genesis(&config).unwrap();
+
+        assert_eq!(1, bc.len());
+        assert_eq!(Some(500), bc.get_balance("alice"));
+        assert_eq!(Some(0), bc.get_balance("bob"));
+        assert_eq!("test", bc.policy().chain_id);
+        assert_eq!(5, bc.policy().block_reward);
+        assert_eq!(2, bc.policy().difficulty.initial_difficulty);
+        // Parameters that are not configured keep their defaults
+        assert_eq!(10, bc.policy().difficulty.retarget_interval);
+        assert_eq!(1000, bc.blocks[0].timestamp());
+        assert!(bc.check_validity().is_ok());
+    }
+
+    #[test]
+    fn json_and_toml_are_equivalent() {
+        let json = r#"{
+            "chain_id": "test",
+            "timestamp": 1000,
+            "accounts": [{ "id": "alice", "balance": 500 }, { "id": "bob" }],
+            "consensus": { "block_reward": 5, "difficulty": { "initial_difficulty": 2 } }
+        }"#;
+        assert_eq!(
+            GenesisConfig::from_toml(TOML).unwrap(),
+            GenesisConfig::from_json(json).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        assert!(matches!(
+            GenesisConfig::from_toml("accounts = 1"),
+            Err(BlockchainError::InvalidConfig(_))
+        ));
+
+        // A genesis block needs at least one account
+        let config = GenesisConfig::from_toml("chain_id = \"empty\"").unwrap();
+        assert_eq!(
+            Err(BlockchainError::EmptyBlock),
+            Blockchain::from_genesis(&config).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn large_allocations_are_batched() {
+        let mut toml = String::from("chain_id = \"test\"\nbatch_accounts = true\n");
+        for i in 0..1000 {
+            toml.push_str(&format!(
+                "[[accounts]]\nid = \"user{}\"\nbalance = {}\n",
+                i, i
+            ));
+        }
+        toml.push_str("[[accounts]]\nid = \"root\"\nbalance = 5\nadmin = true\n");
+        let config = GenesisConfig::from_toml(&toml).unwrap();
+        let mut bc = Blockchain::from_genesis(&config).unwrap();
+
+        // The users are created by a single transaction, the admin by its own ones
+        assert_eq!(3, bc.blocks[0].body.transactions.len());
+        assert_eq!(Some(0), bc.get_balance("user0"));
+        assert_eq!(Some(999), bc.get_balance("user999"));
+        assert_eq!(Some(5), bc.get_balance("root"));
+        assert!(bc.check_validity().is_ok());
+
+        // Only the genesis block may create them
+        let batch = Transaction::new(
+            "root".into(),
+            TransactionData::CreateUserAccounts(vec![("carol".into(), 10)]),
+            0,
+        );
+        assert_eq!(
+            Err(BlockchainError::GenesisOnly),
+            batch.execute(&mut bc, &false).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn canonical_genesis_blocks_are_reproducible() {
+        let genesis = Blockchain::create_genesis(&[("alice", 500), ("bob", 0)]).unwrap();
+        let reordered = Blockchain::create_genesis(&[("bob", 0), ("alice", 500)]).unwrap();
+        assert_eq!(genesis.hash(), reordered.hash());
+        assert_eq!(encode(&genesis).unwrap(), encode(&reordered).unwrap());
+
+        let mut bc = Blockchain::new();
+        bc.append_block(genesis).unwrap();
+        assert_eq!(Some(500), bc.get_balance("alice"));
+        assert_eq!(Some(0), bc.get_balance("bob"));
+        assert!(bc.check_validity().is_ok());
+    }
+}
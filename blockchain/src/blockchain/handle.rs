@@ -0,0 +1,158 @@
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{
+    Block, Blockchain, BlockchainError, ChainEvent, ExecutionResult, Keypair, Transaction,
+};
+
+/// A Blockchain shared between threads: clones of the handle refer to the same chain,
+/// which is guarded by a single RwLock, so that any number of readers query it at once,
+/// whilst changes (submitting transactions, appending and producing blocks) are exclusive.<br/>
+/// Producing a block only locks the chain for building and for appending it: the block is
+/// mined (the expensive part under Proof of Work) in between, without holding the lock, so
+/// that queries keep being answered meanwhile.<br/>
+/// Locking order:
+/// - a thread holds at most one guard (of `read` or `write`) of a chain at a time, and none
+///   while calling the other methods of the handle, since the lock can't be upgraded (a
+///   reader waiting for the write lock deadlocks)
+/// - the chain's lock comes last: locks of the caller are taken before it (if at all) and no
+///   other lock is waited for while holding a guard, so that changes of the chain never wait
+///   for the caller
+/// - the events of `subscribe` are sent whilst the chain is changed, so receivers mustn't
+///   hold a guard while waiting for them
+///
+/// A thread panicking whilst changing the chain poisons the lock, as the chain may be left
+/// half-changed: the handle panics from then on.
+#[derive(Clone, Debug, Default)]
+pub struct ChainHandle(Arc<RwLock<Blockchain>>);
+
+impl ChainHandle {
+    /// C'tor, sharing the chain
+    pub fn new(chain: Blockchain) -> Self {
+        ChainHandle(Arc::new(RwLock::new(chain)))
+    }
+
+    /// Will lock the chain for reading, waiting for a change in progress (if any).<br/>
+    /// The guard should be dropped soon, since changes wait for it.
+    pub fn read(&self) -> RwLockReadGuard<'_, Blockchain> {
+        self.0.read().expect("The chain was poisoned by a panic")
+    }
+
+    /// Will lock the chain for changing it, waiting for all readers (and changes) to finish
+    pub fn write(&self) -> RwLockWriteGuard<'_, Blockchain> {
+        self.0.write().expect("The chain was poisoned by a panic")
+    }
+
+    /// Will submit the transaction (see `Blockchain::submit_transaction`)
+    pub fn submit_transaction(&self, transaction: Transaction) -> Result<(), BlockchainError> {
+        self.write().submit_transaction(transaction)
+    }
+
+    /// Will append the block (see `Blockchain::append_block`)
+    pub fn append_block(&self, block: Block) -> Result<ExecutionResult, BlockchainError> {
+        self.write().append_block(block)
+    }
+
+    /// Will produce a block like `Blockchain::produce_block` does, mining it without holding
+    /// the lock.<br/>
+    /// If another block is appended meanwhile, the produced one is rejected (as it doesn't
+    /// follow the new tip) and its transactions are dropped.
+    pub fn produce_block(&self, max_txs: usize) -> Result<ExecutionResult, BlockchainError> {
+        self.produce(max_txs, None)
+    }
+
+    /// Will produce a block signed by the keypair like `Blockchain::produce_signed_block`
+    /// does, mining it without holding the lock (see `produce_block`).
+    pub fn produce_signed_block(
+        &self,
+        max_txs: usize,
+        keypair: &Keypair,
+    ) -> Result<ExecutionResult, BlockchainError> {
+        self.produce(max_txs, Some(keypair))
+    }
+
+    /// Will subscribe to the events of the chain (see `Blockchain::subscribe`)
+    pub fn subscribe(&self) -> Receiver<ChainEvent> {
+        self.write().subscribe()
+    }
+
+    fn produce(
+        &self,
+        max_txs: usize,
+        keypair: Option<&Keypair>,
+    ) -> Result<ExecutionResult, BlockchainError> {
+        let (mut block, difficulty) = {
+            let mut chain = self.write();
+            if let (Some(keypair), Some(expected)) = (keypair, chain.next_producer()) {
+                if expected != keypair.address().to_string() {
+                    return Err(BlockchainError::InvalidProducer { expected });
+                }
+            }
+            let block = chain.assemble_block(max_txs)?;
+            (block, chain.next_difficulty())
+        };
+
+        block.mine(difficulty);
+        if let Some(keypair) = keypair {
+            block.sign(keypair);
+        }
+        self.append_block(block)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::thread;
+
+    use super::ChainHandle;
+    use crate::test_utils::{account_id, keypair, ChainBuilder, TEST_CHAIN_ID};
+    use crate::{Transaction, TransactionData, WorldState};
+
+    #[test]
+    fn readers_and_producers_share_the_chain() {
+        let handle = ChainHandle::new(
+            ChainBuilder::new()
+                .with_account("alice", 100)
+                .with_account("bob", 0)
+                .build(),
+        );
+        let transfer = |nonce| {
+            let transfer = TransactionData::TransferTokens {
+                to: account_id("bob"),
+                amount: 1,
+                asset: None,
+            };
+            let mut transaction = Transaction::new(account_id("alice"), transfer, nonce)
+                .with_chain_id(TEST_CHAIN_ID.into());
+            transaction.sign(&keypair("alice"));
+            transaction
+        };
+
+        let submitters: Vec<_> = (0..4)
+            .map(|nonce| {
+                let handle = handle.clone();
+                let transaction = transfer(nonce);
+                thread::spawn(move || handle.submit_transaction(transaction))
+            })
+            .collect();
+        let reader = {
+            let handle = handle.clone();
+            thread::spawn(move || (0..100).map(|_| handle.read().len()).max())
+        };
+        for submitter in submitters {
+            submitter.join().unwrap().unwrap();
+        }
+        handle.produce_block(10).unwrap();
+
+        assert!(reader.join().unwrap().unwrap() <= 2);
+        let chain = handle.read();
+        assert_eq!(2, chain.len());
+        assert_eq!(Some(4), chain.get_balance(&account_id("bob")));
+        assert!(chain.check_validity().is_ok());
+    }
+}
@@ -0,0 +1,156 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use blake2::{Blake2b, Digest};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::BlockchainError;
+
+/// The size (in bytes) of a (Blake2b) hash.
+pub const HASH_BYTE_SIZE: usize = 64;
+
+/// A (Blake2b) hash of a block or a transaction.<br/>
+/// It is displayed, parsed and serialized as a lowercase hex string.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hash([u8; HASH_BYTE_SIZE]);
+
+impl Hash {
+    /// C'tor.
+    pub fn new(bytes: [u8; HASH_BYTE_SIZE]) -> Self {
+        Hash(bytes)
+    }
+
+    /// Will hash the bytes using Blake2 hasher
+    pub fn digest(bytes: &[u8]) -> Self {
+        let mut hasher = Blake2b::new();
+        hasher.update(bytes);
+        Self::from_slice(&hasher.finalize()).expect("Blake2b produces 64 byte hashes")
+    }
+
+    /// Will create a hash out of a slice, if it has the right size
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        let mut hash = [0u8; HASH_BYTE_SIZE];
+        if bytes.len() != HASH_BYTE_SIZE {
+            return None;
+        }
+        hash.copy_from_slice(bytes);
+        Some(Hash(hash))
+    }
+
+    /// Will return the raw bytes of the hash
+    pub fn as_bytes(&self) -> &[u8; HASH_BYTE_SIZE] {
+        &self.0
+    }
+
+    /// Will return the lowercase hex representation of the hash
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0[..])
+    }
+
+    /// Will return the legacy representation of the hash, which
+    /// interprets every byte as a character (as hashes used to be stored)
+    pub fn to_legacy_string(&self) -> String {
+        self.0.iter().map(|&c| c as char).collect()
+    }
+
+    /// Will parse a hash from its legacy representation (see `to_legacy_string`)
+    pub fn from_legacy_string(legacy: &str) -> Option<Self> {
+        let bytes = legacy
+            .chars()
+            .map(|c| u8::try_from(u32::from(c)).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Self::from_slice(&bytes)
+    }
+}
+
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash({})", self.to_hex())
+    }
+}
+
+impl FromStr for Hash {
+    type Err = BlockchainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; HASH_BYTE_SIZE];
+        hex::decode_to_slice(s, &mut bytes)
+            .map_err(|err| BlockchainError::InvalidHash(err.to_string()))?;
+        Ok(Hash(bytes))
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Hash, HASH_BYTE_SIZE};
+
+    fn sample() -> Hash {
+        let mut bytes = [0u8; HASH_BYTE_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        Hash::new(bytes)
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let hash = sample();
+        let hex = hash.to_string();
+        assert_eq!(2 * HASH_BYTE_SIZE, hex.len());
+        assert!(hex.starts_with("00070e15"));
+        assert_eq!(hash, hex.parse().unwrap());
+    }
+
+    #[test]
+    fn invalid_hex_is_rejected() {
+        assert!("zz".parse::<Hash>().is_err());
+        assert!("0011".parse::<Hash>().is_err());
+    }
+
+    #[test]
+    fn legacy_round_trip() {
+        let hash = sample();
+        let legacy = hash.to_legacy_string();
+        assert_eq!(Some(hash), Hash::from_legacy_string(&legacy));
+        assert_eq!(None, Hash::from_legacy_string("too short"));
+    }
+
+    #[test]
+    fn serde_uses_hex() {
+        let hash = sample();
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(format!("\"{}\"", hash), json);
+        assert_eq!(hash, serde_json::from_str::<Hash>(&json).unwrap());
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+
+use super::canonical;
+use super::merkle::{leaf_hash, MerkleProof};
+use super::miner::meets_difficulty;
+use crate::{BlockchainError, Hash};
+
+/// Will return the leaf of the transaction (hash) in the Merkle tree of the transactions of
+/// a block (see `Block::transactions_root`)
+pub(crate) fn transaction_leaf(transaction_hash: &Hash) -> Hash {
+    leaf_hash(transaction_hash.as_bytes())
+}
+
+/// The part of a block that its hash commits to (see `Block::calculate_hash`), and all that
+/// light clients (and header sync) need: enough to verify that it is linked to the previous
+/// one (and mined), but only committing to the transactions and the world state through
+/// their Merkle roots.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    /// This actually connects the blocks together (`None` for the genesis block)
+    pub prev_hash: Option<Hash>,
+
+    /// The root of the Merkle tree over the transactions of the block
+    pub transactions_root: Hash,
+
+    /// The commitment over the world state after executing the block (see `Blockchain::state_root`)
+    pub state_root: Option<Hash>,
+
+    /// The time (unix epoch, in milliseconds) the block was created at
+    pub timestamp: u64,
+
+    /// Some arbitrary number which is used for Proof of Work
+    pub nonce: u128,
+
+    /// The amount of leading zero bits the hash of the block has to have
+    pub difficulty: u32,
+
+    /// The height of the block in its chain (0 for the genesis block)
+    pub height: u64,
+
+    /// The version of the rules the block follows, which has to be the one active at its
+    /// height (see `ValidationPolicy::version_at`)
+    pub version: u32,
+}
+
+impl BlockHeader {
+    /// Will return the hash of the block, over the canonical encoding (see `canonical::encode`)
+    /// of the header.<br/>
+    /// The hashes of the blocks of version 0 don't commit to their version.
+    pub fn hash(&self) -> Hash {
+        let bytes = |hash: Option<Hash>| hash.map(|hash| hash.as_bytes().to_vec());
+        let fields = (
+            bytes(self.prev_hash),
+            self.transactions_root.as_bytes().to_vec(),
+            self.nonce,
+            self.difficulty,
+            self.timestamp,
+            bytes(self.state_root),
+            self.height,
+        );
+        match self.version {
+            0 => Hash::digest(&canonical::encode(&fields)),
+            version => Hash::digest(&canonical::encode(&(version, fields))),
+        }
+    }
+
+    /// Will check that the hash of the header meets its difficulty
+    pub fn verify(&self) -> Result<(), BlockchainError> {
+        if !meets_difficulty(self.hash().as_bytes(), self.difficulty) {
+            return Err(BlockchainError::InvalidProofOfWork {
+                expected: self.difficulty,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A chain of block headers, as kept by a light (SPV) client: it verifies that the headers
+/// are linked and mined, and that transactions are included by its blocks (given their
+/// Merkle proofs, see `Blockchain::transaction_proof`), without storing any state.<br/>
+/// Unlike a full node, it can't check the rules of the consensus (e.g. the expected difficulty
+/// or the producers of the blocks), nor execute the transactions.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeaderChain {
+    headers: Vec<BlockHeader>,
+}
+
+impl HeaderChain {
+    /// C'tor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Will build the chain out of the headers (starting with the one of the genesis block),
+    /// verifying each of them.
+    pub fn from_headers<I>(headers: I) -> Result<Self, BlockchainError>
+    where
+        I: IntoIterator<Item = BlockHeader>,
+    {
+        let mut chain = Self::new();
+        for header in headers {
+            chain.append(header)?;
+        }
+        Ok(chain)
+    }
+
+    /// Will verify the header and append it, if it is linked to the last header (and at the
+    /// height following it)
+    pub fn append(&mut self, header: BlockHeader) -> Result<(), BlockchainError> {
+        let height = self.headers.len();
+        header.verify().map_err(|err| err.in_block(height))?;
+        if header.prev_hash != self.tip().map(BlockHeader::hash) {
+            return Err(BlockchainError::PrevHashMismatch.in_block(height));
+        }
+        if header.height != height as u64 {
+            let found = header.height as usize;
+            return Err(BlockchainError::HeightMismatch {
+                expected: height,
+                found,
+            }
+            .in_block(height));
+        }
+        self.headers.push(header);
+        Ok(())
+    }
+
+    /// Will return the amount of headers
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Will return true if there are no headers
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// Will return the header at the height
+    pub fn get(&self, height: usize) -> Option<&BlockHeader> {
+        self.headers.get(height)
+    }
+
+    /// Will return the last header
+    pub fn tip(&self) -> Option<&BlockHeader> {
+        self.headers.last()
+    }
+
+    /// Will check that the transaction (hash) is included by the block at the height,
+    /// given its Merkle proof
+    pub fn verify_transaction(&self, height: usize, hash: &Hash, proof: &MerkleProof) -> bool {
+        self.get(height)
+            .is_some_and(|header| proof.verify(&transaction_leaf(hash), &header.transactions_root))
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{BlockHeader, HeaderChain};
+    use crate::{Block, Blockchain, BlockchainError, Transaction, TransactionData};
+
+    #[test]
+    fn light_clients_verify_headers_and_transactions() {
+        let mut chain = Blockchain::new();
+        for id in ["alice", "bob", "carol"] {
+            chain
+                .submit_transaction(Transaction::new(
+                    "alice".into(),
+                    TransactionData::CreateUserAccount(id.into()),
+                    0,
+                ))
+                .unwrap();
+        }
+        chain.produce_block(3).unwrap();
+        let create_dave = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("dave".into()),
+            0,
+        );
+        chain.submit_transaction(create_dave.clone()).unwrap();
+        chain.produce_block(1).unwrap();
+
+        let headers = chain.blocks().iter().map(|block| block.header().clone());
+        let light = HeaderChain::from_headers(headers).unwrap();
+        assert_eq!(2, light.len());
+
+        let hash = create_dave.calculate_hash();
+        let (location, proof) = chain.transaction_proof(&hash).unwrap();
+        assert!(light.verify_transaction(location.block_height, &hash, &proof));
+        assert!(!light.verify_transaction(0, &hash, &proof));
+        let (_, other_proof) = chain
+            .transaction_proof(&chain.blocks()[0].transactions()[1].calculate_hash())
+            .unwrap();
+        assert!(!light.verify_transaction(1, &hash, &other_proof));
+
+        // Headers claiming more work than their hashes carry, or unlinked ones, are rejected
+        let mut tampered: BlockHeader = chain.blocks()[1].header().clone();
+        tampered.difficulty = 64;
+        let mut light =
+            HeaderChain::from_headers(vec![chain.blocks()[0].header().clone()]).unwrap();
+        assert!(matches!(
+            light.append(tampered).unwrap_err(),
+            BlockchainError::InvalidBlock { height: 1, source }
+                if *source == BlockchainError::InvalidProofOfWork { expected: 64 }
+        ));
+        let mut unlinked = Block::new(None);
+        unlinked.add_transaction(create_dave);
+        unlinked.update_hash();
+        assert!(matches!(
+            light.append(unlinked.header().clone()).unwrap_err(),
+            BlockchainError::InvalidBlock { source, .. }
+                if *source == BlockchainError::PrevHashMismatch
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{AccountType, BlockchainError, TimeLock};
+
+use super::vesting;
+use super::world_state::WorldState;
+
+/// The (reserved) account holding the registry of the hashed timelock contracts: its store
+/// maps the ids of the open ones to their records (see `HtlcRecord`), whilst its tokens are
+/// the locked ones.
+pub const HTLC_REGISTRY: &str = "@htlcs";
+
+/// An open hashed timelock contract: the amount of tokens the sender locked for the receiver,
+/// who claims them by revealing the preimage of the hash lock before the timeout, after which
+/// they are refunded to the sender.<br/>
+/// The hash lock is the (hex encoded) SHA-256 hash of the preimage, as on most other chains,
+/// so that the same secret settles both sides of a cross-chain atomic swap.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HtlcRecord {
+    pub from: String,
+
+    pub to: String,
+
+    pub amount: u128,
+
+    pub hash_lock: String,
+
+    pub timeout: TimeLock,
+}
+
+impl HtlcRecord {
+    /// Will parse the record as stored by the registry (JSON)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        serde_json::from_str(value).ok()
+    }
+
+    /// Will return true if the timeout is reached at the block being executed (measured like
+    /// the vesting locks, see `vesting::check_unlocked`)
+    fn is_expired<T: WorldState>(&self, world_state: &T) -> bool {
+        self.timeout
+            .is_reached(world_state.height(), world_state.timestamp())
+    }
+}
+
+/// Will open the contract of the id, moving the amount of the sender's tokens to the registry
+/// (which is created with the first contract), and return its record.<br/>
+/// The receiver (which may be an alias) has to exist, the hash lock has to be a hex encoded
+/// SHA-256 hash and the timeout may not be reached yet.
+pub(crate) fn lock<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    from: &str,
+    to: &str,
+    amount: u128,
+    hash_lock: &str,
+    timeout: TimeLock,
+) -> Result<HtlcRecord, BlockchainError> {
+    if world_state.get_htlc(id).is_some() {
+        return Err(BlockchainError::InvalidHtlc(format!(
+            "`{}` already exists",
+            id
+        )));
+    }
+    let hash_lock = hash_lock.to_lowercase();
+    if !matches!(hex::decode(&hash_lock), Ok(hash) if hash.len() == 32) {
+        return Err(BlockchainError::InvalidHtlc(
+            "The hash lock has to be a hex encoded SHA-256 hash".into(),
+        ));
+    }
+    let record = HtlcRecord {
+        from: from.to_string(),
+        to: world_state.resolve_account_id(to),
+        amount,
+        hash_lock,
+        timeout,
+    };
+    if record.is_expired(world_state) {
+        return Err(BlockchainError::HtlcExpired(id.to_string()));
+    }
+    if !world_state.account_exists(&record.to) {
+        return Err(BlockchainError::UnknownAccount(record.to));
+    }
+
+    let sender = world_state
+        .get_account_by_id(from)
+        .ok_or_else(|| BlockchainError::UnknownAccount(from.to_string()))?;
+    if sender.frozen {
+        return Err(BlockchainError::AccountFrozen(from.to_string()));
+    }
+    let remaining = sender
+        .tokens
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    vesting::check_unlocked(world_state, from, remaining)?;
+    world_state.get_account_by_id_mut(from).unwrap().tokens = remaining;
+
+    if !world_state.account_exists(HTLC_REGISTRY) {
+        world_state.create_account(HTLC_REGISTRY.into(), AccountType::User)?;
+    }
+    let json = serde_json::to_string(&record)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()))?;
+    let registry = world_state
+        .get_account_by_id_mut(HTLC_REGISTRY)
+        .expect("The registry exists");
+    registry.tokens = registry
+        .tokens
+        .checked_add(amount)
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    registry.store.insert(id.to_string(), json);
+    Ok(record)
+}
+
+/// Will pay the tokens of the contract to its receiver, if the (hex encoded) preimage matches
+/// its hash lock and its timeout is not reached yet, and return its record.<br/>
+/// Anyone knowing the preimage may claim the contract, the tokens going to the receiver.
+pub(crate) fn claim<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    preimage: &str,
+) -> Result<HtlcRecord, BlockchainError> {
+    let record = world_state
+        .get_htlc(id)
+        .ok_or_else(|| BlockchainError::UnknownHtlc(id.to_string()))?;
+    if record.is_expired(world_state) {
+        return Err(BlockchainError::HtlcExpired(id.to_string()));
+    }
+    let preimage = hex::decode(preimage)
+        .map_err(|_| BlockchainError::InvalidHtlc("The preimage has to be hex encoded".into()))?;
+    if hex::encode(Sha256::digest(&preimage)) != record.hash_lock {
+        return Err(BlockchainError::InvalidHtlc(
+            "The preimage doesn't match the hash lock".into(),
+        ));
+    }
+    close(world_state, id, &record.to, record.amount)?;
+    Ok(record)
+}
+
+/// Will pay the tokens of the contract back to its sender, once its timeout is reached, and
+/// return its record
+pub(crate) fn refund<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+) -> Result<HtlcRecord, BlockchainError> {
+    let record = world_state
+        .get_htlc(id)
+        .ok_or_else(|| BlockchainError::UnknownHtlc(id.to_string()))?;
+    if !record.is_expired(world_state) {
+        return Err(BlockchainError::HtlcNotExpired(id.to_string()));
+    }
+    close(world_state, id, &record.from, record.amount)?;
+    Ok(record)
+}
+
+/// Will remove the contract from the registry, paying its tokens to the beneficiary
+fn close<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    beneficiary: &str,
+    amount: u128,
+) -> Result<(), BlockchainError> {
+    if !world_state.account_exists(beneficiary) {
+        return Err(BlockchainError::UnknownAccount(beneficiary.to_string()));
+    }
+    let registry = world_state
+        .get_account_by_id_mut(HTLC_REGISTRY)
+        .expect("The registry holds the contract");
+    registry.store.remove(id);
+    registry.tokens = registry
+        .tokens
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    let beneficiary = world_state.get_account_by_id_mut(beneficiary).unwrap();
+    beneficiary.tokens = beneficiary
+        .tokens
+        .checked_add(amount)
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use sha2::{Digest, Sha256};
+
+    use super::HTLC_REGISTRY;
+    use crate::test_utils::{account_id, ChainBuilder};
+    use crate::{BlockchainError, Event, TimeLock, Transaction, TransactionData, WorldState};
+
+    #[test]
+    fn htlcs_are_claimed_with_the_preimage_or_refunded_after_the_timeout() {
+        let (alice, bob) = (account_id("alice"), account_id("bob"));
+        let secret = b"atomic swap secret";
+        let lock = TransactionData::HtlcLock {
+            to: bob.clone(),
+            amount: 100,
+            hash_lock: hex::encode(Sha256::digest(secret)),
+            timeout: TimeLock::Height(3),
+        };
+        let builder = ChainBuilder::new()
+            .with_account("alice", 1_000)
+            .with_account("bob", 0)
+            .transaction("alice", lock);
+        let mut bc = builder.clone().build();
+        let id = bc.blocks[1].transactions()[0].calculate_hash().to_string();
+        assert_eq!(Some(900), bc.get_balance(&alice));
+        assert_eq!(Some(100), bc.get_balance(HTLC_REGISTRY));
+        assert_eq!(100, bc.get_htlc(&id).unwrap().amount);
+
+        let claim = |preimage: &[u8], nonce| {
+            let data = TransactionData::HtlcClaim {
+                id: id.clone(),
+                preimage: hex::encode(preimage),
+            };
+            Transaction::new(alice.clone(), data, nonce)
+        };
+        let refund = |nonce| {
+            Transaction::new(
+                alice.clone(),
+                TransactionData::HtlcRefund { id: id.clone() },
+                nonce,
+            )
+        };
+        assert!(matches!(
+            claim(b"wrong secret", 1).execute(&mut bc, &false),
+            Err(BlockchainError::InvalidHtlc(_))
+        ));
+        assert_eq!(
+            Err(BlockchainError::HtlcNotExpired(id.clone())),
+            refund(1).execute(&mut bc, &false).map(|_| ())
+        );
+
+        // Anyone revealing the preimage pays the tokens to the receiver (and publishes it)
+        let receipt = claim(secret, 1).execute(&mut bc.clone(), &false).unwrap();
+        assert!(matches!(
+            &receipt.events[..],
+            [Event::HtlcClaimed { preimage, amount: 100, .. }] if *preimage == hex::encode(secret)
+        ));
+        claim(secret, 1).execute(&mut bc, &false).unwrap();
+        assert_eq!(Some(100), bc.get_balance(&bob));
+        assert_eq!(None, bc.get_htlc(&id));
+
+        // Once the timeout is reached, the tokens can only be refunded
+        let mut bc = builder
+            .next_block()
+            .transaction("alice", TransactionData::CreateUserAccount("carol".into()))
+            .build();
+        assert_eq!(
+            Err(BlockchainError::HtlcExpired(id.clone())),
+            claim(secret, 2).execute(&mut bc, &false).map(|_| ())
+        );
+        refund(2).execute(&mut bc, &false).unwrap();
+        assert_eq!(Some(1_000), bc.get_balance(&alice));
+        assert_eq!(Some(0), bc.get_balance(HTLC_REGISTRY));
+    }
+}
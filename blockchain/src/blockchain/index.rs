@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Block, Hash, TransactionKind, TransactionReceipt};
+
+/// Where a transaction is stored on the chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionLocation {
+    /// The height of the block including the transaction
+    pub block_height: usize,
+
+    /// The position of the transaction inside the block
+    pub index: usize,
+}
+
+/// A change of an account by a transaction on the chain (see `Blockchain::account_history`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountChange {
+    /// The height of the block including the transaction
+    pub block_height: usize,
+
+    /// The hash of the transaction
+    pub transaction_hash: Hash,
+
+    /// The amount of tokens the account gained (or, if negative, lost)
+    pub delta: i128,
+}
+
+/// Lookups from the hashes of the blocks and of the transactions to their
+/// positions on the chain (kept in sync by `Blockchain::append_block`).<br/>
+/// The transactions are also listed by their senders and by their kinds, and the changes
+/// of the accounts (see the receipts) by the accounts, in the order of the chain.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChainIndex {
+    blocks: HashMap<Hash, usize>,
+    transactions: HashMap<Hash, TransactionLocation>,
+    senders: HashMap<String, Vec<TransactionLocation>>,
+    kinds: HashMap<TransactionKind, Vec<TransactionLocation>>,
+    accounts: HashMap<String, Vec<AccountChange>>,
+}
+
+impl ChainIndex {
+    /// Will index the block (and its transactions, along with their receipts) at the height
+    pub(crate) fn add_block(
+        &mut self,
+        block_height: usize,
+        block: &Block,
+        receipts: &[TransactionReceipt],
+    ) {
+        if let Some(hash) = block.hash {
+            self.blocks.insert(hash, block_height);
+        }
+        for (index, transaction) in block.body.transactions.iter().enumerate() {
+            let location = TransactionLocation {
+                block_height,
+                index,
+            };
+            self.transactions
+                .insert(transaction.calculate_hash(), location);
+            self.senders
+                .entry(transaction.from.clone())
+                .or_default()
+                .push(location);
+            self.kinds
+                .entry(transaction.record.kind())
+                .or_default()
+                .push(location);
+        }
+        for receipt in receipts {
+            for change in &receipt.balance_changes {
+                self.accounts
+                    .entry(change.account.clone())
+                    .or_default()
+                    .push(AccountChange {
+                        block_height,
+                        transaction_hash: receipt.transaction_hash,
+                        delta: change.delta,
+                    });
+            }
+        }
+    }
+
+    /// Will forget the transactions of the block (e.g. when it is pruned), the block stays indexed
+    pub(crate) fn remove_transactions(&mut self, block_height: usize, block: &Block) {
+        for transaction in block.body.transactions.iter() {
+            self.transactions.remove(&transaction.calculate_hash());
+        }
+        for locations in self.senders.values_mut().chain(self.kinds.values_mut()) {
+            locations.retain(|location| location.block_height != block_height);
+        }
+        for changes in self.accounts.values_mut() {
+            changes.retain(|change| change.block_height != block_height);
+        }
+        self.senders.retain(|_, locations| !locations.is_empty());
+        self.kinds.retain(|_, locations| !locations.is_empty());
+        self.accounts.retain(|_, changes| !changes.is_empty());
+    }
+
+    /// Will return the height of the block with the hash
+    pub(crate) fn block_height(&self, hash: &Hash) -> Option<usize> {
+        self.blocks.get(hash).copied()
+    }
+
+    /// Will return the location of the transaction with the hash
+    pub(crate) fn transaction_location(&self, hash: &Hash) -> Option<TransactionLocation> {
+        self.transactions.get(hash).copied()
+    }
+
+    /// Will return the locations of the transactions sent by the account
+    pub(crate) fn sender_transactions(&self, sender: &str) -> &[TransactionLocation] {
+        self.senders.get(sender).map_or(&[], Vec::as_slice)
+    }
+
+    /// Will return the changes of the account
+    pub(crate) fn account_changes(&self, id: &str) -> &[AccountChange] {
+        self.accounts.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Will return the locations of the transactions of the kind
+    pub(crate) fn kind_transactions(&self, kind: TransactionKind) -> &[TransactionLocation] {
+        self.kinds.get(&kind).map_or(&[], Vec::as_slice)
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Account, BalanceChange};
+
+/// An undo-log of the accounts changed while executing a block.<br/>
+/// Before an account is changed for the first time, its previous value (or its absence,
+/// for newly created accounts) is recorded, so that a failed block can be rolled back
+/// in O(changes) instead of cloning the whole world state upfront.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Journal {
+    /// The previous values of the changed accounts, in the order they were changed
+    entries: Vec<(String, Option<Account>)>,
+
+    /// The accounts already recorded
+    recorded: HashSet<String>,
+
+    /// The balances of the accounts touched by the current transaction before it touched them
+    /// (zero for newly created accounts), in the order they were touched
+    touched: Vec<(String, u128)>,
+}
+
+impl Journal {
+    /// Will record the current value of the account (only the first time it is changed)
+    pub(crate) fn record(&mut self, id: &str, accounts: &HashMap<String, Account>) {
+        if self.recorded.insert(id.to_string()) {
+            self.entries
+                .push((id.to_string(), accounts.get(id).cloned()));
+        }
+        if !self.touched.iter().any(|(touched, _)| touched == id) {
+            let balance = accounts.get(id).map_or(0, Account::tokens);
+            self.touched.push((id.to_string(), balance));
+        }
+    }
+
+    /// Will return the changes of the balances of the accounts touched since the last call
+    /// (i.e. by the current transaction)
+    pub(crate) fn take_balance_changes(
+        &mut self,
+        accounts: &HashMap<String, Account>,
+    ) -> Vec<BalanceChange> {
+        self.touched
+            .drain(..)
+            .map(|(id, before)| {
+                let after = accounts.get(&id).map_or(0, Account::tokens);
+                BalanceChange::between(id, before, after)
+            })
+            .collect()
+    }
+
+    /// Will return the recorded accounts as they are now (`None` for deleted ones)
+    pub(crate) fn changes(
+        &self,
+        accounts: &HashMap<String, Account>,
+    ) -> HashMap<String, Option<Account>> {
+        self.recorded
+            .iter()
+            .map(|id| (id.clone(), accounts.get(id).cloned()))
+            .collect()
+    }
+
+    /// Will restore all the recorded accounts to their previous values
+    pub(crate) fn rollback(self, accounts: &mut HashMap<String, Account>) {
+        for (id, previous) in self.entries.into_iter().rev() {
+            match previous {
+                Some(account) => accounts.insert(id, account),
+                None => accounts.remove(&id),
+            };
+        }
+    }
+}
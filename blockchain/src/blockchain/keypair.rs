@@ -0,0 +1,136 @@
+use std::fmt;
+
+use ed25519_dalek::Signer;
+use k256::ecdsa;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+
+use crate::{Address, BlockchainError};
+
+/// The signature schemes that transactions may be signed with.<br/>
+/// Ed25519 is the native one, whereas secp256k1 (ECDSA over the SHA-256 hash of the message,
+/// with recoverable signatures) lets the keys of Ethereum or Bitcoin tooling sign for this
+/// chain too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    #[default]
+    Ed25519,
+    Secp256k1,
+}
+
+/// The secret key of a keypair, of either scheme.
+#[derive(Clone)]
+enum SigningKey {
+    Ed25519(ed25519_dalek::SigningKey),
+    Secp256k1(ecdsa::SigningKey),
+}
+
+/// A keypair that is used for signing transactions, Ed25519 unless created for another
+/// scheme (see `SignatureScheme`).<br/>
+/// The address derived from the public key is meant to be used as the account ID,
+/// so that the signature of a transaction can be checked against its `from` field.
+#[derive(Clone)]
+pub struct Keypair {
+    signing_key: SigningKey,
+}
+
+// Custom implementation of the `Debug` trait, so that the secret key never ends up in logs.
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Keypair {{ public_key: {} }}", self.public_key())
+    }
+}
+
+impl Keypair {
+    /// Generates a new random keypair using the OS randomness source.
+    pub fn generate() -> Self {
+        Keypair {
+            signing_key: SigningKey::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng)),
+        }
+    }
+
+    /// Generates a new random secp256k1 keypair using the OS randomness source.
+    pub fn generate_secp256k1() -> Self {
+        Keypair {
+            signing_key: SigningKey::Secp256k1(ecdsa::SigningKey::random(&mut OsRng)),
+        }
+    }
+
+    /// Restores a keypair from its 32 bytes secret key.
+    pub fn from_secret_bytes(secret: &[u8; 32]) -> Self {
+        Keypair {
+            signing_key: SigningKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(secret)),
+        }
+    }
+
+    /// Restores a secp256k1 keypair from its 32 bytes secret key (e.g. the private key of an
+    /// Ethereum account), which has to be a valid scalar of the curve.
+    pub fn from_secp256k1_secret_bytes(secret: &[u8; 32]) -> Result<Self, BlockchainError> {
+        let signing_key = ecdsa::SigningKey::from_slice(secret)
+            .map_err(|_| BlockchainError::Keystore("Invalid secp256k1 secret key".into()))?;
+        Ok(Keypair {
+            signing_key: SigningKey::Secp256k1(signing_key),
+        })
+    }
+
+    /// Restores a keypair of the scheme from its 32 bytes secret key.
+    pub fn from_scheme_secret_bytes(
+        scheme: SignatureScheme,
+        secret: &[u8; 32],
+    ) -> Result<Self, BlockchainError> {
+        match scheme {
+            SignatureScheme::Ed25519 => Ok(Keypair::from_secret_bytes(secret)),
+            SignatureScheme::Secp256k1 => Keypair::from_secp256k1_secret_bytes(secret),
+        }
+    }
+
+    /// Returns the signature scheme of the keypair.
+    pub fn scheme(&self) -> SignatureScheme {
+        match self.signing_key {
+            SigningKey::Ed25519(_) => SignatureScheme::Ed25519,
+            SigningKey::Secp256k1(_) => SignatureScheme::Secp256k1,
+        }
+    }
+
+    /// Returns the 32 bytes secret key.
+    pub fn secret_bytes(&self) -> [u8; 32] {
+        match &self.signing_key {
+            SigningKey::Ed25519(key) => key.to_bytes(),
+            SigningKey::Secp256k1(key) => key.to_bytes().into(),
+        }
+    }
+
+    /// Returns the hex encoded public key (compressed, for secp256k1).
+    pub fn public_key(&self) -> String {
+        hex::encode(self.public_key_bytes())
+    }
+
+    /// Returns the address derived from the public key (to be used as the account ID).
+    pub fn address(&self) -> Address {
+        Address::from_public_key(&self.public_key_bytes())
+    }
+
+    fn public_key_bytes(&self) -> Vec<u8> {
+        match &self.signing_key {
+            SigningKey::Ed25519(key) => key.verifying_key().to_bytes().to_vec(),
+            SigningKey::Secp256k1(key) => key.verifying_key().to_sec1_bytes().to_vec(),
+        }
+    }
+
+    /// Signs the provided message: 64 bytes for Ed25519, whereas the 65 bytes of secp256k1
+    /// are the signature followed by its recovery ID.
+    pub(crate) fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match &self.signing_key {
+            SigningKey::Ed25519(key) => key.sign(message).to_bytes().to_vec(),
+            SigningKey::Secp256k1(key) => {
+                let (signature, recovery_id) = key
+                    .sign_recoverable(message)
+                    .expect("Signing with a valid secp256k1 key can't fail");
+                let mut bytes = signature.to_bytes().to_vec();
+                bytes.push(recovery_id.to_byte());
+                bytes
+            }
+        }
+    }
+}
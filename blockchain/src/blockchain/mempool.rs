@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use super::storage::encoded_size;
+use crate::{BlockchainError, Transaction, TransactionData};
+
+/// Limits of the mempool of a node, which keep it from growing without bounds.<br/>
+/// Unlike `ChainParams`, they are a local choice of every node (see
+/// `Blockchain::set_mempool_policy`): the transactions evicted from its mempool may still
+/// be included by the blocks of others.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MempoolPolicy {
+    /// The maximum amount of pending transactions; once reached, a new transaction evicts
+    /// the one paying the lowest fee (or gets rejected if it doesn't pay more)
+    pub max_transactions: usize,
+
+    /// How long (in milliseconds) a transaction may wait in the mempool before it expires
+    pub ttl: u64,
+}
+
+impl Default for MempoolPolicy {
+    fn default() -> Self {
+        MempoolPolicy {
+            max_transactions: 10_000,
+            ttl: 3 * 60 * 60 * 1000,
+        }
+    }
+}
+
+/// Will order the (ready) pending transactions for the next block: the ones paying the
+/// highest fee per byte go first, whilst the transactions of a sender keep the order of
+/// their nonces.<br/>
+/// Transactions paying the same fee per byte keep the order they arrived in.
+pub(crate) fn order_by_fee(
+    transactions: Vec<Transaction>,
+) -> Result<Vec<Transaction>, BlockchainError> {
+    let count = transactions.len();
+    let mut queues: HashMap<String, VecDeque<(usize, Transaction)>> = HashMap::new();
+    for (position, transaction) in transactions.into_iter().enumerate() {
+        queues
+            .entry(transaction.from.clone())
+            .or_default()
+            .push_back((position, transaction));
+    }
+
+    // Only the next transaction of every sender is a candidate
+    let mut candidates = BinaryHeap::new();
+    for queue in queues.values_mut() {
+        queue
+            .make_contiguous()
+            .sort_by_key(|(_, transaction)| transaction.nonce);
+        candidates.push(Candidate::of(&queue[0])?);
+    }
+
+    let mut ordered = Vec::with_capacity(count);
+    while let Some(candidate) = candidates.pop() {
+        let queue = queues
+            .get_mut(&candidate.sender)
+            .expect("queue of the candidate");
+        if let Some((_, transaction)) = queue.pop_front() {
+            ordered.push(transaction);
+        }
+        if let Some(next) = queue.front() {
+            candidates.push(Candidate::of(next)?);
+        }
+    }
+    Ok(ordered)
+}
+
+/// Will return the most native tokens that executing the transaction may take from its
+/// sender: its fee, its gas (at the gas price, not counting the gas used by contract code)
+/// and the tokens its operation transfers or stakes.
+pub(crate) fn max_cost(transaction: &Transaction, gas_price: u128) -> u128 {
+    let gas = gas_price.saturating_mul(u128::from(transaction.record.gas_cost()));
+    transaction
+        .fee
+        .saturating_add(gas)
+        .saturating_add(spent_tokens(&transaction.record))
+}
+
+/// Will return the native tokens that the operation transfers or stakes
+fn spent_tokens(operation: &TransactionData) -> u128 {
+    match operation {
+        TransactionData::TransferTokens {
+            amount,
+            asset: None,
+            ..
+        }
+        | TransactionData::Stake { amount } => *amount,
+        TransactionData::Batch(operations) => operations
+            .iter()
+            .map(spent_tokens)
+            .fold(0, u128::saturating_add),
+        _ => 0,
+    }
+}
+
+/// The next transaction of a sender, ordered by its fee per byte (and its arrival)
+#[derive(PartialEq, Eq)]
+struct Candidate {
+    fee: u128,
+    size: u64,
+    position: usize,
+    sender: String,
+}
+
+impl Candidate {
+    fn of((position, transaction): &(usize, Transaction)) -> Result<Self, BlockchainError> {
+        Ok(Candidate {
+            fee: transaction.fee,
+            size: encoded_size(transaction)?.max(1),
+            position: *position,
+            sender: transaction.from.clone(),
+        })
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Comparing the fractions (fee / size) without dividing
+        let fee_rate = self.fee.saturating_mul(other.size.into());
+        let other_fee_rate = other.fee.saturating_mul(self.size.into());
+        fee_rate
+            .cmp(&other_fee_rate)
+            .then_with(|| other.position.cmp(&self.position))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::order_by_fee;
+    use crate::{Transaction, TransactionData};
+
+    fn transaction(from: &str, nonce: u128, fee: u128) -> Transaction {
+        Transaction::new(
+            from.into(),
+            TransactionData::CreateUserAccount(format!("{}-{}", from, nonce)),
+            nonce,
+        )
+        .with_fee(fee)
+    }
+
+    #[test]
+    fn higher_fees_are_preferred_in_nonce_order() {
+        let ordered = order_by_fee(vec![
+            transaction("alice", 0, 1),
+            transaction("bob", 0, 5),
+            transaction("alice", 2, 9),
+            transaction("alice", 1, 3),
+            transaction("carol", 0, 3),
+        ])
+        .unwrap();
+
+        let order: Vec<(String, u128)> = ordered
+            .iter()
+            .map(|transaction| (transaction.from.clone(), transaction.nonce))
+            .collect();
+        // Alice's high-fee transactions have to wait for her first (low-fee) one
+        let expected = [
+            ("bob", 0),
+            ("carol", 0),
+            ("alice", 0),
+            ("alice", 1),
+            ("alice", 2),
+        ];
+        assert_eq!(
+            expected
+                .iter()
+                .map(|(from, nonce)| (from.to_string(), *nonce))
+                .collect::<Vec<_>>(),
+            order
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+/// Prefixes that separate the hashes of leaves from the ones of inner nodes,
+/// so that an inner node can't be passed off as a leaf.
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Will hash the data of a leaf of a Merkle tree
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut bytes = Vec::with_capacity(data.len() + 1);
+    bytes.push(LEAF_PREFIX);
+    bytes.extend_from_slice(data);
+    Hash::digest(&bytes)
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = Vec::with_capacity(1 + 2 * left.as_bytes().len());
+    bytes.push(NODE_PREFIX);
+    bytes.extend_from_slice(left.as_bytes());
+    bytes.extend_from_slice(right.as_bytes());
+    Hash::digest(&bytes)
+}
+
+/// Will calculate the root of the (binary) Merkle tree over the leaf hashes.<br/>
+/// A level having an odd amount of nodes pairs its last node with itself.
+/// The root of an empty tree is the hash of no data.
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return Hash::digest(&[]);
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| node_hash(&pair[0], pair.last().unwrap()))
+            .collect();
+    }
+    level[0]
+}
+
+/// Proves that a leaf is part of a Merkle tree, given (only) the tree's root.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// The position of the leaf
+    pub index: usize,
+
+    /// The hashes of the siblings on the path from the leaf up to the root
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Will build the proof for the leaf at the provided index (if it exists)
+    pub fn new(leaves: &[Hash], index: usize) -> Option<Self> {
+        if index >= leaves.len() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut level = leaves.to_vec();
+        let mut position = index;
+        while level.len() > 1 {
+            let sibling = (position ^ 1).min(level.len() - 1);
+            siblings.push(level[sibling]);
+            level = level
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], pair.last().unwrap()))
+                .collect();
+            position /= 2;
+        }
+
+        Some(MerkleProof { index, siblings })
+    }
+
+    /// Checks if the leaf (hash) is part of the tree having the provided root
+    pub fn verify(&self, leaf: &Hash, root: &Hash) -> bool {
+        let mut hash = *leaf;
+        let mut position = self.index;
+        for sibling in &self.siblings {
+            hash = if position.is_multiple_of(2) {
+                node_hash(&hash, sibling)
+            } else {
+                node_hash(sibling, &hash)
+            };
+            position /= 2;
+        }
+        position == 0 && &hash == root
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{leaf_hash, merkle_root, MerkleProof};
+    use crate::Hash;
+
+    fn leaves(count: u8) -> Vec<Hash> {
+        (0..count).map(|i| leaf_hash(&[i])).collect()
+    }
+
+    #[test]
+    fn proofs_verify_against_root() {
+        for count in 1..=7 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = MerkleProof::new(&leaves, index).unwrap();
+                assert!(proof.verify(leaf, &root));
+                assert!(!proof.verify(&leaf_hash(b"other"), &root));
+            }
+            assert_eq!(None, MerkleProof::new(&leaves, count as usize));
+        }
+    }
+
+    #[test]
+    fn root_depends_on_every_leaf() {
+        let mut leaves = leaves(5);
+        let root = merkle_root(&leaves);
+        leaves[4] = leaf_hash(b"changed");
+        assert_ne!(root, merkle_root(&leaves));
+        assert_ne!(merkle_root(&[]), root);
+    }
+}
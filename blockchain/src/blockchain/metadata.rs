@@ -0,0 +1,131 @@
+use crate::BlockchainError;
+
+use super::world_state::WorldState;
+
+/// The maximum amount of metadata entries of an account.
+pub const MAX_METADATA_ENTRIES: usize = 32;
+
+/// The maximum length (in bytes) of the key of a metadata entry.
+pub const MAX_METADATA_KEY_LENGTH: usize = 64;
+
+/// The maximum length (in bytes) of the value of a metadata entry.
+pub const MAX_METADATA_VALUE_LENGTH: usize = 1024;
+
+/// Will set the metadata entry of the account (e.g. `display_name`, `url` or the key the
+/// account rotates to), or remove it if the value is empty.<br/>
+/// Keys are 1 to 64 bytes and values at most 1024 bytes, whilst an account has at most 32
+/// entries.
+pub(crate) fn set<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), BlockchainError> {
+    if key.is_empty() || key.len() > MAX_METADATA_KEY_LENGTH {
+        return Err(BlockchainError::InvalidMetadata(format!(
+            "The key has to be 1 to {} bytes",
+            MAX_METADATA_KEY_LENGTH
+        )));
+    }
+    if value.len() > MAX_METADATA_VALUE_LENGTH {
+        return Err(BlockchainError::InvalidMetadata(format!(
+            "The value may be at most {} bytes",
+            MAX_METADATA_VALUE_LENGTH
+        )));
+    }
+
+    let account = world_state
+        .get_account_by_id_mut(id)
+        .ok_or_else(|| BlockchainError::UnknownAccount(id.to_string()))?;
+    if value.is_empty() {
+        account.metadata.remove(key);
+        return Ok(());
+    }
+    if !account.metadata.contains_key(key) && account.metadata.len() >= MAX_METADATA_ENTRIES {
+        return Err(BlockchainError::InvalidMetadata(format!(
+            "An account has at most {} entries",
+            MAX_METADATA_ENTRIES
+        )));
+    }
+    account.metadata.insert(key.to_string(), value.to_string());
+    Ok(())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{MAX_METADATA_ENTRIES, MAX_METADATA_VALUE_LENGTH};
+    use crate::{Blockchain, BlockchainError, Event, Transaction, TransactionData, WorldState};
+
+    /// Sets the metadata of alice, at the next nonce of the account
+    fn set_metadata(bc: &Blockchain, key: &str, value: &str) -> Transaction {
+        Transaction::new(
+            "alice".into(),
+            TransactionData::SetAccountMetadata {
+                key: key.into(),
+                value: value.into(),
+            },
+            bc.get_nonce("alice").unwrap_or(0),
+        )
+    }
+
+    #[test]
+    fn accounts_carry_metadata() {
+        let mut bc = Blockchain::new();
+        Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("alice".into()),
+            0,
+        )
+        .execute(&mut bc, &true)
+        .unwrap();
+
+        let receipt = set_metadata(&bc, "display_name", "Alice")
+            .execute(&mut bc, &false)
+            .unwrap();
+        assert_eq!(
+            vec![Event::MetadataChanged {
+                id: "alice".into(),
+                key: "display_name".into(),
+            }],
+            receipt.events
+        );
+        assert_eq!(
+            Some(&"Alice".to_string()),
+            bc.get_metadata("alice", "display_name")
+        );
+        assert_eq!(1, bc.get_account_by_id("alice").unwrap().metadata().len());
+
+        // An empty value removes the entry
+        set_metadata(&bc, "display_name", "")
+            .execute(&mut bc, &false)
+            .unwrap();
+        assert_eq!(None, bc.get_metadata("alice", "display_name"));
+
+        // The entries are limited in size and number
+        let long = "x".repeat(MAX_METADATA_VALUE_LENGTH + 1);
+        for (key, value) in [("", "value"), ("url", long.as_str())] {
+            assert!(matches!(
+                set_metadata(&bc, key, value).execute(&mut bc, &false),
+                Err(BlockchainError::InvalidMetadata(_))
+            ));
+        }
+        for i in 0..MAX_METADATA_ENTRIES {
+            set_metadata(&bc, &format!("key{}", i), "value")
+                .execute(&mut bc, &false)
+                .unwrap();
+        }
+        assert!(matches!(
+            set_metadata(&bc, "url", "https://example.com").execute(&mut bc, &false),
+            Err(BlockchainError::InvalidMetadata(_))
+        ));
+        // ... whilst existing entries may still be changed
+        set_metadata(&bc, "key0", "changed")
+            .execute(&mut bc, &false)
+            .unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::{BlockchainError, ExecutionResult};
+
+/// Counts what happened to a chain since it was constructed (or opened), e.g. to be
+/// exported to a monitoring system.<br/>
+/// The counters are updated by the chain itself, whenever a block is appended or rejected
+/// (see `Blockchain::metrics`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChainMetrics {
+    /// The amount of appended blocks
+    pub blocks_appended: u64,
+
+    /// The amount of transactions executed by the appended blocks (successfully or not)
+    pub transactions_executed: u64,
+
+    /// The total time spent on appending the blocks (checking and executing them)
+    pub block_execution_time: Duration,
+
+    /// The amount of rejected blocks, by the reason (`BlockchainError::code`) of the rejection
+    pub blocks_rejected: BTreeMap<&'static str, u64>,
+
+    /// The amount of transactions that left the mempool without being included, by the
+    /// reason: `expired`, `evicted` (by a transaction paying a higher fee) or `replaced`
+    /// (by one with the same sender and nonce, paying a higher fee)
+    pub transactions_evicted: BTreeMap<&'static str, u64>,
+}
+
+impl ChainMetrics {
+    /// Will count the block, whose appending took `elapsed`
+    pub(crate) fn record_block(
+        &mut self,
+        appended: &Result<ExecutionResult, BlockchainError>,
+        elapsed: Duration,
+    ) {
+        match appended {
+            Ok(result) => {
+                self.blocks_appended += 1;
+                self.transactions_executed += result.receipts.len() as u64;
+                self.block_execution_time += elapsed;
+            }
+            Err(err) => *self.blocks_rejected.entry(err.code()).or_insert(0) += 1,
+        }
+    }
+
+    /// Will count the transactions evicted from the mempool (for the reason)
+    pub(crate) fn record_eviction(&mut self, reason: &'static str, count: usize) {
+        if count > 0 {
+            *self.transactions_evicted.entry(reason).or_insert(0) += count as u64;
+        }
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{Block, Blockchain, Transaction, TransactionData};
+
+    #[test]
+    fn appended_and_rejected_blocks_are_counted() {
+        let mut chain = Blockchain::new();
+        let mut genesis = Block::new(None);
+        for id in ["alice", "bob"] {
+            genesis.add_transaction(Transaction::new(
+                "alice".into(),
+                TransactionData::CreateUserAccount(id.into()),
+                0,
+            ));
+        }
+        chain.append_block(genesis.clone()).unwrap();
+        assert!(chain.append_block(genesis).is_err());
+        assert!(chain.append_block(Block::new(None)).is_err());
+
+        let metrics = chain.metrics();
+        assert_eq!(1, metrics.blocks_appended);
+        assert_eq!(2, metrics.transactions_executed);
+        assert_eq!(Some(&1), metrics.blocks_rejected.get("prev_hash_mismatch"));
+        assert_eq!(2, metrics.blocks_rejected.values().sum::<u64>());
+    }
+}
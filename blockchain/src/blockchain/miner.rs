@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Block;
+
+/// The amount of blocks whose median timestamp stands for the time of a retarget boundary,
+/// so that a single (e.g. manipulated) timestamp doesn't decide the retargeting
+const TIMESTAMP_MEDIAN_SPAN: usize = 5;
+
+/// Parameters of the Proof of Work and of its difficulty retargeting.<br/>
+/// The difficulty is the amount of leading zero bits a block hash has to have,
+/// so increasing it by one doubles the expected mining work.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyParams {
+    /// The difficulty of the genesis block (and of the first retarget period).<br/>
+    /// A difficulty of zero means that no Proof of Work is required.
+    pub initial_difficulty: u32,
+
+    /// The expected time (in milliseconds) between two consecutive blocks
+    pub target_block_time: u64,
+
+    /// The number of blocks after which the difficulty gets recalculated
+    pub retarget_interval: usize,
+
+    /// The maximum amount of bits the difficulty changes by at a retarget
+    pub max_adjustment: u32,
+
+    /// Dampens the retargeting: the mining time is measured over the last `dampening` retarget
+    /// intervals (1 measures the last one only), so that a single unusually fast or slow
+    /// interval moves the difficulty less
+    pub dampening: usize,
+}
+
+impl Default for DifficultyParams {
+    fn default() -> Self {
+        DifficultyParams {
+            initial_difficulty: 0,
+            target_block_time: 10_000,
+            retarget_interval: 10,
+            max_adjustment: 1,
+            dampening: 1,
+        }
+    }
+}
+
+impl DifficultyParams {
+    /// Will calculate the difficulty of the block following the provided ones.<br/>
+    /// At every retarget interval, the time spent for mining the last interval's blocks
+    /// (or the ones of the last `dampening` intervals) is compared with the expected one
+    /// (see `retarget`): the difficulty is increased if they
+    /// were mined more than twice as fast and decreased if they were mined more than twice as
+    /// slow.<br/>
+    /// The timestamps can't easily be gamed: the intervals overlap (each one starts with the
+    /// last block of the previous one, so no time between them goes unaccounted), their
+    /// boundaries are the median timestamps of the blocks up to them and the adjustment is
+    /// bounded by `max_adjustment`, however far the timestamps are off.
+    pub fn next_difficulty(&self, blocks: &[Block]) -> u32 {
+        let last = match blocks.last() {
+            Some(block) => block,
+            None => return self.initial_difficulty,
+        };
+
+        if self.retarget_interval < 2 || !blocks.len().is_multiple_of(self.retarget_interval) {
+            return last.header.difficulty;
+        }
+
+        let end = blocks.len() - 1;
+        let measured = self.retarget_interval.saturating_mul(self.dampening.max(1));
+        let start = blocks.len().saturating_sub(measured.saturating_add(1));
+        let actual =
+            median_timestamp(&blocks[..=end]).saturating_sub(median_timestamp(&blocks[..=start]));
+        let expected = self.target_block_time.saturating_mul((end - start) as u64);
+
+        self.retarget(last.header.difficulty, actual, expected)
+    }
+
+    /// Will adjust the difficulty based on the actual and the expected mining time: by the
+    /// amount of times (bits) that the actual time is more than twice as short or as long as
+    /// the expected one, at most by `max_adjustment` bits.
+    pub fn retarget(&self, difficulty: u32, actual: u64, expected: u64) -> u32 {
+        // The value doubled `times` times
+        let doubled = |value: u64, times: u32| {
+            value.saturating_mul(1u64.checked_shl(times).unwrap_or(u64::MAX))
+        };
+        let mut faster = 0;
+        while faster < self.max_adjustment && doubled(actual, faster + 1) < expected {
+            faster += 1;
+        }
+        let mut slower = 0;
+        while slower < self.max_adjustment && actual > doubled(expected, slower + 1) {
+            slower += 1;
+        }
+        difficulty.saturating_add(faster).saturating_sub(slower)
+    }
+}
+
+/// Will adjust the difficulty by one bit, based on the actual and the expected mining time
+/// (see `DifficultyParams::retarget`).
+pub fn retarget(difficulty: u32, actual: u64, expected: u64) -> u32 {
+    DifficultyParams::default().retarget(difficulty, actual, expected)
+}
+
+/// Will return the median timestamp of the last `TIMESTAMP_MEDIAN_SPAN` blocks
+fn median_timestamp(blocks: &[Block]) -> u64 {
+    let span = TIMESTAMP_MEDIAN_SPAN.min(blocks.len());
+    let mut timestamps: Vec<u64> = blocks[blocks.len() - span..]
+        .iter()
+        .map(|block| block.header.timestamp)
+        .collect();
+    timestamps.sort_unstable();
+    timestamps.get(timestamps.len() / 2).copied().unwrap_or(0)
+}
+
+/// Will check if the hash has (at least) `difficulty` leading zero bits.
+pub fn meets_difficulty(hash: &[u8], difficulty: u32) -> bool {
+    let mut zeros = 0;
+    for byte in hash {
+        if *byte != 0 {
+            zeros += byte.leading_zeros();
+            break;
+        }
+        zeros += 8;
+    }
+    zeros >= difficulty
+}
+
+impl Block {
+    /// Will increment the nonce until the hash of the block meets the difficulty
+    /// (Proof of Work) and update the hash.
+    pub fn mine(&mut self, difficulty: u32) {
+        self.header.difficulty = difficulty;
+        while !meets_difficulty(self.calculate_hash().as_bytes(), difficulty) {
+            self.header.nonce = self.header.nonce.wrapping_add(1);
+        }
+        self.update_hash();
+    }
+
+    /// Checks if the block hash meets the difficulty the block claims to be mined with.<br/>
+    /// The stored hash is checked for pruned blocks, since it can't be recalculated.
+    pub fn verify_proof_of_work(&self) -> bool {
+        let hash = match (self.pruned, self.hash) {
+            (true, Some(hash)) => hash,
+            (true, None) => return false,
+            (false, _) => self.calculate_hash(),
+        };
+        meets_difficulty(hash.as_bytes(), self.header.difficulty)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{meets_difficulty, retarget, DifficultyParams};
+    use crate::{Block, Transaction, TransactionData};
+
+    #[test]
+    fn leading_zero_bits() {
+        assert!(meets_difficulty(&[0xff], 0));
+        assert!(!meets_difficulty(&[0x80], 1));
+        assert!(meets_difficulty(&[0x00, 0x10], 11));
+        assert!(!meets_difficulty(&[0x00, 0x10], 12));
+    }
+
+    #[test]
+    fn mining_meets_difficulty() {
+        let mut block = Block::new(None);
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("alice".into()),
+            0,
+        ));
+        block.mine(8);
+
+        assert!(block.verify_own_hash());
+        assert!(block.verify_proof_of_work());
+        assert_eq!(8, block.difficulty());
+    }
+
+    #[test]
+    fn retarget_adjusts_by_one_bit() {
+        assert_eq!(5, retarget(4, 100, 1000));
+        assert_eq!(4, retarget(4, 1000, 1000));
+        assert_eq!(3, retarget(4, 5000, 1000));
+        assert_eq!(0, retarget(0, 5000, 1000));
+    }
+
+    #[test]
+    fn next_difficulty_retargets_on_interval() {
+        let params = DifficultyParams {
+            initial_difficulty: 2,
+            target_block_time: 1_000,
+            retarget_interval: 3,
+            ..DifficultyParams::default()
+        };
+        assert_eq!(2, params.next_difficulty(&[]));
+
+        let mut blocks = Vec::new();
+        for i in 0..3 {
+            let mut block = Block::new(None);
+            block.header.difficulty = 2;
+            block.set_timestamp(i * 100);
+            blocks.push(block);
+        }
+        assert_eq!(2, params.next_difficulty(&blocks[..2]));
+        // Three blocks in 200ms instead of 2000ms
+        assert_eq!(3, params.next_difficulty(&blocks));
+    }
+
+    /// Will return `count` blocks of the difficulty, mined `interval` milliseconds apart
+    fn blocks(count: u64, difficulty: u32, interval: u64) -> Vec<Block> {
+        (0..count)
+            .map(|i| {
+                let mut block = Block::new(None);
+                block.header.difficulty = difficulty;
+                block.set_timestamp(1_000_000 + i * interval);
+                block
+            })
+            .collect()
+    }
+
+    #[test]
+    fn retargeting_is_bounded_and_dampened() {
+        let params = DifficultyParams {
+            max_adjustment: 2,
+            ..DifficultyParams::default()
+        };
+        assert_eq!(6, params.retarget(4, 0, 1000));
+        assert_eq!(6, params.retarget(4, 100, 1000));
+        assert_eq!(5, params.retarget(4, 300, 1000));
+        assert_eq!(2, params.retarget(4, u64::MAX, 1000));
+        assert_eq!(6, params.retarget(4, 1000, u64::MAX / 2));
+
+        // A fast interval following one on target only raises the undampened difficulty
+        let params = DifficultyParams {
+            initial_difficulty: 4,
+            target_block_time: 1_000,
+            retarget_interval: 10,
+            ..DifficultyParams::default()
+        };
+        let mut blocks = blocks(20, 4, 1_000);
+        for (i, block) in blocks.iter_mut().enumerate().skip(10) {
+            block.set_timestamp(1_009_000 + (i as u64 - 9) * 300);
+        }
+        assert_eq!(5, params.next_difficulty(&blocks));
+        let dampened = DifficultyParams {
+            dampening: 2,
+            ..params
+        };
+        assert_eq!(4, dampened.next_difficulty(&blocks));
+    }
+
+    #[test]
+    fn retargeting_resists_timestamp_gaming() {
+        let params = DifficultyParams {
+            initial_difficulty: 4,
+            target_block_time: 1_000,
+            retarget_interval: 10,
+            ..DifficultyParams::default()
+        };
+
+        // Blocks mined ten times too fast raise the difficulty,
+        // even if the last one claims to be from the far future
+        let mut fast = blocks(10, 4, 100);
+        assert_eq!(5, params.next_difficulty(&fast));
+        fast[9].set_timestamp(u64::MAX / 2);
+        assert_eq!(5, params.next_difficulty(&fast));
+
+        // Neither does a single timestamp from the past lower the difficulty of slow blocks
+        let mut slow = blocks(10, 4, 10_000);
+        assert_eq!(3, params.next_difficulty(&slow));
+        slow[9].set_timestamp(0);
+        assert_eq!(3, params.next_difficulty(&slow));
+
+        // The time between two intervals is accounted for (no time warp): the second interval
+        // starts with the last block of the first one
+        let mut warped = blocks(20, 4, 1_000);
+        for block in &mut warped[10..] {
+            let timestamp = block.header.timestamp;
+            block.set_timestamp(timestamp + 100_000);
+        }
+        assert_eq!(3, params.next_difficulty(&warped));
+
+        // However far off the timestamps are, the difficulty changes by one bit at most
+        let mut extreme = blocks(10, 4, 0);
+        assert_eq!(5, params.next_difficulty(&extreme));
+        extreme[5..]
+            .iter_mut()
+            .for_each(|block| block.set_timestamp(u64::MAX));
+        assert_eq!(3, params.next_difficulty(&extreme));
+    }
+}
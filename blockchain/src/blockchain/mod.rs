@@ -1,6 +1,61 @@
 // Registering to the module tree.
 pub(crate) mod account;
+pub(crate) mod address;
+pub(crate) mod admin;
+pub(crate) mod alias;
+pub(crate) mod allowance;
+pub(crate) mod asset;
 pub(crate) mod block;
+pub(crate) mod block_builder;
+pub(crate) mod block_file;
+pub(crate) mod bridge;
+pub(crate) mod cached_state;
+pub(crate) mod canonical;
 pub(crate) mod chain;
+pub(crate) mod checkpoint;
+pub mod consensus;
+pub(crate) mod contracts;
+pub(crate) mod error;
+pub(crate) mod escrow;
+pub(crate) mod evidence;
+pub mod gas;
+pub(crate) mod genesis;
+pub(crate) mod handle;
+pub(crate) mod hash;
+pub(crate) mod header;
+pub(crate) mod htlc;
+pub(crate) mod index;
+pub(crate) mod journal;
+pub(crate) mod keypair;
+pub(crate) mod mempool;
+pub mod merkle;
+pub(crate) mod metadata;
+pub(crate) mod metrics;
+pub mod miner;
+pub(crate) mod multisig;
+pub(crate) mod names;
+pub(crate) mod nft;
+pub(crate) mod orphans;
+pub(crate) mod overlay;
+pub(crate) mod params;
+pub(crate) mod policy;
+pub(crate) mod receipt;
+pub(crate) mod serialization;
+pub(crate) mod sign_doc;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod sim;
+pub(crate) mod snapshot;
+pub(crate) mod staking;
+pub(crate) mod state_root;
+pub mod storage;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod strategies;
+pub(crate) mod subscription;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub(crate) mod tombstone;
 pub(crate) mod transaction;
+pub(crate) mod transaction_builder;
+pub(crate) mod vesting;
+pub(crate) mod wallet;
 pub(crate) mod world_state;
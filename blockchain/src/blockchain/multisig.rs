@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Address, BlockchainError};
+
+/// The maximum number of owners of a multisignature account.
+pub const MAX_MULTISIG_OWNERS: usize = 16;
+
+/// A signature of an owner of a multisignature account (both hex encoded).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cosignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Will check the owners and the threshold of a new multisignature account: the owners
+/// have to be distinct addresses and the threshold has to be reachable (and not zero)
+pub(crate) fn validate(owners: &[String], threshold: u32) -> Result<(), BlockchainError> {
+    if owners.is_empty() || owners.len() > MAX_MULTISIG_OWNERS {
+        return Err(BlockchainError::InvalidMultisig(format!(
+            "1 to {} owners are required",
+            MAX_MULTISIG_OWNERS
+        )));
+    }
+    if threshold == 0 || threshold as usize > owners.len() {
+        return Err(BlockchainError::InvalidMultisig(format!(
+            "The threshold has to be between 1 and {}",
+            owners.len()
+        )));
+    }
+    let mut distinct = HashSet::new();
+    for owner in owners {
+        match owner.parse::<Address>() {
+            Ok(address) if &address.to_string() == owner => {}
+            _ => {
+                return Err(BlockchainError::InvalidMultisig(format!(
+                    "The owner {} is not an address",
+                    owner
+                )))
+            }
+        }
+        if !distinct.insert(owner) {
+            return Err(BlockchainError::InvalidMultisig(format!(
+                "The owner {} is listed twice",
+                owner
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Will check if the signers (the addresses of valid cosignatures) include
+/// at least `threshold` owners
+pub(crate) fn check_threshold(
+    owners: &[String],
+    threshold: u32,
+    signers: &HashSet<String>,
+) -> Result<(), BlockchainError> {
+    let approvals = owners
+        .iter()
+        .filter(|owner| signers.contains(*owner))
+        .count();
+    if approvals < threshold as usize {
+        return Err(BlockchainError::MultisigThresholdNotMet {
+            threshold,
+            approvals,
+        });
+    }
+    Ok(())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashSet;
+
+    use crate::{BlockchainError, Keypair};
+
+    fn owners(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| Keypair::generate().address().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn owners_and_threshold_are_validated() {
+        let owners = owners(3);
+        assert!(super::validate(&owners, 2).is_ok());
+        assert!(super::validate(&owners, 0).is_err());
+        assert!(super::validate(&owners, 4).is_err());
+        assert!(super::validate(&[], 1).is_err());
+        assert!(super::validate(&["alice".to_string()], 1).is_err());
+        assert!(super::validate(&[owners[0].clone(), owners[0].clone()], 1).is_err());
+    }
+
+    #[test]
+    fn threshold_counts_owners_only() {
+        let owners = owners(3);
+        let mut signers: HashSet<String> = vec![owners[0].clone(), "mallory".to_string()]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            Err(BlockchainError::MultisigThresholdNotMet {
+                threshold: 2,
+                approvals: 1
+            }),
+            super::check_threshold(&owners, 2, &signers)
+        );
+
+        signers.insert(owners[2].clone());
+        assert!(super::check_threshold(&owners, 2, &signers).is_ok());
+    }
+}
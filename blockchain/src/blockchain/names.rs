@@ -0,0 +1,95 @@
+use crate::{AccountType, Address, BlockchainError};
+
+use super::alias::validate_alias;
+use super::world_state::WorldState;
+
+/// The (reserved) account holding the name registry, its store maps the names to their
+/// records (see `NameRecord`).
+pub const NAME_REGISTRY: &str = "@names";
+
+/// The amount of blocks a name is registered (or renewed) for, unless the validation policy
+/// configures another period (see `ValidationPolicy::name_registration_period`).
+pub(crate) const DEFAULT_REGISTRATION_PERIOD: usize = 100_000;
+
+/// The registration of a name: its owner and the height of the first block it expires at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameRecord {
+    pub owner: Address,
+    pub expires_at: usize,
+}
+
+impl NameRecord {
+    /// Will parse the record as stored by the registry (`<expires_at>:<owner>`)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let (expires_at, owner) = value.split_once(':')?;
+        Some(NameRecord {
+            owner: owner.parse().ok()?,
+            expires_at: expires_at.parse().ok()?,
+        })
+    }
+
+    /// Checks if the name is still registered at the height
+    pub fn is_active(&self, height: usize) -> bool {
+        height < self.expires_at
+    }
+}
+
+/// Will register the name for the owner (which has to be an address) for the registration
+/// period, creating the registry with the first name.<br/>
+/// A name whose registration expired may be registered by anyone, whilst the owner of an
+/// active name renews it, extending its registration by another period.
+/// Returns the height the name expires at.
+pub(crate) fn register<T: WorldState>(
+    world_state: &mut T,
+    name: &str,
+    owner: &str,
+) -> Result<usize, BlockchainError> {
+    validate_alias(name).map_err(|_| BlockchainError::InvalidName(name.to_string()))?;
+    let address = match owner.parse::<Address>() {
+        Ok(address) if address.to_string() == owner => address,
+        _ => return Err(BlockchainError::InvalidName(name.to_string())),
+    };
+
+    let height = world_state.height();
+    let period = world_state.name_registration_period();
+    let expires_at = match world_state.get_name(name) {
+        Some(record) if record.is_active(height) && record.owner != address => {
+            return Err(BlockchainError::NameTaken(name.to_string()))
+        }
+        Some(record) if record.is_active(height) => record.expires_at.saturating_add(period),
+        _ => height.saturating_add(period),
+    };
+
+    if !world_state.account_exists(NAME_REGISTRY) {
+        world_state.create_account(NAME_REGISTRY.into(), AccountType::User)?;
+    }
+    world_state
+        .get_account_by_id_mut(NAME_REGISTRY)
+        .expect("The registry exists")
+        .store
+        .insert(name.to_string(), format!("{}:{}", expires_at, owner));
+    Ok(expires_at)
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::NameRecord;
+    use crate::Keypair;
+
+    #[test]
+    fn records_round_trip() {
+        let owner = Keypair::generate().address();
+        let record = NameRecord::parse(&format!("42:{}", owner)).unwrap();
+        assert_eq!(owner, record.owner);
+        assert!(record.is_active(41));
+        assert!(!record.is_active(42));
+
+        assert!(NameRecord::parse(&owner.to_string()).is_none());
+        assert!(NameRecord::parse("42:alice").is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{AccountType, BlockchainError};
+
+use super::world_state::WorldState;
+
+/// The (reserved) account holding the NFT registry, its store maps the ids of the
+/// (non-fungible) tokens to their owners.
+pub const NFT_REGISTRY: &str = "@nfts";
+
+/// The maximum length of the id of an NFT.
+const MAX_NFT_ID_LEN: usize = 64;
+
+/// Will mint the NFT for its owner, creating the registry with the first NFT.<br/>
+/// The id has to be unique, whilst the metadata (e.g. an URI) is up to the minter.
+pub(crate) fn mint<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    owner: &str,
+    metadata: &str,
+) -> Result<(), BlockchainError> {
+    if id.is_empty() || id.len() > MAX_NFT_ID_LEN {
+        return Err(BlockchainError::InvalidNft(id.to_string()));
+    }
+    if world_state.get_nft_owner(id).is_some() {
+        return Err(BlockchainError::NftExists(id.to_string()));
+    }
+    let account = world_state
+        .get_account_by_id_mut(owner)
+        .ok_or_else(|| BlockchainError::UnknownAccount(owner.to_string()))?;
+    account.nfts.insert(id.to_string(), metadata.to_string());
+    set_owner(world_state, id, owner)
+}
+
+/// Will move the NFT (and its metadata) from its owner to the receiver (which may be an alias)
+/// and return the id of the receiver
+pub(crate) fn transfer<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    from: &str,
+    to: &str,
+) -> Result<String, BlockchainError> {
+    match world_state.get_nft_owner(id) {
+        Some(owner) if owner == from => {}
+        Some(_) => return Err(BlockchainError::NotNftOwner(id.to_string())),
+        None => return Err(BlockchainError::UnknownNft(id.to_string())),
+    }
+    let to = world_state.resolve_account_id(to);
+    if !world_state.account_exists(&to) {
+        return Err(BlockchainError::UnknownAccount(to));
+    }
+
+    let sender = world_state
+        .get_account_by_id_mut(from)
+        .ok_or_else(|| BlockchainError::UnknownAccount(from.to_string()))?;
+    if sender.frozen {
+        return Err(BlockchainError::AccountFrozen(from.to_string()));
+    }
+    let metadata = sender.nfts.remove(id).unwrap_or_default();
+    give(world_state, id, metadata, &to)?;
+    Ok(to)
+}
+
+/// Will make the (existing) receiver the owner of the NFT, which its previous owner
+/// gave away (by transferring it or by deleting its account)
+pub(crate) fn give<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    metadata: String,
+    to: &str,
+) -> Result<(), BlockchainError> {
+    world_state
+        .get_account_by_id_mut(to)
+        .ok_or_else(|| BlockchainError::UnknownAccount(to.to_string()))?
+        .nfts
+        .insert(id.to_string(), metadata);
+    set_owner(world_state, id, to)
+}
+
+/// Will record the owner of the NFT in the registry
+fn set_owner<T: WorldState>(
+    world_state: &mut T,
+    id: &str,
+    owner: &str,
+) -> Result<(), BlockchainError> {
+    if !world_state.account_exists(NFT_REGISTRY) {
+        world_state.create_account(NFT_REGISTRY.into(), AccountType::User)?;
+    }
+    world_state
+        .get_account_by_id_mut(NFT_REGISTRY)
+        .expect("The registry exists")
+        .store
+        .insert(id.to_string(), owner.to_string());
+    Ok(())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{mint, transfer};
+    use crate::{AccountType, Blockchain, BlockchainError, WorldState};
+
+    #[test]
+    fn nfts_are_unique_and_transferable() {
+        let mut chain = Blockchain::new();
+        for id in ["alice", "bob"] {
+            chain.create_account(id.into(), AccountType::User).unwrap();
+        }
+
+        mint(&mut chain, "cat-1", "alice", "ipfs://cat").unwrap();
+        assert_eq!(Some(&"alice".to_string()), chain.get_nft_owner("cat-1"));
+        assert_eq!(
+            Err(BlockchainError::NftExists("cat-1".into())),
+            mint(&mut chain, "cat-1", "bob", "ipfs://other-cat")
+        );
+        assert!(mint(&mut chain, "", "bob", "").is_err());
+
+        assert_eq!(
+            Err(BlockchainError::NotNftOwner("cat-1".into())),
+            transfer(&mut chain, "cat-1", "bob", "bob")
+        );
+        assert_eq!(
+            Err(BlockchainError::UnknownNft("dog-1".into())),
+            transfer(&mut chain, "dog-1", "alice", "bob")
+        );
+        transfer(&mut chain, "cat-1", "alice", "bob").unwrap();
+        assert_eq!(Some(&"bob".to_string()), chain.get_nft_owner("cat-1"));
+        let bob = chain.get_account_by_id("bob").unwrap();
+        assert_eq!(Some(&"ipfs://cat".to_string()), bob.nfts().get("cat-1"));
+        assert!(chain.get_account_by_id("alice").unwrap().nfts().is_empty());
+    }
+}
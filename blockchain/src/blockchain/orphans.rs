@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Block, Hash};
+
+/// Limits of the orphan pool of a node, which keeps the blocks whose parents are unknown
+/// (yet) until the parents arrive (see `Blockchain::append_block`).<br/>
+/// Like the `MempoolPolicy`, they are a local choice of every node (see
+/// `Blockchain::set_orphan_policy`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrphanPolicy {
+    /// The maximum amount of orphan blocks; once reached, a new orphan evicts the oldest one
+    pub max_blocks: usize,
+
+    /// How long (in milliseconds) an orphan block is kept before it expires
+    pub ttl: u64,
+}
+
+impl Default for OrphanPolicy {
+    fn default() -> Self {
+        OrphanPolicy {
+            max_blocks: 100,
+            ttl: 10 * 60 * 1000,
+        }
+    }
+}
+
+/// The blocks whose parents are unknown, along with the time (unix epoch, in milliseconds)
+/// they arrived at, in the order they arrived in.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OrphanPool(Vec<(Block, u64)>);
+
+impl OrphanPool {
+    /// Will keep the block, unless it is kept already, evicting the expired orphans
+    /// (and the oldest one, if the pool is full) first
+    pub(crate) fn insert(&mut self, block: Block, now: u64, policy: &OrphanPolicy) {
+        self.evict_expired(now, policy);
+        if self.0.iter().any(|(orphan, _)| orphan.hash == block.hash) || policy.max_blocks == 0 {
+            return;
+        }
+        if self.0.len() >= policy.max_blocks {
+            self.0.remove(0);
+        }
+        self.0.push((block, now));
+    }
+
+    /// Will remove the (unexpired) orphans whose parent is the provided block and return them
+    pub(crate) fn take_children(
+        &mut self,
+        parent: &Hash,
+        now: u64,
+        policy: &OrphanPolicy,
+    ) -> Vec<Block> {
+        self.evict_expired(now, policy);
+        let (children, orphans) = self
+            .0
+            .drain(..)
+            .partition(|(orphan, _)| orphan.header.prev_hash.as_ref() == Some(parent));
+        self.0 = orphans;
+        children.into_iter().map(|(block, _)| block).collect()
+    }
+
+    /// Will return the orphan blocks, in the order they arrived in
+    pub(crate) fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.0.iter().map(|(block, _)| block)
+    }
+
+    fn evict_expired(&mut self, now: u64, policy: &OrphanPolicy) {
+        self.0
+            .retain(|(_, arrival)| now.saturating_sub(*arrival) < policy.ttl);
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{OrphanPolicy, OrphanPool};
+    use crate::{Block, Hash, Transaction, TransactionData};
+
+    fn block(parent: &str, id: &str) -> Block {
+        let mut block = Block::new(Some(Hash::digest(parent.as_bytes())));
+        block.add_transaction(
+            Transaction::new(id.into(), TransactionData::CreateUserAccount(id.into()), 0)
+                .with_created_at(0),
+        );
+        block.set_timestamp(0);
+        block
+    }
+
+    #[test]
+    fn orphans_are_limited_and_expire() {
+        let policy = OrphanPolicy {
+            max_blocks: 2,
+            ttl: 1_000,
+        };
+        let mut pool = OrphanPool::default();
+        pool.insert(block("a", "alice"), 0, &policy);
+        pool.insert(block("a", "alice"), 0, &policy);
+        pool.insert(block("a", "bob"), 10, &policy);
+        assert_eq!(2, pool.blocks().count());
+
+        // The oldest orphan is evicted by a new one once the pool is full
+        pool.insert(block("b", "carol"), 20, &policy);
+        let children = pool.take_children(&Hash::digest(b"a"), 30, &policy);
+        assert_eq!(1, children.len());
+        assert_eq!(block("a", "bob").hash, children[0].hash);
+
+        assert!(pool
+            .take_children(&Hash::digest(b"b"), 1_020, &policy)
+            .is_empty());
+        assert_eq!(0, pool.blocks().count());
+    }
+}
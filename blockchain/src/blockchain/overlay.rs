@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use super::tombstone;
+use crate::{Account, AccountType, BlockchainError, BridgeConfig, WorldState};
+
+/// A copy-on-write view of a world state, which transactions can be executed against
+/// without changing it (see `Blockchain::simulate_transaction`).<br/>
+/// The accounts are read from the underlying world state until they are changed for the
+/// first time, after which the overlay holds their copies (or their absence, once deleted).
+pub(crate) struct Overlay<'a> {
+    base: &'a dyn WorldState,
+
+    /// The changed accounts; `None` marks a deleted one
+    changed: HashMap<String, Option<Account>>,
+}
+
+impl<'a> Overlay<'a> {
+    /// C'tor.
+    pub(crate) fn new(base: &'a dyn WorldState) -> Self {
+        Overlay {
+            base,
+            changed: HashMap::new(),
+        }
+    }
+
+    /// Will return the changes made on top of the underlying world state
+    pub(crate) fn into_changes(self) -> HashMap<String, Option<Account>> {
+        self.changed
+    }
+
+    /// Will apply the changes (e.g. the ones of an overlay on top of this one)
+    pub(crate) fn apply(&mut self, changes: HashMap<String, Option<Account>>) {
+        self.changed.extend(changes);
+    }
+}
+
+impl WorldState for Overlay<'_> {
+    fn get_user_ids(&self) -> Vec<String> {
+        self.accounts().map(|(id, _)| id.clone()).collect()
+    }
+
+    fn get_account_by_id_mut(&mut self, id: &str) -> Option<&mut Account> {
+        if !self.changed.contains_key(id) {
+            let account = self.base.get_account_by_id(id)?.clone();
+            self.changed.insert(id.to_string(), Some(account));
+        }
+        self.changed.get_mut(id).and_then(Option::as_mut)
+    }
+
+    fn get_account_by_id(&self, id: &str) -> Option<&Account> {
+        match self.changed.get(id) {
+            Some(account) => account.as_ref(),
+            None => self.base.get_account_by_id(id),
+        }
+    }
+
+    fn accounts(&self) -> Box<dyn Iterator<Item = (&String, &Account)> + '_> {
+        let changed = &self.changed;
+        let unchanged = self
+            .base
+            .accounts()
+            .filter(move |(id, _)| !changed.contains_key(*id));
+        let changed = changed
+            .iter()
+            .filter_map(|(id, account)| account.as_ref().map(|account| (id, account)));
+        Box::new(unchanged.chain(changed))
+    }
+
+    fn gas_price(&self) -> u128 {
+        self.base.gas_price()
+    }
+
+    fn height(&self) -> usize {
+        self.base.height()
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.base.timestamp()
+    }
+
+    fn chain_id(&self) -> &str {
+        self.base.chain_id()
+    }
+
+    fn bridge(&self, chain_id: &str) -> Option<BridgeConfig> {
+        self.base.bridge(chain_id)
+    }
+
+    fn name_registration_period(&self) -> usize {
+        self.base.name_registration_period()
+    }
+
+    fn create_account(
+        &mut self,
+        id: String,
+        account_type: AccountType,
+    ) -> Result<(), BlockchainError> {
+        if self.account_exists(&id) {
+            return Err(BlockchainError::AccountExists(id));
+        }
+        let account = tombstone::new_account(self, &id, account_type);
+        self.changed.insert(id, Some(account));
+        Ok(())
+    }
+
+    fn delete_account(&mut self, id: &str) -> Result<Account, BlockchainError> {
+        let account = self
+            .get_account_by_id(id)
+            .cloned()
+            .ok_or_else(|| BlockchainError::UnknownAccount(id.to_string()))?;
+        self.changed.insert(id.to_string(), None);
+        Ok(account)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::Overlay;
+    use crate::test_utils::{account_id, ChainBuilder};
+    use crate::{AccountType, WorldState};
+
+    #[test]
+    fn changes_stay_in_the_overlay() {
+        let chain = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_account("bob", 0)
+            .build();
+        let (alice, bob) = (account_id("alice"), account_id("bob"));
+
+        let mut overlay = Overlay::new(&chain);
+        overlay.get_account_by_id_mut(&alice).unwrap().tokens = 40;
+        overlay.delete_account(&bob).unwrap();
+        overlay
+            .create_account("carol".into(), AccountType::User)
+            .unwrap();
+        assert!(overlay
+            .create_account(alice.clone(), AccountType::User)
+            .is_err());
+
+        assert_eq!(Some(40), overlay.get_balance(&alice));
+        assert!(!overlay.account_exists(&bob));
+        let mut ids = overlay.get_user_ids();
+        ids.sort();
+        let mut expected = vec![alice.clone(), "carol".to_string()];
+        expected.sort();
+        assert_eq!(expected, ids);
+
+        // The underlying world state is untouched
+        assert_eq!(Some(100), chain.get_balance(&alice));
+        assert!(chain.account_exists(&bob));
+        assert!(!chain.account_exists("carol"));
+    }
+}
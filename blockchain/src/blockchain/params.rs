@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Block, BlockchainError, Transaction, TransactionData};
+
+/// Limits of the blocks (and of their transactions), which prevent blocks that would
+/// exhaust the resources (memory, storage, bandwidth) of the nodes.<br/>
+/// They are checked when a transaction is added to a block (see `Block::try_add_transaction`)
+/// and when a block is appended.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainParams {
+    /// The maximum amount of transactions of a block
+    pub max_transactions_per_block: usize,
+
+    /// The maximum size (in bytes) of a block, serialized with the (bincode) binary format
+    pub max_block_size: u64,
+
+    /// The maximum length (in bytes) of a value stored by a `ChangeStoreValue` transaction
+    /// (or of the metadata of a minted NFT)
+    pub max_store_value_length: usize,
+
+    /// If set, the network is meant for development: anyone may mint tokens with a `Faucet`
+    /// transaction
+    pub dev_mode: bool,
+
+    /// The maximum amount of tokens a `Faucet` transaction mints
+    pub max_faucet_amount: u128,
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        ChainParams {
+            max_transactions_per_block: 10_000,
+            max_block_size: 4 * 1024 * 1024,
+            max_store_value_length: 64 * 1024,
+            dev_mode: false,
+            max_faucet_amount: 1_000_000,
+        }
+    }
+}
+
+impl ChainParams {
+    /// Will check the limits of a single transaction
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        self.check_operation(&transaction.record)
+    }
+
+    /// Will check the limits of an operation (including the ones of a batch), as well as
+    /// that the faucet is only used on development networks
+    fn check_operation(&self, operation: &TransactionData) -> Result<(), BlockchainError> {
+        match operation {
+            TransactionData::ChangeStoreValue { value, .. }
+            | TransactionData::MintNft {
+                metadata: value, ..
+            } if value.len() > self.max_store_value_length => {
+                Err(BlockchainError::StoreValueTooLong {
+                    max: self.max_store_value_length,
+                })
+            }
+            TransactionData::Faucet { .. } if !self.dev_mode => {
+                Err(BlockchainError::FaucetDisabled)
+            }
+            TransactionData::Faucet { amount, .. } if *amount > self.max_faucet_amount => {
+                Err(BlockchainError::FaucetLimitExceeded {
+                    max: self.max_faucet_amount,
+                })
+            }
+            TransactionData::Batch(operations) => operations
+                .iter()
+                .try_for_each(|operation| self.check_operation(operation)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Will check the limits of a block (but not the ones of its transactions)
+    pub fn check_block(&self, block: &Block) -> Result<(), BlockchainError> {
+        if block.get_transaction_count() > self.max_transactions_per_block {
+            return Err(BlockchainError::TooManyTransactions {
+                max: self.max_transactions_per_block,
+            });
+        }
+        if block.size()? > self.max_block_size {
+            return Err(BlockchainError::BlockTooLarge {
+                max: self.max_block_size,
+            });
+        }
+        Ok(())
+    }
+}
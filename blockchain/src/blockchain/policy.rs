@@ -0,0 +1,302 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use super::names;
+use super::transaction;
+use crate::consensus::{NoOpConsensus, ProofOfAuthority, ProofOfStake, ProofOfWork};
+use crate::{
+    Block, BlockchainError, BridgeConfig, ChainParams, Checkpoint, Consensus, ConsensusEngine,
+    DifficultyParams, Transaction,
+};
+
+/// The chain id of the (local) development network, used unless another one is configured.
+pub const DEFAULT_CHAIN_ID: &str = "dev";
+
+/// Defines the rules that the blocks (and their transactions) have to follow
+/// in order to be accepted by the blockchain.<br/>
+/// The default policy keeps the (demo) behavior of accepting unsigned transactions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationPolicy {
+    /// Identifies the network (dev, test, main, ...) the chain belongs to
+    pub chain_id: String,
+
+    /// If set, every transaction has to carry a valid signature.
+    pub require_signatures: bool,
+
+    /// If set, the transactions of the genesis block may be unsigned,
+    /// even if `require_signatures` is set.
+    pub allow_unsigned_genesis: bool,
+
+    /// If set, every block has to commit to the world state after executing it (state root).
+    /// A block that does carry a state root is always checked against it.
+    pub require_state_root: bool,
+
+    /// The consensus mechanism deciding who may produce the blocks (Proof of Work by default)
+    pub engine: ConsensusEngine,
+
+    /// The Proof of Work parameters (by default no Proof of Work is required)
+    pub difficulty: DifficultyParams,
+
+    /// The amount of tokens (subsidy) a block producer gets through the coinbase,
+    /// on top of the fees of the block
+    pub block_reward: u128,
+
+    /// The amount of previous blocks whose median timestamp (median time past)
+    /// a new block must not be older than
+    pub median_time_span: usize,
+
+    /// How far (in milliseconds) the timestamp of a new block may be ahead of our clock
+    pub max_future_drift: u64,
+
+    /// The amount of tokens the sender pays (burns) per unit of gas a transaction uses
+    pub gas_price: u128,
+
+    /// The maximum amount of gas the transactions of a block may use (together)
+    pub block_gas_limit: u64,
+
+    /// The amount of blocks a name is registered (or renewed) for (see `RegisterName`)
+    pub name_registration_period: usize,
+
+    /// The limits of the blocks and of their transactions (size, amount, stored values)
+    pub params: ChainParams,
+
+    /// The hard-coded checkpoints: the blocks at their heights have to have their hashes and
+    /// are finalized once they are appended
+    pub checkpoints: Vec<Checkpoint>,
+
+    /// The scheduled changes of the parameters, activated at their heights (see `at`)
+    pub upgrades: Vec<PolicyUpgrade>,
+
+    /// The chains whose messages are accepted (see `TransactionData::BridgeIn`)
+    pub bridges: Vec<BridgeConfig>,
+}
+
+/// A change of the parameters of the validation policy, activated at a height: the blocks
+/// from the height on are validated (and executed) with the changed parameters, whilst the
+/// blocks before it keep being validated with the previous ones, so that the existing chain
+/// stays valid.<br/>
+/// Only the parameters that are set are changed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyUpgrade {
+    /// The height of the first block the upgrade applies to
+    pub height: usize,
+
+    /// The version of the parameters from the height on (see `ValidationPolicy::version_at`)
+    pub version: u32,
+
+    pub require_signatures: Option<bool>,
+    pub require_state_root: Option<bool>,
+    pub difficulty: Option<DifficultyParams>,
+    pub block_reward: Option<u128>,
+    pub gas_price: Option<u128>,
+    pub block_gas_limit: Option<u64>,
+    pub name_registration_period: Option<usize>,
+    pub params: Option<ChainParams>,
+}
+
+impl PolicyUpgrade {
+    /// Will change the parameters of the policy that the upgrade sets
+    fn apply(&self, policy: &mut ValidationPolicy) {
+        if let Some(require_signatures) = self.require_signatures {
+            policy.require_signatures = require_signatures;
+        }
+        if let Some(require_state_root) = self.require_state_root {
+            policy.require_state_root = require_state_root;
+        }
+        if let Some(difficulty) = &self.difficulty {
+            policy.difficulty = difficulty.clone();
+        }
+        if let Some(block_reward) = self.block_reward {
+            policy.block_reward = block_reward;
+        }
+        if let Some(gas_price) = self.gas_price {
+            policy.gas_price = gas_price;
+        }
+        if let Some(block_gas_limit) = self.block_gas_limit {
+            policy.block_gas_limit = block_gas_limit;
+        }
+        if let Some(period) = self.name_registration_period {
+            policy.name_registration_period = period;
+        }
+        if let Some(params) = &self.params {
+            policy.params = params.clone();
+        }
+    }
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        ValidationPolicy {
+            chain_id: DEFAULT_CHAIN_ID.into(),
+            require_signatures: false,
+            allow_unsigned_genesis: true,
+            require_state_root: false,
+            engine: ConsensusEngine::default(),
+            difficulty: DifficultyParams::default(),
+            block_reward: 0,
+            median_time_span: 11,
+            max_future_drift: 2 * 60 * 60 * 1000,
+            gas_price: 0,
+            block_gas_limit: 10_000_000,
+            name_registration_period: names::DEFAULT_REGISTRATION_PERIOD,
+            params: ChainParams::default(),
+            checkpoints: Vec::new(),
+            upgrades: Vec::new(),
+            bridges: Vec::new(),
+        }
+    }
+}
+
+impl ValidationPolicy {
+    /// Will return the consensus mechanism of the configured engine
+    pub fn consensus(&self) -> Box<dyn Consensus> {
+        match &self.engine {
+            ConsensusEngine::NoOp => Box::new(NoOpConsensus),
+            ConsensusEngine::ProofOfWork => Box::new(ProofOfWork {
+                params: self.difficulty.clone(),
+            }),
+            ConsensusEngine::ProofOfStake { min_stake } => Box::new(ProofOfStake {
+                min_stake: *min_stake,
+            }),
+            ConsensusEngine::ProofOfAuthority { authorities } => Box::new(ProofOfAuthority {
+                authorities: authorities.clone(),
+            }),
+        }
+    }
+
+    /// Will return the policy that the block at the height is validated (and executed) with:
+    /// the upgrades activated up to the height are applied, in the order of their heights.
+    pub fn at(&self, height: usize) -> Cow<'_, ValidationPolicy> {
+        let mut upgrades: Vec<&PolicyUpgrade> = self
+            .upgrades
+            .iter()
+            .filter(|upgrade| upgrade.height <= height)
+            .collect();
+        if upgrades.is_empty() {
+            return Cow::Borrowed(self);
+        }
+        upgrades.sort_by_key(|upgrade| upgrade.height);
+        let mut policy = self.clone();
+        for upgrade in upgrades {
+            upgrade.apply(&mut policy);
+        }
+        Cow::Owned(policy)
+    }
+
+    /// Will return the version of the parameters at the height: the one of the latest
+    /// upgrade activated up to the height (0 before the first one)
+    pub fn version_at(&self, height: usize) -> u32 {
+        self.upgrades
+            .iter()
+            .filter(|upgrade| upgrade.height <= height)
+            .max_by_key(|upgrade| upgrade.height)
+            .map_or(0, |upgrade| upgrade.version)
+    }
+
+    /// Will check that the block (at the height) has the version active at the height,
+    /// and that none of its transactions was created for a newer one
+    pub(crate) fn check_versions(
+        &self,
+        height: usize,
+        block: &Block,
+    ) -> Result<(), BlockchainError> {
+        let expected = self.version_at(height);
+        if block.header.version != expected {
+            return Err(BlockchainError::UnexpectedBlockVersion {
+                expected,
+                found: block.header.version,
+            });
+        }
+        for (i, transaction) in block.body.transactions.iter().enumerate() {
+            self.check_transaction_version(height, transaction)
+                .map_err(|err| err.in_transaction(i))?;
+        }
+        Ok(())
+    }
+
+    /// Will check that the transaction wasn't created for a version newer than the one
+    /// active at the height
+    pub(crate) fn check_transaction_version(
+        &self,
+        height: usize,
+        transaction: &Transaction,
+    ) -> Result<(), BlockchainError> {
+        let max = self.version_at(height);
+        if transaction.version() > max {
+            return Err(BlockchainError::UnsupportedTransactionVersion {
+                max,
+                found: transaction.version(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Will return the hard-coded checkpoint at the height, if any
+    pub(crate) fn checkpoint_at(&self, height: usize) -> Option<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.height == height)
+    }
+
+    /// Will check the signature of a transaction (see `accepts_signature`).<br/>
+    /// The coinbase may be unsigned, since it only pays the (anonymous) block producer.
+    pub(crate) fn accepts_transaction(&self, transaction: &Transaction, is_genesis: bool) -> bool {
+        let is_signed = transaction.is_signed();
+        if !is_signed && transaction.is_coinbase() {
+            return true;
+        }
+        let is_valid = is_signed && transaction.check_signature();
+        self.accepts_signature(is_signed, is_valid, is_genesis)
+    }
+
+    /// Will check the signatures of the transactions of a block (see `accepts_transaction`),
+    /// returning the index of the first rejected transaction, if any.<br/>
+    /// The signed transactions are verified with a single batched verification, only if it
+    /// fails they are verified one by one (to find the offending transaction).
+    pub(crate) fn first_rejected_transaction(
+        &self,
+        transactions: &[Transaction],
+        is_genesis: bool,
+    ) -> Option<usize> {
+        let signed: Vec<&Transaction> = transactions.iter().filter(|t| t.is_signed()).collect();
+        if signed.is_empty() || transaction::verify_batch(&signed) {
+            // Only the unsigned transactions may be rejected
+            return transactions.iter().position(|transaction| {
+                !transaction.is_signed() && !self.accepts_transaction(transaction, is_genesis)
+            });
+        }
+        transactions
+            .iter()
+            .position(|transaction| !self.accepts_transaction(transaction, is_genesis))
+    }
+
+    /// Will check if the signature state of a transaction is acceptable.<br/>
+    /// A signed transaction must always have a valid signature, whilst an unsigned one
+    /// is only accepted if signatures are not required (for that block).
+    pub(crate) fn accepts_signature(
+        &self,
+        is_signed: bool,
+        is_valid: bool,
+        is_genesis: bool,
+    ) -> bool {
+        if is_signed {
+            return is_valid;
+        }
+        !self.require_signatures || (is_genesis && self.allow_unsigned_genesis)
+    }
+
+    /// Will return the median timestamp of the last `median_time_span` blocks
+    /// (or `None` if there are no blocks yet).
+    pub(crate) fn median_time_past(&self, blocks: &[Block]) -> Option<u64> {
+        let span = self.median_time_span.max(1).min(blocks.len());
+        let mut timestamps: Vec<u64> = blocks[blocks.len() - span..]
+            .iter()
+            .map(|block| block.header.timestamp)
+            .collect();
+        timestamps.sort_unstable();
+        timestamps.get(timestamps.len() / 2).copied()
+    }
+}
@@ -0,0 +1,219 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+/// The outcome of a transaction that was included into a block.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ReceiptStatus {
+    /// All changes of the transaction were applied
+    Success,
+
+    /// The called contract failed (or ran out of gas): only the fee and the gas
+    /// were paid, its storage is unchanged
+    Failed(String),
+}
+
+/// Something that happened while executing a transaction, meant for off-chain indexers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// An account (user, contract or multisignature) was created
+    AccountCreated { id: String },
+
+    /// An account was closed, its remaining tokens were moved to the beneficiary
+    AccountDeleted { id: String, beneficiary: String },
+
+    /// Tokens were created (by the genesis block or as block reward)
+    TokensCreated { receiver: String, amount: u128 },
+
+    /// An admin froze an account, it can't transfer its tokens anymore
+    AccountFrozen { id: String },
+
+    /// An admin unfroze an account
+    AccountUnfrozen { id: String },
+
+    /// Tokens were moved from one account to another
+    Transfer {
+        from: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// The owner allowed the spender to transfer (up to) the amount of its tokens
+    Approval {
+        owner: String,
+        spender: String,
+        amount: u128,
+    },
+
+    /// An amount of an asset (other than the native tokens) was created, either as the supply
+    /// of a new asset or by the genesis block
+    AssetsCreated {
+        asset: String,
+        receiver: String,
+        amount: u128,
+    },
+
+    /// An amount of an asset (other than the native tokens) was moved from one account to another
+    AssetTransfer {
+        asset: String,
+        from: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// A (non-fungible) token was minted for its owner
+    NftMinted { id: String, owner: String },
+
+    /// A (non-fungible) token was moved from one account to another
+    NftTransferred {
+        id: String,
+        from: String,
+        to: String,
+    },
+
+    /// Tokens were put aside by the sender for the receiver (see `EscrowRecord`)
+    EscrowCreated {
+        id: String,
+        from: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// The tokens of an escrow were paid to its receiver
+    EscrowReleased {
+        id: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// The tokens of an escrow were paid back to its sender
+    EscrowRefunded {
+        id: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// Tokens were locked by the sender for the receiver (see `HtlcRecord`)
+    HtlcLocked {
+        id: String,
+        from: String,
+        to: String,
+        amount: u128,
+        hash_lock: String,
+    },
+
+    /// The tokens of a hashed timelock contract were claimed by revealing the preimage of its
+    /// hash lock (which the counterparty of an atomic swap reads from this event)
+    HtlcClaimed {
+        id: String,
+        to: String,
+        amount: u128,
+        preimage: String,
+    },
+
+    /// The tokens of an expired hashed timelock contract were paid back to its sender
+    HtlcRefunded {
+        id: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// Tokens were burnt by the sender, to be minted for the receiver by the target chain
+    BridgeTransferSent {
+        target_chain: String,
+        from: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// Tokens burnt by the source chain were minted for the receiver (the id being the hash
+    /// of the `BridgeOut` transaction)
+    BridgeTransferReceived {
+        source_chain: String,
+        id: String,
+        to: String,
+        amount: u128,
+    },
+
+    /// A value was stored by an account
+    StoreValueChanged { id: String, key: String },
+
+    /// A metadata entry of an account was set (or removed)
+    MetadataChanged { id: String, key: String },
+
+    /// An alias was registered for an address
+    AliasRegistered { alias: String, address: String },
+
+    /// A name was registered (or renewed) for an address, until the height it expires at
+    NameRegistered {
+        name: String,
+        owner: String,
+        expires_at: usize,
+    },
+
+    /// Tokens were locked as the stake of a validator
+    Staked { validator: String, amount: u128 },
+
+    /// Tokens of the stake of a validator were unlocked
+    Unstaked { validator: String, amount: u128 },
+
+    /// The stake of a misbehaving validator was burned
+    Slashed { validator: String, amount: u128 },
+
+    /// An event emitted by the code of a contract (through the `emit_event` host function)
+    Contract {
+        address: String,
+        topic: String,
+        data: Vec<u8>,
+    },
+}
+
+/// The change of the balance (of native tokens) of an account by a transaction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub account: String,
+
+    /// The amount of tokens the account gained (or, if negative, lost)
+    pub delta: i128,
+}
+
+impl BalanceChange {
+    /// Will calculate the change from the balance before to the balance after
+    /// (saturating at the bounds of `i128`)
+    pub(crate) fn between(account: String, before: u128, after: u128) -> Self {
+        let delta = match after.checked_sub(before) {
+            Some(gained) => i128::try_from(gained).unwrap_or(i128::MAX),
+            None => i128::try_from(before - after).map_or(i128::MIN, |lost| -lost),
+        };
+        BalanceChange { account, delta }
+    }
+}
+
+/// The receipt of an executed transaction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    /// The hash of the transaction
+    pub transaction_hash: Hash,
+
+    pub status: ReceiptStatus,
+
+    /// The gas the transaction used (and paid for)
+    pub gas_used: u64,
+
+    /// The events of the transaction, in the order they were emitted
+    pub events: Vec<Event>,
+
+    /// The changes of the balances of the accounts the transaction touched (even if it left
+    /// their balances unchanged), in the order they were touched first
+    #[serde(default)]
+    pub balance_changes: Vec<BalanceChange>,
+}
+
+impl TransactionReceipt {
+    /// Checks if all changes of the transaction were applied
+    pub fn is_success(&self) -> bool {
+        self.status == ReceiptStatus::Success
+    }
+}
@@ -0,0 +1,238 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Block, Blockchain, BlockchainError, Transaction};
+
+/// The maximum depth that values containing values of their own type (batches of operations,
+/// evidences holding blocks) are decoded to
+const MAX_NESTING: usize = 8;
+
+thread_local! {
+    static NESTING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Will deserialize a value that may (indirectly) contain values of its own type, failing
+/// if it is nested deeper than `MAX_NESTING`, so that adversarial input can't overflow
+/// the stack of the decoder.
+pub(crate) fn nested<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct Level;
+    impl Drop for Level {
+        fn drop(&mut self) {
+            NESTING.with(|nesting| nesting.set(nesting.get() - 1));
+        }
+    }
+
+    let depth = NESTING.with(|nesting| {
+        nesting.set(nesting.get() + 1);
+        nesting.get()
+    });
+    let _level = Level;
+    if depth > MAX_NESTING {
+        return Err(D::Error::custom("the value is nested too deeply"));
+    }
+    T::deserialize(deserializer)
+}
+
+/// Will serialize a HashMap ordered by its keys, so that the encoding is stable
+/// (the iteration order of a HashMap is random).
+pub(crate) fn ordered_map<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+impl Blockchain {
+    /// Will encode the Blockchain (blocks, world state, mempool and policy) as JSON.
+    pub fn to_json(&self) -> Result<String, BlockchainError> {
+        serde_json::to_string(self).map_err(|err| BlockchainError::Serialization(err.to_string()))
+    }
+
+    /// Will decode a Blockchain from JSON.<br/>
+    /// The decoded chain is not validated, use `check_validity` if it is not trusted.
+    pub fn from_json(json: &str) -> Result<Self, BlockchainError> {
+        serde_json::from_str(json).map_err(|err| BlockchainError::Serialization(err.to_string()))
+    }
+
+    /// Will encode the Blockchain using the (bincode) binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BlockchainError> {
+        super::storage::encode(self)
+    }
+
+    /// Will decode a Blockchain from the (bincode) binary format.<br/>
+    /// The decoded chain is not validated, use `check_validity` if it is not trusted.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BlockchainError> {
+        super::storage::decode(bytes)
+    }
+}
+
+impl Block {
+    /// Will encode the block using the (bincode) binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BlockchainError> {
+        super::storage::encode(self)
+    }
+
+    /// Will decode a block from the (bincode) binary format, failing gracefully on malformed
+    /// (e.g. adversarial) bytes.<br/>
+    /// The decoded block is not validated, that's what `Blockchain::append_block` does.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BlockchainError> {
+        super::storage::decode(bytes)
+    }
+}
+
+impl Transaction {
+    /// Will encode the transaction using the (bincode) binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BlockchainError> {
+        super::storage::encode(self)
+    }
+
+    /// Will decode a transaction from the (bincode) binary format, failing gracefully on
+    /// malformed (e.g. adversarial) bytes.<br/>
+    /// The decoded transaction is not validated, that's what `Blockchain::submit_transaction`
+    /// does.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BlockchainError> {
+        super::storage::decode(bytes)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use proptest::prelude::*;
+
+    use super::super::storage::decode;
+    use crate::test_utils::{
+        account_id, mine_block, signed_transaction, ChainBuilder, TEST_CHAIN_ID,
+    };
+    use crate::{Block, Blockchain, Transaction, TransactionData};
+
+    fn sample_chain() -> Blockchain {
+        let mut bc = Blockchain::new();
+        let mut genesis = Block::new(None);
+        for user in &["alice", "bob", "carol"] {
+            genesis.add_transaction(Transaction::new(
+                (*user).into(),
+                TransactionData::CreateUserAccount((*user).into()),
+                0,
+            ));
+            genesis.add_transaction(Transaction::new(
+                (*user).into(),
+                TransactionData::CreateTokens {
+                    receiver: (*user).into(),
+                    amount: 10,
+                    asset: None,
+                },
+                0,
+            ));
+        }
+        bc.append_block(genesis).unwrap();
+        bc
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let bc = sample_chain();
+        let json = bc.to_json().unwrap();
+        let decoded = Blockchain::from_json(&json).unwrap();
+
+        assert_eq!(bc.get_last_block_hash(), decoded.get_last_block_hash());
+        assert_eq!(10, decoded.accounts["bob"].tokens);
+        assert!(decoded.check_validity().is_ok());
+
+        // The encoding is stable
+        assert_eq!(json, decoded.to_json().unwrap());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let bc = sample_chain();
+        let bytes = bc.to_bytes().unwrap();
+        let decoded = Blockchain::from_bytes(&bytes).unwrap();
+
+        assert_eq!(bc.get_last_block_hash(), decoded.get_last_block_hash());
+        assert!(decoded.check_validity().is_ok());
+        assert_eq!(bytes, decoded.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn decoding_garbage_fails() {
+        assert!(Blockchain::from_json("{").is_err());
+        assert!(Blockchain::from_bytes(&[1, 2, 3]).is_err());
+    }
+
+    /// Will encode a batch (the variant 21 of the operations) nested `depth` times
+    fn nested_batch(depth: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for _ in 0..depth {
+            bytes.extend_from_slice(&21u32.to_le_bytes());
+            bytes.extend_from_slice(&1u64.to_le_bytes());
+        }
+        bytes.extend_from_slice(&21u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn adversarial_bytes_fail_gracefully() {
+        let decode = |bytes: &[u8]| decode::<TransactionData>(bytes);
+        assert_eq!(
+            TransactionData::Batch(vec![TransactionData::Batch(Vec::new())]),
+            decode(&nested_batch(1)).unwrap()
+        );
+        // Without a limit, decoding would overflow the stack
+        assert!(decode(&nested_batch(100_000)).is_err());
+
+        // A length that exceeds the bytes is rejected before anything is allocated
+        let mut huge = Vec::new();
+        huge.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Block::from_bytes(&huge).is_err());
+        assert!(Transaction::from_bytes(&huge).is_err());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn corrupted_blocks_never_panic(
+            mutations in proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+            truncate in any::<prop::sample::Index>(),
+        ) {
+            let mut chain = ChainBuilder::new()
+                .with_account("alice", 100)
+                .transfer("alice", "bob", 10)
+                .build();
+            let transfer = TransactionData::TransferTokens {
+                to: account_id("bob"),
+                amount: 5,
+                asset: None,
+            };
+            let transaction = signed_transaction(TEST_CHAIN_ID, "alice", transfer, 1);
+            let block = mine_block(&mut chain, vec![transaction]).unwrap();
+
+            let mut bytes = block.to_bytes().unwrap();
+            for (index, byte) in mutations {
+                let index = index.index(bytes.len());
+                bytes[index] = byte;
+            }
+            // Whatever the decoded block is, it is rejected (or accepted) without panicking
+            if let Ok(block) = Block::from_bytes(&bytes) {
+                let _ = chain.clone().append_block(block);
+            }
+            let truncated = &bytes[..truncate.index(bytes.len())];
+            prop_assert!(Block::from_bytes(truncated).is_err());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Serialize;
+
+use super::canonical;
+use crate::{TimeLock, TransactionData};
+
+/// The domain tag every signed document starts with, so that a signature of a transaction
+/// can't be passed off as a signature of anything else (e.g. of a block or of a message of
+/// another protocol using the same keys).
+pub const SIGN_DOC_DOMAIN: &str = "abc/transaction/v1";
+
+/// Exactly what the sender (and the cosigners) of a transaction sign (see
+/// `Transaction::sign_doc`): every field that determines what the transaction does and where
+/// it may be included, but not its signatures.<br/>
+/// The signed bytes (see `to_bytes`) are the canonical encoding (see `canonical::encode`) of
+/// the domain tag followed by the fields, in the order of their declaration, so that other
+/// implementations can produce (and check) the same signatures.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SignDoc<'a> {
+    /// The network the transaction is meant for, so that it can't be replayed on another one
+    pub chain_id: &'a str,
+
+    pub nonce: u128,
+
+    pub from: &'a str,
+
+    pub record: &'a TransactionData,
+
+    pub fee: u128,
+
+    /// The time (unix epoch, in milliseconds) the transaction was created
+    pub timestamp: u64,
+
+    /// The version of the rules the transaction was created for
+    pub version: u32,
+
+    pub valid_after: Option<TimeLock>,
+
+    pub valid_before: Option<TimeLock>,
+}
+
+impl SignDoc<'_> {
+    /// Will return the bytes that are signed: the domain tag and the fields, encoded
+    /// canonically
+    pub fn to_bytes(&self) -> Vec<u8> {
+        canonical::encode(&(SIGN_DOC_DOMAIN, self))
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{Keypair, Transaction, TransactionData};
+
+    #[test]
+    fn sign_docs_are_locked_and_domain_separated() {
+        let transaction = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            3,
+        )
+        .with_chain_id("dev".into())
+        .with_created_at(1_600_000_000_000);
+        // The domain tag, the chain id, the nonce, the sender, the record, the fee, the
+        // timestamp, the version and the (missing) validity window
+        assert_eq!(
+            "1200000000000000616263\
+             2f7472616e73616374696f6e2f7631\
+             0300000000000000646576\
+             03000000000000000000000000000000\
+             0500000000000000616c696365\
+             000000000300000000000000626f62\
+             00000000000000000000000000000000\
+             00806e8774010000\
+             00000000\
+             0000",
+            hex::encode(transaction.sign_doc().to_bytes())
+        );
+
+        // A signature of the bare hash of the transaction (like blocks and checkpoints are
+        // signed) isn't a signature of the transaction
+        let keypair = Keypair::generate();
+        let mut transaction = Transaction::new(
+            keypair.address().to_string(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        transaction.sign(&keypair);
+        assert!(transaction.check_signature());
+        let mut json = serde_json::to_value(&transaction).unwrap();
+        let signature = keypair.sign(transaction.calculate_hash().as_bytes());
+        json["signature"] = hex::encode(signature).into();
+        let forged: Transaction = serde_json::from_value(json).unwrap();
+        assert!(!forged.check_signature());
+    }
+}
@@ -0,0 +1,472 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::test_utils::mine_block;
+use crate::{Block, Blockchain, BlockchainError, Hash, Transaction};
+
+/// The (simulated) time, in ticks
+pub type Tick = u64;
+
+/// The messages that the simulated nodes exchange over the bus, like the nodes of a real
+/// network gossip them.
+#[derive(Clone, Debug)]
+pub enum SimMessage {
+    /// A block that was appended to the sender's chain
+    NewBlock(Block),
+
+    /// A transaction that was accepted into the sender's mempool
+    NewTransaction(Transaction),
+
+    /// Requests the blocks starting with the provided height (e.g. to resolve a fork)
+    GetBlocks { from: usize },
+
+    /// The response to `GetBlocks`
+    Blocks { from: usize, blocks: Vec<Block> },
+}
+
+/// A message on its way between two nodes
+#[derive(Clone, Debug)]
+struct Envelope {
+    from: usize,
+    to: usize,
+    message: SimMessage,
+}
+
+/// Runs multiple chains (nodes) in-process, connected by an in-memory message bus, so that
+/// tests can check how they converge without real sockets.<br/>
+/// Messages take a (configurable) amount of ticks to be delivered and are dropped between
+/// partitioned nodes. Everything is deterministic: messages are delivered in the order they
+/// are due and, at the same tick, in the order they were sent.<br/>
+/// The nodes follow the longest chain: a block that doesn't extend the tip makes the node
+/// fetch the sender's chain and switch to it if it is longer (or as long, with a lower tip
+/// hash, so that competing forks of the same length converge too), unless the fork would
+/// replace finalized blocks.
+pub struct Simulation {
+    nodes: Vec<Blockchain>,
+    now: Tick,
+    latency: Tick,
+    link_latencies: HashMap<(usize, usize), Tick>,
+    /// The group of every node, if the network is partitioned
+    partition: Option<Vec<usize>>,
+    /// The messages in flight, by their delivery time and their sending order
+    in_flight: BTreeMap<(Tick, u64), Envelope>,
+    sent: u64,
+    dropped: usize,
+}
+
+impl Simulation {
+    /// C'tor, out of `nodes` copies of the chain (e.g. built by `ChainBuilder`),
+    /// delivering messages after one tick.
+    pub fn new(chain: &Blockchain, nodes: usize) -> Self {
+        Simulation {
+            nodes: (0..nodes).map(|_| chain.clone()).collect(),
+            now: 0,
+            latency: 1,
+            link_latencies: HashMap::new(),
+            partition: None,
+            in_flight: BTreeMap::new(),
+            sent: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Sets the amount of ticks that messages take to be delivered
+    pub fn with_latency(mut self, latency: Tick) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Sets the amount of ticks that messages take between two nodes (in both directions),
+    /// overriding the latency of the network
+    pub fn set_link_latency(&mut self, a: usize, b: usize, latency: Tick) {
+        self.link_latencies.insert((a.min(b), a.max(b)), latency);
+    }
+
+    /// Will split the network into the groups of nodes: messages between the groups are
+    /// dropped (including the ones in flight), until the network is healed.<br/>
+    /// Nodes not named by any group are isolated.
+    pub fn partition(&mut self, groups: &[&[usize]]) {
+        let mut partition: Vec<usize> = (groups.len()..groups.len() + self.nodes.len()).collect();
+        for (group, nodes) in groups.iter().enumerate() {
+            for node in nodes.iter() {
+                partition[*node] = group;
+            }
+        }
+        self.partition = Some(partition);
+    }
+
+    /// Will reconnect all nodes, which then announce their tips to each other
+    /// (like reconnecting peers do).
+    pub fn heal(&mut self) {
+        self.partition = None;
+        for node in 0..self.nodes.len() {
+            if let Some(tip) = self.nodes[node].blocks.last().cloned() {
+                self.broadcast(node, None, SimMessage::NewBlock(tip));
+            }
+        }
+    }
+
+    /// Will return the chain of the node
+    pub fn node(&self, node: usize) -> &Blockchain {
+        &self.nodes[node]
+    }
+
+    /// Will return the chain of the node, for altering it without notifying the others
+    pub fn node_mut(&mut self, node: usize) -> &mut Blockchain {
+        &mut self.nodes[node]
+    }
+
+    /// Will return the amount of nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Will return true if there are no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Will return the current time
+    pub fn now(&self) -> Tick {
+        self.now
+    }
+
+    /// Will return the amount of messages that were dropped (between partitioned nodes)
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Will return the amount of messages in flight
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Will submit the transaction to the mempool of the node and gossip it
+    pub fn submit_transaction(
+        &mut self,
+        node: usize,
+        transaction: Transaction,
+    ) -> Result<(), BlockchainError> {
+        self.nodes[node].submit_transaction(transaction.clone())?;
+        self.broadcast(node, None, SimMessage::NewTransaction(transaction));
+        Ok(())
+    }
+
+    /// Will mine a block out of the transactions on top of the node's chain (see
+    /// `test_utils::mine_block`), append it and gossip it, returning its hash
+    pub fn mine_block(
+        &mut self,
+        node: usize,
+        transactions: Vec<Transaction>,
+    ) -> Result<Hash, BlockchainError> {
+        let block = mine_block(&mut self.nodes[node], transactions)?;
+        self.append_block(node, block)
+    }
+
+    /// Will append the block to the node's chain and gossip it, returning its hash
+    pub fn append_block(&mut self, node: usize, block: Block) -> Result<Hash, BlockchainError> {
+        self.nodes[node].append_block(block.clone())?;
+        let hash = block.hash.ok_or(BlockchainError::HashMismatch)?;
+        self.broadcast(node, None, SimMessage::NewBlock(block));
+        Ok(hash)
+    }
+
+    /// Will advance the time by one tick, delivering the messages that are due
+    pub fn step(&mut self) {
+        self.now += 1;
+        while let Some(entry) = self.in_flight.first_entry() {
+            if entry.key().0 > self.now {
+                break;
+            }
+            let envelope = entry.remove();
+            if self.is_connected(envelope.from, envelope.to) {
+                self.deliver(envelope);
+            } else {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Will advance the time until no messages are in flight (or `max_ticks` passed),
+    /// returning true if the network became idle
+    pub fn run(&mut self, max_ticks: Tick) -> bool {
+        for _ in 0..max_ticks {
+            if self.in_flight.is_empty() {
+                return true;
+            }
+            self.step();
+        }
+        self.in_flight.is_empty()
+    }
+
+    /// Will return true if all nodes have the same tip
+    pub fn is_converged(&self) -> bool {
+        self.nodes
+            .windows(2)
+            .all(|pair| pair[0].get_last_block_hash() == pair[1].get_last_block_hash())
+    }
+
+    /// Will send the message from the node to all the others (except `except`)
+    fn broadcast(&mut self, from: usize, except: Option<usize>, message: SimMessage) {
+        for to in 0..self.nodes.len() {
+            if to != from && Some(to) != except {
+                self.send(from, to, message.clone());
+            }
+        }
+    }
+
+    /// Will put the message on the bus, or drop it if the nodes are partitioned
+    fn send(&mut self, from: usize, to: usize, message: SimMessage) {
+        if !self.is_connected(from, to) {
+            self.dropped += 1;
+            return;
+        }
+        let latency = self
+            .link_latencies
+            .get(&(from.min(to), from.max(to)))
+            .copied()
+            .unwrap_or(self.latency);
+        self.sent += 1;
+        let envelope = Envelope { from, to, message };
+        self.in_flight
+            .insert((self.now + latency, self.sent), envelope);
+    }
+
+    fn is_connected(&self, a: usize, b: usize) -> bool {
+        match &self.partition {
+            Some(partition) => partition[a] == partition[b],
+            None => true,
+        }
+    }
+
+    /// Will handle the message like a node does: accepted blocks and transactions are relayed,
+    /// forks are resolved by fetching the sender's chain.
+    fn deliver(&mut self, envelope: Envelope) {
+        let Envelope { from, to, message } = envelope;
+        match message {
+            SimMessage::NewBlock(block) => {
+                let chain = &self.nodes[to];
+                let hash = match block.hash {
+                    Some(hash) if chain.block_height(&hash).is_none() => hash,
+                    _ => return,
+                };
+                if block.header.prev_hash == chain.get_last_block_hash() {
+                    if self.nodes[to].append_block(block.clone()).is_ok() {
+                        self.broadcast(to, Some(from), SimMessage::NewBlock(block));
+                    }
+                } else if self.prefers(to, chain_len_after(&block, chain), hash) {
+                    self.send(to, from, SimMessage::GetBlocks { from: 0 });
+                } else if let Some(tip) = chain.blocks.last().cloned() {
+                    // Let the sender know about the better chain
+                    self.send(to, from, SimMessage::NewBlock(tip));
+                }
+            }
+            SimMessage::NewTransaction(transaction) => {
+                if self.nodes[to]
+                    .submit_transaction(transaction.clone())
+                    .is_ok()
+                {
+                    self.broadcast(to, Some(from), SimMessage::NewTransaction(transaction));
+                }
+            }
+            SimMessage::GetBlocks { from: height } => {
+                let blocks = self.nodes[to].blocks_range(height, usize::MAX).to_vec();
+                let message = SimMessage::Blocks {
+                    from: height,
+                    blocks,
+                };
+                self.send(to, from, message);
+            }
+            SimMessage::Blocks {
+                from: height,
+                blocks,
+            } => self.switch_fork(to, height, blocks),
+        }
+    }
+
+    /// Will switch the node to the fork (the blocks starting with `height`), if it prefers it
+    fn switch_fork(&mut self, node: usize, height: usize, blocks: Vec<Block>) {
+        let tip = match blocks.last().and_then(|block| block.hash) {
+            Some(tip) => tip,
+            None => return,
+        };
+        if !self.prefers(node, height + blocks.len(), tip) {
+            return;
+        }
+        let chain = &mut self.nodes[node];
+        let common = blocks
+            .iter()
+            .zip(chain.blocks.iter().skip(height))
+            .take_while(|(theirs, ours)| theirs.hash == ours.hash)
+            .count();
+        let fork = height + common;
+        // Finalized blocks are never switched away from, however long the fork is
+        if chain
+            .finalized_height()
+            .is_some_and(|finalized| fork <= finalized)
+        {
+            return;
+        }
+        let blocks = blocks[common..].to_vec();
+        if chain.reorganize(fork, blocks).is_ok() {
+            if let Some(tip) = chain.blocks.last().cloned() {
+                self.broadcast(node, None, SimMessage::NewBlock(tip));
+            }
+        }
+    }
+
+    /// Will return true if the node prefers a chain of the length and the tip over its own:
+    /// the longer chain wins, the lower tip hash breaks ties
+    fn prefers(&self, node: usize, len: usize, tip: Hash) -> bool {
+        let chain = &self.nodes[node];
+        match len.cmp(&chain.len()) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Equal => chain.get_last_block_hash().is_none_or(|own| tip < own),
+            std::cmp::Ordering::Less => false,
+        }
+    }
+}
+
+/// Will return the length of the sender's chain, as far as the block tells: its parent is
+/// either known (a fork) or not (the sender is ahead)
+fn chain_len_after(block: &Block, chain: &Blockchain) -> usize {
+    match block
+        .header
+        .prev_hash
+        .and_then(|prev| chain.block_height(&prev))
+    {
+        Some(height) => height + 2,
+        None => usize::MAX,
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::Simulation;
+    use crate::test_utils::{account_id, keypair, signed_transaction, ChainBuilder, TEST_CHAIN_ID};
+    use crate::{Blockchain, Checkpoint, Transaction, TransactionData, WorldState};
+
+    fn genesis() -> Blockchain {
+        ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_account("bob", 100)
+            .with_account("carol", 0)
+            .build()
+    }
+
+    fn transfer(from: &str, to: &str, amount: u128, nonce: u128) -> Transaction {
+        let data = TransactionData::TransferTokens {
+            to: account_id(to),
+            amount,
+            asset: None,
+        };
+        signed_transaction(TEST_CHAIN_ID, from, data, nonce)
+    }
+
+    fn balance(sim: &Simulation, node: usize, name: &str) -> u128 {
+        sim.node(node)
+            .get_account_by_id(&account_id(name))
+            .unwrap()
+            .tokens()
+    }
+
+    #[test]
+    fn blocks_and_transactions_propagate_with_latency() {
+        let mut sim = Simulation::new(&genesis(), 3).with_latency(3);
+        sim.submit_transaction(0, transfer("alice", "carol", 5, 0))
+            .unwrap();
+        sim.step();
+        sim.step();
+        assert!(sim.node(1).pending_transactions().is_empty());
+        sim.step();
+        assert_eq!(1, sim.node(2).pending_transactions().len());
+
+        sim.mine_block(0, vec![transfer("alice", "carol", 5, 0)])
+            .unwrap();
+        assert!(!sim.is_converged());
+        assert!(sim.run(100));
+        assert!(sim.is_converged());
+        assert_eq!(5, balance(&sim, 2, "carol"));
+        assert!(sim.node(2).pending_transactions().is_empty());
+    }
+
+    #[test]
+    fn partitioned_forks_converge_on_the_longest_chain() {
+        let mut sim = Simulation::new(&genesis(), 4);
+        sim.partition(&[&[0, 1], &[2, 3]]);
+
+        sim.mine_block(0, vec![transfer("alice", "carol", 10, 0)])
+            .unwrap();
+        sim.mine_block(0, vec![transfer("bob", "carol", 20, 0)])
+            .unwrap();
+        sim.mine_block(2, vec![transfer("alice", "carol", 40, 0)])
+            .unwrap();
+        assert!(sim.run(100));
+        assert!(sim.dropped() > 0);
+        assert_eq!(30, balance(&sim, 1, "carol"));
+        assert_eq!(40, balance(&sim, 3, "carol"));
+        assert!(!sim.is_converged());
+
+        sim.heal();
+        assert!(sim.run(100));
+        assert!(sim.is_converged());
+        for node in 0..sim.len() {
+            assert_eq!(3, sim.node(node).len());
+            assert_eq!(30, balance(&sim, node, "carol"));
+            assert_eq!(90, balance(&sim, node, "alice"));
+            assert!(sim.node(node).check_validity().is_ok());
+        }
+    }
+
+    #[test]
+    fn forks_of_the_same_length_converge() {
+        let mut sim = Simulation::new(&genesis(), 3);
+        sim.set_link_latency(0, 1, 5);
+        sim.mine_block(0, vec![transfer("alice", "carol", 1, 0)])
+            .unwrap();
+        sim.mine_block(1, vec![transfer("alice", "carol", 2, 0)])
+            .unwrap();
+        assert!(sim.run(100));
+        assert!(sim.is_converged());
+        assert_eq!(2, sim.node(2).len());
+
+        // Blocks built on the converged tip keep the nodes converged
+        sim.mine_block(2, vec![transfer("bob", "carol", 3, 0)])
+            .unwrap();
+        assert!(sim.run(100));
+        assert!(sim.is_converged());
+        assert_eq!(3, sim.node(0).len());
+    }
+
+    #[test]
+    fn finalized_blocks_are_kept_despite_longer_forks() {
+        let genesis = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_validator("alice", 100)
+            .with_account("carol", 0)
+            .build();
+        let mut sim = Simulation::new(&genesis, 2);
+        sim.partition(&[&[0], &[1]]);
+
+        let hash = sim
+            .mine_block(0, vec![transfer("alice", "carol", 1, 0)])
+            .unwrap();
+        let mut checkpoint = Checkpoint::new(1, hash);
+        checkpoint.sign(&keypair("alice"));
+        sim.node_mut(0).finalize(checkpoint).unwrap();
+        sim.mine_block(1, vec![transfer("alice", "carol", 2, 0)])
+            .unwrap();
+        sim.mine_block(1, vec![transfer("alice", "carol", 3, 1)])
+            .unwrap();
+
+        sim.heal();
+        assert!(sim.run(100));
+        assert!(!sim.is_converged());
+        assert_eq!(Some(hash), sim.node(0).get_last_block_hash());
+        assert_eq!(1, balance(&sim, 0, "carol"));
+    }
+}
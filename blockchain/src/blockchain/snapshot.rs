@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::storage::{decode, encode};
+use crate::{Account, Block, BlockchainError, Hash};
+
+/// A compact copy of a chain at some height: the headers of its blocks (without their
+/// transactions) and the world state after executing them.<br/>
+/// A node restored from it doesn't have to replay every block, the world state is
+/// trusted since the last header commits to it (see `Blockchain::restore_from_snapshot`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The id of the network the chain belongs to
+    pub(crate) chain_id: String,
+
+    /// The headers of all blocks, starting with the genesis block
+    pub(crate) headers: Vec<Block>,
+
+    /// The world state after executing all blocks
+    #[serde(serialize_with = "super::serialization::ordered_map")]
+    pub(crate) accounts: HashMap<String, Account>,
+}
+
+impl Snapshot {
+    /// Will return the id of the network the chain belongs to
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    /// Will return the height the snapshot was taken at (the amount of blocks)
+    pub fn height(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Will return the hash of the last block
+    pub fn last_block_hash(&self) -> Option<Hash> {
+        self.headers.last().and_then(Block::hash)
+    }
+
+    /// Will encode the snapshot using the (bincode) binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BlockchainError> {
+        encode(self)
+    }
+
+    /// Will decode a snapshot from the (bincode) binary format.<br/>
+    /// The decoded snapshot is validated when a chain is restored from it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BlockchainError> {
+        decode(bytes)
+    }
+}
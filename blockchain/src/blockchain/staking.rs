@@ -0,0 +1,155 @@
+use crate::{AccountType, Address, BlockchainError};
+
+use super::world_state::WorldState;
+
+/// Will lock the amount of the staker's tokens as stake, turning its (user) account into a
+/// validator account.<br/>
+/// The staker has to be an address, since the blocks it produces are signed by its key.
+pub(crate) fn stake<T: WorldState>(
+    world_state: &mut T,
+    staker: &str,
+    amount: u128,
+) -> Result<(), BlockchainError> {
+    match staker.parse::<Address>() {
+        Ok(address) if address.to_string() == staker => {}
+        _ => return Err(BlockchainError::InvalidAddress(staker.to_string())),
+    }
+
+    let account = world_state
+        .get_account_by_id_mut(staker)
+        .ok_or_else(|| BlockchainError::UnknownAccount(staker.to_string()))?;
+    let staked = match account.acc_type {
+        AccountType::User => 0,
+        AccountType::Validator { stake } => stake,
+        _ => return Err(BlockchainError::NotStakeable(staker.to_string())),
+    };
+    account.tokens = account
+        .tokens
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    account.acc_type = AccountType::Validator {
+        stake: staked
+            .checked_add(amount)
+            .ok_or(BlockchainError::ArithmeticOverflow)?,
+    };
+    Ok(())
+}
+
+/// Will unlock the amount of the validator's stake, turning it back into a
+/// user account once all of it is unlocked.
+pub(crate) fn unstake<T: WorldState>(
+    world_state: &mut T,
+    validator: &str,
+    amount: u128,
+) -> Result<(), BlockchainError> {
+    let account = world_state
+        .get_account_by_id_mut(validator)
+        .ok_or_else(|| BlockchainError::UnknownAccount(validator.to_string()))?;
+    let staked = match account.acc_type {
+        AccountType::Validator { stake } => stake,
+        _ => 0,
+    };
+    let remaining = staked
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientStake)?;
+    account.tokens = account
+        .tokens
+        .checked_add(amount)
+        .ok_or(BlockchainError::ArithmeticOverflow)?;
+    account.acc_type = match remaining {
+        0 => AccountType::User,
+        stake => AccountType::Validator { stake },
+    };
+    Ok(())
+}
+
+/// Will burn the whole stake of the (misbehaving) validator, turning it back into a
+/// user account, and return the burned amount.
+pub(crate) fn slash<T: WorldState>(
+    world_state: &mut T,
+    validator: &str,
+) -> Result<u128, BlockchainError> {
+    let account = world_state
+        .get_account_by_id_mut(validator)
+        .ok_or_else(|| BlockchainError::UnknownAccount(validator.to_string()))?;
+    match account.acc_type {
+        AccountType::Validator { stake } if stake > 0 => {
+            account.acc_type = AccountType::User;
+            Ok(stake)
+        }
+        _ => Err(BlockchainError::InsufficientStake),
+    }
+}
+
+/// Will return the validators (and their stakes) that have staked at least `min_stake`,
+/// ordered by their ids
+pub(crate) fn validators<T: WorldState + ?Sized>(
+    world_state: &T,
+    min_stake: u128,
+) -> Vec<(String, u128)> {
+    let mut validators: Vec<(String, u128)> = world_state
+        .accounts()
+        .filter_map(|(id, account)| match account.acc_type {
+            AccountType::Validator { stake } if stake > 0 && stake >= min_stake => {
+                Some((id.clone(), stake))
+            }
+            _ => None,
+        })
+        .collect();
+    validators.sort();
+    validators
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{stake, unstake, validators};
+    use crate::{AccountType, Blockchain, BlockchainError, Keypair, WorldState};
+
+    #[test]
+    fn stake_and_unstake() {
+        let address = Keypair::generate().address().to_string();
+        let mut chain = Blockchain::new();
+        chain
+            .create_account(address.clone(), AccountType::User)
+            .unwrap();
+        chain.get_account_by_id_mut(&address).unwrap().tokens = 100;
+
+        stake(&mut chain, &address, 60).unwrap();
+        stake(&mut chain, &address, 10).unwrap();
+        assert_eq!(Some(30), chain.get_balance(&address));
+        assert_eq!(vec![(address.clone(), 70)], validators(&chain, 0));
+        assert!(validators(&chain, 100).is_empty());
+        assert_eq!(
+            Err(BlockchainError::InsufficientBalance),
+            stake(&mut chain, &address, 31)
+        );
+
+        assert_eq!(
+            Err(BlockchainError::InsufficientStake),
+            unstake(&mut chain, &address, 71)
+        );
+        unstake(&mut chain, &address, 70).unwrap();
+        assert_eq!(Some(100), chain.get_balance(&address));
+        assert_eq!(
+            &AccountType::User,
+            chain.get_account_by_id(&address).unwrap().account_type()
+        );
+    }
+
+    #[test]
+    fn only_addresses_may_stake() {
+        let mut chain = Blockchain::new();
+        chain
+            .create_account("alice".into(), AccountType::User)
+            .unwrap();
+        assert!(matches!(
+            stake(&mut chain, "alice", 0),
+            Err(BlockchainError::InvalidAddress(_))
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::merkle::{leaf_hash, merkle_root, MerkleProof};
+use super::storage::encode;
+use crate::{Account, Blockchain, Hash};
+
+/// Will calculate the commitment over the world state: the root of the Merkle tree
+/// whose leaves are the accounts, ordered by their ids.
+pub(crate) fn state_root(accounts: &HashMap<String, Account>) -> Hash {
+    merkle_root(&sorted_leaves(accounts).1)
+}
+
+/// Will return the account ids (sorted) along with the matching leaf hashes
+fn sorted_leaves(accounts: &HashMap<String, Account>) -> (Vec<&String>, Vec<Hash>) {
+    let mut ids: Vec<&String> = accounts.keys().collect();
+    ids.sort();
+    let leaves = ids
+        .iter()
+        .map(|id| account_leaf(id, &accounts[*id]))
+        .collect();
+    (ids, leaves)
+}
+
+fn account_leaf(id: &str, account: &Account) -> Hash {
+    // Encoding the account (with its ordered store) can't fail
+    leaf_hash(&encode(&(id, account)).expect("Accounts are always encodable"))
+}
+
+/// Proves the state of an account against the state root of a block,
+/// so that a light client doesn't have to execute the blocks itself.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountProof {
+    /// The id of the account
+    pub id: String,
+
+    /// The (proven) state of the account
+    pub account: Account,
+
+    /// The path from the account up to the state root
+    pub proof: MerkleProof,
+}
+
+impl AccountProof {
+    /// Checks if the account is part of the world state having the provided root
+    pub fn verify(&self, state_root: &Hash) -> bool {
+        self.proof
+            .verify(&account_leaf(&self.id, &self.account), state_root)
+    }
+}
+
+impl Blockchain {
+    /// Will return the commitment over the current world state
+    pub fn state_root(&self) -> Hash {
+        state_root(&self.accounts)
+    }
+
+    /// Will prove the current state of the account (if it exists) against `state_root`
+    pub fn prove_account(&self, id: &str) -> Option<AccountProof> {
+        let (ids, leaves) = sorted_leaves(&self.accounts);
+        let index = ids.binary_search(&&id.to_string()).ok()?;
+        Some(AccountProof {
+            id: id.to_string(),
+            account: self.accounts[id].clone(),
+            proof: MerkleProof::new(&leaves, index)?,
+        })
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{Block, Blockchain, Transaction, TransactionData};
+
+    fn chain_with(users: &[&str]) -> Blockchain {
+        let mut bc = Blockchain::new();
+        let mut genesis = Block::new(None);
+        for user in users {
+            genesis.add_transaction(Transaction::new(
+                (*user).into(),
+                TransactionData::CreateUserAccount((*user).into()),
+                0,
+            ));
+        }
+        bc.append_block(genesis).unwrap();
+        bc
+    }
+
+    #[test]
+    fn accounts_are_proven_against_state_root() {
+        let bc = chain_with(&["alice", "bob", "carol"]);
+        let root = bc.state_root();
+
+        let proof = bc.prove_account("bob").unwrap();
+        assert!(proof.verify(&root));
+        assert!(bc.prove_account("dave").is_none());
+
+        // A forged balance doesn't verify
+        let mut forged = proof;
+        forged.account.tokens = 1_000;
+        assert!(!forged.verify(&root));
+    }
+
+    #[test]
+    fn state_root_ignores_insertion_order() {
+        let first = chain_with(&["alice", "bob"]);
+        let second = chain_with(&["bob", "alice"]);
+        assert_eq!(first.state_root(), second.state_root());
+        assert_ne!(first.state_root(), chain_with(&["alice"]).state_root());
+    }
+}
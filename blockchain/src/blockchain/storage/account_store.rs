@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::Path;
+
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::Transactional;
+
+use super::{decode, encode};
+use crate::{Account, BlockchainError};
+
+const ACCOUNTS_TREE: &str = "accounts";
+const META_TREE: &str = "meta";
+pub(crate) const HEIGHT_KEY: &str = "height";
+
+/// A persistent backend for the accounts of a world state (see `CachedWorldState`), which
+/// stores every account on its own, so that they can be read (and written) one by one.
+pub trait AccountStore: Send {
+    /// Will return the stored account, if it exists
+    fn get_account(&self, id: &str) -> Result<Option<Account>, BlockchainError>;
+
+    /// Will return the ids of all stored accounts
+    fn account_ids(&self) -> Result<Vec<String>, BlockchainError>;
+
+    /// Will write the changes of the accounts (`None` deletes an account), together with the
+    /// height of the world state after them, all at once
+    fn write_batch(
+        &mut self,
+        height: usize,
+        changes: &HashMap<String, Option<Account>>,
+    ) -> Result<(), BlockchainError>;
+
+    /// Will return the height of the stored world state (0 until the first batch is written)
+    fn height(&self) -> Result<usize, BlockchainError>;
+}
+
+/// An AccountStore backed by the embedded sled database, keeping the accounts in a tree
+/// keyed by their ids, next to a tree holding the height.<br/>
+/// Clones share the same database.
+#[derive(Clone, Debug)]
+pub struct SledAccountStore {
+    accounts: sled::Tree,
+    meta: sled::Tree,
+}
+
+impl SledAccountStore {
+    /// Will open (or create, if missing) the database in the provided directory.<br/>
+    /// Since every batch is flushed, the database runs without a background flusher, which
+    /// would otherwise keep it locked for a while after the store is dropped.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        let db = sled::Config::new()
+            .path(path)
+            .flush_every_ms(None)
+            .open()
+            .map_err(db_error)?;
+        Ok(SledAccountStore {
+            accounts: db.open_tree(ACCOUNTS_TREE).map_err(db_error)?,
+            meta: db.open_tree(META_TREE).map_err(db_error)?,
+        })
+    }
+}
+
+impl AccountStore for SledAccountStore {
+    fn get_account(&self, id: &str) -> Result<Option<Account>, BlockchainError> {
+        match self.accounts.get(id.as_bytes()).map_err(db_error)? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn account_ids(&self) -> Result<Vec<String>, BlockchainError> {
+        self.accounts
+            .iter()
+            .keys()
+            .map(|key| decode_id(&key.map_err(db_error)?))
+            .collect()
+    }
+
+    fn write_batch(
+        &mut self,
+        height: usize,
+        changes: &HashMap<String, Option<Account>>,
+    ) -> Result<(), BlockchainError> {
+        let mut encoded = Vec::with_capacity(changes.len());
+        for (id, account) in changes {
+            encoded.push((id, account.as_ref().map(encode).transpose()?));
+        }
+        (&self.accounts, &self.meta)
+            .transaction(|(accounts, meta)| {
+                for (id, bytes) in &encoded {
+                    match bytes {
+                        Some(bytes) => accounts.insert(id.as_bytes(), bytes.as_slice())?,
+                        None => accounts.remove(id.as_bytes())?,
+                    };
+                }
+                meta.insert(HEIGHT_KEY, &(height as u64).to_be_bytes())?;
+                Ok::<_, ConflictableTransactionError>(())
+            })
+            .map_err(|err: TransactionError| BlockchainError::Storage(err.to_string()))?;
+        self.accounts.flush().map_err(db_error)?;
+        Ok(())
+    }
+
+    fn height(&self) -> Result<usize, BlockchainError> {
+        match self.meta.get(HEIGHT_KEY).map_err(db_error)? {
+            Some(bytes) => decode_height(&bytes),
+            None => Ok(0),
+        }
+    }
+}
+
+/// Will decode the id of an account out of the key it is stored by
+pub(crate) fn decode_id(key: &[u8]) -> Result<String, BlockchainError> {
+    String::from_utf8(key.to_vec()).map_err(|err| BlockchainError::Storage(err.to_string()))
+}
+
+/// Will decode the stored (big endian) height
+pub(crate) fn decode_height(bytes: &[u8]) -> Result<usize, BlockchainError> {
+    let bytes: [u8; 8] = bytes
+        .try_into()
+        .map_err(|_| BlockchainError::Storage("The stored height is malformed".into()))?;
+    Ok(u64::from_be_bytes(bytes) as usize)
+}
+
+fn db_error(err: sled::Error) -> BlockchainError {
+    BlockchainError::Storage(err.to_string())
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{decode, encode, Storage, StoredState, WalEntry};
+use crate::{Account, Block, BlockchainError, Hash, TransactionReceipt};
+
+/// A Storage that keeps every block in its own file (`blocks/<height>.bin`),
+/// next to its receipts (`receipts/<height>.bin`), and the world state in a
+/// `state.bin` file (and the write-ahead log in a `wal.bin` file), inside a directory.
+#[derive(Debug)]
+pub struct FileStorage {
+    /// The directory holding the files
+    dir: PathBuf,
+
+    /// Lookup from block hash to block height (rebuilt on opening)
+    hash_index: HashMap<Hash, usize>,
+
+    /// The amount of stored blocks
+    block_count: usize,
+}
+
+impl FileStorage {
+    /// Will open (or create, if missing) the storage in the provided directory
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, BlockchainError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join("blocks")).map_err(io_error)?;
+        fs::create_dir_all(dir.join("receipts")).map_err(io_error)?;
+
+        let mut storage = FileStorage {
+            dir,
+            hash_index: HashMap::new(),
+            block_count: 0,
+        };
+
+        // Blocks are stored without gaps, so we count until the first missing one
+        while let Some(block) = storage.get_block_by_height(storage.block_count)? {
+            if let Some(hash) = block.hash {
+                storage.hash_index.insert(hash, storage.block_count);
+            }
+            storage.block_count += 1;
+        }
+
+        Ok(storage)
+    }
+
+    fn block_path(&self, height: usize) -> PathBuf {
+        self.dir.join("blocks").join(format!("{:010}.bin", height))
+    }
+
+    fn receipts_path(&self, height: usize) -> PathBuf {
+        self.dir
+            .join("receipts")
+            .join(format!("{:010}.bin", height))
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.dir.join("state.bin")
+    }
+
+    fn wal_path(&self) -> PathBuf {
+        self.dir.join("wal.bin")
+    }
+}
+
+impl Storage for FileStorage {
+    fn put_block(&mut self, height: usize, block: &Block) -> Result<(), BlockchainError> {
+        if height > self.block_count {
+            return Err(BlockchainError::Storage(format!(
+                "Block at height {} would leave a gap",
+                height
+            )));
+        }
+
+        write_atomically(&self.block_path(height), &encode(block)?)?;
+        if let Some(hash) = &block.hash {
+            self.hash_index.insert(*hash, height);
+        }
+        self.block_count = self.block_count.max(height + 1);
+        Ok(())
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainError> {
+        let path = self.block_path(height);
+        if !path.exists() {
+            return Ok(None);
+        }
+        decode(&fs::read(path).map_err(io_error)?).map(Some)
+    }
+
+    fn get_block_by_hash(&self, hash: &Hash) -> Result<Option<Block>, BlockchainError> {
+        match self.hash_index.get(hash) {
+            Some(height) => self.get_block_by_height(*height),
+            None => Ok(None),
+        }
+    }
+
+    fn put_receipts(
+        &mut self,
+        height: usize,
+        receipts: &[TransactionReceipt],
+    ) -> Result<(), BlockchainError> {
+        write_atomically(&self.receipts_path(height), &encode(&receipts)?)
+    }
+
+    fn get_receipts(
+        &self,
+        height: usize,
+    ) -> Result<Option<Vec<TransactionReceipt>>, BlockchainError> {
+        let path = self.receipts_path(height);
+        if !path.exists() {
+            return Ok(None);
+        }
+        decode(&fs::read(path).map_err(io_error)?).map(Some)
+    }
+
+    fn truncate(&mut self, height: usize) -> Result<(), BlockchainError> {
+        // The last blocks are removed first, so that an interruption never leaves a gap
+        for removed in (height..self.block_count).rev() {
+            let receipts_path = self.receipts_path(removed);
+            if receipts_path.exists() {
+                fs::remove_file(receipts_path).map_err(io_error)?;
+            }
+            fs::remove_file(self.block_path(removed)).map_err(io_error)?;
+            self.block_count = removed;
+        }
+        self.hash_index
+            .retain(|_, block_height| *block_height < height);
+        Ok(())
+    }
+
+    fn block_count(&self) -> Result<usize, BlockchainError> {
+        Ok(self.block_count)
+    }
+
+    fn put_state(
+        &mut self,
+        height: usize,
+        accounts: &HashMap<String, Account>,
+    ) -> Result<(), BlockchainError> {
+        write_atomically(&self.state_path(), &encode(&(height, accounts))?)
+    }
+
+    fn get_state(&self) -> Result<Option<StoredState>, BlockchainError> {
+        let path = self.state_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        decode(&fs::read(path).map_err(io_error)?).map(Some)
+    }
+
+    fn put_wal(&mut self, entry: &WalEntry) -> Result<(), BlockchainError> {
+        write_atomically(&self.wal_path(), &encode(entry)?)
+    }
+
+    fn get_wal(&self) -> Result<Option<WalEntry>, BlockchainError> {
+        let path = self.wal_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        decode(&fs::read(path).map_err(io_error)?).map(Some)
+    }
+
+    fn clear_wal(&mut self) -> Result<(), BlockchainError> {
+        let path = self.wal_path();
+        if path.exists() {
+            fs::remove_file(path).map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockchainError> {
+        // Every write is already persisted (and atomic) by itself
+        Ok(())
+    }
+}
+
+/// Will write the file next to its final location and then move it into place,
+/// so that a crash never leaves a partially written file behind
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), BlockchainError> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes).map_err(io_error)?;
+    fs::rename(&tmp_path, path).map_err(io_error)
+}
+
+fn io_error(err: std::io::Error) -> BlockchainError {
+    BlockchainError::Storage(err.to_string())
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{Account, Block, BlockchainError, Hash, TransactionReceipt};
+
+// Registering to the module tree.
+mod account_store;
+mod file_storage;
+#[cfg(feature = "rocksdb")]
+mod rocks_account_store;
+mod sled_storage;
+pub(crate) mod wal;
+
+pub use account_store::{AccountStore, SledAccountStore};
+pub use file_storage::FileStorage;
+#[cfg(feature = "rocksdb")]
+pub use rocks_account_store::RocksAccountStore;
+pub use sled_storage::SledStorage;
+pub use wal::WalEntry;
+
+/// A persisted world state, together with the height it corresponds to
+pub type StoredState = (usize, HashMap<String, Account>);
+
+/// A persistent backend for the blocks and the world state of a Blockchain.<br/>
+/// Blocks are addressed by their height (the genesis block has height 0).<br/>
+/// Storages are shared along with their chain (see `ChainHandle`), hence `Sync`.
+pub trait Storage: Send + Sync {
+    /// Will store a block at the provided height
+    fn put_block(&mut self, height: usize, block: &Block) -> Result<(), BlockchainError>;
+
+    /// Will return the block at the provided height, if it exists
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainError>;
+
+    /// Will return the block having the provided hash, if it exists
+    fn get_block_by_hash(&self, hash: &Hash) -> Result<Option<Block>, BlockchainError>;
+
+    /// Will store the receipts of the transactions of the block at the provided height
+    fn put_receipts(
+        &mut self,
+        height: usize,
+        receipts: &[TransactionReceipt],
+    ) -> Result<(), BlockchainError>;
+
+    /// Will return the receipts of the block at the provided height, if they are stored
+    fn get_receipts(
+        &self,
+        height: usize,
+    ) -> Result<Option<Vec<TransactionReceipt>>, BlockchainError>;
+
+    /// Will remove the blocks (and their receipts) starting with the provided height
+    fn truncate(&mut self, height: usize) -> Result<(), BlockchainError>;
+
+    /// Will return the amount of stored blocks
+    fn block_count(&self) -> Result<usize, BlockchainError>;
+
+    /// Will store the world state, as it is after executing the blocks up to `height` (exclusive)
+    fn put_state(
+        &mut self,
+        height: usize,
+        accounts: &HashMap<String, Account>,
+    ) -> Result<(), BlockchainError>;
+
+    /// Will return the last stored world state, together with its height
+    fn get_state(&self) -> Result<Option<StoredState>, BlockchainError>;
+
+    /// Will write the entry to the write-ahead log (replacing the previous one) and make sure
+    /// it is persisted, before the block of the entry is
+    fn put_wal(&mut self, entry: &WalEntry) -> Result<(), BlockchainError>;
+
+    /// Will return the entry of the write-ahead log, if the application of its block
+    /// wasn't completed
+    fn get_wal(&self) -> Result<Option<WalEntry>, BlockchainError>;
+
+    /// Will clear the write-ahead log, once the block of its entry is persisted
+    fn clear_wal(&mut self) -> Result<(), BlockchainError>;
+
+    /// Will make sure that everything written so far is persisted
+    fn flush(&mut self) -> Result<(), BlockchainError>;
+
+    /// Will iterate over all stored blocks, in order
+    fn blocks(&self) -> Box<dyn Iterator<Item = Result<Block, BlockchainError>> + '_> {
+        let count = match self.block_count() {
+            Ok(count) => count,
+            Err(err) => return Box::new(std::iter::once(Err(err))),
+        };
+        Box::new((0..count).map(move |height| {
+            self.get_block_by_height(height)?.ok_or_else(|| {
+                BlockchainError::Storage(format!("Block at height {} is missing", height))
+            })
+        }))
+    }
+}
+
+/// Holds the (optional) storage a Blockchain is attached to.<br/>
+/// A clone of a Blockchain is detached from the storage, so that it can be
+/// freely altered (e.g. for demonstrating attacks) without touching the persisted data.
+#[derive(Default)]
+pub(crate) struct StorageHandle(pub(crate) Option<Box<dyn Storage>>);
+
+impl Clone for StorageHandle {
+    fn clone(&self) -> Self {
+        StorageHandle(None)
+    }
+}
+
+impl fmt::Debug for StorageHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StorageHandle {{ attached: {} }}", self.0.is_some())
+    }
+}
+
+/// Will encode a value using the binary storage format
+pub(crate) fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, BlockchainError> {
+    bincode::serialize(value).map_err(|err| BlockchainError::Serialization(err.to_string()))
+}
+
+/// Will return the size (in bytes) of a value encoded with the binary storage format
+pub(crate) fn encoded_size<T: Serialize>(value: &T) -> Result<u64, BlockchainError> {
+    bincode::serialized_size(value).map_err(|err| BlockchainError::Serialization(err.to_string()))
+}
+
+/// Will decode a value from the binary storage format.<br/>
+/// The bytes may be adversarial (e.g. received from peers): the lengths they claim are checked
+/// against their size before anything is allocated, so that they can't exhaust the memory.
+pub(crate) fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BlockchainError> {
+    // The options of `bincode::deserialize`, limited to the size of the bytes
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(bytes.len() as u64)
+        .deserialize(bytes)
+        .map_err(|err| BlockchainError::Serialization(err.to_string()))
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use super::{FileStorage, SledStorage, Storage};
+    use crate::{
+        Account, AccountType, Block, Hash, ReceiptStatus, Transaction, TransactionData,
+        TransactionReceipt,
+    };
+
+    fn block(prev_hash: Option<Hash>) -> Block {
+        let mut block = Block::new(prev_hash);
+        block.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("alice".into()),
+            0,
+        ));
+        block
+    }
+
+    fn exercise(storage: &mut dyn Storage) {
+        assert_eq!(0, storage.block_count().unwrap());
+        assert!(storage.get_state().unwrap().is_none());
+
+        let first = block(None);
+        let second = block(first.hash);
+        storage.put_block(0, &first).unwrap();
+        storage.put_block(1, &second).unwrap();
+
+        assert_eq!(2, storage.block_count().unwrap());
+        let by_height = storage.get_block_by_height(1).unwrap().unwrap();
+        assert_eq!(second.hash, by_height.hash);
+        let by_hash = storage
+            .get_block_by_hash(first.hash.as_ref().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.hash, by_hash.hash);
+        assert!(storage.get_block_by_height(2).unwrap().is_none());
+
+        let hashes: Vec<_> = storage.blocks().map(|b| b.unwrap().hash).collect();
+        assert_eq!(vec![first.hash, second.hash], hashes);
+
+        let receipts = vec![TransactionReceipt {
+            transaction_hash: second.body.transactions[0].calculate_hash(),
+            status: ReceiptStatus::Success,
+            gas_used: 1,
+            events: Vec::new(),
+            balance_changes: Vec::new(),
+        }];
+        storage.put_receipts(1, &receipts).unwrap();
+        assert_eq!(Some(receipts), storage.get_receipts(1).unwrap());
+        assert!(storage.get_receipts(0).unwrap().is_none());
+
+        let mut accounts = HashMap::new();
+        accounts.insert("alice".to_string(), Account::new(AccountType::User));
+        storage.put_state(2, &accounts).unwrap();
+        let (height, state) = storage.get_state().unwrap().unwrap();
+        assert_eq!(2, height);
+        assert!(state.contains_key("alice"));
+
+        storage.flush().unwrap();
+    }
+
+    fn exercise_truncate(storage: &mut dyn Storage) {
+        let first = block(None);
+        let second = block(first.hash);
+        storage.put_block(0, &first).unwrap();
+        storage.put_block(1, &second).unwrap();
+        storage.put_receipts(1, &[]).unwrap();
+
+        storage.truncate(1).unwrap();
+        assert_eq!(1, storage.block_count().unwrap());
+        assert!(storage.get_block_by_height(1).unwrap().is_none());
+        assert!(storage
+            .get_block_by_hash(second.hash.as_ref().unwrap())
+            .unwrap()
+            .is_none());
+        assert!(storage.get_receipts(1).unwrap().is_none());
+
+        // The freed height can be used again
+        storage.put_block(1, &second).unwrap();
+        assert_eq!(2, storage.block_count().unwrap());
+    }
+
+    #[test]
+    fn file_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise(&mut FileStorage::open(dir.path()).unwrap());
+        assert_eq!(
+            2,
+            FileStorage::open(dir.path())
+                .unwrap()
+                .block_count()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn sled_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise(&mut SledStorage::open(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise_truncate(&mut FileStorage::open(dir.path().join("files")).unwrap());
+        exercise_truncate(&mut SledStorage::open(dir.path().join("sled")).unwrap());
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use rocksdb::{ColumnFamily, IteratorMode, Options, WriteBatch, DB};
+
+use super::account_store::{decode_height, decode_id, HEIGHT_KEY};
+use super::{decode, encode, AccountStore};
+use crate::{Account, BlockchainError};
+
+const ACCOUNTS_CF: &str = "accounts";
+const META_CF: &str = "meta";
+
+/// An AccountStore backed by RocksDB (enabled by the `rocksdb` feature), keeping the
+/// accounts in a column family keyed by their ids, next to one holding the height.<br/>
+/// The changes of a block are written by a single write batch, so that a crash never
+/// leaves a partially written world state behind.
+pub struct RocksAccountStore {
+    db: DB,
+}
+
+impl RocksAccountStore {
+    /// Will open (or create, if missing) the database in the provided directory
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = DB::open_cf(&options, path, [ACCOUNTS_CF, META_CF]).map_err(db_error)?;
+        Ok(RocksAccountStore { db })
+    }
+
+    fn column_family(&self, name: &str) -> Result<&ColumnFamily, BlockchainError> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| BlockchainError::Storage(format!("Column family {} is missing", name)))
+    }
+}
+
+impl AccountStore for RocksAccountStore {
+    fn get_account(&self, id: &str) -> Result<Option<Account>, BlockchainError> {
+        let accounts = self.column_family(ACCOUNTS_CF)?;
+        match self.db.get_cf(accounts, id.as_bytes()).map_err(db_error)? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn account_ids(&self) -> Result<Vec<String>, BlockchainError> {
+        let accounts = self.column_family(ACCOUNTS_CF)?;
+        self.db
+            .iterator_cf(accounts, IteratorMode::Start)
+            .map(|entry| decode_id(&entry.map_err(db_error)?.0))
+            .collect()
+    }
+
+    fn write_batch(
+        &mut self,
+        height: usize,
+        changes: &HashMap<String, Option<Account>>,
+    ) -> Result<(), BlockchainError> {
+        let accounts = self.column_family(ACCOUNTS_CF)?;
+        let mut batch = WriteBatch::default();
+        for (id, account) in changes {
+            match account {
+                Some(account) => batch.put_cf(accounts, id.as_bytes(), encode(account)?),
+                None => batch.delete_cf(accounts, id.as_bytes()),
+            }
+        }
+        batch.put_cf(
+            self.column_family(META_CF)?,
+            HEIGHT_KEY,
+            (height as u64).to_be_bytes(),
+        );
+        self.db.write(batch).map_err(db_error)
+    }
+
+    fn height(&self) -> Result<usize, BlockchainError> {
+        let meta = self.column_family(META_CF)?;
+        match self.db.get_cf(meta, HEIGHT_KEY).map_err(db_error)? {
+            Some(bytes) => decode_height(&bytes),
+            None => Ok(0),
+        }
+    }
+}
+
+fn db_error(err: rocksdb::Error) -> BlockchainError {
+    BlockchainError::Storage(err.to_string())
+}
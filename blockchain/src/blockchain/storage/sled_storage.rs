@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{decode, encode, Storage, StoredState, WalEntry};
+use crate::{Account, Block, BlockchainError, Hash, TransactionReceipt};
+
+const BLOCKS_TREE: &str = "blocks";
+const HASHES_TREE: &str = "hashes";
+const RECEIPTS_TREE: &str = "receipts";
+const STATE_KEY: &str = "state";
+const WAL_KEY: &str = "wal";
+
+/// A Storage backed by the embedded sled database.<br/>
+/// Blocks (and their receipts) are kept in trees keyed by their (big endian) height,
+/// next to a tree mapping block hashes to heights.
+#[derive(Debug)]
+pub struct SledStorage {
+    db: sled::Db,
+    blocks: sled::Tree,
+    hashes: sled::Tree,
+    receipts: sled::Tree,
+}
+
+impl SledStorage {
+    /// Will open (or create, if missing) the database in the provided directory
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        let db = sled::open(path).map_err(db_error)?;
+        let blocks = db.open_tree(BLOCKS_TREE).map_err(db_error)?;
+        let hashes = db.open_tree(HASHES_TREE).map_err(db_error)?;
+        let receipts = db.open_tree(RECEIPTS_TREE).map_err(db_error)?;
+        Ok(SledStorage {
+            db,
+            blocks,
+            hashes,
+            receipts,
+        })
+    }
+}
+
+impl Storage for SledStorage {
+    fn put_block(&mut self, height: usize, block: &Block) -> Result<(), BlockchainError> {
+        let key = (height as u64).to_be_bytes();
+        self.blocks.insert(key, encode(block)?).map_err(db_error)?;
+        if let Some(hash) = &block.hash {
+            self.hashes
+                .insert(hash.as_bytes(), &key)
+                .map_err(db_error)?;
+        }
+        Ok(())
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainError> {
+        match self
+            .blocks
+            .get((height as u64).to_be_bytes())
+            .map_err(db_error)?
+        {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn get_block_by_hash(&self, hash: &Hash) -> Result<Option<Block>, BlockchainError> {
+        match self.hashes.get(hash.as_bytes()).map_err(db_error)? {
+            Some(key) => match self.blocks.get(key).map_err(db_error)? {
+                Some(bytes) => decode(&bytes).map(Some),
+                None => Ok(None),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn put_receipts(
+        &mut self,
+        height: usize,
+        receipts: &[TransactionReceipt],
+    ) -> Result<(), BlockchainError> {
+        self.receipts
+            .insert((height as u64).to_be_bytes(), encode(&receipts)?)
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn get_receipts(
+        &self,
+        height: usize,
+    ) -> Result<Option<Vec<TransactionReceipt>>, BlockchainError> {
+        match self
+            .receipts
+            .get((height as u64).to_be_bytes())
+            .map_err(db_error)?
+        {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn truncate(&mut self, height: usize) -> Result<(), BlockchainError> {
+        let from = (height as u64).to_be_bytes();
+        for entry in self.blocks.range(from..) {
+            let (key, bytes) = entry.map_err(db_error)?;
+            let block: Block = decode(&bytes)?;
+            if let Some(hash) = &block.hash {
+                self.hashes.remove(hash.as_bytes()).map_err(db_error)?;
+            }
+            self.blocks.remove(&key).map_err(db_error)?;
+            self.receipts.remove(key).map_err(db_error)?;
+        }
+        Ok(())
+    }
+
+    fn block_count(&self) -> Result<usize, BlockchainError> {
+        Ok(self.blocks.len())
+    }
+
+    fn put_state(
+        &mut self,
+        height: usize,
+        accounts: &HashMap<String, Account>,
+    ) -> Result<(), BlockchainError> {
+        self.db
+            .insert(STATE_KEY, encode(&(height, accounts))?)
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn get_state(&self) -> Result<Option<StoredState>, BlockchainError> {
+        match self.db.get(STATE_KEY).map_err(db_error)? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn put_wal(&mut self, entry: &WalEntry) -> Result<(), BlockchainError> {
+        self.db.insert(WAL_KEY, encode(entry)?).map_err(db_error)?;
+        self.db.flush().map_err(db_error)?;
+        Ok(())
+    }
+
+    fn get_wal(&self) -> Result<Option<WalEntry>, BlockchainError> {
+        match self.db.get(WAL_KEY).map_err(db_error)? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn clear_wal(&mut self) -> Result<(), BlockchainError> {
+        self.db.remove(WAL_KEY).map_err(db_error)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockchainError> {
+        self.db.flush().map_err(db_error)?;
+        Ok(())
+    }
+}
+
+fn db_error(err: sled::Error) -> BlockchainError {
+    BlockchainError::Storage(err.to_string())
+}
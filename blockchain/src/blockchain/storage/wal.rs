@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::Storage;
+use crate::{Account, Block, BlockchainError, TransactionReceipt};
+
+/// An entry of the write-ahead log: a block about to be persisted, together with its
+/// receipts and the accounts it changes (`None` for deleted ones).<br/>
+/// It is written before the block (see `Storage::put_wal`) and cleared once the block is
+/// persisted, so that an interrupted application can be completed on startup (see `recover`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalEntry {
+    /// The height the block is appended at
+    pub height: usize,
+
+    pub block: Block,
+
+    pub receipts: Vec<TransactionReceipt>,
+
+    /// The accounts changed by the block, as they are after it
+    pub changes: HashMap<String, Option<Account>>,
+}
+
+/// Will complete (or discard) the block application left in the write-ahead log of the
+/// storage, if any.<br/>
+/// A block following the stored blocks is (re)written along with its receipts, and the
+/// stored world state is brought up to date if it lagged behind by the block, whilst a block
+/// that doesn't follow them (e.g. as they were rewound meanwhile) is rolled back.
+pub(crate) fn recover(storage: &mut dyn Storage) -> Result<(), BlockchainError> {
+    let entry = match storage.get_wal()? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    let height = entry.height;
+
+    let parent_hash = match height {
+        0 => None,
+        _ => storage
+            .get_block_by_height(height - 1)?
+            .and_then(|parent| parent.hash),
+    };
+    let stored_hash = storage
+        .get_block_by_height(height)?
+        .map(|stored| stored.hash);
+    let follows = (height == 0 || parent_hash.is_some())
+        && entry.block.header.prev_hash == parent_hash
+        && stored_hash.is_none_or(|hash| hash == entry.block.hash);
+
+    if follows {
+        storage.put_receipts(height, &entry.receipts)?;
+        storage.put_block(height, &entry.block)?;
+        if let Some((state_height, mut accounts)) = storage.get_state()? {
+            if state_height == height {
+                for (id, change) in entry.changes {
+                    match change {
+                        Some(account) => accounts.insert(id, account),
+                        None => accounts.remove(&id),
+                    };
+                }
+                storage.put_state(height + 1, &accounts)?;
+            }
+        }
+        info!(height, "Completed the interrupted application of a block");
+    } else {
+        warn!(height, "Rolled back the interrupted application of a block");
+    }
+
+    storage.clear_wal()?;
+    storage.flush()
+}
@@ -0,0 +1,196 @@
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::test_utils::{account_id, signed_transaction, ChainBuilder, TEST_CHAIN_ID};
+use crate::{Transaction, TransactionData};
+
+/// The named accounts (see `test_utils::account_id`) of the generated scenarios
+pub const ACCOUNTS: [&str; 4] = ["alice", "bob", "carol", "dave"];
+
+/// An operation of a generated scenario, between the accounts of `ACCOUNTS` (by their indexes)
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// Transfers a share (in permille) of the balance the sender has at that point
+    Transfer { from: usize, to: usize, share: u16 },
+
+    /// Stores a value in the store of the sender
+    Store { from: usize, key: u8, value: u32 },
+}
+
+/// A random sequence of blocks of valid transactions between the accounts of `ACCOUNTS`
+/// (see `arb_scenario`)
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    /// The balances the accounts start with
+    pub balances: Vec<u128>,
+
+    /// The steps of the blocks following the genesis block
+    pub blocks: Vec<Vec<Step>>,
+}
+
+impl Scenario {
+    /// Will return the builder of the chain holding (only) the genesis block of the scenario
+    pub fn genesis(&self) -> ChainBuilder {
+        ACCOUNTS
+            .iter()
+            .zip(&self.balances)
+            .fold(ChainBuilder::new(), |builder, (name, balance)| {
+                builder.with_account(name, *balance)
+            })
+    }
+
+    /// Will return the (signed) transactions of the blocks following the genesis block.<br/>
+    /// The amounts of the transfers are derived from the balances the senders have at that
+    /// point, so that every transaction is valid.
+    pub fn transactions(&self) -> Vec<Vec<Transaction>> {
+        let mut balances = self.balances.clone();
+        let mut nonces = vec![0u128; ACCOUNTS.len()];
+        self.blocks
+            .iter()
+            .map(|steps| {
+                steps
+                    .iter()
+                    .map(|step| {
+                        let (from, data) = match *step {
+                            Step::Transfer { from, to, share } => {
+                                let amount = balances[from] * u128::from(share) / 1000;
+                                balances[from] -= amount;
+                                balances[to] += amount;
+                                let to = account_id(ACCOUNTS[to]);
+                                let data = TransactionData::TransferTokens {
+                                    to,
+                                    amount,
+                                    asset: None,
+                                };
+                                (from, data)
+                            }
+                            Step::Store { from, key, value } => {
+                                let data = TransactionData::ChangeStoreValue {
+                                    key: format!("key{}", key),
+                                    value: value.to_string(),
+                                };
+                                (from, data)
+                            }
+                        };
+                        let nonce = nonces[from];
+                        nonces[from] += 1;
+                        signed_transaction(TEST_CHAIN_ID, ACCOUNTS[from], data, nonce)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Will generate a step between the accounts of `ACCOUNTS`, mostly transfers
+pub fn arb_step() -> impl Strategy<Value = Step> {
+    let account = 0..ACCOUNTS.len();
+    prop_oneof![
+        3 => (account.clone(), account.clone(), 0..=1000u16)
+            .prop_map(|(from, to, share)| Step::Transfer { from, to, share }),
+        1 => (account, 0..4u8, any::<u32>())
+            .prop_map(|(from, key, value)| Step::Store { from, key, value }),
+    ]
+}
+
+/// Will generate a scenario of up to 5 blocks (of up to 8 transactions each)
+pub fn arb_scenario() -> impl Strategy<Value = Scenario> {
+    (
+        vec(0..=1_000_000u128, ACCOUNTS.len()),
+        vec(vec(arb_step(), 1..=8), 0..=5),
+    )
+        .prop_map(|(balances, blocks)| Scenario { balances, blocks })
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::BTreeMap;
+
+    use proptest::prelude::*;
+
+    use super::{arb_scenario, ACCOUNTS};
+    use crate::test_utils::{
+        account_id, mine_block, signed_transaction, BLOCK_INTERVAL, TEST_CHAIN_ID,
+    };
+    use crate::{Account, Blockchain, TransactionData, WorldState};
+
+    fn accounts(chain: &Blockchain) -> BTreeMap<String, Account> {
+        chain
+            .accounts()
+            .map(|(id, account)| (id.clone(), account.clone()))
+            .collect()
+    }
+
+    fn total_supply(chain: &Blockchain) -> u128 {
+        chain.accounts().map(|(_, account)| account.tokens()).sum()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn state_transitions_keep_the_invariants(scenario in arb_scenario()) {
+            let mut chain = scenario.genesis().build();
+            let supply = total_supply(&chain);
+            for transactions in scenario.transactions() {
+                let before = accounts(&chain);
+                let mut sent: BTreeMap<String, u128> = BTreeMap::new();
+                for transaction in &transactions {
+                    *sent.entry(transaction.sender().into()).or_default() += 1;
+                }
+                let block = mine_block(&mut chain, transactions).unwrap();
+                chain.append_block(block).unwrap();
+
+                // Transfers neither create nor destroy tokens (nor overdraw accounts)
+                prop_assert_eq!(supply, total_supply(&chain));
+                for (id, account) in chain.accounts() {
+                    prop_assert!(account.tokens() <= supply);
+                    // Every executed transaction increments the nonce of its sender
+                    let sent = sent.get(id).copied().unwrap_or_default();
+                    prop_assert_eq!(before[id].nonce() + sent, account.nonce());
+                }
+            }
+            prop_assert!(chain.check_validity().is_ok());
+        }
+
+        #[test]
+        fn rejected_blocks_leave_the_state_untouched(scenario in arb_scenario()) {
+            let mut chain = scenario.genesis().build();
+            let mut blocks = scenario.transactions();
+            let mut transactions = blocks.pop().unwrap_or_default();
+            for transactions in blocks {
+                let block = mine_block(&mut chain, transactions).unwrap();
+                chain.append_block(block).unwrap();
+            }
+
+            // The last block of the scenario, ending with a transfer of more than Alice may have
+            let overdraft = TransactionData::TransferTokens {
+                to: account_id(ACCOUNTS[1]),
+                amount: total_supply(&chain) + 1,
+                asset: None,
+            };
+            let alice = account_id(ACCOUNTS[0]);
+            let nonce = chain.get_nonce(&alice).unwrap_or_default()
+                + transactions.iter().filter(|transaction| transaction.sender() == alice).count() as u128;
+            transactions.push(signed_transaction(TEST_CHAIN_ID, ACCOUNTS[0], overdraft, nonce));
+            let mut block = chain.new_block();
+            for transaction in transactions {
+                block.add_transaction(transaction);
+            }
+            block.set_timestamp(chain.blocks.last().unwrap().timestamp() + BLOCK_INTERVAL);
+
+            let before = accounts(&chain);
+            let state_root = chain.state_root();
+            let height = chain.len();
+            prop_assert!(chain.append_block(block).is_err());
+            prop_assert_eq!(before, accounts(&chain));
+            prop_assert_eq!(state_root, chain.state_root());
+            prop_assert_eq!(height, chain.len());
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Hash, TransactionReceipt};
+
+/// A change of the chain, as published to its subscribers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ChainEvent {
+    /// A block was appended at the provided height
+    BlockAppended { height: usize, hash: Hash },
+
+    /// A transaction was executed as part of an appended block
+    TransactionExecuted {
+        block_height: usize,
+        index: usize,
+        receipt: TransactionReceipt,
+    },
+
+    /// The blocks starting with the provided height were removed (e.g. to switch to a fork),
+    /// their transactions are reverted
+    Reorg { height: usize, removed: Vec<Hash> },
+}
+
+/// Holds the senders of the channels that the chain's events are published to.<br/>
+/// A clone of a Blockchain has no subscribers, so that altering it (e.g. for
+/// sealing or demonstrating attacks) doesn't notify anyone.
+#[derive(Default)]
+pub(crate) struct Subscribers(Vec<Sender<ChainEvent>>);
+
+impl Subscribers {
+    /// Will return the receiving end of a new channel that gets all future events
+    pub(crate) fn subscribe(&mut self) -> Receiver<ChainEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.0.push(sender);
+        receiver
+    }
+
+    /// Will send the event to all subscribers, dropping the ones that went away
+    pub(crate) fn publish(&mut self, event: ChainEvent) {
+        self.0.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+impl Clone for Subscribers {
+    fn clone(&self) -> Self {
+        Subscribers::default()
+    }
+}
+
+impl fmt::Debug for Subscribers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Subscribers {{ count: {} }}", self.0.len())
+    }
+}
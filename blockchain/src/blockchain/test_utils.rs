@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use crate::{
+    Block, Blockchain, BlockchainError, GenesisAccount, GenesisConfig, Hash, Keypair, Transaction,
+    TransactionData, ValidationPolicy,
+};
+
+/// The chain id of the chains built by `ChainBuilder` (unless another one is set)
+pub const TEST_CHAIN_ID: &str = "test";
+
+/// The time (unix epoch, in milliseconds) of the genesis blocks built by `ChainBuilder`
+pub const GENESIS_TIMESTAMP: u64 = 1_600_000_000_000;
+
+/// The time (in milliseconds) between the blocks built by `mine_block`
+pub const BLOCK_INTERVAL: u64 = 10_000;
+
+/// Will return the keypair of the named account (derived from the name)
+pub fn keypair(name: &str) -> Keypair {
+    let mut secret = [0; 32];
+    secret.copy_from_slice(&Hash::digest(name.as_bytes()).as_bytes()[..32]);
+    Keypair::from_secret_bytes(&secret)
+}
+
+/// Will return the id of the named account: the address of its keypair
+pub fn account_id(name: &str) -> String {
+    keypair(name).address().to_string()
+}
+
+/// Will return the transaction of the named account, signed with its keypair
+pub fn signed_transaction(
+    chain_id: &str,
+    from: &str,
+    data: TransactionData,
+    nonce: u128,
+) -> Transaction {
+    let mut transaction = Transaction::new(account_id(from), data, nonce)
+        .with_chain_id(chain_id.into())
+        .with_created_at(GENESIS_TIMESTAMP);
+    transaction.sign(&keypair(from));
+    transaction
+}
+
+/// Will build the block following the last one of the chain (`BLOCK_INTERVAL` later) out of
+/// the transactions, committing to the world state after executing them and mined with the
+/// next difficulty.<br/>
+/// The block is not appended, so that tests may tamper with it.
+pub fn mine_block(
+    chain: &mut Blockchain,
+    transactions: Vec<Transaction>,
+) -> Result<Block, BlockchainError> {
+    let mut block = chain.new_block();
+    let timestamp = chain
+        .blocks
+        .last()
+        .map_or(GENESIS_TIMESTAMP, |last| last.timestamp() + BLOCK_INTERVAL);
+    block.set_timestamp(timestamp);
+    for transaction in transactions {
+        block.add_transaction(transaction);
+    }
+    chain.seal_block(&mut block)?;
+    Ok(block)
+}
+
+/// Builds a chain requiring signatures, out of named accounts and the transactions between
+/// them (so that tests don't have to repeat the genesis boilerplate), e.g.
+/// `ChainBuilder::new().with_account("alice", 1000).transfer("alice", "bob", 10).build()`.
+/// <br/>
+/// The accounts are created (and funded) by the genesis block, including the ones that are
+/// only named by transfers. Every other block holds the transactions added (in order) since
+/// the previous `next_block`.<br/>
+/// Everything is deterministic: the keys of the accounts are derived from their names (see
+/// `keypair`) and the blocks have fixed timestamps, so that building the same chain twice
+/// results in the same blocks.
+#[derive(Clone, Debug)]
+pub struct ChainBuilder {
+    chain_id: String,
+    policy: ValidationPolicy,
+    accounts: Vec<GenesisAccount>,
+    blocks: Vec<Vec<(String, TransactionData)>>,
+}
+
+impl Default for ChainBuilder {
+    fn default() -> Self {
+        ChainBuilder {
+            chain_id: TEST_CHAIN_ID.into(),
+            policy: ValidationPolicy {
+                require_signatures: true,
+                ..ValidationPolicy::default()
+            },
+            accounts: Vec::new(),
+            blocks: vec![Vec::new()],
+        }
+    }
+}
+
+impl ChainBuilder {
+    /// C'tor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the chain id of the network
+    pub fn with_chain_id(mut self, chain_id: &str) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+
+    /// Sets the validation policy of the network (its chain id is the one of the builder)
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Adds the named account, holding the balance from the genesis block on
+    pub fn with_account(mut self, name: &str, balance: u128) -> Self {
+        self.account(name).balance = balance;
+        self
+    }
+
+    /// Adds the named account as a validator, funded with (and staking) the amount on top of
+    /// its balance from the genesis block on
+    pub fn with_validator(mut self, name: &str, stake: u128) -> Self {
+        let account = self.account(name);
+        account.balance = account.balance.saturating_add(stake);
+        account.stake = stake;
+        self
+    }
+
+    /// Adds the transfer of tokens between the named accounts to the current block
+    pub fn transfer(self, from: &str, to: &str, amount: u128) -> Self {
+        let data = TransactionData::TransferTokens {
+            to: account_id(to),
+            amount,
+            asset: None,
+        };
+        self.transaction(from, data).with_name(to)
+    }
+
+    /// Adds the operation of the named account to the current block
+    pub fn transaction(mut self, from: &str, data: TransactionData) -> Self {
+        self.account(from);
+        self.blocks
+            .last_mut()
+            .expect("there is always a current block")
+            .push((from.into(), data));
+        self
+    }
+
+    /// Ends the current block, the following transactions are added to a new one
+    pub fn next_block(mut self) -> Self {
+        self.blocks.push(Vec::new());
+        self
+    }
+
+    /// Will build the chain, panicking if one of its blocks is rejected
+    pub fn build(self) -> Blockchain {
+        match self.try_build() {
+            Ok(chain) => chain,
+            Err(err) => panic!("The built chain is invalid: {}", err),
+        }
+    }
+
+    /// Will build the chain, returning the error if one of its blocks is rejected
+    pub fn try_build(self) -> Result<Blockchain, BlockchainError> {
+        let chain_id = self.chain_id;
+        let config = GenesisConfig {
+            chain_id: chain_id.clone(),
+            timestamp: Some(GENESIS_TIMESTAMP),
+            accounts: self.accounts,
+            batch_accounts: false,
+            consensus: self.policy,
+        };
+        let mut chain = Blockchain::from_genesis(&config)?;
+
+        let mut nonces = HashMap::new();
+        for operations in self.blocks.into_iter().filter(|block| !block.is_empty()) {
+            let transactions = operations
+                .into_iter()
+                .map(|(from, data)| {
+                    let nonce = nonces.entry(from.clone()).or_insert(0);
+                    let transaction = signed_transaction(&chain_id, &from, data, *nonce);
+                    *nonce += 1;
+                    transaction
+                })
+                .collect();
+            let block = mine_block(&mut chain, transactions)?;
+            chain.append_block(block)?;
+        }
+        Ok(chain)
+    }
+
+    fn with_name(mut self, name: &str) -> Self {
+        self.account(name);
+        self
+    }
+
+    /// Will return the genesis account of the name, adding it if it is not known yet
+    fn account(&mut self, name: &str) -> &mut GenesisAccount {
+        let id = account_id(name);
+        match self.accounts.iter().position(|account| account.id == id) {
+            Some(index) => &mut self.accounts[index],
+            None => {
+                self.accounts.push(GenesisAccount {
+                    id,
+                    balance: 0,
+                    stake: 0,
+                    admin: false,
+                });
+                self.accounts
+                    .last_mut()
+                    .expect("the account was just added")
+            }
+        }
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{account_id, mine_block, signed_transaction, ChainBuilder, TEST_CHAIN_ID};
+    use crate::{BlockchainError, TransactionData, WorldState};
+
+    #[test]
+    fn built_chains_are_valid_and_deterministic() {
+        let build = || {
+            ChainBuilder::new()
+                .with_account("alice", 1000)
+                .transfer("alice", "bob", 10)
+                .transfer("alice", "carol", 5)
+                .next_block()
+                .transfer("bob", "carol", 3)
+                .build()
+        };
+        let chain = build();
+        assert_eq!(3, chain.len());
+        assert!(chain.check_validity().is_ok());
+        let balance = |name| chain.get_account_by_id(&account_id(name)).unwrap().tokens();
+        assert_eq!(985, balance("alice"));
+        assert_eq!(7, balance("bob"));
+        assert_eq!(8, balance("carol"));
+        assert_eq!(chain.get_last_block_hash(), build().get_last_block_hash());
+
+        let overdrawn = ChainBuilder::new()
+            .with_account("alice", 1)
+            .transfer("alice", "bob", 10)
+            .try_build();
+        assert!(overdrawn.is_err());
+    }
+
+    #[test]
+    fn mined_blocks_are_checked() {
+        let mut chain = ChainBuilder::new()
+            .with_account("alice", 10)
+            .with_account("bob", 0)
+            .build();
+        let transfer = TransactionData::TransferTokens {
+            to: account_id("bob"),
+            amount: 1,
+            asset: None,
+        };
+        let signed = signed_transaction(TEST_CHAIN_ID, "alice", transfer.clone(), 0);
+        let mut forged = signed_transaction(TEST_CHAIN_ID, "mallory", transfer, 0);
+        forged.from = account_id("alice");
+
+        let block = mine_block(&mut chain, vec![forged]).unwrap();
+        assert!(matches!(
+            chain.append_block(block).unwrap_err(),
+            BlockchainError::InvalidBlock { .. } | BlockchainError::InvalidTransaction { .. }
+        ));
+        let block = mine_block(&mut chain, vec![signed]).unwrap();
+        assert!(chain.append_block(block).is_ok());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{Account, AccountType, BlockchainError};
+
+use super::world_state::WorldState;
+
+/// The (reserved) account holding the tombstones of the deleted accounts: its store maps
+/// their ids to the nonces they were deleted with, so that a re-created account continues
+/// with its nonce (instead of accepting its old transactions again).
+pub const TOMBSTONE_REGISTRY: &str = "@tombstones";
+
+/// Will record the nonce of the deleted account, creating the registry with the first
+/// tombstone
+pub(crate) fn bury<T: WorldState + ?Sized>(
+    world_state: &mut T,
+    id: &str,
+    nonce: u128,
+) -> Result<(), BlockchainError> {
+    if !world_state.account_exists(TOMBSTONE_REGISTRY) {
+        world_state.create_account(TOMBSTONE_REGISTRY.into(), AccountType::User)?;
+    }
+    world_state
+        .get_account_by_id_mut(TOMBSTONE_REGISTRY)
+        .expect("The registry exists")
+        .store
+        .insert(id.to_string(), nonce.to_string());
+    Ok(())
+}
+
+/// Will return a new account of the id, which continues with the nonce of its tombstone
+/// (if the id belonged to a deleted account)
+pub(crate) fn new_account<T: WorldState + ?Sized>(
+    world_state: &T,
+    id: &str,
+    account_type: AccountType,
+) -> Account {
+    let mut account = Account::new(account_type);
+    if let Some(nonce) = world_state
+        .get_store_value(TOMBSTONE_REGISTRY, id)
+        .and_then(|nonce| nonce.parse().ok())
+    {
+        account.nonce = nonce;
+    }
+    account
+}
@@ -1,162 +1,2156 @@
-use std::time::SystemTime;
+use std::collections::HashSet;
 
 use blake2::{Blake2b, Digest};
+use ed25519_dalek::{Signature, VerifyingKey};
+use k256::ecdsa;
+use serde::{Deserialize, Serialize};
 
-use crate::AccountType;
+use crate::{
+    AccountType, Address, BlockchainError, BridgePayload, BridgeProof, Cosignature, Event,
+    Evidence, Hash, Keypair, ReceiptStatus, SignDoc, SignatureScheme, TransactionReceipt,
+    VestingTranche,
+};
 
-use super::world_state::WorldState;
+use super::address::ADDRESS_BYTE_SIZE;
+use super::admin;
+use super::alias;
+use super::allowance;
+use super::asset;
+use super::block::now;
+use super::bridge;
+use super::canonical;
+use super::contracts;
+use super::escrow;
+use super::htlc;
+use super::metadata;
+use super::multisig;
+use super::names;
+use super::nft;
+use super::policy::DEFAULT_CHAIN_ID;
+use super::staking;
+use super::tombstone;
+use super::vesting;
+use super::world_state::{is_reserved_account, WorldState};
 
 /// Stores a request to the blockchain
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     /// Unique number (will be used for randomization later; prevents replay attacks)
-    nonce: u128,
+    pub(crate) nonce: u128,
 
     /// Account ID
-    from: String,
+    pub(crate) from: String,
 
-    /// Stores the time the transaction was created
-    created_at: SystemTime,
+    /// Amount of tokens paid by the sender to the block producer
+    pub(crate) fee: u128,
+
+    /// The network the transaction is meant for (prevents replaying it on other networks)
+    pub(crate) chain_id: String,
+
+    /// Stores the time (unix epoch, in milliseconds) the transaction was created
+    created_at: u64,
 
     /// the type of the transaction and its additional information
     pub(crate) record: TransactionData,
 
-    /// Signature of the hash of the whole message
+    /// The public key of the signer (hex encoded), its address has to be the sender
+    public_key: Option<String>,
+
+    /// Signature of the document of the transaction (hex encoded, see `SignDoc`)
     signature: Option<String>,
+
+    /// The signatures of the owners, if the sender is a multisignature account
+    cosignatures: Vec<Cosignature>,
+
+    /// The scheme of the signature (and of the cosignatures), set when it is signed
+    #[serde(default)]
+    scheme: SignatureScheme,
+
+    /// If set, the transaction may only be included once the chain reached the lock
+    #[serde(default)]
+    valid_after: Option<TimeLock>,
+
+    /// If set, the transaction may only be included before the chain reached the lock
+    /// (it expires afterwards)
+    #[serde(default)]
+    valid_before: Option<TimeLock>,
+
+    /// The version of the rules the transaction was created for, which may not be newer
+    /// than the one active at the height of its block (see `ValidationPolicy::version_at`)
+    #[serde(default)]
+    version: u32,
+}
+
+/// A point in the time of the chain: a block height or a block timestamp
+/// (unix epoch, in milliseconds), see `Transaction::with_valid_after`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeLock {
+    Height(u64),
+    Timestamp(u64),
+}
+
+impl TimeLock {
+    /// Will return true if the block at the height (with the timestamp) reached the lock
+    pub fn is_reached(&self, height: usize, timestamp: u64) -> bool {
+        match *self {
+            TimeLock::Height(lock) => height as u64 >= lock,
+            TimeLock::Timestamp(lock) => timestamp >= lock,
+        }
+    }
 }
 
 /// A single operation to be stored on the chain
 /// Noticeable, enums in rust actually can carry data in a
 /// tuple-like structure (CreateUserAccount) or a dictionary-like (the ChangeStoreValue)
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TransactionData {
     /// Will be used to store a new user account
     CreateUserAccount(String),
 
+    /// Will be used to store a new admin account (only by the genesis block)
+    CreateAdminAccount(String),
+
+    /// Will freeze the account (which may be an alias), so that it can't transfer its tokens
+    /// anymore. Only admins may do so.
+    FreezeAccount { account: String },
+
+    /// Will unfreeze the (frozen) account. Only admins may do so.
+    UnfreezeAccount { account: String },
+
+    /// Will mint a (non-fungible) token with the unique id for the sender (see `NFT_REGISTRY`)
+    MintNft { id: String, metadata: String },
+
+    /// Will transfer the sender's (non-fungible) token to the receiver (which may be an alias)
+    TransferNft { id: String, to: String },
+
+    /// Will allow the spender to transfer up to `amount` of the sender's tokens
+    /// (replacing the previous allowance, zero revokes it)
+    Approve { spender: String, amount: u128 },
+
+    /// Will transfer the amount of the owner's tokens on its behalf, spending the allowance
+    /// the owner gave to the sender (see `Approve`)
+    TransferFrom {
+        owner: String,
+        to: String,
+        amount: u128,
+    },
+
     /// Will be used to change or create a arbitrary value into an account
     ChangeStoreValue { key: String, value: String },
 
-    /// Will be used to move tokens from one owner to another
-    TransferTokens { to: String, amount: u128 },
+    /// Will be used to move tokens from one owner to another (the receiver may be an alias).<br/>
+    /// The native tokens are moved, unless another asset is provided (see `CreateAsset`).
+    TransferTokens {
+        to: String,
+        amount: u128,
+        #[serde(default)]
+        asset: Option<String>,
+    },
+
+    /// Just create tokens (of the native or the provided asset) out of nowhere
+    CreateTokens {
+        receiver: String,
+        amount: u128,
+        #[serde(default)]
+        asset: Option<String>,
+    },
+
+    /// Will register a new (fungible) asset, whose whole supply is given to the sender
+    /// (its issuer). The id has to be unique (see `ASSET_REGISTRY`).
+    CreateAsset { asset: String, supply: u128 },
+
+    /// Will close the sender's account, moving its remaining tokens to the beneficiary
+    /// (which may be an alias).<br/>
+    /// Accounts that still hold stored values can't be deleted. The nonce of the account is
+    /// kept (see `TOMBSTONE_REGISTRY`), should it be re-created.
+    DeleteAccount { beneficiary: String },
 
-    /// Just create tokens out of nowhere
-    CreateTokens { receiver: String, amount: u128 },
+    /// Rewards the block producer with the block subsidy plus the fees of the block.<br/>
+    /// It may only be included once per block, by the producer (sender and receiver).
+    Coinbase { receiver: String, amount: u128 },
+
+    /// Will create a contract account holding the WASM code, whose address is derived of the
+    /// sender, the nonce of the transaction and the index of the operation within its batch
+    /// (see `contract_address`)
+    DeployContract { code: Vec<u8> },
+
+    /// Will execute the contract's code, which may change the contract's storage
+    CallContract { address: String, input: Vec<u8> },
+
+    /// Will register a human-readable alias for the sender's address (see `ALIAS_REGISTRY`)
+    RegisterAlias { alias: String },
+
+    /// Will create a multisignature account (see `Transaction::multisig_address`)
+    CreateMultisigAccount { owners: Vec<String>, threshold: u32 },
+
+    /// Will lock the amount of the sender's tokens as stake, making it a validator
+    Stake { amount: u128 },
+
+    /// Will unlock the amount of the sender's stake
+    Unstake { amount: u128 },
+
+    /// Will slash (burn) the whole stake of the validator that the evidence proves
+    /// to have misbehaved. Anyone may report it.
+    ReportMisbehavior {
+        #[serde(deserialize_with = "super::serialization::nested")]
+        evidence: Box<Evidence>,
+    },
+
+    /// Will execute the operations in order, atomically: if one of them fails, the whole
+    /// transaction fails (and is reverted with its block, like any failed transaction).<br/>
+    /// Batches can't be nested and can't contain a coinbase.
+    #[serde(deserialize_with = "super::serialization::nested")]
+    Batch(Vec<TransactionData>),
+
+    /// Will register a human-readable name for the sender's address for a period of blocks
+    /// (see `NAME_REGISTRY`), or renew it if the sender owns it already. Unlike an alias,
+    /// an expired name may be registered by anyone.
+    RegisterName { name: String },
+
+    /// Will mint the amount of tokens for the receiver (which may be an alias), creating its
+    /// account if it doesn't exist yet, so that developers can fund their test accounts.<br/>
+    /// Only development networks accept it, up to an amount (see `ChainParams::dev_mode`).
+    Faucet { to: String, amount: u128 },
+
+    /// Will create the user accounts with their initial balances (only by the genesis block),
+    /// so that large initial allocations don't need a couple of transactions per account
+    CreateUserAccounts(Vec<(String, u128)>),
+
+    /// Will set an entry of the sender's metadata (see `Account::metadata`), or remove it if
+    /// the value is empty. The entries are limited in size and number (see `metadata::set`).
+    SetAccountMetadata { key: String, value: String },
+
+    /// Will create the account of the receiver (which may not exist yet), funded with the
+    /// sender's tokens of the tranches, each of which stays locked until the chain reaches its
+    /// lock (see `vesting::create`), e.g. for the token distributions of a team
+    CreateVestingAccount {
+        to: String,
+        tranches: Vec<VestingTranche>,
+    },
+
+    /// Will put the amount of the sender's tokens aside for the receiver (see `EscrowRecord`),
+    /// in an escrow whose id is the hash of the transaction, until it is released or refunded
+    EscrowCreate {
+        to: String,
+        amount: u128,
+        arbiter: String,
+    },
+
+    /// Will pay the tokens of the escrow to its receiver (only by its sender or its arbiter)
+    EscrowRelease { id: String },
+
+    /// Will pay the tokens of the escrow back to its sender (only by its receiver or its
+    /// arbiter)
+    EscrowRefund { id: String },
+
+    /// Will lock the amount of the sender's tokens for the receiver (see `HtlcRecord`), in a
+    /// hashed timelock contract whose id is the hash of the transaction, until the preimage of
+    /// the (hex encoded SHA-256) hash lock is revealed or the timeout is reached
+    HtlcLock {
+        to: String,
+        amount: u128,
+        hash_lock: String,
+        timeout: TimeLock,
+    },
+
+    /// Will pay the tokens of the contract to its receiver, revealing the (hex encoded)
+    /// preimage of its hash lock (only before its timeout)
+    HtlcClaim { id: String, preimage: String },
+
+    /// Will pay the tokens of the contract back to its sender (only once its timeout is
+    /// reached)
+    HtlcRefund { id: String },
+
+    /// Will send the message to the target chain (e.g. burning the tokens it transfers), which
+    /// a relayer passes on with the proof of this transaction (see `Relayer`)
+    BridgeOut {
+        target_chain: String,
+        payload: BridgePayload,
+    },
+
+    /// Will execute the message of another chain (e.g. minting the tokens it transfers), given
+    /// the proof of its `BridgeOut` transaction (attested for a bridge of the policy)
+    BridgeIn { proof: Box<BridgeProof> },
     // ... Extend it as you wish, you get the idea
 }
 
+/// The kind of an operation (a `TransactionData` without its parameters), e.g. to query the
+/// transactions of a kind (see `Blockchain::transactions_by_type`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TransactionKind {
+    CreateUserAccount,
+    CreateAdminAccount,
+    FreezeAccount,
+    UnfreezeAccount,
+    MintNft,
+    TransferNft,
+    Approve,
+    TransferFrom,
+    ChangeStoreValue,
+    TransferTokens,
+    CreateTokens,
+    CreateAsset,
+    DeleteAccount,
+    Coinbase,
+    DeployContract,
+    CallContract,
+    RegisterAlias,
+    CreateMultisigAccount,
+    Stake,
+    Unstake,
+    ReportMisbehavior,
+    Batch,
+    RegisterName,
+    Faucet,
+    CreateUserAccounts,
+    SetAccountMetadata,
+    CreateVestingAccount,
+    EscrowCreate,
+    EscrowRelease,
+    EscrowRefund,
+    HtlcLock,
+    HtlcClaim,
+    HtlcRefund,
+    BridgeOut,
+    BridgeIn,
+}
+
+impl TransactionData {
+    /// Will return the kind of the operation
+    pub fn kind(&self) -> TransactionKind {
+        match self {
+            TransactionData::CreateUserAccount(_) => TransactionKind::CreateUserAccount,
+            TransactionData::CreateAdminAccount(_) => TransactionKind::CreateAdminAccount,
+            TransactionData::FreezeAccount { .. } => TransactionKind::FreezeAccount,
+            TransactionData::UnfreezeAccount { .. } => TransactionKind::UnfreezeAccount,
+            TransactionData::MintNft { .. } => TransactionKind::MintNft,
+            TransactionData::TransferNft { .. } => TransactionKind::TransferNft,
+            TransactionData::Approve { .. } => TransactionKind::Approve,
+            TransactionData::TransferFrom { .. } => TransactionKind::TransferFrom,
+            TransactionData::ChangeStoreValue { .. } => TransactionKind::ChangeStoreValue,
+            TransactionData::TransferTokens { .. } => TransactionKind::TransferTokens,
+            TransactionData::CreateTokens { .. } => TransactionKind::CreateTokens,
+            TransactionData::CreateAsset { .. } => TransactionKind::CreateAsset,
+            TransactionData::DeleteAccount { .. } => TransactionKind::DeleteAccount,
+            TransactionData::Coinbase { .. } => TransactionKind::Coinbase,
+            TransactionData::DeployContract { .. } => TransactionKind::DeployContract,
+            TransactionData::CallContract { .. } => TransactionKind::CallContract,
+            TransactionData::RegisterAlias { .. } => TransactionKind::RegisterAlias,
+            TransactionData::CreateMultisigAccount { .. } => TransactionKind::CreateMultisigAccount,
+            TransactionData::Stake { .. } => TransactionKind::Stake,
+            TransactionData::Unstake { .. } => TransactionKind::Unstake,
+            TransactionData::ReportMisbehavior { .. } => TransactionKind::ReportMisbehavior,
+            TransactionData::Batch(_) => TransactionKind::Batch,
+            TransactionData::RegisterName { .. } => TransactionKind::RegisterName,
+            TransactionData::Faucet { .. } => TransactionKind::Faucet,
+            TransactionData::CreateUserAccounts(_) => TransactionKind::CreateUserAccounts,
+            TransactionData::SetAccountMetadata { .. } => TransactionKind::SetAccountMetadata,
+            TransactionData::CreateVestingAccount { .. } => TransactionKind::CreateVestingAccount,
+            TransactionData::EscrowCreate { .. } => TransactionKind::EscrowCreate,
+            TransactionData::EscrowRelease { .. } => TransactionKind::EscrowRelease,
+            TransactionData::EscrowRefund { .. } => TransactionKind::EscrowRefund,
+            TransactionData::HtlcLock { .. } => TransactionKind::HtlcLock,
+            TransactionData::HtlcClaim { .. } => TransactionKind::HtlcClaim,
+            TransactionData::HtlcRefund { .. } => TransactionKind::HtlcRefund,
+            TransactionData::BridgeOut { .. } => TransactionKind::BridgeOut,
+            TransactionData::BridgeIn { .. } => TransactionKind::BridgeIn,
+        }
+    }
+}
+
 impl Transaction {
     pub fn new(from: String, transaction_data: TransactionData, nonce: u128) -> Self {
         Transaction {
             from,
             nonce,
+            fee: 0,
+            chain_id: DEFAULT_CHAIN_ID.into(),
             record: transaction_data,
-            created_at: SystemTime::now(),
+            created_at: now(),
+            public_key: None,
             signature: None,
+            cosignatures: Vec::new(),
+            scheme: SignatureScheme::Ed25519,
+            valid_after: None,
+            valid_before: None,
+            version: 0,
+        }
+    }
+
+    /// Sets the time (unix epoch, in milliseconds) the transaction was created, which is
+    /// part of its hash (e.g. for reproducible hashes in tests)
+    pub fn with_created_at(mut self, created_at: u64) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
+    /// Will return the time (unix epoch, in milliseconds) the transaction was created
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Sets the lock that the chain has to reach before the transaction may be included,
+    /// so that it can be signed upfront but only becomes executable later
+    pub fn with_valid_after(mut self, lock: TimeLock) -> Self {
+        self.valid_after = Some(lock);
+        self
+    }
+
+    /// Sets the lock after which the transaction may not be included anymore (it expires)
+    pub fn with_valid_before(mut self, lock: TimeLock) -> Self {
+        self.valid_before = Some(lock);
+        self
+    }
+
+    /// Will check if the transaction may be included by the block at the height
+    /// (with the timestamp), as far as its validity window is concerned
+    pub fn check_validity_window(
+        &self,
+        height: usize,
+        timestamp: u64,
+    ) -> Result<(), BlockchainError> {
+        if let Some(lock) = self.valid_after {
+            if !lock.is_reached(height, timestamp) {
+                return Err(BlockchainError::TransactionNotYetValid(lock));
+            }
+        }
+        if let Some(lock) = self.valid_before {
+            if lock.is_reached(height, timestamp) {
+                return Err(BlockchainError::TransactionExpired(lock));
+            }
         }
+        Ok(())
+    }
+
+    /// Sets the version of the rules the transaction is created for
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Will return the version of the rules the transaction is created for
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Sets the fee paid by the sender to the block producer
+    pub fn with_fee(mut self, fee: u128) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Will return the fee paid by the sender to the block producer
+    pub fn fee(&self) -> u128 {
+        self.fee
+    }
+
+    /// Sets the network the transaction is meant for (the development network by default)
+    pub fn with_chain_id(mut self, chain_id: String) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Will return the network the transaction is meant for
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    /// Will return the account ID of the sender
+    pub fn sender(&self) -> &str {
+        &self.from
+    }
+
+    /// Will return the nonce of the transaction
+    pub fn nonce(&self) -> u128 {
+        self.nonce
+    }
+
+    /// Will return the operation of the transaction
+    pub fn record(&self) -> &TransactionData {
+        &self.record
+    }
+
+    /// Checks if this is a coinbase (block reward) transaction
+    pub fn is_coinbase(&self) -> bool {
+        matches!(self.record, TransactionData::Coinbase { .. })
+    }
+
+    /// Will return true if the transaction, or one of the operations of its batch, is a
+    /// coinbase
+    pub fn performs_coinbase(&self) -> bool {
+        self.performs(|operation| matches!(operation, TransactionData::Coinbase { .. }))
+    }
+
+    /// Will return the address of the contract deployed by this transaction, or of the first
+    /// one deployed by the operations of its batch (`None` if it doesn't deploy a contract),
+    /// see `contract_addresses`
+    pub fn contract_address(&self) -> Option<String> {
+        self.contract_addresses().into_iter().next()
+    }
+
+    /// Will return the addresses of the contracts deployed by this transaction (or by the
+    /// operations of its batch, in their order), see `contract_address`
+    pub fn contract_addresses(&self) -> Vec<String> {
+        let operations = match &self.record {
+            TransactionData::Batch(operations) => operations.as_slice(),
+            operation => std::slice::from_ref(operation),
+        };
+        operations
+            .iter()
+            .enumerate()
+            .filter(|(_, operation)| matches!(operation, TransactionData::DeployContract { .. }))
+            .map(|(index, _)| contracts::contract_address(&self.from, self.nonce, index))
+            .collect()
+    }
+
+    /// Will return the address of the multisignature account created by this transaction, or
+    /// by one of the operations of its batch (`None` if it doesn't create one)
+    pub fn multisig_address(&self) -> Option<String> {
+        self.performs(|operation| {
+            matches!(operation, TransactionData::CreateMultisigAccount { .. })
+        })
+        .then(|| self.derived_address())
+    }
+
+    /// Will return true if the operation of the transaction (or one of its batch) matches
+    fn performs(&self, matches: impl Fn(&TransactionData) -> bool) -> bool {
+        match &self.record {
+            TransactionData::Batch(operations) => operations.iter().any(matches),
+            operation => matches(operation),
+        }
+    }
+
+    /// Will derive an address of the hash, for the accounts created by the transaction
+    fn derived_address(&self) -> String {
+        let mut bytes = [0u8; ADDRESS_BYTE_SIZE];
+        bytes.copy_from_slice(&self.calculate_hash().as_bytes()[..ADDRESS_BYTE_SIZE]);
+        Address::new(bytes).to_string()
     }
 
     /// Will change the world state according to the transactions commands
+    /// and return its receipt (the gas it used and the events it emitted).<br/>
+    /// The gas is paid by the sender (at the gas price of the world state),
+    /// except for the transactions of the genesis block.<br/>
+    /// The nonce has to be the sender's next one (the genesis transactions and the coinbases
+    /// don't count).<br/>
+    /// A failing contract call doesn't fail the transaction, its receipt reports the failure.
     pub fn execute<T: WorldState>(
         &self,
         world_state: &mut T,
         is_initial: &bool,
-    ) -> Result<(), &'static str> {
+    ) -> Result<TransactionReceipt, BlockchainError> {
+        // The registries can only be changed through their own transactions
+        if is_reserved_account(&self.from) {
+            return Err(BlockchainError::ReservedAccount(self.from.clone()));
+        }
+
         // Check if sending user does exist (no one not on the chain can execute transactions)
         if let Some(_account) = world_state.get_account_by_id(&self.from) {
             // Do some more checkups later on...
         } else {
             if !is_initial {
-                return Err("Account does not exist (Code: 93482390)");
+                return Err(BlockchainError::UnknownAccount(self.from.clone()));
+            }
+        }
+
+        // The transactions of a multisignature account need the signatures of its owners
+        if let Some(AccountType::Multisig { owners, threshold }) = world_state
+            .get_account_by_id(&self.from)
+            .map(|account| &account.acc_type)
+        {
+            multisig::check_threshold(owners, *threshold, &self.signers())?;
+        }
+
+        // Reject replayed (and skipped) transactions, the genesis ones and the coinbases (which
+        // the block producers don't send) don't count
+        let counts_nonce = !is_initial && !self.is_coinbase();
+        let nonce = match world_state.get_account_by_id(&self.from) {
+            Some(sender) if counts_nonce => sender.nonce(),
+            _ => self.nonce,
+        };
+        if self.nonce != nonce {
+            return Err(BlockchainError::InvalidNonce {
+                expected: nonce,
+                found: self.nonce,
+            });
+        }
+
+        // Pay the fee (it is collected by the block producer through the coinbase)
+        if self.fee > 0 {
+            let sender = world_state
+                .get_account_by_id_mut(&self.from)
+                .ok_or_else(|| BlockchainError::UnknownAccount(self.from.clone()))?;
+            sender.tokens = sender
+                .tokens
+                .checked_sub(self.fee)
+                .ok_or(BlockchainError::InsufficientBalance)?;
+        }
+
+        // Pay the gas of the operation up front (the sender may not exist afterwards)
+        let gas_cost = self.record.gas_cost();
+        self.charge_gas(world_state, gas_cost, is_initial)?;
+        let mut events = Vec::new();
+        let (contract_gas, status) =
+            self.execute_operation(&self.record, world_state, nonce, 0, is_initial, &mut events)?;
+
+        // Pay the gas used by executing contract code
+        self.charge_gas(world_state, contract_gas, is_initial)?;
+
+        // Count the transactions of the sender (it doesn't exist anymore after deleting itself),
+        // the accounts start at nonce 0 after the genesis block
+        if counts_nonce {
+            if let Some(sender) = world_state.get_account_by_id_mut(&self.from) {
+                sender.nonce = sender.nonce.saturating_add(1);
             }
         }
+        Ok(TransactionReceipt {
+            transaction_hash: self.calculate_hash(),
+            status,
+            gas_used: gas_cost.saturating_add(contract_gas),
+            events,
+            balance_changes: Vec::new(),
+        })
+    }
+
+    /// Will execute an operation (the record of the transaction or the one at the index of its
+    /// batch, 0 otherwise) at the nonce of the sender, returning the gas used by executing
+    /// contract code and the status of the receipt
+    fn execute_operation<T: WorldState>(
+        &self,
+        operation: &TransactionData,
+        world_state: &mut T,
+        nonce: u128,
+        index: usize,
+        is_initial: &bool,
+        events: &mut Vec<Event>,
+    ) -> Result<(u64, ReceiptStatus), BlockchainError> {
+        let mut contract_gas = 0;
+        let mut status = ReceiptStatus::Success;
 
         // match is like a switch (pattern matching) in C++ or Java
         // We will check for the type of transaction here and execute its logic
-        return match &self.record {
+        match operation {
             TransactionData::CreateUserAccount(account) => {
-                world_state.create_account(account.into(), AccountType::User)
+                if is_reserved_account(account) {
+                    return Err(BlockchainError::ReservedAccount(account.clone()));
+                }
+                world_state.create_account(account.into(), AccountType::User)?;
+                events.push(Event::AccountCreated {
+                    id: account.clone(),
+                });
+                Ok(())
             }
 
-            TransactionData::CreateTokens { receiver, amount } => {
+            TransactionData::CreateAdminAccount(account) => {
                 if !is_initial {
-                    return Err(
-                        "Token creation is only available on initial creation (Code: 2394233)",
-                    );
+                    return Err(BlockchainError::GenesisOnly);
+                }
+                if is_reserved_account(account) {
+                    return Err(BlockchainError::ReservedAccount(account.clone()));
+                }
+                world_state.create_account(account.into(), AccountType::Admin)?;
+                events.push(Event::AccountCreated {
+                    id: account.clone(),
+                });
+                Ok(())
+            }
+
+            TransactionData::FreezeAccount { account } => {
+                let id = admin::set_frozen(world_state, &self.from, account, true)?;
+                events.push(Event::AccountFrozen { id });
+                Ok(())
+            }
+
+            TransactionData::UnfreezeAccount { account } => {
+                let id = admin::set_frozen(world_state, &self.from, account, false)?;
+                events.push(Event::AccountUnfrozen { id });
+                Ok(())
+            }
+
+            TransactionData::CreateTokens {
+                receiver,
+                amount,
+                asset: Some(asset),
+            } => {
+                if !is_initial {
+                    return Err(BlockchainError::GenesisOnly);
+                }
+                asset::mint(world_state, asset, receiver, *amount)?;
+                events.push(Event::AssetsCreated {
+                    asset: asset.clone(),
+                    receiver: receiver.clone(),
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::CreateTokens {
+                receiver,
+                amount,
+                asset: None,
+            } => {
+                if !is_initial {
+                    return Err(BlockchainError::GenesisOnly);
                 }
                 // Get the receiving user (must exist)
-                return if let Some(account) = world_state.get_account_by_id_mut(receiver) {
-                    account.tokens += *amount;
+                if let Some(account) = world_state.get_account_by_id_mut(receiver) {
+                    account.tokens = account
+                        .tokens
+                        .checked_add(*amount)
+                        .ok_or(BlockchainError::ArithmeticOverflow)?;
+                    events.push(Event::TokensCreated {
+                        receiver: receiver.clone(),
+                        amount: *amount,
+                    });
                     Ok(())
                 } else {
-                    Err("Receiver Account does not exist (Code: 23482309)")
-                };
+                    Err(BlockchainError::UnknownAccount(receiver.clone()))
+                }
+            }
+
+            TransactionData::TransferTokens {
+                to,
+                amount,
+                asset: Some(asset),
+            } => {
+                let to = asset::transfer(world_state, asset, &self.from, to, *amount)?;
+                events.push(Event::AssetTransfer {
+                    asset: asset.clone(),
+                    from: self.from.clone(),
+                    to,
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::CreateAsset { asset, supply } => {
+                asset::create(world_state, asset, &self.from, *supply)?;
+                events.push(Event::AssetsCreated {
+                    asset: asset.clone(),
+                    receiver: self.from.clone(),
+                    amount: *supply,
+                });
+                Ok(())
             }
 
-            TransactionData::TransferTokens { to, amount } => {
-                let recv_tokens: u128;
+            TransactionData::TransferTokens {
+                to,
+                amount,
+                asset: None,
+            } => {
+                let to = &world_state.resolve_account_id(to);
                 let sender_tokens: u128;
 
-                if let Some(recv) = world_state.get_account_by_id_mut(to) {
-                    // Be extra careful here, even in the genesis block the sender account has to exist
-                    recv_tokens = recv.tokens;
-                } else {
-                    return Err("Receiver Account does not exist! (Code: 3242342380)");
+                // Be extra careful here, even in the genesis block the sender account has to exist
+                if !world_state.account_exists(to) {
+                    return Err(BlockchainError::UnknownAccount(to.clone()));
                 }
 
                 if let Some(sender) = world_state.get_account_by_id_mut(&self.from) {
+                    if sender.frozen {
+                        return Err(BlockchainError::AccountFrozen(self.from.clone()));
+                    }
                     sender_tokens = sender.tokens;
                 } else {
-                    return Err("That account does not exist! (Code: 23423923)");
+                    return Err(BlockchainError::UnknownAccount(self.from.clone()));
                 }
 
-                let balance_recv_new = recv_tokens.checked_add(*amount);
-                let balance_sender_new = sender_tokens.checked_sub(*amount);
+                let balance_sender_new = sender_tokens
+                    .checked_sub(*amount)
+                    .ok_or(BlockchainError::InsufficientBalance)?;
+                vesting::check_unlocked(world_state, &self.from, balance_sender_new)?;
+
+                // The receiver is credited after the sender is debited, since they may be the same
+                world_state
+                    .get_account_by_id_mut(&self.from)
+                    .unwrap()
+                    .tokens = balance_sender_new;
+                let recv = world_state.get_account_by_id_mut(to).unwrap();
+                recv.tokens = recv
+                    .tokens
+                    .checked_add(*amount)
+                    .ok_or(BlockchainError::ArithmeticOverflow)?;
+                events.push(Event::Transfer {
+                    from: self.from.clone(),
+                    to: to.clone(),
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::MintNft { id, metadata } => {
+                nft::mint(world_state, id, &self.from, metadata)?;
+                events.push(Event::NftMinted {
+                    id: id.clone(),
+                    owner: self.from.clone(),
+                });
+                Ok(())
+            }
 
-                if balance_recv_new.is_some() && balance_sender_new.is_some() {
-                    world_state
-                        .get_account_by_id_mut(&self.from)
-                        .unwrap()
-                        .tokens = balance_sender_new.unwrap();
-                    world_state.get_account_by_id_mut(to).unwrap().tokens =
-                        balance_recv_new.unwrap();
-                    return Ok(());
+            TransactionData::TransferNft { id, to } => {
+                let to = nft::transfer(world_state, id, &self.from, to)?;
+                events.push(Event::NftTransferred {
+                    id: id.clone(),
+                    from: self.from.clone(),
+                    to,
+                });
+                Ok(())
+            }
+
+            TransactionData::Approve { spender, amount } => {
+                let spender = allowance::approve(world_state, &self.from, spender, *amount)?;
+                events.push(Event::Approval {
+                    owner: self.from.clone(),
+                    spender,
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::TransferFrom { owner, to, amount } => {
+                let (owner, to) =
+                    allowance::transfer_from(world_state, &self.from, owner, to, *amount)?;
+                events.push(Event::Transfer {
+                    from: owner,
+                    to,
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::ChangeStoreValue { key, value } => {
+                // Values are stored into the sender's own account
+                if let Some(account) = world_state.get_account_by_id_mut(&self.from) {
+                    account.store.insert(key.clone(), value.clone());
+                    events.push(Event::StoreValueChanged {
+                        id: self.from.clone(),
+                        key: key.clone(),
+                    });
+                    Ok(())
                 } else {
-                    return Err("Overspent or Arithmetic error (Code: 48239084203)");
+                    Err(BlockchainError::UnknownAccount(self.from.clone()))
                 }
             }
 
-            _ => {
-                // Not implemented transaction type
-                Err("Unknown Transaction type (not implemented) (Code: 487289724389)")
+            TransactionData::SetAccountMetadata { key, value } => {
+                metadata::set(world_state, &self.from, key, value)?;
+                events.push(Event::MetadataChanged {
+                    id: self.from.clone(),
+                    key: key.clone(),
+                });
+                Ok(())
             }
-        };
+
+            TransactionData::CreateVestingAccount { to, tranches } => {
+                let amount = vesting::create(world_state, &self.from, to, tranches)?;
+                events.push(Event::AccountCreated { id: to.clone() });
+                events.push(Event::Transfer {
+                    from: self.from.clone(),
+                    to: to.clone(),
+                    amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::EscrowCreate {
+                to,
+                amount,
+                arbiter,
+            } => {
+                let id = self.calculate_hash().to_string();
+                let record = escrow::create(world_state, &id, &self.from, to, arbiter, *amount)?;
+                events.push(Event::EscrowCreated {
+                    id,
+                    from: record.from,
+                    to: record.to,
+                    amount: record.amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::EscrowRelease { id } => {
+                let record = escrow::settle(world_state, id, &self.from, true)?;
+                events.push(Event::EscrowReleased {
+                    id: id.clone(),
+                    to: record.to,
+                    amount: record.amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::EscrowRefund { id } => {
+                let record = escrow::settle(world_state, id, &self.from, false)?;
+                events.push(Event::EscrowRefunded {
+                    id: id.clone(),
+                    to: record.from,
+                    amount: record.amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::HtlcLock {
+                to,
+                amount,
+                hash_lock,
+                timeout,
+            } => {
+                let id = self.calculate_hash().to_string();
+                let record = htlc::lock(
+                    world_state,
+                    &id,
+                    &self.from,
+                    to,
+                    *amount,
+                    hash_lock,
+                    *timeout,
+                )?;
+                events.push(Event::HtlcLocked {
+                    id,
+                    from: record.from,
+                    to: record.to,
+                    amount: record.amount,
+                    hash_lock: record.hash_lock,
+                });
+                Ok(())
+            }
+
+            TransactionData::HtlcClaim { id, preimage } => {
+                let record = htlc::claim(world_state, id, preimage)?;
+                events.push(Event::HtlcClaimed {
+                    id: id.clone(),
+                    to: record.to,
+                    amount: record.amount,
+                    preimage: preimage.to_lowercase(),
+                });
+                Ok(())
+            }
+
+            TransactionData::HtlcRefund { id } => {
+                let record = htlc::refund(world_state, id)?;
+                events.push(Event::HtlcRefunded {
+                    id: id.clone(),
+                    to: record.from,
+                    amount: record.amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::BridgeOut {
+                target_chain,
+                payload,
+            } => {
+                bridge::send(world_state, &self.from, payload)?;
+                match payload {
+                    BridgePayload::Transfer { to, amount } => {
+                        events.push(Event::BridgeTransferSent {
+                            target_chain: target_chain.clone(),
+                            from: self.from.clone(),
+                            to: to.clone(),
+                            amount: *amount,
+                        });
+                    }
+                }
+                Ok(())
+            }
+
+            TransactionData::BridgeIn { proof } => {
+                let (id, payload) = bridge::receive(world_state, proof)?;
+                match payload {
+                    BridgePayload::Transfer { to, amount } => {
+                        events.push(Event::BridgeTransferReceived {
+                            source_chain: proof.source_chain().to_string(),
+                            id,
+                            to,
+                            amount,
+                        });
+                    }
+                }
+                Ok(())
+            }
+
+            TransactionData::DeleteAccount { beneficiary } => {
+                let beneficiary = &world_state.resolve_account_id(beneficiary);
+                if beneficiary == &self.from {
+                    return Err(BlockchainError::InvalidBeneficiary);
+                }
+                if world_state.get_account_by_id(beneficiary).is_none() {
+                    return Err(BlockchainError::UnknownAccount(beneficiary.clone()));
+                }
+                match world_state.get_account_by_id(&self.from) {
+                    Some(account) if !account.store.is_empty() => {
+                        return Err(BlockchainError::AccountHasStorage(self.from.clone()))
+                    }
+                    Some(account) if account.stake() > 0 => {
+                        return Err(BlockchainError::AccountHasStake(self.from.clone()))
+                    }
+                    Some(_) => vesting::check_unlocked(world_state, &self.from, 0)?,
+                    None => return Err(BlockchainError::UnknownAccount(self.from.clone())),
+                }
+
+                let account = world_state.delete_account(&self.from)?;
+                // The deleting transaction counts as well (unless it is a genesis one)
+                let next_nonce = match is_initial {
+                    true => account.nonce(),
+                    false => account.nonce().saturating_add(1),
+                };
+                tombstone::bury(world_state, &self.from, next_nonce)?;
+                let beneficiary_account = world_state.get_account_by_id_mut(beneficiary).unwrap();
+                beneficiary_account.tokens = beneficiary_account
+                    .tokens
+                    .checked_add(account.tokens)
+                    .ok_or(BlockchainError::ArithmeticOverflow)?;
+                // The other assets and the NFTs go to the beneficiary as well
+                for (asset, amount) in &account.assets {
+                    asset::mint(world_state, asset, beneficiary, *amount)?;
+                }
+                for (id, metadata) in account.nfts {
+                    nft::give(world_state, &id, metadata, beneficiary)?;
+                }
+                events.push(Event::AccountDeleted {
+                    id: self.from.clone(),
+                    beneficiary: beneficiary.clone(),
+                });
+                Ok(())
+            }
+
+            TransactionData::Coinbase { receiver, amount } => {
+                if receiver != &self.from {
+                    return Err(BlockchainError::CoinbaseReceiverMismatch);
+                }
+                if let Some(account) = world_state.get_account_by_id_mut(receiver) {
+                    account.tokens = account
+                        .tokens
+                        .checked_add(*amount)
+                        .ok_or(BlockchainError::ArithmeticOverflow)?;
+                    events.push(Event::TokensCreated {
+                        receiver: receiver.clone(),
+                        amount: *amount,
+                    });
+                    Ok(())
+                } else {
+                    Err(BlockchainError::UnknownAccount(receiver.clone()))
+                }
+            }
+
+            TransactionData::DeployContract { code } => {
+                contracts::validate(code)?;
+                // Derived of the sender's nonce and of the operation's index within its batch
+                let address = contracts::contract_address(&self.from, nonce, index);
+                world_state.create_account(
+                    address.clone(),
+                    AccountType::Contract { code: code.clone() },
+                )?;
+                events.push(Event::AccountCreated { id: address });
+                Ok(())
+            }
+
+            TransactionData::CallContract { address, input } => {
+                let contract = world_state
+                    .get_account_by_id_mut(address)
+                    .ok_or_else(|| BlockchainError::UnknownAccount(address.clone()))?;
+                let code = contract
+                    .contract_code()
+                    .ok_or_else(|| BlockchainError::NotAContract(address.clone()))?
+                    .to_vec();
+                let outcome = contracts::call(&code, &mut contract.store, &self.from, input);
+                contract_gas = outcome.gas_used;
+                match outcome.result {
+                    Ok(()) => events.extend(outcome.events.into_iter().map(|(topic, data)| {
+                        Event::Contract {
+                            address: address.clone(),
+                            topic,
+                            data,
+                        }
+                    })),
+                    Err(err) => status = ReceiptStatus::Failed(err.to_string()),
+                }
+                Ok(())
+            }
+
+            TransactionData::RegisterAlias { alias } => {
+                alias::register(world_state, alias, &self.from)?;
+                events.push(Event::AliasRegistered {
+                    alias: alias.clone(),
+                    address: self.from.clone(),
+                });
+                Ok(())
+            }
+
+            TransactionData::RegisterName { name } => {
+                let expires_at = names::register(world_state, name, &self.from)?;
+                events.push(Event::NameRegistered {
+                    name: name.clone(),
+                    owner: self.from.clone(),
+                    expires_at,
+                });
+                Ok(())
+            }
+
+            TransactionData::Faucet { to, amount } => {
+                let to = world_state.resolve_account_id(to);
+                if !world_state.account_exists(&to) {
+                    if is_reserved_account(&to) {
+                        return Err(BlockchainError::ReservedAccount(to));
+                    }
+                    world_state.create_account(to.clone(), AccountType::User)?;
+                    events.push(Event::AccountCreated { id: to.clone() });
+                }
+                let account = world_state.get_account_by_id_mut(&to).unwrap();
+                account.tokens = account
+                    .tokens
+                    .checked_add(*amount)
+                    .ok_or(BlockchainError::ArithmeticOverflow)?;
+                events.push(Event::TokensCreated {
+                    receiver: to,
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::CreateUserAccounts(accounts) => {
+                if !is_initial {
+                    return Err(BlockchainError::GenesisOnly);
+                }
+                for (id, balance) in accounts {
+                    if is_reserved_account(id) {
+                        return Err(BlockchainError::ReservedAccount(id.clone()));
+                    }
+                    world_state.create_account(id.clone(), AccountType::User)?;
+                    events.push(Event::AccountCreated { id: id.clone() });
+                    if *balance > 0 {
+                        world_state.get_account_by_id_mut(id).unwrap().tokens = *balance;
+                        events.push(Event::TokensCreated {
+                            receiver: id.clone(),
+                            amount: *balance,
+                        });
+                    }
+                }
+                Ok(())
+            }
+
+            TransactionData::CreateMultisigAccount { owners, threshold } => {
+                multisig::validate(owners, *threshold)?;
+                // Derived of the transaction, even if the operation is part of its batch
+                let address = self.derived_address();
+                world_state.create_account(
+                    address.clone(),
+                    AccountType::Multisig {
+                        owners: owners.clone(),
+                        threshold: *threshold,
+                    },
+                )?;
+                events.push(Event::AccountCreated { id: address });
+                Ok(())
+            }
+
+            TransactionData::Stake { amount } => {
+                staking::stake(world_state, &self.from, *amount)?;
+                events.push(Event::Staked {
+                    validator: self.from.clone(),
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::Unstake { amount } => {
+                staking::unstake(world_state, &self.from, *amount)?;
+                events.push(Event::Unstaked {
+                    validator: self.from.clone(),
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionData::ReportMisbehavior { evidence } => {
+                let offender = evidence.verify()?.to_string();
+                let amount = staking::slash(world_state, &offender)?;
+                events.push(Event::Slashed {
+                    validator: offender,
+                    amount,
+                });
+                Ok(())
+            }
+            TransactionData::Batch(operations) => {
+                if operations.is_empty() {
+                    return Err(BlockchainError::InvalidBatch("it is empty".into()));
+                }
+                for (index, operation) in operations.iter().enumerate() {
+                    if let TransactionData::Batch(_) | TransactionData::Coinbase { .. } = operation
+                    {
+                        return Err(BlockchainError::InvalidBatch(
+                            "batches can't contain batches or coinbases".into(),
+                        ));
+                    }
+                    let (gas, operation_status) = self.execute_operation(
+                        operation,
+                        world_state,
+                        nonce,
+                        index,
+                        is_initial,
+                        events,
+                    )?;
+                    // A failed contract call fails the whole batch, which is reverted with its block
+                    if let ReceiptStatus::Failed(reason) = operation_status {
+                        return Err(BlockchainError::ContractFailed(reason));
+                    }
+                    contract_gas = contract_gas.saturating_add(gas);
+                }
+                Ok(())
+            }
+        }?;
+        Ok((contract_gas, status))
     }
 
-    /// Will calculate the hash using Blake2 hasher
-    pub fn calculate_hash(&self) -> Vec<u8> {
+    /// Will deduct the price of the gas from the sender's tokens
+    fn charge_gas<T: WorldState>(
+        &self,
+        world_state: &mut T,
+        gas: u64,
+        is_initial: &bool,
+    ) -> Result<(), BlockchainError> {
+        let price = world_state.gas_price();
+        if *is_initial || gas == 0 || price == 0 {
+            return Ok(());
+        }
+
+        let cost = price
+            .checked_mul(u128::from(gas))
+            .ok_or(BlockchainError::ArithmeticOverflow)?;
+        let sender = world_state
+            .get_account_by_id_mut(&self.from)
+            .ok_or_else(|| BlockchainError::UnknownAccount(self.from.clone()))?;
+        sender.tokens = sender
+            .tokens
+            .checked_sub(cost)
+            .ok_or(BlockchainError::InsufficientBalance)?;
+        Ok(())
+    }
+
+    /// Will calculate the hash using Blake2 hasher, over the canonical encoding of the
+    /// transaction (see `canonical::encode`) without its signatures.<br/>
+    /// Like the ones of blocks, the hashes of version 0 transactions don't commit to their
+    /// version, so that they keep their hashes.
+    pub fn calculate_hash(&self) -> Hash {
         let mut hasher = Blake2b::new();
-        let transaction_as_string = format!(
-            "{:?}",
-            (&self.created_at, &self.record, &self.from, &self.nonce)
+        let fields = (
+            &self.created_at,
+            &self.record,
+            &self.from,
+            &self.nonce,
+            &self.fee,
+            &self.chain_id,
+            &self.valid_after,
+            &self.valid_before,
         );
+        match self.version {
+            0 => hasher.update(canonical::encode(&fields)),
+            version => hasher.update(canonical::encode(&(version, fields))),
+        }
+        Hash::from_slice(&hasher.finalize()).expect("Blake2b produces 64 byte hashes")
+    }
+
+    /// Will return the document that the signatures of the transaction sign (see `SignDoc`)
+    pub fn sign_doc(&self) -> SignDoc<'_> {
+        SignDoc {
+            chain_id: &self.chain_id,
+            nonce: self.nonce,
+            from: &self.from,
+            record: &self.record,
+            fee: self.fee,
+            timestamp: self.created_at,
+            version: self.version,
+            valid_after: self.valid_after,
+            valid_before: self.valid_before,
+        }
+    }
+
+    /// Will return the bytes that the signatures of the transaction sign (the canonical
+    /// encoding of its `sign_doc`), so that it can be signed offline (e.g. by a hardware
+    /// wallet, see `attach_signature`)
+    pub fn sign_bytes(&self) -> Vec<u8> {
+        self.sign_doc().to_bytes()
+    }
+
+    /// Will sign the document of the transaction (see `sign_doc`) using the provided keypair.
+    /// <br/>
+    /// For the signature to be valid, the (bech32) address of the keypair has to be
+    /// the `from` account ID of the transaction.
+    pub fn sign(&mut self, keypair: &Keypair) {
+        let signature = keypair.sign(&self.sign_bytes());
+        self.scheme = keypair.scheme();
+        self.public_key = Some(keypair.public_key());
+        self.signature = Some(hex::encode(signature));
+    }
+
+    /// Will attach a signature of the `sign_bytes` that was produced elsewhere (e.g. by a
+    /// hardware wallet), along with the signer's public key, whose length tells the scheme:
+    /// 32 bytes for Ed25519, 33 (compressed) bytes for secp256k1.<br/>
+    /// The signature is checked first, the transaction is left unchanged if it isn't valid.
+    pub fn attach_signature(
+        &mut self,
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<(), BlockchainError> {
+        let scheme = match public_key.len() {
+            32 => SignatureScheme::Ed25519,
+            33 => SignatureScheme::Secp256k1,
+            _ => return Err(BlockchainError::InvalidSignature),
+        };
+        let mut signed = self.clone();
+        signed.scheme = scheme;
+        signed.public_key = Some(hex::encode(public_key));
+        signed.signature = Some(hex::encode(signature));
+        if !signed.check_signature() {
+            return Err(BlockchainError::InvalidSignature);
+        }
+        *self = signed;
+        Ok(())
+    }
+
+    /// Will add the signature of an owner of the (multisignature) sender.<br/>
+    /// The owners sign one after another, until the threshold of the account is met, all with
+    /// keys of the same scheme.
+    pub fn add_signature(&mut self, keypair: &Keypair) {
+        let signature = keypair.sign(&self.sign_bytes());
+        self.scheme = keypair.scheme();
+        self.cosignatures.push(Cosignature {
+            public_key: keypair.public_key(),
+            signature: hex::encode(signature),
+        });
+    }
+
+    /// Will return the scheme the transaction is signed with
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
 
-        hasher.update(&transaction_as_string);
-        return Vec::from(hasher.finalize().as_ref());
+    /// Will return the addresses of the owners that (validly) signed the transaction
+    pub fn signers(&self) -> HashSet<String> {
+        let doc = self.sign_doc().to_bytes();
+        self.cosignatures
+            .iter()
+            .filter_map(|cosignature| {
+                let public_key = Some(cosignature.public_key.as_str());
+                verify_with(self.scheme, public_key, &cosignature.signature, &doc)
+            })
+            .map(|address| address.to_string())
+            .collect()
     }
 
-    /// Will hash the transaction and check if the signature is valid
+    /// Will check if the signature of the transaction's document (see `sign_doc`) is valid
     /// (i.e., it is created by the owners private key)
-    /// if the message is not signed it will always return false
+    /// if the message is not signed it will always return false.<br/>
+    /// The signatures of a multisignature transaction all have to be valid, whereas
+    /// whether the signers own the sender is checked on execution.
     pub fn check_signature(&self) -> bool {
-        if !(self.is_signed()) {
-            return false;
+        let doc = self.sign_doc().to_bytes();
+        match &self.signature {
+            // The `from` account ID is the address of the signer's public key (which may be
+            // left out of secp256k1 signatures, as it is recovered from them).
+            Some(signature) => {
+                verify_with(self.scheme, self.public_key.as_deref(), signature, &doc)
+                    .is_some_and(|address| address.to_string() == self.from)
+            }
+            None => {
+                !self.cosignatures.is_empty()
+                    && self.cosignatures.iter().all(|cosignature| {
+                        let public_key = Some(cosignature.public_key.as_str());
+                        verify_with(self.scheme, public_key, &cosignature.signature, &doc).is_some()
+                    })
+            }
         }
-
-        //@TODO check signature
-        false
     }
 
     pub fn is_signed(&self) -> bool {
-        self.signature.is_some()
+        self.signature.is_some() || !self.cosignatures.is_empty()
+    }
+}
+
+/// Will check the signatures of the (signed) transactions with a single batched verification,
+/// which is a lot faster than verifying them one by one.<br/>
+/// It only tells whether all of them are valid (like `check_signature` would), so the invalid
+/// ones have to be found by checking them one by one.<br/>
+/// Only Ed25519 signatures can be batched, the secp256k1 ones are checked one by one.
+pub(crate) fn verify_batch(transactions: &[&Transaction]) -> bool {
+    let mut docs = Vec::new();
+    let mut signed = Vec::new();
+    for transaction in transactions {
+        if transaction.scheme == SignatureScheme::Secp256k1 {
+            if !transaction.check_signature() {
+                return false;
+            }
+            continue;
+        }
+        let doc = transaction.sign_doc().to_bytes();
+        let signatures: Vec<(&String, &String)> = match &transaction.signature {
+            Some(signature) => match &transaction.public_key {
+                Some(public_key) => vec![(public_key, signature)],
+                None => return false,
+            },
+            None if transaction.cosignatures.is_empty() => return false,
+            None => transaction
+                .cosignatures
+                .iter()
+                .map(|cosignature| (&cosignature.public_key, &cosignature.signature))
+                .collect(),
+        };
+
+        for (public_key, signature) in signatures {
+            let parsed = decode_fixed::<32>(public_key).and_then(|public_key| {
+                let verifying_key = VerifyingKey::from_bytes(&public_key).ok()?;
+                let signature = Signature::from_bytes(&decode_fixed::<64>(signature)?);
+                Some((public_key, verifying_key, signature))
+            });
+            // Weak keys are rejected by the (strict) single verification, but not by the batch
+            let (public_key, verifying_key, signature) = match parsed {
+                Some(parsed) if !parsed.1.is_weak() => parsed,
+                _ => return false,
+            };
+            if transaction.signature.is_some()
+                && Address::from_public_key(&public_key).to_string() != transaction.from
+            {
+                return false;
+            }
+            signed.push((docs.len(), verifying_key, signature));
+        }
+        docs.push(doc);
+    }
+
+    let messages: Vec<&[u8]> = signed
+        .iter()
+        .map(|(index, _, _)| &docs[*index][..])
+        .collect();
+    let signatures: Vec<Signature> = signed.iter().map(|(_, _, signature)| *signature).collect();
+    let keys: Vec<VerifyingKey> = signed.iter().map(|(_, key, _)| *key).collect();
+    ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok()
+}
+
+/// Will check the (hex encoded) Ed25519 signature of the message, returning the signer's
+/// address if it is valid.
+pub(crate) fn verify(public_key: &str, signature: &str, message: &[u8]) -> Option<Address> {
+    let public_key = decode_fixed::<32>(public_key)?;
+    let verifying_key = VerifyingKey::from_bytes(&public_key).ok()?;
+    let signature = Signature::from_bytes(&decode_fixed::<64>(signature)?);
+    verifying_key.verify_strict(message, &signature).ok()?;
+    Some(Address::from_public_key(&public_key))
+}
+
+/// Will check the (hex encoded) signature of the message in the scheme, returning the signer's
+/// address if it is valid.<br/>
+/// The public key of a secp256k1 signature is recovered from it, so it is optional (but has to
+/// match the recovered one if set), whereas it is required for Ed25519.
+pub(crate) fn verify_with(
+    scheme: SignatureScheme,
+    public_key: Option<&str>,
+    signature: &str,
+    message: &[u8],
+) -> Option<Address> {
+    match scheme {
+        SignatureScheme::Ed25519 => verify(public_key?, signature, message),
+        SignatureScheme::Secp256k1 => {
+            let bytes = decode_fixed::<65>(signature)?;
+            let signature = ecdsa::Signature::from_slice(&bytes[..64]).ok()?;
+            // The (malleable) high S form is rejected, like Ed25519 signatures are strictly
+            // verified
+            if signature.normalize_s().is_some() {
+                return None;
+            }
+            let recovery_id = ecdsa::RecoveryId::from_byte(bytes[64])?;
+            let verifying_key =
+                ecdsa::VerifyingKey::recover_from_msg(message, &signature, recovery_id).ok()?;
+            let recovered = verifying_key.to_sec1_bytes();
+            match public_key {
+                Some(public_key) if hex::decode(public_key).ok()?[..] != recovered[..] => None,
+                _ => Some(Address::from_public_key(&recovered)),
+            }
+        }
+    }
+}
+
+/// Will decode a hex string into a fixed size array of bytes.
+fn decode_fixed<const N: usize>(hex_str: &str) -> Option<[u8; N]> {
+    let mut bytes = [0u8; N];
+    hex::decode_to_slice(hex_str, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::verify_batch;
+    use std::convert::TryInto;
+
+    use crate::{
+        contract_address, Blockchain, BlockchainError, Event, KeyFile, Keypair, ReceiptStatus,
+        SignatureScheme, Transaction, TransactionData, ValidationPolicy, WorldState,
+        ALIAS_REGISTRY, ASSET_REGISTRY,
+    };
+
+    fn transfer_from(from: String) -> Transaction {
+        Transaction::new(
+            from,
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 1,
+                asset: None,
+            },
+            0,
+        )
+    }
+
+    #[test]
+    fn unsigned_transaction_has_no_valid_signature() {
+        let txn = transfer_from("alice".into());
+        assert!(!txn.is_signed());
+        assert!(!txn.check_signature());
+    }
+
+    #[test]
+    fn signed_transaction_is_valid() {
+        let keypair = Keypair::generate();
+        let mut txn = transfer_from(keypair.address().to_string());
+        txn.sign(&keypair);
+
+        assert!(txn.is_signed());
+        assert!(txn.check_signature());
+    }
+
+    #[test]
+    fn signature_by_other_key_is_invalid() {
+        let alice = Keypair::generate();
+        let mallory = Keypair::generate();
+        let mut txn = transfer_from(alice.address().to_string());
+        txn.sign(&mallory);
+
+        assert!(!txn.check_signature());
+    }
+
+    #[test]
+    fn tampering_invalidates_signature() {
+        let keypair = Keypair::generate();
+        let mut txn = transfer_from(keypair.address().to_string());
+        txn.sign(&keypair);
+
+        txn.record = TransactionData::TransferTokens {
+            to: "bob".into(),
+            amount: 100,
+            asset: None,
+        };
+
+        assert!(!txn.check_signature());
+    }
+
+    #[test]
+    fn secp256k1_signatures_are_recovered() {
+        // The well-known first account of the Ethereum development tooling
+        let secret =
+            hex::decode("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80")
+                .unwrap();
+        let keypair = Keypair::from_secp256k1_secret_bytes(secret[..].try_into().unwrap()).unwrap();
+        assert_eq!(SignatureScheme::Secp256k1, keypair.scheme());
+        assert_eq!(66, keypair.public_key().len());
+        assert!(Keypair::from_secp256k1_secret_bytes(&[0u8; 32]).is_err());
+
+        let mut txn = transfer_from(keypair.address().to_string());
+        txn.sign(&keypair);
+        assert_eq!(SignatureScheme::Secp256k1, txn.scheme());
+        assert_eq!(130, txn.signature.as_ref().unwrap().len());
+        assert!(txn.check_signature());
+        let ed25519 = {
+            let keypair = Keypair::generate();
+            let mut txn = transfer_from(keypair.address().to_string());
+            txn.sign(&keypair);
+            txn
+        };
+        assert!(verify_batch(&[&txn, &ed25519]));
+
+        // The public key is recovered from the signature, but has to match if it is set
+        let mut recovered = txn.clone();
+        recovered.public_key = None;
+        assert!(recovered.check_signature());
+        let mut mismatched = txn.clone();
+        mismatched.public_key = Some(Keypair::generate_secp256k1().public_key());
+        assert!(!mismatched.check_signature());
+        assert!(!verify_batch(&[&mismatched, &ed25519]));
+
+        // Neither is the signature valid in the other scheme, nor of another transaction
+        let mut ed25519_scheme = txn.clone();
+        ed25519_scheme.scheme = SignatureScheme::Ed25519;
+        assert!(!ed25519_scheme.check_signature());
+        let mut tampered = txn.clone();
+        tampered.fee = 1;
+        assert!(!tampered.check_signature());
+
+        // The secp256k1 keys are kept in key files too
+        let key_file = KeyFile::encrypt(&keypair, "secret", 4).unwrap();
+        let restored = key_file.decrypt("secret").unwrap();
+        assert_eq!(keypair.address(), restored.address());
+        assert_eq!(SignatureScheme::Secp256k1, restored.scheme());
+    }
+
+    #[test]
+    fn offline_signatures_are_attached() {
+        for keypair in [Keypair::generate(), Keypair::generate_secp256k1()] {
+            let mut txn = transfer_from(keypair.address().to_string());
+            // The signer only sees the bytes to sign (e.g. on an air-gapped device)
+            let signature = keypair.sign(&txn.sign_bytes());
+            let public_key = hex::decode(keypair.public_key()).unwrap();
+
+            let mut other = transfer_from(keypair.address().to_string()).with_fee(1);
+            assert_eq!(
+                Err(BlockchainError::InvalidSignature),
+                other.attach_signature(&signature, &public_key)
+            );
+            assert!(!other.is_signed());
+            assert_eq!(
+                Err(BlockchainError::InvalidSignature),
+                txn.attach_signature(&signature, &public_key[1..])
+            );
+
+            txn.attach_signature(&signature, &public_key).unwrap();
+            assert_eq!(keypair.scheme(), txn.scheme());
+            assert!(txn.check_signature());
+        }
+    }
+
+    /// Creates a world state with funded accounts for alice and bob
+    fn world_state() -> Blockchain {
+        let mut bc = Blockchain::new();
+        for user in &["alice", "bob"] {
+            Transaction::new(
+                (*user).into(),
+                TransactionData::CreateUserAccount((*user).into()),
+                0,
+            )
+            .execute(&mut bc, &true)
+            .unwrap();
+            Transaction::new(
+                (*user).into(),
+                TransactionData::CreateTokens {
+                    receiver: (*user).into(),
+                    amount: 100,
+                    asset: None,
+                },
+                0,
+            )
+            .execute(&mut bc, &true)
+            .unwrap();
+        }
+        bc
+    }
+
+    fn delete_account(beneficiary: &str, nonce: u128) -> Transaction {
+        Transaction::new(
+            "alice".into(),
+            TransactionData::DeleteAccount {
+                beneficiary: beneficiary.into(),
+            },
+            nonce,
+        )
+    }
+
+    #[test]
+    fn delete_account_moves_tokens_to_beneficiary() {
+        let mut bc = world_state();
+        delete_account("bob", 0).execute(&mut bc, &false).unwrap();
+
+        assert!(!bc.accounts.contains_key("alice"));
+        assert_eq!(200, bc.accounts["bob"].tokens);
+    }
+
+    #[test]
+    fn delete_account_is_validated() {
+        let mut bc = world_state();
+        assert!(delete_account("alice", 0).execute(&mut bc, &false).is_err());
+        assert!(delete_account("carol", 0).execute(&mut bc, &false).is_err());
+
+        bc.accounts
+            .get_mut("alice")
+            .unwrap()
+            .store
+            .insert("key".into(), "value".into());
+        assert!(delete_account("bob", 0).execute(&mut bc, &false).is_err());
+        assert!(bc.accounts.contains_key("alice"));
+    }
+
+    #[test]
+    fn change_store_value_writes_sender_store() {
+        let mut bc = world_state();
+        let set = |key: &str, value: &str, nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::ChangeStoreValue {
+                    key: key.into(),
+                    value: value.into(),
+                },
+                nonce,
+            )
+        };
+
+        set("color", "red", 0).execute(&mut bc, &false).unwrap();
+        assert_eq!(
+            Some(&"red".to_string()),
+            bc.get_store_value("alice", "color")
+        );
+
+        set("color", "blue", 1).execute(&mut bc, &false).unwrap();
+        assert_eq!(
+            Some(&"blue".to_string()),
+            bc.get_store_value("alice", "color")
+        );
+        assert_eq!(None, bc.get_store_value("bob", "color"));
+        assert_eq!(None, bc.get_store_value("carol", "color"));
+    }
+
+    #[test]
+    fn transfers_to_oneself_keep_the_balance() {
+        let mut bc = world_state();
+        Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "alice".into(),
+                amount: 10,
+                asset: None,
+            },
+            0,
+        )
+        .execute(&mut bc, &false)
+        .unwrap();
+        assert_eq!(Some(100), bc.get_balance("alice"));
+    }
+
+    #[test]
+    fn aliases_resolve_to_addresses() {
+        let mut bc = world_state();
+        let carol = Keypair::generate().address().to_string();
+        Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount(carol.clone()),
+            0,
+        )
+        .execute(&mut bc, &false)
+        .unwrap();
+
+        let register = |from: &str, alias: &str, nonce| {
+            Transaction::new(
+                from.into(),
+                TransactionData::RegisterAlias {
+                    alias: alias.into(),
+                },
+                nonce,
+            )
+        };
+        register(&carol, "carol", 0)
+            .execute(&mut bc, &false)
+            .unwrap();
+        assert_eq!(Some(carol.parse().unwrap()), bc.resolve_alias("carol"));
+        assert_eq!(
+            Err(BlockchainError::AliasTaken("carol".into())),
+            register(&carol, "carol", 1).execute(&mut bc, &false)
+        );
+        // Only addresses can register aliases
+        assert!(register("bob", "bobby", 0)
+            .execute(&mut bc, &false)
+            .is_err());
+
+        Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "carol".into(),
+                amount: 10,
+                asset: None,
+            },
+            1,
+        )
+        .execute(&mut bc, &false)
+        .unwrap();
+        assert_eq!(Some(10), bc.get_balance(&carol));
+
+        // The registry can't be changed directly
+        let tamper = Transaction::new(
+            ALIAS_REGISTRY.into(),
+            TransactionData::ChangeStoreValue {
+                key: "carol".into(),
+                value: "bob".into(),
+            },
+            0,
+        );
+        assert!(tamper.execute(&mut bc, &false).is_err());
+        assert!(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount(ALIAS_REGISTRY.into()),
+            2
+        )
+        .execute(&mut bc, &false)
+        .is_err());
+    }
+
+    #[test]
+    fn assets_are_created_and_transferred() {
+        let mut bc = world_state();
+        let gold = || Some("GOLD".to_string());
+        let balances = |bc: &Blockchain, id: &str| {
+            (
+                bc.get_balance(id).unwrap(),
+                bc.get_asset_balance(id, "GOLD"),
+            )
+        };
+        let create = TransactionData::CreateAsset {
+            asset: "GOLD".into(),
+            supply: 100,
+        };
+        Transaction::new("alice".into(), create.clone(), 0)
+            .execute(&mut bc, &false)
+            .unwrap();
+        assert_eq!(
+            Err(BlockchainError::AssetExists("GOLD".into())),
+            Transaction::new("bob".into(), create, 0).execute(&mut bc, &false)
+        );
+        let (alice_tokens, _) = balances(&bc, "alice");
+
+        let transfer = Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 40,
+                asset: gold(),
+            },
+            1,
+        );
+        let receipt = transfer.execute(&mut bc, &false).unwrap();
+        assert_eq!(
+            vec![Event::AssetTransfer {
+                asset: "GOLD".into(),
+                from: "alice".into(),
+                to: "bob".into(),
+                amount: 40
+            }],
+            receipt.events
+        );
+        assert_eq!((alice_tokens, Some(60)), balances(&bc, "alice"));
+        assert_eq!(Some(40), balances(&bc, "bob").1);
+
+        // Only the genesis block creates tokens, the assets of deleted accounts are inherited
+        let mint = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateTokens {
+                receiver: "alice".into(),
+                amount: 1,
+                asset: gold(),
+            },
+            2,
+        );
+        assert_eq!(
+            Err(BlockchainError::GenesisOnly),
+            mint.execute(&mut bc, &false)
+        );
+        mint.execute(&mut bc, &true).unwrap();
+        assert_eq!(Some(61), balances(&bc, "alice").1);
+        Transaction::new(
+            "bob".into(),
+            TransactionData::DeleteAccount {
+                beneficiary: "alice".into(),
+            },
+            0,
+        )
+        .execute(&mut bc, &false)
+        .unwrap();
+        assert_eq!(Some(101), balances(&bc, "alice").1);
+
+        // The registry can't be changed directly
+        assert!(Transaction::new(
+            ASSET_REGISTRY.into(),
+            TransactionData::ChangeStoreValue {
+                key: "GOLD".into(),
+                value: "bob".into(),
+            },
+            0,
+        )
+        .execute(&mut bc, &false)
+        .is_err());
+    }
+
+    #[test]
+    fn nfts_are_minted_and_inherited() {
+        let mut bc = world_state();
+        let mint = |nonce| {
+            Transaction::new(
+                "alice".into(),
+                TransactionData::MintNft {
+                    id: "cat-1".into(),
+                    metadata: "ipfs://cat".into(),
+                },
+                nonce,
+            )
+        };
+        let receipt = mint(0).execute(&mut bc, &false).unwrap();
+        assert_eq!(
+            vec![Event::NftMinted {
+                id: "cat-1".into(),
+                owner: "alice".into()
+            }],
+            receipt.events
+        );
+        assert_eq!(
+            Err(BlockchainError::NftExists("cat-1".into())),
+            mint(1).execute(&mut bc, &false)
+        );
+
+        delete_account("bob", 1).execute(&mut bc, &false).unwrap();
+        assert_eq!(Some(&"bob".to_string()), bc.get_nft_owner("cat-1"));
+        assert!(bc.accounts["bob"].nfts().contains_key("cat-1"));
+    }
+
+    #[test]
+    fn multisig_transactions_need_owner_signatures() {
+        let mut bc = world_state();
+        let owners: Vec<Keypair> = (0..3).map(|_| Keypair::generate()).collect();
+        let create = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateMultisigAccount {
+                owners: owners.iter().map(|o| o.address().to_string()).collect(),
+                threshold: 2,
+            },
+            0,
+        );
+        create.execute(&mut bc, &false).unwrap();
+        let multisig = create.multisig_address().unwrap();
+        bc.get_account_by_id_mut(&multisig).unwrap().tokens = 50;
+
+        let mut transfer = Transaction::new(
+            multisig.clone(),
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 20,
+                asset: None,
+            },
+            0,
+        );
+        transfer.add_signature(&owners[0]);
+        assert!(transfer.check_signature());
+        assert_eq!(
+            Err(BlockchainError::MultisigThresholdNotMet {
+                threshold: 2,
+                approvals: 1
+            }),
+            transfer.execute(&mut bc, &false)
+        );
+
+        // Signatures of others (or the same owner twice) don't count
+        transfer.add_signature(&Keypair::generate());
+        transfer.add_signature(&owners[0]);
+        assert!(transfer.execute(&mut bc, &false).is_err());
+
+        transfer.add_signature(&owners[2]);
+        transfer.execute(&mut bc, &false).unwrap();
+        assert_eq!(Some(30), bc.get_balance(&multisig));
+        assert_eq!(Some(120), bc.get_balance("bob"));
+    }
+
+    #[test]
+    fn receipts_report_events_and_failures() {
+        let mut bc = world_state();
+        let transfer = Transaction::new(
+            "alice".into(),
+            TransactionData::TransferTokens {
+                to: "bob".into(),
+                amount: 5,
+                asset: None,
+            },
+            0,
+        );
+        let receipt = transfer.execute(&mut bc, &false).unwrap();
+        assert_eq!(transfer.calculate_hash(), receipt.transaction_hash);
+        assert_eq!(
+            vec![Event::Transfer {
+                from: "alice".into(),
+                to: "bob".into(),
+                amount: 5
+            }],
+            receipt.events
+        );
+
+        // A trapping contract doesn't fail the transaction, but its receipt
+        let code = wat::parse_str(
+            r#"(module (memory (export "memory") 1) (func (export "call") unreachable))"#,
+        )
+        .unwrap();
+        let deploy = Transaction::new("alice".into(), TransactionData::DeployContract { code }, 1);
+        deploy.execute(&mut bc, &false).unwrap();
+        let call = Transaction::new(
+            "bob".into(),
+            TransactionData::CallContract {
+                address: deploy.contract_address().unwrap(),
+                input: Vec::new(),
+            },
+            0,
+        );
+        let nonce = bc.get_nonce("bob").unwrap();
+        let receipt = call.execute(&mut bc, &false).unwrap();
+        assert!(!receipt.is_success());
+        assert!(matches!(receipt.status, ReceiptStatus::Failed(_)));
+        assert!(receipt.events.is_empty());
+        assert_eq!(Some(nonce + 1), bc.get_nonce("bob"));
+    }
+
+    #[test]
+    fn deploy_and_call_contract() {
+        // Stores the input of the call under the key "last"
+        let code = wat::parse_str(
+            r#"(module
+                (import "env" "input_len" (func $input_len (result i32)))
+                (import "env" "input_read" (func $input_read (param i32)))
+                (import "env" "storage_write" (func $storage_write (param i32 i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "last")
+                (func (export "call")
+                    (call $input_read (i32.const 16))
+                    (call $storage_write
+                        (i32.const 0) (i32.const 4)
+                        (i32.const 16) (call $input_len))))"#,
+        )
+        .unwrap();
+
+        let mut bc = world_state();
+        let deploy = TransactionData::DeployContract { code };
+        let deploy = Transaction::new("alice".into(), deploy, 0);
+        deploy.execute(&mut bc, &false).unwrap();
+        let address = deploy.contract_address().unwrap();
+        assert_eq!(contract_address("alice", 0, 0), address);
+        assert!(bc
+            .get_account_by_id(&address)
+            .unwrap()
+            .contract_code()
+            .is_some());
+
+        let call = |address: &str, input: &str, nonce| {
+            Transaction::new(
+                "bob".into(),
+                TransactionData::CallContract {
+                    address: address.into(),
+                    input: input.as_bytes().to_vec(),
+                },
+                nonce,
+            )
+        };
+        call(&address, "hi", 0).execute(&mut bc, &false).unwrap();
+        assert_eq!(
+            Some(&"hi".to_string()),
+            bc.get_store_value(&address, "last")
+        );
+
+        assert_eq!(
+            Err(BlockchainError::NotAContract("alice".into())),
+            call("alice", "hi", 1).execute(&mut bc, &false)
+        );
+
+        // The deployment can't be replayed, its nonce is used
+        assert_eq!(
+            Err(BlockchainError::InvalidNonce {
+                expected: 1,
+                found: 0
+            }),
+            deploy.execute(&mut bc, &false).map(|_| ())
+        );
+
+        // ... so that the next deployment (e.g. through a batch) gets another address, of the
+        // next nonce
+        let batch = TransactionData::Batch(vec![deploy.record.clone()]);
+        let batch = Transaction::new("alice".into(), batch, 1);
+        batch.execute(&mut bc, &false).unwrap();
+        let next = batch.contract_address().unwrap();
+        assert_eq!(contract_address("alice", 1, 0), next);
+        assert_ne!(address, next);
+        assert!(bc
+            .get_account_by_id(&next)
+            .unwrap()
+            .contract_code()
+            .is_some());
+
+        // Deploying to an address taken already collides with its account
+        let taken = contract_address("alice", 2, 0);
+        Transaction::new(
+            "bob".into(),
+            TransactionData::CreateUserAccount(taken.clone()),
+            1,
+        )
+        .execute(&mut bc, &false)
+        .unwrap();
+        let deploy = Transaction::new("alice".into(), deploy.record.clone(), 2);
+        assert_eq!(
+            Err(BlockchainError::AccountExists(taken)),
+            deploy.execute(&mut bc, &false).map(|_| ())
+        );
+
+        let invalid = Transaction::new(
+            "alice".into(),
+            TransactionData::DeployContract {
+                code: vec![1, 2, 3],
+            },
+            2,
+        );
+        assert!(matches!(
+            invalid.execute(&mut bc, &false),
+            Err(BlockchainError::InvalidContract(_))
+        ));
+    }
+
+    #[test]
+    fn signatures_are_verified_in_batches() {
+        let policy = ValidationPolicy {
+            require_signatures: true,
+            ..ValidationPolicy::default()
+        };
+        let mut transactions: Vec<Transaction> = (0..8)
+            .map(|_| {
+                let keypair = Keypair::generate();
+                let mut transaction = transfer_from(keypair.address().to_string());
+                transaction.sign(&keypair);
+                transaction
+            })
+            .collect();
+        let all: Vec<&Transaction> = transactions.iter().collect();
+        assert!(verify_batch(&all));
+        assert_eq!(
+            None,
+            policy.first_rejected_transaction(&transactions, false)
+        );
+
+        // A tampered transaction fails the batch, so the transactions are checked one by one
+        transactions[5].fee = 1;
+        transactions[6].signature = None;
+        let all: Vec<&Transaction> = transactions.iter().collect();
+        assert!(!verify_batch(&all));
+        assert_eq!(
+            Some(5),
+            policy.first_rejected_transaction(&transactions, false)
+        );
+        transactions.remove(5);
+        assert_eq!(
+            Some(5),
+            policy.first_rejected_transaction(&transactions, false)
+        );
+        transactions.remove(5);
+        assert_eq!(
+            None,
+            policy.first_rejected_transaction(&transactions, false)
+        );
+    }
+
+    #[test]
+    fn transaction_hashes_are_locked() {
+        let transaction = transfer_from("alice".into())
+            .with_fee(1)
+            .with_created_at(1_600_000_000_000);
+        assert_eq!(
+            "01a4cc5cbee23bd21437dfb4ba02ef1b6a31ee7ebf4ffc5b3f0658b70e912dd9\
+             d48ae52152a8726b635536b5c0f9d4ab4389bae494090b50eb6dc732b27ebcf4",
+            transaction.calculate_hash().to_hex()
+        );
     }
 }
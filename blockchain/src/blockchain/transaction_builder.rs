@@ -0,0 +1,177 @@
+use crate::{Blockchain, BlockchainError, Keypair, TimeLock, Transaction, TransactionData};
+
+/// Builds a transaction out of its operation and its sender's keypair, filling in the
+/// rest (see `build`), e.g.
+/// `Transaction::builder().from(&key).transfer("bob", 10).fee(1).build_signed(&chain)`.
+#[derive(Clone, Debug, Default)]
+pub struct TransactionBuilder {
+    keypair: Option<Keypair>,
+    record: Option<TransactionData>,
+    nonce: Option<u128>,
+    fee: Option<u128>,
+    valid_after: Option<TimeLock>,
+    valid_before: Option<TimeLock>,
+}
+
+impl Transaction {
+    /// Will return a builder of a transaction (see `TransactionBuilder`)
+    pub fn builder() -> TransactionBuilder {
+        TransactionBuilder::default()
+    }
+}
+
+impl TransactionBuilder {
+    /// Sets the sender: the account whose address is the one of the keypair
+    pub fn from(mut self, keypair: &Keypair) -> Self {
+        self.keypair = Some(keypair.clone());
+        self
+    }
+
+    /// Sets the operation of the transaction
+    pub fn operation(mut self, record: TransactionData) -> Self {
+        self.record = Some(record);
+        self
+    }
+
+    /// Sets the operation to transfer the amount of native tokens to the receiver
+    /// (which may be an alias or a name)
+    pub fn transfer<S: Into<String>>(self, to: S, amount: u128) -> Self {
+        self.operation(TransactionData::TransferTokens {
+            to: to.into(),
+            amount,
+            asset: None,
+        })
+    }
+
+    /// Sets the operation to transfer the amount of the asset to the receiver
+    pub fn transfer_asset<S: Into<String>, A: Into<String>>(
+        self,
+        to: S,
+        amount: u128,
+        asset: A,
+    ) -> Self {
+        self.operation(TransactionData::TransferTokens {
+            to: to.into(),
+            amount,
+            asset: Some(asset.into()),
+        })
+    }
+
+    /// Sets the nonce, instead of the next one of the sender
+    pub fn nonce(mut self, nonce: u128) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Sets the fee, instead of the lowest one the mempool accepts
+    pub fn fee(mut self, fee: u128) -> Self {
+        self.fee = Some(fee);
+        self
+    }
+
+    /// Sets the lock that the chain has to reach before the transaction may be included
+    pub fn valid_after(mut self, lock: TimeLock) -> Self {
+        self.valid_after = Some(lock);
+        self
+    }
+
+    /// Sets the lock after which the transaction may not be included anymore
+    pub fn valid_before(mut self, lock: TimeLock) -> Self {
+        self.valid_before = Some(lock);
+        self
+    }
+
+    /// Will build the (unsigned) transaction for the chain: it is meant for the chain's
+    /// network, created for the version of the rules of its next block, and (unless they
+    /// are set) has the next nonce of the sender (see `Blockchain::next_nonce`) and the
+    /// lowest fee the mempool accepts (see `Blockchain::minimum_fee`).<br/>
+    /// Fails if the sender or the operation is missing.
+    pub fn build(&self, chain: &Blockchain) -> Result<Transaction, BlockchainError> {
+        let keypair = self
+            .keypair
+            .as_ref()
+            .ok_or(BlockchainError::IncompleteTransaction("sender"))?;
+        let record = self
+            .record
+            .clone()
+            .ok_or(BlockchainError::IncompleteTransaction("operation"))?;
+        let from = keypair.address().to_string();
+
+        let nonce = self.nonce.unwrap_or_else(|| chain.next_nonce(&from));
+        let mut transaction = Transaction::new(from, record, nonce)
+            .with_chain_id(chain.chain_id().into())
+            .with_version(chain.policy().version_at(chain.len()))
+            .with_fee(self.fee.unwrap_or_else(|| chain.minimum_fee()));
+        if let Some(lock) = self.valid_after {
+            transaction = transaction.with_valid_after(lock);
+        }
+        if let Some(lock) = self.valid_before {
+            transaction = transaction.with_valid_before(lock);
+        }
+        Ok(transaction)
+    }
+
+    /// Will build the transaction like `build` does and sign it with the sender's keypair
+    pub fn build_signed(&self, chain: &Blockchain) -> Result<Transaction, BlockchainError> {
+        let mut transaction = self.build(chain)?;
+        if let Some(keypair) = &self.keypair {
+            transaction.sign(keypair);
+        }
+        Ok(transaction)
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use crate::test_utils::{account_id, keypair, ChainBuilder, TEST_CHAIN_ID};
+    use crate::{BlockchainError, MempoolPolicy, Transaction, WorldState};
+
+    #[test]
+    fn transactions_are_filled_in_and_signed() {
+        let mut chain = ChainBuilder::new()
+            .with_account("alice", 100)
+            .with_account("bob", 0)
+            .build();
+        let alice = keypair("alice");
+        let nonce = chain.get_nonce(&account_id("alice")).unwrap();
+
+        let transaction = Transaction::builder()
+            .from(&alice)
+            .transfer(account_id("bob"), 10)
+            .fee(1)
+            .build_signed(&chain)
+            .unwrap();
+        assert_eq!(account_id("alice"), transaction.sender());
+        assert_eq!(TEST_CHAIN_ID, transaction.chain_id());
+        assert_eq!(nonce, transaction.nonce());
+        assert_eq!(1, transaction.fee());
+        assert!(transaction.check_signature());
+        chain.submit_transaction(transaction).unwrap();
+        chain.produce_block(1).unwrap();
+        assert_eq!(Some(10), chain.get_balance(&account_id("bob")));
+
+        // The nonce follows the pending transactions, the fee outbids the full mempool
+        let builder = Transaction::builder()
+            .from(&alice)
+            .transfer(account_id("bob"), 20);
+        let transaction = builder.build_signed(&chain).unwrap();
+        assert_eq!((nonce + 1, 0), (transaction.nonce(), transaction.fee()));
+        chain.submit_transaction(transaction).unwrap();
+        chain.set_mempool_policy(MempoolPolicy {
+            max_transactions: 1,
+            ..MempoolPolicy::default()
+        });
+        let transaction = builder.build_signed(&chain).unwrap();
+        assert_eq!((nonce + 2, 1), (transaction.nonce(), transaction.fee()));
+
+        assert!(matches!(
+            Transaction::builder().from(&alice).build(&chain),
+            Err(BlockchainError::IncompleteTransaction("operation"))
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{AccountType, BlockchainError, TimeLock};
+
+use super::world_state::{is_reserved_account, WorldState};
+
+/// The maximum amount of tranches of a vesting schedule.
+pub const MAX_VESTING_TRANCHES: usize = 64;
+
+/// A tranche of the vesting schedule of an account: an amount of its tokens that can't be
+/// transferred until the chain reaches the lock (see `TransactionData::CreateVestingAccount`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VestingTranche {
+    pub amount: u128,
+    pub unlocks_at: TimeLock,
+}
+
+/// Will create the account of the receiver, funded by the sender with the tokens of the
+/// tranches, which stay locked until their locks are reached.<br/>
+/// The receiver may not exist yet, whilst the schedule has 1 to 64 tranches.
+pub(crate) fn create<T: WorldState>(
+    world_state: &mut T,
+    from: &str,
+    to: &str,
+    tranches: &[VestingTranche],
+) -> Result<u128, BlockchainError> {
+    if tranches.is_empty() || tranches.len() > MAX_VESTING_TRANCHES {
+        return Err(BlockchainError::InvalidVesting(format!(
+            "1 to {} tranches are required",
+            MAX_VESTING_TRANCHES
+        )));
+    }
+    let amount = tranches.iter().try_fold(0u128, |sum, tranche| {
+        sum.checked_add(tranche.amount)
+            .ok_or(BlockchainError::ArithmeticOverflow)
+    })?;
+    if is_reserved_account(to) {
+        return Err(BlockchainError::ReservedAccount(to.to_string()));
+    }
+    if world_state.account_exists(to) {
+        return Err(BlockchainError::AccountExists(to.to_string()));
+    }
+
+    let sender = world_state
+        .get_account_by_id(from)
+        .ok_or_else(|| BlockchainError::UnknownAccount(from.to_string()))?;
+    if sender.frozen {
+        return Err(BlockchainError::AccountFrozen(from.to_string()));
+    }
+    let remaining = sender
+        .tokens
+        .checked_sub(amount)
+        .ok_or(BlockchainError::InsufficientBalance)?;
+    check_unlocked(world_state, from, remaining)?;
+
+    world_state.get_account_by_id_mut(from).unwrap().tokens = remaining;
+    world_state.create_account(to.to_string(), AccountType::User)?;
+    let receiver = world_state.get_account_by_id_mut(to).unwrap();
+    receiver.tokens = amount;
+    receiver.vesting = tranches.to_vec();
+    Ok(amount)
+}
+
+/// Will check that the tokens the account is left with cover the ones that are still locked
+/// at the block being executed.<br/>
+/// The timestamp locks are compared to the timestamp of the previous block (like the ones of
+/// the validity windows of transactions), so that a producer can't unlock tokens early by
+/// stamping its block.
+pub(crate) fn check_unlocked<T: WorldState>(
+    world_state: &T,
+    id: &str,
+    remaining: u128,
+) -> Result<(), BlockchainError> {
+    let locked = world_state.get_account_by_id(id).map_or(0, |account| {
+        account.locked_tokens(world_state.height(), world_state.timestamp())
+    });
+    if remaining < locked {
+        return Err(BlockchainError::TokensLocked { locked });
+    }
+    Ok(())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::VestingTranche;
+    use crate::test_utils::{account_id, ChainBuilder};
+    use crate::{BlockchainError, TimeLock, Transaction, TransactionData, WorldState};
+
+    #[test]
+    fn vested_tokens_unlock_over_time() {
+        let (alice, bob) = (account_id("alice"), account_id("bob"));
+        let vesting = TransactionData::CreateVestingAccount {
+            to: bob.clone(),
+            tranches: vec![
+                VestingTranche {
+                    amount: 100,
+                    unlocks_at: TimeLock::Height(3),
+                },
+                VestingTranche {
+                    amount: 200,
+                    unlocks_at: TimeLock::Height(5),
+                },
+            ],
+        };
+        let builder = ChainBuilder::new()
+            .with_account("alice", 1_000)
+            .transaction("alice", vesting.clone());
+        let mut bc = builder.clone().build();
+        assert_eq!(Some(700), bc.get_balance(&alice));
+        assert_eq!(Some(300), bc.get_balance(&bob));
+        let account = bc.get_account_by_id(&bob).unwrap();
+        assert_eq!(2, account.vesting().len());
+        assert_eq!(300, account.locked_tokens(2, 0));
+        assert_eq!(200, account.locked_tokens(3, 0));
+        assert_eq!(0, account.locked_tokens(5, 0));
+
+        // The locked tokens can neither be transferred nor taken along by deleting the account
+        let transfer = |amount, nonce| {
+            let data = TransactionData::TransferTokens {
+                to: alice.clone(),
+                amount,
+                asset: None,
+            };
+            Transaction::new(bob.clone(), data, nonce)
+        };
+        assert_eq!(
+            Err(BlockchainError::TokensLocked { locked: 300 }),
+            transfer(1, 0).execute(&mut bc, &false).map(|_| ())
+        );
+        let beneficiary = alice.clone();
+        let delete = Transaction::new(
+            bob.clone(),
+            TransactionData::DeleteAccount { beneficiary },
+            0,
+        );
+        assert_eq!(
+            Err(BlockchainError::TokensLocked { locked: 300 }),
+            delete.execute(&mut bc, &false).map(|_| ())
+        );
+
+        // ... until the chain reaches their locks
+        let mut bc = builder
+            .next_block()
+            .transaction("alice", TransactionData::CreateUserAccount("carol".into()))
+            .build();
+        assert_eq!(3, bc.len());
+        transfer(100, 0).execute(&mut bc, &false).unwrap();
+        assert_eq!(
+            Err(BlockchainError::TokensLocked { locked: 200 }),
+            transfer(1, 1).execute(&mut bc, &false).map(|_| ())
+        );
+
+        // An existing account can't be turned into a vesting one
+        assert_eq!(
+            Err(BlockchainError::AccountExists(bob.clone())),
+            Transaction::new(alice.clone(), vesting, 2)
+                .execute(&mut bc, &false)
+                .map(|_| ())
+        );
+    }
+}
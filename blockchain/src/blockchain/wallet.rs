@@ -0,0 +1,509 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+
+use crate::{BlockchainError, Keypair, SignatureScheme, Transaction};
+
+/// The scrypt cost (log2 of N) used for new key files.
+pub const DEFAULT_KDF_LOG_N: u8 = 15;
+
+/// The extension of the key files inside the keystore directory.
+const KEY_FILE_EXTENSION: &str = "json";
+
+/// The cipher the secret keys are encrypted with.
+const CIPHER: &str = "aes-256-gcm";
+
+/// The number of words a mnemonic seed phrase may have (as of BIP39).
+const MNEMONIC_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// The PBKDF2 rounds that stretch a mnemonic into a seed (as of BIP39).
+const MNEMONIC_ROUNDS: u32 = 2048;
+
+/// The coin type of the derivation paths (SLIP-44 reserves 1 for test networks).
+pub const COIN_TYPE: u32 = 1;
+
+/// The bit marking an index of a derivation path as hardened.
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// A hierarchical deterministic wallet: one seed generates (any number of) keypairs.<br/>
+/// The seed is derived from a BIP39 mnemonic and the keys are derived as of SLIP-10,
+/// the Ed25519 variant of BIP32 (which only supports hardened derivation).
+#[derive(Clone)]
+pub struct Wallet {
+    seed: Vec<u8>,
+}
+
+// Custom implementation of the `Debug` trait, so that the seed never ends up in logs.
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Wallet {{ .. }}")
+    }
+}
+
+impl Wallet {
+    /// C'tor.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        Wallet {
+            seed: seed.to_vec(),
+        }
+    }
+
+    /// Will restore the wallet of a (BIP39) mnemonic seed phrase and an optional passphrase.<br/>
+    /// The words are only checked for their count and for being lowercase ASCII, the
+    /// checksum of the phrase is not verified.
+    pub fn from_mnemonic(mnemonic: &str, passphrase: &str) -> Result<Self, BlockchainError> {
+        let words: Vec<&str> = mnemonic.split_whitespace().collect();
+        if !MNEMONIC_WORD_COUNTS.contains(&words.len()) {
+            return Err(BlockchainError::InvalidMnemonic(format!(
+                "{} words instead of 12, 15, 18, 21 or 24",
+                words.len()
+            )));
+        }
+        if let Some(word) = words
+            .iter()
+            .find(|word| !word.bytes().all(|b| b.is_ascii_lowercase()))
+        {
+            return Err(BlockchainError::InvalidMnemonic(format!(
+                "Unexpected word `{}`",
+                word
+            )));
+        }
+
+        let mut seed = [0u8; 64];
+        pbkdf2::pbkdf2_hmac::<Sha512>(
+            words.join(" ").as_bytes(),
+            format!("mnemonic{}", passphrase).as_bytes(),
+            MNEMONIC_ROUNDS,
+            &mut seed,
+        );
+        Ok(Wallet::from_seed(&seed))
+    }
+
+    /// Will derive the keypair of the account with the index (`m/44'/1'/index'`)
+    pub fn derive_account(&self, index: u32) -> Keypair {
+        self.derive_path(&[44, COIN_TYPE, index])
+    }
+
+    /// Will derive the keypair of the path, all of whose indexes are hardened
+    pub fn derive_path(&self, path: &[u32]) -> Keypair {
+        let (mut key, mut chain_code) = split(hmac_sha512(b"ed25519 seed", &[&self.seed]));
+        for index in path {
+            let index = (index | HARDENED).to_be_bytes();
+            let child = hmac_sha512(&chain_code, &[&[0u8], &key, &index]);
+            let (child_key, child_chain_code) = split(child);
+            key = child_key;
+            chain_code = child_chain_code;
+        }
+        Keypair::from_secret_bytes(&key)
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    let mut mac =
+        <Hmac<Sha512> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    for bytes in data {
+        mac.update(bytes);
+    }
+    let mut output = [0u8; 64];
+    output.copy_from_slice(&mac.finalize().into_bytes());
+    output
+}
+
+/// Will split the output of a derivation step into the key and the chain code
+fn split(output: [u8; 64]) -> ([u8; 32], [u8; 32]) {
+    let mut key = [0u8; 32];
+    let mut chain_code = [0u8; 32];
+    key.copy_from_slice(&output[..32]);
+    chain_code.copy_from_slice(&output[32..]);
+    (key, chain_code)
+}
+
+/// The parameters of the scrypt key derivation of a key file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+
+    /// The (hex encoded) random salt
+    pub salt: String,
+}
+
+/// A secret key, encrypted with a key derived from a password (stored as JSON).<br/>
+/// The address is authenticated along with the secret key, so a key file can't
+/// be renamed to another address unnoticed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyFile {
+    /// The (bech32) address of the key
+    pub address: String,
+
+    pub kdf: KdfParams,
+
+    pub cipher: String,
+
+    /// The (hex encoded) nonce of the encryption
+    pub nonce: String,
+
+    /// The (hex encoded) encrypted secret key
+    pub ciphertext: String,
+
+    /// The signature scheme of the key
+    #[serde(default)]
+    pub scheme: SignatureScheme,
+}
+
+impl KeyFile {
+    /// Will encrypt the secret key of the keypair with the password
+    pub fn encrypt(keypair: &Keypair, password: &str, log_n: u8) -> Result<Self, BlockchainError> {
+        let mut salt = [0u8; 32];
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+
+        let kdf = KdfParams {
+            log_n,
+            r: 8,
+            p: 1,
+            salt: hex::encode(salt),
+        };
+        let address = keypair.address().to_string();
+        let ciphertext = cipher(password, &kdf)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &keypair.secret_bytes(),
+                    aad: address.as_bytes(),
+                },
+            )
+            .map_err(|err| BlockchainError::Keystore(err.to_string()))?;
+
+        Ok(KeyFile {
+            address,
+            kdf,
+            cipher: CIPHER.into(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+            scheme: keypair.scheme(),
+        })
+    }
+
+    /// Will decrypt the secret key with the password
+    pub fn decrypt(&self, password: &str) -> Result<Keypair, BlockchainError> {
+        if self.cipher != CIPHER {
+            return Err(BlockchainError::Keystore(format!(
+                "Unsupported cipher: {}",
+                self.cipher
+            )));
+        }
+        let nonce = decode_hex(&self.nonce)?;
+        if nonce.len() != 12 {
+            return Err(BlockchainError::Keystore("Invalid nonce".into()));
+        }
+        let ciphertext = decode_hex(&self.ciphertext)?;
+
+        let secret = cipher(password, &self.kdf)?
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: self.address.as_bytes(),
+                },
+            )
+            .map_err(|_| BlockchainError::WrongPassword(self.address.clone()))?;
+
+        let mut bytes = [0u8; 32];
+        if secret.len() != bytes.len() {
+            return Err(BlockchainError::Keystore("Invalid secret key".into()));
+        }
+        bytes.copy_from_slice(&secret);
+        let keypair = Keypair::from_scheme_secret_bytes(self.scheme, &bytes)?;
+        if keypair.address().to_string() != self.address {
+            return Err(BlockchainError::Keystore(format!(
+                "The key does not belong to {}",
+                self.address
+            )));
+        }
+        Ok(keypair)
+    }
+}
+
+/// A directory of encrypted key files, one per address.
+#[derive(Debug)]
+pub struct Keystore {
+    dir: PathBuf,
+    kdf_log_n: u8,
+}
+
+impl Keystore {
+    /// Will open the keystore in the directory, creating the directory if needed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, BlockchainError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(keystore_error)?;
+        Ok(Keystore {
+            dir,
+            kdf_log_n: DEFAULT_KDF_LOG_N,
+        })
+    }
+
+    /// Will use the scrypt cost for the keys stored from now on.<br/>
+    /// Lower costs make brute forcing the passwords cheaper, so this is meant for tests.
+    pub fn with_kdf_log_n(mut self, log_n: u8) -> Self {
+        self.kdf_log_n = log_n;
+        self
+    }
+
+    /// Will generate a new keypair and store it, returning its address
+    pub fn generate(&self, password: &str) -> Result<String, BlockchainError> {
+        self.import(&Keypair::generate(), password)
+    }
+
+    /// Will store the keypair (encrypted with the password), returning its address
+    pub fn import(&self, keypair: &Keypair, password: &str) -> Result<String, BlockchainError> {
+        let key_file = KeyFile::encrypt(keypair, password, self.kdf_log_n)?;
+        let json = serde_json::to_string_pretty(&key_file)
+            .map_err(|err| BlockchainError::Serialization(err.to_string()))?;
+        fs::write(self.path(&key_file.address), json).map_err(keystore_error)?;
+        Ok(key_file.address)
+    }
+
+    /// Will return the (sorted) addresses of all stored keys
+    pub fn addresses(&self) -> Result<Vec<String>, BlockchainError> {
+        let mut addresses = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(keystore_error)? {
+            let path = entry.map_err(keystore_error)?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(KEY_FILE_EXTENSION) {
+                continue;
+            }
+            if let Some(address) = path.file_stem().and_then(|stem| stem.to_str()) {
+                addresses.push(address.to_string());
+            }
+        }
+        addresses.sort();
+        Ok(addresses)
+    }
+
+    /// Will decrypt the keypair of the address
+    pub fn load(&self, address: &str, password: &str) -> Result<Keypair, BlockchainError> {
+        let path = self.path(address);
+        if !path.exists() {
+            return Err(BlockchainError::Keystore(format!(
+                "No key stored for {}",
+                address
+            )));
+        }
+        let json = fs::read_to_string(path).map_err(keystore_error)?;
+        let key_file: KeyFile = serde_json::from_str(&json)
+            .map_err(|err| BlockchainError::Serialization(err.to_string()))?;
+        if key_file.address != address {
+            return Err(BlockchainError::Keystore(format!(
+                "The key file of {} belongs to {}",
+                address, key_file.address
+            )));
+        }
+        key_file.decrypt(password)
+    }
+
+    /// Will sign the transaction with the key of its sender
+    pub fn sign(
+        &self,
+        transaction: &mut Transaction,
+        password: &str,
+    ) -> Result<(), BlockchainError> {
+        let keypair = self.load(&transaction.from, password)?;
+        transaction.sign(&keypair);
+        Ok(())
+    }
+
+    /// Will add the signature of an owner of the (multisignature) sender to the transaction
+    pub fn cosign(
+        &self,
+        transaction: &mut Transaction,
+        owner: &str,
+        password: &str,
+    ) -> Result<(), BlockchainError> {
+        let keypair = self.load(owner, password)?;
+        transaction.add_signature(&keypair);
+        Ok(())
+    }
+
+    fn path(&self, address: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", address, KEY_FILE_EXTENSION))
+    }
+}
+
+/// Will derive the encryption key from the password
+fn cipher(password: &str, kdf: &KdfParams) -> Result<Aes256Gcm, BlockchainError> {
+    let salt = decode_hex(&kdf.salt)?;
+    let params = scrypt::Params::new(kdf.log_n, kdf.r, kdf.p, 32)
+        .map_err(|err| BlockchainError::Keystore(err.to_string()))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key)
+        .map_err(|err| BlockchainError::Keystore(err.to_string()))?;
+    Aes256Gcm::new_from_slice(&key).map_err(|err| BlockchainError::Keystore(err.to_string()))
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, BlockchainError> {
+    hex::decode(hex).map_err(|err| BlockchainError::Keystore(err.to_string()))
+}
+
+fn keystore_error(err: std::io::Error) -> BlockchainError {
+    BlockchainError::Keystore(err.to_string())
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Keystore, Wallet};
+    use crate::{BlockchainError, Keypair, Transaction, TransactionData};
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon \
+                            abandon abandon abandon abandon abandon about";
+
+    fn keystore(dir: &tempfile::TempDir) -> Keystore {
+        Keystore::open(dir.path().join("keystore"))
+            .unwrap()
+            .with_kdf_log_n(4)
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = keystore(&dir);
+
+        let keypair = Keypair::generate();
+        let imported = keystore.import(&keypair, "secret").unwrap();
+        let generated = keystore.generate("other").unwrap();
+        assert_eq!(keypair.address().to_string(), imported);
+
+        let mut expected = vec![imported.clone(), generated.clone()];
+        expected.sort();
+        assert_eq!(expected, keystore.addresses().unwrap());
+
+        let loaded = keystore.load(&imported, "secret").unwrap();
+        assert_eq!(keypair.secret_bytes(), loaded.secret_bytes());
+        assert_eq!(
+            generated,
+            keystore
+                .load(&generated, "other")
+                .unwrap()
+                .address()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn wrong_password_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = keystore(&dir);
+        let address = keystore.generate("secret").unwrap();
+
+        assert_eq!(
+            Err(BlockchainError::WrongPassword(address.clone())),
+            keystore.load(&address, "guess").map(|_| ())
+        );
+        assert!(keystore.load("unknown", "secret").is_err());
+    }
+
+    #[test]
+    fn key_files_are_bound_to_their_address() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = keystore(&dir);
+        let address = keystore.generate("secret").unwrap();
+
+        let mut key_file: super::KeyFile =
+            serde_json::from_str(&std::fs::read_to_string(keystore.path(&address)).unwrap())
+                .unwrap();
+        key_file.address = Keypair::generate().address().to_string();
+        assert!(key_file.decrypt("secret").is_err());
+    }
+
+    #[test]
+    fn mnemonic_seed_matches_bip39() {
+        let wallet = Wallet::from_mnemonic(MNEMONIC, "TREZOR").unwrap();
+        assert_eq!(
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            hex::encode(&wallet.seed[..])
+        );
+
+        assert!(Wallet::from_mnemonic("abandon about", "").is_err());
+        assert!(Wallet::from_mnemonic(&MNEMONIC.replace("about", "About"), "").is_err());
+    }
+
+    #[test]
+    fn key_derivation_matches_slip10() {
+        // The first Ed25519 test vector of SLIP-10
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let wallet = Wallet::from_seed(&seed);
+        assert_eq!(
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            hex::encode(wallet.derive_path(&[]).secret_bytes())
+        );
+        assert_eq!(
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            hex::encode(wallet.derive_path(&[0]).secret_bytes())
+        );
+    }
+
+    #[test]
+    fn accounts_are_deterministic() {
+        let wallet = Wallet::from_mnemonic(MNEMONIC, "").unwrap();
+        let restored = Wallet::from_mnemonic(&MNEMONIC.replace(' ', "  "), "").unwrap();
+        assert_eq!(
+            wallet.derive_account(0).public_key(),
+            restored.derive_account(0).public_key()
+        );
+        assert_ne!(
+            wallet.derive_account(0).public_key(),
+            wallet.derive_account(1).public_key()
+        );
+        assert_ne!(
+            wallet.derive_account(0).public_key(),
+            Wallet::from_mnemonic(MNEMONIC, "passphrase")
+                .unwrap()
+                .derive_account(0)
+                .public_key()
+        );
+    }
+
+    #[test]
+    fn signs_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = keystore(&dir);
+        let address = keystore.generate("secret").unwrap();
+
+        let mut transaction = Transaction::new(
+            address.clone(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        keystore.sign(&mut transaction, "secret").unwrap();
+        assert!(transaction.check_signature());
+
+        let mut multisig = Transaction::new(
+            "multisig".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        keystore.cosign(&mut multisig, &address, "secret").unwrap();
+        assert_eq!(1, multisig.signers().len());
+
+        let mut foreign = Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        );
+        assert!(keystore.sign(&mut foreign, "secret").is_err());
+    }
+}
@@ -1,4 +1,11 @@
-use crate::{Account, AccountType, Blockchain};
+use super::names::{self, NameRecord};
+use super::policy::DEFAULT_CHAIN_ID;
+use super::tombstone;
+use crate::{
+    Account, AccountType, Address, Block, Blockchain, BlockchainError, BridgeConfig, EscrowRecord,
+    HtlcRecord, ALIAS_REGISTRY, ASSET_REGISTRY, BRIDGE_REGISTRY, ESCROW_REGISTRY, HTLC_REGISTRY,
+    NAME_REGISTRY, NFT_REGISTRY, TOMBSTONE_REGISTRY,
+};
 
 /// Represents the current state of the blockchain after all Blocks are executed
 /// A world state is technically not necessary since we always could build the information
@@ -11,40 +18,297 @@ pub trait WorldState {
     fn get_user_ids(&self) -> Vec<String>;
 
     /// Will return an account given it id if is available (mutable)
-    fn get_account_by_id_mut(&mut self, id: &String) -> Option<&mut Account>;
+    fn get_account_by_id_mut(&mut self, id: &str) -> Option<&mut Account>;
 
     /// Will return an account given it id if is available
-    fn get_account_by_id(&self, id: &String) -> Option<&Account>;
+    fn get_account_by_id(&self, id: &str) -> Option<&Account>;
 
-    /// Will add a new account
-    fn create_account(&mut self, id: String, account_type: AccountType)
-        -> Result<(), &'static str>;
+    /// Will iterate over all accounts (in no particular order)
+    fn accounts(&self) -> Box<dyn Iterator<Item = (&String, &Account)> + '_>;
+
+    /// Checks if an account with the given id exists
+    fn account_exists(&self, id: &str) -> bool {
+        self.get_account_by_id(id).is_some()
+    }
+
+    /// Will return the amount of tokens an account owns (if it exists)
+    fn get_balance(&self, id: &str) -> Option<u128> {
+        self.get_account_by_id(id).map(Account::tokens)
+    }
+
+    /// Will return the amount of the asset an account owns (if it exists)
+    fn get_asset_balance(&self, id: &str, asset: &str) -> Option<u128> {
+        self.get_account_by_id(id)
+            .map(|account| account.asset_balance(asset))
+    }
+
+    /// Will return the issuer of an asset (if it is registered)
+    fn get_asset_issuer(&self, asset: &str) -> Option<&String> {
+        self.get_store_value(ASSET_REGISTRY, asset)
+    }
+
+    /// Will return the owner of a (non-fungible) token (if it is minted)
+    fn get_nft_owner(&self, id: &str) -> Option<&String> {
+        self.get_store_value(NFT_REGISTRY, id)
+    }
+
+    /// Will return the open escrow of the id
+    fn get_escrow(&self, id: &str) -> Option<EscrowRecord> {
+        EscrowRecord::parse(self.get_store_value(ESCROW_REGISTRY, id)?)
+    }
+
+    /// Will return the open hashed timelock contract of the id
+    fn get_htlc(&self, id: &str) -> Option<HtlcRecord> {
+        HtlcRecord::parse(self.get_store_value(HTLC_REGISTRY, id)?)
+    }
+
+    /// Will return the amount of the owner's tokens that the spender may transfer
+    /// (if the owner exists)
+    fn get_allowance(&self, owner: &str, spender: &str) -> Option<u128> {
+        self.get_account_by_id(owner)
+            .map(|account| account.allowance(spender))
+    }
+
+    /// Will return the amount of transactions an account has sent (if it exists)
+    fn get_nonce(&self, id: &str) -> Option<u128> {
+        self.get_account_by_id(id).map(Account::nonce)
+    }
+
+    /// Will return the value stored under the key by an account (if both exist)
+    fn get_store_value(&self, id: &str, key: &str) -> Option<&String> {
+        self.get_account_by_id(id)?.store.get(key)
+    }
+
+    /// Will return the metadata entry of an account (if both exist)
+    fn get_metadata(&self, id: &str, key: &str) -> Option<&String> {
+        self.get_account_by_id(id)?.metadata.get(key)
+    }
+
+    /// Will return the account of an address if is available
+    fn get_account_by_address(&self, address: &Address) -> Option<&Account> {
+        self.get_account_by_id(&address.to_string())
+    }
+
+    /// Will return the address an alias is registered for (if it is)
+    fn resolve_alias(&self, alias: &str) -> Option<Address> {
+        self.get_store_value(ALIAS_REGISTRY, alias)?.parse().ok()
+    }
+
+    /// Will return the registration of a name (if it was ever registered, even if it expired)
+    fn get_name(&self, name: &str) -> Option<NameRecord> {
+        NameRecord::parse(self.get_store_value(NAME_REGISTRY, name)?)
+    }
+
+    /// Will return the address a name is registered for (if its registration didn't expire)
+    fn resolve_name(&self, name: &str) -> Option<Address> {
+        self.get_name(name)
+            .filter(|record| record.is_active(self.height()))
+            .map(|record| record.owner)
+    }
+
+    /// Will return the account ID the provided one refers to: itself if such an
+    /// account exists, otherwise the address of the alias or of the name (if it is registered)
+    fn resolve_account_id(&self, id: &str) -> String {
+        if self.account_exists(id) {
+            return id.to_string();
+        }
+        match self.resolve_alias(id).or_else(|| self.resolve_name(id)) {
+            Some(address) => address.to_string(),
+            None => id.to_string(),
+        }
+    }
+
+    /// Will return the height of the block being executed (i.e. the amount of blocks before it)
+    fn height(&self) -> usize {
+        0
+    }
+
+    /// Will return the timestamp of the block before the one being executed (the time locks
+    /// of the accounts are measured against it, see `vesting::check_unlocked`)
+    fn timestamp(&self) -> u64 {
+        0
+    }
+
+    /// Will return the chain id of the network
+    fn chain_id(&self) -> &str {
+        DEFAULT_CHAIN_ID
+    }
+
+    /// Will return the bridge accepting the messages of the chain (see
+    /// `ValidationPolicy::bridges`)
+    fn bridge(&self, _chain_id: &str) -> Option<BridgeConfig> {
+        None
+    }
+
+    /// Will return the amount of blocks a name is registered (or renewed) for
+    fn name_registration_period(&self) -> usize {
+        names::DEFAULT_REGISTRATION_PERIOD
+    }
+
+    /// Will return the amount of tokens a unit of gas costs (no gas is charged by default)
+    fn gas_price(&self) -> u128 {
+        0
+    }
+
+    /// Will add a new account (continuing with the nonce of its tombstone, if it was
+    /// deleted before, see `TOMBSTONE_REGISTRY`)
+    fn create_account(
+        &mut self,
+        id: String,
+        account_type: AccountType,
+    ) -> Result<(), BlockchainError>;
+
+    /// Will remove an account, returning it
+    fn delete_account(&mut self, id: &str) -> Result<Account, BlockchainError>;
+}
+
+/// Will return true if the account holds one of the registries, which can only be changed
+/// through their own transactions
+pub(crate) fn is_reserved_account(id: &str) -> bool {
+    id == ALIAS_REGISTRY
+        || id == ASSET_REGISTRY
+        || id == NFT_REGISTRY
+        || id == NAME_REGISTRY
+        || id == ESCROW_REGISTRY
+        || id == HTLC_REGISTRY
+        || id == BRIDGE_REGISTRY
+        || id == TOMBSTONE_REGISTRY
 }
 
 impl WorldState for Blockchain {
     fn get_user_ids(&self) -> Vec<String> {
-        self.accounts.keys().map(|s| s.clone()).collect()
+        self.accounts.keys().cloned().collect()
     }
 
-    fn get_account_by_id_mut(&mut self, id: &String) -> Option<&mut Account> {
+    fn get_account_by_id_mut(&mut self, id: &str) -> Option<&mut Account> {
+        // The account may be changed, so its current value is recorded for a rollback
+        if let Some(journal) = self.journal.as_mut() {
+            if self.accounts.contains_key(id) {
+                journal.record(id, &self.accounts);
+            }
+        }
         self.accounts.get_mut(id)
     }
 
-    fn get_account_by_id(&self, id: &String) -> Option<&Account> {
+    fn get_account_by_id(&self, id: &str) -> Option<&Account> {
         self.accounts.get(id)
     }
 
+    fn accounts(&self) -> Box<dyn Iterator<Item = (&String, &Account)> + '_> {
+        Box::new(self.accounts.iter())
+    }
+
+    fn gas_price(&self) -> u128 {
+        self.policy_at(self.blocks.len()).gas_price
+    }
+
+    fn height(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.blocks.last().map_or(0, Block::timestamp)
+    }
+
+    fn chain_id(&self) -> &str {
+        &self.policy().chain_id
+    }
+
+    fn bridge(&self, chain_id: &str) -> Option<BridgeConfig> {
+        let bridges = &self.policy().bridges;
+        bridges
+            .iter()
+            .find(|bridge| bridge.chain_id == chain_id)
+            .cloned()
+    }
+
+    fn name_registration_period(&self) -> usize {
+        self.policy_at(self.blocks.len()).name_registration_period
+    }
+
     fn create_account(
         &mut self,
         id: String,
         account_type: AccountType,
-    ) -> Result<(), &'static str> {
-        return if !self.get_user_ids().contains(&id) {
-            let acc = Account::new(account_type);
+    ) -> Result<(), BlockchainError> {
+        if !self.accounts.contains_key(&id) {
+            if let Some(journal) = self.journal.as_mut() {
+                journal.record(&id, &self.accounts);
+            }
+            let acc = tombstone::new_account(self, &id, account_type);
             self.accounts.insert(id, acc);
             Ok(())
         } else {
-            Err("User already exists! (Code: 934823094)")
-        };
+            Err(BlockchainError::AccountExists(id))
+        }
+    }
+
+    fn delete_account(&mut self, id: &str) -> Result<Account, BlockchainError> {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.record(id, &self.accounts);
+        }
+        self.accounts
+            .remove(id)
+            .ok_or_else(|| BlockchainError::UnknownAccount(id.to_string()))
+    }
+}
+
+// -------------------------
+//        unit tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{Block, Blockchain, Transaction, TransactionData, WorldState};
+
+    #[test]
+    fn query_accounts() {
+        let mut bc = Blockchain::new();
+        let mut genesis = Block::new(None);
+        genesis.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("alice".into()),
+            0,
+        ));
+        genesis.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateTokens {
+                receiver: "alice".into(),
+                amount: 50,
+                asset: None,
+            },
+            0,
+        ));
+        genesis.add_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("bob".into()),
+            0,
+        ));
+        bc.append_block(genesis).unwrap();
+        bc.submit_transaction(Transaction::new(
+            "alice".into(),
+            TransactionData::CreateUserAccount("dave".into()),
+            0,
+        ))
+        .unwrap();
+        bc.produce_block(1).unwrap();
+
+        assert!(bc.account_exists("alice"));
+        assert!(!bc.account_exists("carol"));
+        assert_eq!(Some(50), bc.get_balance("alice"));
+        assert_eq!(Some(0), bc.get_balance("bob"));
+        assert_eq!(None, bc.get_balance("carol"));
+
+        // Every executed transaction of the sender is counted, except for the genesis ones
+        assert_eq!(Some(1), bc.get_nonce("alice"));
+        assert_eq!(Some(0), bc.get_nonce("bob"));
+        assert_eq!(None, bc.get_nonce("carol"));
+
+        let mut ids: Vec<_> = bc.accounts().map(|(id, _)| id.clone()).collect();
+        ids.sort();
+        assert_eq!(vec!["alice", "bob", "dave"], ids);
+        let total: u128 = bc.accounts().map(|(_, account)| account.tokens()).sum();
+        assert_eq!(50, total);
     }
 }
@@ -3,6 +3,61 @@ mod blockchain;
 
 // Exporting to the public with a simple path.
 pub use blockchain::account::{Account, AccountType};
-pub use blockchain::block::Block;
+pub use blockchain::address::Address;
+pub use blockchain::alias::ALIAS_REGISTRY;
+pub use blockchain::asset::ASSET_REGISTRY;
+pub use blockchain::block::{Block, BlockBody};
+pub use blockchain::block_builder::BlockBuilder;
+pub use blockchain::bridge::{BridgeConfig, BridgePayload, BridgeProof, Relayer, BRIDGE_REGISTRY};
+pub use blockchain::cached_state::CachedWorldState;
 pub use blockchain::chain::Blockchain;
-pub use blockchain::transaction::{Transaction, TransactionData};
+pub use blockchain::checkpoint::Checkpoint;
+pub use blockchain::consensus;
+pub use blockchain::consensus::{Consensus, ConsensusEngine};
+pub use blockchain::contracts::contract_address;
+pub use blockchain::error::BlockchainError;
+pub use blockchain::escrow::{EscrowRecord, ESCROW_REGISTRY};
+pub use blockchain::evidence::Evidence;
+pub use blockchain::gas;
+pub use blockchain::gas::ExecutionResult;
+pub use blockchain::genesis::{GenesisAccount, GenesisConfig};
+pub use blockchain::handle::ChainHandle;
+pub use blockchain::hash::Hash;
+pub use blockchain::header::{BlockHeader, HeaderChain};
+pub use blockchain::htlc::{HtlcRecord, HTLC_REGISTRY};
+pub use blockchain::index::{AccountChange, TransactionLocation};
+pub use blockchain::keypair::{Keypair, SignatureScheme};
+pub use blockchain::mempool::MempoolPolicy;
+pub use blockchain::merkle;
+pub use blockchain::merkle::MerkleProof;
+pub use blockchain::metadata::{
+    MAX_METADATA_ENTRIES, MAX_METADATA_KEY_LENGTH, MAX_METADATA_VALUE_LENGTH,
+};
+pub use blockchain::metrics::ChainMetrics;
+pub use blockchain::miner;
+pub use blockchain::miner::DifficultyParams;
+pub use blockchain::multisig::Cosignature;
+pub use blockchain::names::{NameRecord, NAME_REGISTRY};
+pub use blockchain::nft::NFT_REGISTRY;
+pub use blockchain::orphans::OrphanPolicy;
+pub use blockchain::params::ChainParams;
+pub use blockchain::policy::{PolicyUpgrade, ValidationPolicy};
+pub use blockchain::receipt::{BalanceChange, Event, ReceiptStatus, TransactionReceipt};
+pub use blockchain::sign_doc::{SignDoc, SIGN_DOC_DOMAIN};
+#[cfg(any(test, feature = "test-utils"))]
+pub use blockchain::sim;
+pub use blockchain::snapshot::Snapshot;
+pub use blockchain::state_root::AccountProof;
+pub use blockchain::storage;
+pub use blockchain::storage::Storage;
+#[cfg(any(test, feature = "test-utils"))]
+pub use blockchain::strategies;
+pub use blockchain::subscription::ChainEvent;
+#[cfg(any(test, feature = "test-utils"))]
+pub use blockchain::test_utils;
+pub use blockchain::tombstone::TOMBSTONE_REGISTRY;
+pub use blockchain::transaction::{TimeLock, Transaction, TransactionData, TransactionKind};
+pub use blockchain::transaction_builder::TransactionBuilder;
+pub use blockchain::vesting::{VestingTranche, MAX_VESTING_TRANCHES};
+pub use blockchain::wallet::{KeyFile, Keystore, Wallet};
+pub use blockchain::world_state::WorldState;
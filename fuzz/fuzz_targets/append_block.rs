@@ -0,0 +1,20 @@
+#![no_main]
+
+use blockchain::test_utils::ChainBuilder;
+use blockchain::{Block, Blockchain};
+use libfuzzer_sys::fuzz_target;
+
+thread_local! {
+    static CHAIN: Blockchain = ChainBuilder::new()
+        .with_account("alice", 1000)
+        .transfer("alice", "bob", 10)
+        .build();
+}
+
+// Appending any (decodable) block is rejected or accepted, it never panics
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(block) = Block::from_bytes(bytes) {
+        let mut chain = CHAIN.with(Blockchain::clone);
+        let _ = chain.append_block(block);
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use blockchain::Block;
+use libfuzzer_sys::fuzz_target;
+
+// Decoding any bytes (e.g. received from peers) fails gracefully, it never panics
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(block) = Block::from_bytes(bytes) {
+        let _ = block.calculate_hash();
+        let _ = block.to_bytes();
+    }
+});
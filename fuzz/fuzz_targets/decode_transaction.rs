@@ -0,0 +1,12 @@
+#![no_main]
+
+use blockchain::Transaction;
+use libfuzzer_sys::fuzz_target;
+
+// Decoding any bytes (e.g. received from peers) fails gracefully, it never panics
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(transaction) = Transaction::from_bytes(bytes) {
+        let _ = transaction.calculate_hash();
+        let _ = transaction.check_signature();
+    }
+});